@@ -1,4 +1,4 @@
-import { useState, useCallback, useMemo } from 'react'
+import { useState, useCallback, useEffect, useMemo } from 'react'
 import CodeBlock from '../components/CodeBlock'
 
 
@@ -125,6 +125,21 @@ const QUERIES: QueryExample[] = [
     description: 'name=~=(?i)pro — case-insensitive regex',
     path: '/Products/?name=~=(?i)pro',
   },
+  {
+    label: 'Case-insensitive contains (=cti=)',
+    description: 'name=cti=PRO — substring match ignoring case',
+    path: '/Products/?name=cti=PRO&select=name',
+  },
+  {
+    label: 'Case-insensitive equals (=eqi=)',
+    description: 'name=eqi=viewtech — Unicode case folding on both sides',
+    path: '/Brand/?name=eqi=viewtech',
+  },
+  {
+    label: 'Case-insensitive prefix (i:)',
+    description: 'name=sw=i:ultra — the i: prefix makes ==, =ct=, =sw= and =ew= ignore case',
+    path: '/Products/?name=sw=i:ultra&select=name',
+  },
 
   // ── Full-Text Search ──
   {
@@ -137,6 +152,26 @@ const QUERIES: QueryExample[] = [
     description: 'name=ft=ultra monitor — all terms must match (AND)',
     path: '/Products/?name=ft=ultra%20monitor',
   },
+  {
+    label: 'Stemmed full-text (english analyzer)',
+    description: 'description=ft=programs — the english analyzer stems both sides, so "programs" finds "programming"',
+    path: '/Products/?description=ft=programs&select=name,description',
+  },
+  {
+    label: 'Singular finds plural',
+    description: 'name=ft=headphone — matches "Headphones"; synonyms from data/analysis.json let headset find it too',
+    path: '/Products/?name=ft=headphone&select=name',
+  },
+  {
+    label: 'Ranked by relevance (_score)',
+    description: '_text=ft= searches name and description, name boosted 2x; sort=-_score puts the best match first',
+    path: '/Products/?_text=ft=wireless%20OR%20portable&sort=-_score&select=name,_score',
+  },
+  {
+    label: 'Phrase, prefix and NOT',
+    description: '"web APIs" matches the words together, program* any word starting so, -kernel excludes; _highlight marks the matches',
+    path: '/Products/?description=ft=%22web%20APIs%22%20OR%20program*%20-kernel&sort=-_score&select=name,_highlight',
+  },
 
   // ── Set Membership ──
   {
@@ -161,6 +196,52 @@ const QUERIES: QueryExample[] = [
     description: 'inStock==string:true — match literal string "true"',
     path: '/Products/?inStock==string:true',
   },
+  {
+    label: 'Type prefix: date',
+    description: 'createdAt=lt=date:2023-11-01 — compare as a date, not text',
+    path: '/Brand/?createdAt=lt=date:2023-11-01&select=name,createdAt',
+  },
+
+  // ── Dates ──
+  {
+    label: 'Date range (=gele=)',
+    description: 'createdAt=gele=2024-03-01,2024-03-31 — a day bound covers the whole day',
+    path: '/Products/?createdAt=gele=2024-03-01,2024-03-31&select=name,createdAt',
+  },
+  {
+    label: 'Relative date (now-30d)',
+    description: 'updatedAt=lt=now-30d — not changed in the last 30 days',
+    path: '/Products/?updatedAt=lt=now-30d&sort=-updatedAt&select=name,updatedAt&limit=3',
+  },
+
+  // ── Geo ──
+  {
+    label: 'Near a point (=near=)',
+    description: 'location=near=35.68,139.69,50km — brands within 50 km of central Tokyo',
+    path: '/Brand/?location=near=35.68,139.69,50km&select=name,country',
+  },
+  {
+    label: 'Within a box (=within=)',
+    description: 'location=within=36,-10,60,20 — south, west, north, east edges around western Europe',
+    path: '/Brand/?location=within=36,-10,60,20&select=name,country',
+  },
+  {
+    label: 'Sort by distance',
+    description: 'sort=location orders by distance from the =near= point, nearest first',
+    path: '/Brand/?location=near=45.76,4.84,500km&sort=location&select=name,country',
+  },
+
+  // ── Vectors ──
+  {
+    label: 'Similar descriptions (=sim=)',
+    description: 'description=sim=rust%20programming,k=3 — the 3 products whose description embedding is nearest the text',
+    path: '/Products/?description=sim=rust%20programming,k=3&sort=description&select=name,category',
+  },
+  {
+    label: 'Similarity with filters',
+    description: 'category==electronics&inStock==true — the 3 nearest among in-stock electronics, not the in-stock electronics among the 3 nearest',
+    path: '/Products/?category==electronics&inStock==true&description=sim=wireless%20audio,k=3&sort=description&select=name',
+  },
 
   // ── Nested & Array Data ──
   {
@@ -173,6 +254,43 @@ const QUERIES: QueryExample[] = [
     description: 'tags==popular — match any element in array field',
     path: '/Products/?tags==popular',
   },
+  {
+    label: 'Array contains all (=all=)',
+    description: 'tags=all=4k,popular — every listed tag is present',
+    path: '/Products/?tags=all=4k,popular&select=name,tags',
+  },
+  {
+    label: 'Array contains none (=none=)',
+    description: 'tags=none=popular — electronics without the popular tag',
+    path: '/Products/?category==electronics&tags=none=popular&select=name,tags',
+  },
+  {
+    label: 'Array size (=size=gt=)',
+    description: 'tags=size=gt=3 — more than three tags',
+    path: '/Products/?tags=size=gt=3&select=name,tags',
+  },
+  {
+    label: 'Element filter (=any=)',
+    description: 'variants=any=(color==navy&stock=gt=0) — one variant is both navy and in stock',
+    path: '/Products/?variants=any=(color==navy&stock=gt=0)&select=name,variants',
+  },
+
+  // ── Null & Existence ──
+  {
+    label: 'Null (=null=)',
+    description: 'variants=null=true — missing or null (products sold in one size)',
+    path: '/Products/?variants=null=true&select=name,category&limit=3',
+  },
+  {
+    label: 'Exists (=exists=)',
+    description: 'variants=exists=true — key present, even if null',
+    path: '/Products/?variants=exists=true&select=name,variants',
+  },
+  {
+    label: 'Empty (=empty=)',
+    description: 'products=empty=false — brands with at least one product',
+    path: '/Brand/?products=empty=false&select=name&limit=3',
+  },
 
   // ── Logical Operators ──
   {
@@ -317,12 +435,22 @@ const QUERIES: QueryExample[] = [
     description: 'Composite index on (category, price) — shows index optimization',
     path: '/Products/?category==electronics&price=gt=100&explain=true&stream=false',
   },
+  {
+    label: 'Explain: folded index',
+    description: 'Brand.name is @indexed(collation: "ci"), so =eqi= is an index lookup',
+    path: '/Brand/?name=eqi=viewtech&explain=true',
+  },
 ]
 
 // Known FIQL operators (longest first to avoid partial matches)
 const FIQL_OPS = [
+  '=size=ne=', '=size=gt=', '=size=ge=', '=size=lt=', '=size=le=',
+  '=size=', '=none=', '=all=', '=any=',
   '=gele=', '=gelt=', '=gtle=', '=gtlt=',
   '=out=', '=ne=', '=gt=', '=ge=', '=lt=', '=le=',
+  '=exists=', '=empty=', '=null=',
+  '=eqi=', '=cti=', '=swi=', '=ewi=',
+  '=near=', '=within=', '=sim=',
   '=ct=', '=sw=', '=ew=', '=ft=', '=in=', '=~=', '===', '==',
 ]
 
@@ -571,7 +699,35 @@ export function FiqlPage() {
   const selectedQuery = selectedIndex !== null ? QUERIES[selectedIndex] : null
 
   const fiqlUrl = useMemo(() => selectedQuery ? buildFiqlUrl(selectedQuery) : '', [selectedQuery])
-  const resourceJson = useMemo(() => selectedQuery ? parseFiqlToResourceJson(selectedQuery) : '', [selectedQuery])
+  const [resourceJson, setResourceJson] = useState('')
+  const [resourceSource, setResourceSource] = useState<'server' | 'local'>('server')
+
+  // Ask the server's /parse resource for the canonical ResourceQuery; fall back
+  // to the local approximation when it is unreachable.
+  useEffect(() => {
+    if (!selectedQuery) {
+      setResourceJson('')
+      return
+    }
+    let cancelled = false
+    const url = `${__STATIC_ROOT__}/${__RESOURCES_ROOT__}/parse?q=${encodeURIComponent(selectedQuery.path)}`
+    fetch(url)
+      .then(async (response) => {
+        if (!response.ok) throw new Error(`${response.status}`)
+        const json = await response.json()
+        if (cancelled) return
+        setResourceJson(JSON.stringify(json, null, 2))
+        setResourceSource('server')
+      })
+      .catch(() => {
+        if (cancelled) return
+        setResourceJson(parseFiqlToResourceJson(selectedQuery))
+        setResourceSource('local')
+      })
+    return () => {
+      cancelled = true
+    }
+  }, [selectedQuery])
 
   const selectQuery = useCallback((index: number) => {
     setSelectedIndex(index)
@@ -650,7 +806,7 @@ export function FiqlPage() {
         <CodePane language="fiql">{fiqlUrl || 'Select a query from the left'}</CodePane>
         <div className="panel-header">
           <span className="panel-title">Resource JSON</span>
-          <span className="panel-badge">Parsed</span>
+          <span className="panel-badge">{resourceSource === 'server' ? 'Parsed' : 'Parsed (local)'}</span>
         </div>
         <CodePane language="json">{resourceJson || '{ }'}</CodePane>
       </div>
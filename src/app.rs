@@ -0,0 +1,377 @@
+//! The app as a whole: custom resources in front of the table endpoints,
+//! both backed by one [`Store`].
+
+use std::io::Write;
+
+use serde_json::{Value as Json, json};
+
+use crate::auth::Authenticator;
+use crate::config::{self, ArrayStyle, ExportConfig, WriteAction};
+use crate::fiql::{self, Controls, PathQuery, Query};
+use crate::format::{self, ExportError, Format};
+use crate::http::{Method, Request, Response};
+use crate::live::{Subscription, sse};
+use crate::resources::{self, parse_error, split_params};
+use crate::schema::{Schema, TypeDef};
+use crate::store::{self, Estimate, ExecError, OverBudget, Rows, Store, merge_patch, primary_key_of};
+
+pub struct App {
+    store: Store,
+    auth: Authenticator,
+}
+
+impl App {
+    /// An app over `store`, taking the JWT secret for writes from the
+    /// environment variable named in `[package.metadata.auth.jwt]`.
+    pub fn new(store: Store) -> Self {
+        App { store, auth: Authenticator::from_config(&config::bundled().auth) }
+    }
+
+    /// Replaces the JWT secret, e.g. for tests.
+    pub fn with_jwt_secret(mut self, secret: &[u8]) -> Self {
+        self.auth = Authenticator::new(&config::bundled().auth, Some(secret.to_vec()));
+        self
+    }
+
+    pub fn auth(&self) -> &Authenticator {
+        &self.auth
+    }
+
+    /// The bundled schema and seed data.
+    pub fn seeded() -> Self {
+        App::new(Store::seeded())
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut Store {
+        &mut self.store
+    }
+
+    /// Serves `req`: a custom resource if one claims the path, otherwise the
+    /// generated `/Table/` and `/Table/id` endpoints.
+    ///
+    /// A `subscribe=sse` query is answered with the opening of its event
+    /// stream. A server that holds the connection open calls
+    /// [`App::subscribe`] instead and writes the frames of
+    /// [`Subscription::poll`] after each write. Likewise a table read can be
+    /// answered with [`App::export`], writing the body as it is encoded.
+    pub fn handle(&self, req: &Request) -> Response {
+        resources::route(self, req).unwrap_or_else(|| self.table(req))
+    }
+
+    /// Serves `req` like [`App::handle`], and also applies writes:
+    /// `PATCH` and `DELETE` on a table query change every matching record.
+    pub fn handle_mut(&mut self, req: &Request) -> Response {
+        if let Some(response) = resources::route(self, req) {
+            return response;
+        }
+        match req.method {
+            Method::Patch => self.write(req, WriteAction::Update),
+            Method::Delete => self.write(req, WriteAction::Delete),
+            _ => self.table(req),
+        }
+    }
+
+    /// Opens a live query for a `subscribe=sse` request: the response to
+    /// start the event stream with, and the subscription to poll.
+    pub fn subscribe(&self, req: &Request) -> Result<(Response, Subscription), Response> {
+        let (parsed, _) = parse_target(req, TABLE_PARAMS)?;
+        self.within_budget(&parsed)?;
+        self.open(&parsed)
+    }
+
+    /// Rejects `query` with 422 when its estimated cost is over
+    /// `[package.metadata.app.budget]`, naming the part that costs most.
+    pub fn check_budget(&self, table: &TypeDef, query: &Query) -> Result<(), Response> {
+        query_within_budget(&self.store, table, query).map(drop).map_err(over_budget)
+    }
+
+    /// Rejects a request whose combined `estimate` is over the budget, like
+    /// [`App::check_budget`].
+    pub fn check_estimate(&self, estimate: Estimate) -> Result<(), Response> {
+        estimate_within_budget(estimate).map(drop).map_err(over_budget)
+    }
+
+    /// [`App::check_budget`] for a table query; `explain=true` and reads by
+    /// id always go through.
+    fn within_budget(&self, parsed: &PathQuery) -> Result<(), Response> {
+        if parsed.id.is_some() || parsed.query.controls.explain == Some(true) {
+            return Ok(());
+        }
+        match self.store.schema().table(&parsed.table) {
+            Some(table) => self.check_budget(table, &parsed.query),
+            None => Ok(()),
+        }
+    }
+
+    fn open(&self, parsed: &PathQuery) -> Result<(Response, Subscription), Response> {
+        match Subscription::open(&self.store, parsed) {
+            Ok((subscription, snapshot)) => Ok((sse::response(&snapshot), subscription)),
+            Err(err) => Err(Response::error(err.status(), err.to_string())),
+        }
+    }
+
+    fn table(&self, req: &Request) -> Response {
+        match self.export(req) {
+            Ok(export) => export.into_response(),
+            Err(response) => response,
+        }
+    }
+
+    /// Answers a table `GET` with its status and headers, leaving the body
+    /// to [`Export::write_to`]. NDJSON, and Arrow and Parquet with
+    /// `stream=true`, render and encode rows as they are written, so a
+    /// server that writes the body straight to the connection never holds
+    /// the whole export; [`App::handle`] buffers the same body.
+    pub fn export(&self, req: &Request) -> Result<Export<'_>, Response> {
+        if req.method != Method::Get {
+            return Err(Response::method_not_allowed());
+        }
+        let (parsed, params) = parse_target(req, TABLE_PARAMS)?;
+        let param = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+        let format = Format::negotiate(param("format"), req.header("Accept")).map_err(|m| Response::error(406, m))?;
+        let mut export = config::bundled().export.clone();
+        if let Some(style) = param("arrays") {
+            match ArrayStyle::from_name(style) {
+                Some(style) => export.arrays = style,
+                None => {
+                    return Err(Response::error(400, format!("unknown arrays style `{style}`; expected join or json")));
+                }
+            }
+        }
+
+        self.within_budget(&parsed)?;
+        match param("subscribe") {
+            Some("sse") => {
+                return Ok(Export::encoded(self.open(&parsed).map_or_else(|err| err, |(response, _)| response)));
+            }
+            Some(other) => {
+                return Err(Response::error(400, format!("unknown subscription transport `{other}`; expected sse")));
+            }
+            None => {}
+        }
+
+        // Plans and JSON stay JSON; row encodings carry paging in headers.
+        let failed = |err: ExecError| Response::error(err.status(), err.to_string());
+        if format == Format::Json || parsed.query.controls.explain == Some(true) {
+            let output = store::execute(&self.store, &parsed).map_err(failed)?;
+            return Ok(Export::encoded(Response::json(200, &output.to_json())));
+        }
+        let rows = store::rows(&self.store, &parsed).map_err(failed)?;
+        let mut head = Response::new(200, format.content_type(), Vec::new());
+        if let Some(total) = rows.total {
+            head.headers.push(("X-Total-Count".to_string(), total.to_string()));
+        }
+        if let Some(cursor) = &rows.next_cursor {
+            head.headers.push(("X-Next-Cursor".to_string(), cursor.clone()));
+        }
+        let schema = self.store.schema();
+        let table = schema.table(&parsed.table).expect("rows come from a known table");
+        let controls = parsed.query.controls;
+        let encoding = Encoding { format, schema, table, rows, controls, export };
+        Ok(Export { head, body: Body::Rows(Box::new(encoding)) })
+    }
+
+    /// Bulk `PATCH` (JSON merge patch body) or `DELETE` of every record the
+    /// path matches. `dryRun=true` only reports the matches; more than
+    /// `confirm_above` matches need `confirm=<count>`.
+    fn write(&mut self, req: &Request, action: WriteAction) -> Response {
+        let principal = match self.auth.authenticate(req) {
+            Ok(principal) => principal,
+            Err(err) => {
+                let mut response = Response::error(err.status(), err.to_string());
+                response.headers.push(("WWW-Authenticate".to_string(), "Bearer".to_string()));
+                return response;
+            }
+        };
+        let (parsed, params) = match parse_target(req, WRITE_PARAMS) {
+            Ok(parsed) => parsed,
+            Err(response) => return response,
+        };
+        let Some(table) = self.store.schema().table(&parsed.table).cloned() else {
+            return Response::error(404, format!("unknown table `{}`", parsed.table));
+        };
+        let verb = action.name();
+        if !config::bundled().auth.allows(&principal.roles, action, &table.name) {
+            return Response::error(403, format!("no role of this token may {verb} `{}` records", table.name));
+        }
+
+        let param = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+        let dry_run = match param("dryRun") {
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(other) => return Response::error(400, format!("dryRun: expected true or false, got `{other}`")),
+        };
+        let confirm = match param("confirm").map(str::parse::<usize>) {
+            None => None,
+            Some(Ok(count)) => Some(count),
+            Some(Err(_)) => return Response::error(400, "confirm: expected the number of matching records"),
+        };
+        if parsed.id.is_none() && parsed.query.filter.is_none() {
+            return Response::error(400, format!("refusing to {verb} every `{}` record; add a filter", table.name));
+        }
+        let controls = &parsed.query.controls;
+        let set = [
+            ("select", controls.select.is_some()),
+            ("sort", controls.sort.is_some()),
+            ("limit", controls.limit.is_some()),
+            ("offset", controls.offset.is_some()),
+            ("after", controls.after.is_some()),
+            ("pagination", controls.pagination.is_some()),
+            ("explain", controls.explain.is_some()),
+            ("stream", controls.stream.is_some()),
+        ];
+        if let Some((name, _)) = set.iter().find(|(_, set)| *set) {
+            return Response::error(400, format!("`{name}` does not apply to a bulk {verb}"));
+        }
+
+        let patch = match action {
+            WriteAction::Delete => Json::Null,
+            WriteAction::Update => match serde_json::from_slice::<Json>(&req.body) {
+                Ok(patch @ Json::Object(_)) => patch,
+                _ => return Response::error(400, "PATCH body must be a JSON merge patch object"),
+            },
+        };
+        let pk = table.primary_key().map_or("id", |f| f.name.as_str());
+        if patch.get(pk).is_some() {
+            return Response::error(400, format!("a patch cannot change the primary key `{pk}`"));
+        }
+
+        if let Err(response) = self.within_budget(&parsed) {
+            return response;
+        }
+        let ids: Vec<String> = match &parsed.id {
+            Some(id) if self.store.get(&table.name, id).is_some() => vec![id.clone()],
+            Some(id) => return Response::error(404, format!("no `{}` record with id `{id}`", table.name)),
+            None => match store::matching(&self.store, &table, &parsed.query) {
+                Ok(records) => records.into_iter().filter_map(|r| primary_key_of(&table, r)).collect(),
+                Err(err) => return Response::error(err.status(), err.to_string()),
+            },
+        };
+
+        // Every patched record is checked against the schema before any is
+        // written, and before a dry run reports success.
+        let mut patched = Vec::new();
+        if action == WriteAction::Update {
+            for id in &ids {
+                let mut record = self.store.get(&table.name, id).cloned().unwrap_or_default();
+                merge_patch(&mut record, &patch);
+                if let Err(message) = store::conform(self.store.schema(), &table, &record) {
+                    return Response::error(400, format!("record `{id}`: {message}"));
+                }
+                patched.push(record);
+            }
+        }
+        let count = ids.len();
+        let mut summary = json!({ "table": table.name, "action": verb, "count": count, "ids": ids });
+        if dry_run {
+            summary["dryRun"] = json!(true);
+            return Response::json(200, &summary);
+        }
+        match confirm {
+            Some(confirmed) if confirmed != count => {
+                let message = format!("confirm={confirmed} but {count} records match");
+                return Response::json(409, &json!({ "error": message, "count": count }));
+            }
+            None if count > config::bundled().bulk.confirm_above => {
+                let message =
+                    format!("{count} `{}` records match; repeat with confirm={count} to {verb} them", table.name);
+                return Response::json(428, &json!({ "error": message, "count": count }));
+            }
+            _ => {}
+        }
+
+        let written = match action {
+            WriteAction::Delete => ids.iter().try_for_each(|id| self.store.delete(&table.name, id).map(|_| ())),
+            WriteAction::Update => patched.into_iter().try_for_each(|record| self.store.put(&table.name, record)),
+        };
+        if let Err(err) = written {
+            return Response::error(500, err.to_string());
+        }
+        Response::json(200, &summary)
+    }
+}
+
+/// A table response from [`App::export`] whose body is not encoded yet.
+pub struct Export<'a> {
+    /// Status and headers; the body is empty.
+    pub head: Response,
+    body: Body<'a>,
+}
+
+enum Body<'a> {
+    Encoded(Vec<u8>),
+    Rows(Box<Encoding<'a>>),
+}
+
+/// Rows still to render, and how to encode them.
+struct Encoding<'a> {
+    format: Format,
+    schema: &'a Schema,
+    table: &'a TypeDef,
+    rows: Rows<'a>,
+    controls: Controls,
+    export: ExportConfig,
+}
+
+impl Export<'_> {
+    fn encoded(mut response: Response) -> Self {
+        let body = std::mem::take(&mut response.body);
+        Export { head: response, body: Body::Encoded(body) }
+    }
+
+    /// Writes the body to `out`. Once the head has been sent, an error can
+    /// only cut the body short.
+    pub fn write_to<W: Write + Send>(self, mut out: W) -> Result<(), ExportError> {
+        match self.body {
+            Body::Encoded(bytes) => Ok(out.write_all(&bytes)?),
+            Body::Rows(encoding) => {
+                let Encoding { format, schema, table, rows, controls, export } = *encoding;
+                format::write(format, schema, table, rows, &controls, &export, out)
+            }
+        }
+    }
+
+    /// The head with the whole body, or a 500 if encoding fails.
+    pub fn into_response(self) -> Response {
+        let mut response = self.head.clone();
+        match self.write_to(&mut response.body) {
+            Ok(()) => response,
+            Err(err) => Response::error(500, err.to_string()),
+        }
+    }
+}
+
+/// Parameters of the table endpoints that are not part of the FIQL query.
+const TABLE_PARAMS: &[&str] = &["format", "arrays", "subscribe"];
+const WRITE_PARAMS: &[&str] = &["dryRun", "confirm"];
+
+/// Checks the plan estimate of `query` against
+/// `[package.metadata.app.budget]`. The REST endpoints and the WebSocket
+/// session both go through here, so they refuse the same queries.
+pub fn query_within_budget(store: &Store, table: &TypeDef, query: &Query) -> Result<Estimate, OverBudget> {
+    estimate_within_budget(store::plan(store, table, query).estimate)
+}
+
+/// [`query_within_budget`] for an estimate already summed over several
+/// queries.
+pub fn estimate_within_budget(estimate: Estimate) -> Result<Estimate, OverBudget> {
+    estimate.within(config::bundled().budget.max_cost)
+}
+
+fn over_budget(over: OverBudget) -> Response {
+    Response::json(over.status(), &over.to_json())
+}
+
+/// Parses a table request, setting `params` aside.
+fn parse_target(req: &Request, params: &[&str]) -> Result<(PathQuery, Vec<(String, String)>), Response> {
+    let (query, params) = split_params(&req.query, params);
+    let target = if query.is_empty() { req.path.clone() } else { format!("{}?{query}", req.path) };
+    match fiql::parse_path(&target) {
+        Ok(parsed) => Ok((parsed, params)),
+        Err(err) => Err(parse_error(&target, &err)),
+    }
+}
@@ -0,0 +1,143 @@
+//! Bearer-token authentication for writes.
+//!
+//! Reads are public. Writes carry `Authorization: Bearer <jwt>`, an HS256
+//! token signed with the secret named by `[package.metadata.auth.jwt]`.
+//! The token's role claim is matched against the role rules in the same
+//! section; see [`AuthConfig::allows`].
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use serde_json::{Value as Json, json};
+use sha2::Sha256;
+
+use crate::config::AuthConfig;
+use crate::http::Request;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Who a request was made by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// The token's `sub` claim.
+    pub subject: Option<String>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    /// No JWT secret is configured, so no token can be checked.
+    NotConfigured,
+    MissingToken,
+    InvalidToken(&'static str),
+    Expired,
+}
+
+impl AuthError {
+    pub fn status(&self) -> u16 {
+        match self {
+            AuthError::NotConfigured => 503,
+            _ => 401,
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::NotConfigured => f.write_str("writes are disabled: no JWT secret is configured"),
+            AuthError::MissingToken => f.write_str("writes need an `Authorization: Bearer <token>` header"),
+            AuthError::InvalidToken(why) => write!(f, "invalid token: {why}"),
+            AuthError::Expired => f.write_str("token has expired"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+#[derive(Clone)]
+pub struct Authenticator {
+    secret: Option<Vec<u8>>,
+    role_claim: String,
+}
+
+impl fmt::Debug for Authenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authenticator")
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("role_claim", &self.role_claim)
+            .finish()
+    }
+}
+
+impl Authenticator {
+    /// Reads the secret from the environment variable `config` names.
+    pub fn from_config(config: &AuthConfig) -> Self {
+        let secret = config.jwt.as_ref().and_then(|jwt| std::env::var(&jwt.secret_env).ok()).filter(|s| !s.is_empty());
+        Authenticator::new(config, secret.map(String::into_bytes))
+    }
+
+    pub fn new(config: &AuthConfig, secret: Option<Vec<u8>>) -> Self {
+        let role_claim = config.jwt.as_ref().map_or("role", |jwt| jwt.role_claim.as_str()).to_string();
+        Authenticator { secret, role_claim }
+    }
+
+    /// Signs `claims` as an HS256 token, e.g. to mint one for local testing.
+    pub fn sign(&self, claims: &Json) -> Result<String, AuthError> {
+        let secret = self.secret.as_deref().ok_or(AuthError::NotConfigured)?;
+        let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "HS256", "typ": "JWT" }).to_string());
+        let signing_input = format!("{header}.{}", URL_SAFE_NO_PAD.encode(claims.to_string()));
+        let signature = URL_SAFE_NO_PAD.encode(mac(secret, &signing_input).finalize().into_bytes());
+        Ok(format!("{signing_input}.{signature}"))
+    }
+
+    /// Verifies the request's bearer token and reads its roles.
+    pub fn authenticate(&self, req: &Request) -> Result<Principal, AuthError> {
+        let secret = self.secret.as_deref().ok_or(AuthError::NotConfigured)?;
+        let token = req
+            .header("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer ").or_else(|| h.strip_prefix("bearer ")))
+            .map(str::trim)
+            .ok_or(AuthError::MissingToken)?;
+
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(AuthError::InvalidToken("not a JWT"));
+        };
+        let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| AuthError::InvalidToken("bad encoding"));
+        let header: Json =
+            serde_json::from_slice(&decode(header)?).map_err(|_| AuthError::InvalidToken("bad header"))?;
+        if header["alg"] != "HS256" {
+            return Err(AuthError::InvalidToken("only HS256 tokens are accepted"));
+        }
+        mac(secret, &token[..token.len() - signature.len() - 1])
+            .verify_slice(&decode(signature)?)
+            .map_err(|_| AuthError::InvalidToken("bad signature"))?;
+
+        let claims: Json =
+            serde_json::from_slice(&decode(payload)?).map_err(|_| AuthError::InvalidToken("bad claims"))?;
+        if let Some(exp) = claims.get("exp").and_then(Json::as_u64) {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            if exp <= now {
+                return Err(AuthError::Expired);
+            }
+        }
+        let roles = match claims.get(&self.role_claim) {
+            Some(Json::String(role)) => vec![role.clone()],
+            Some(Json::Array(roles)) => roles.iter().filter_map(Json::as_str).map(str::to_string).collect(),
+            _ => Vec::new(),
+        };
+        Ok(Principal { subject: claims.get("sub").and_then(Json::as_str).map(str::to_string), roles })
+    }
+}
+
+fn mac(secret: &[u8], input: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(input.as_bytes());
+    mac
+}
@@ -0,0 +1,189 @@
+//! App settings from `Cargo.toml` metadata.
+//!
+//! The platform reads `[package.metadata.app]` to deploy the app; the
+//! sections below it configure the crate's own resources, and
+//! `[package.metadata.auth]` holds login and role rules. The manifest is
+//! embedded at build time, so the settings always match the deployed build.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+/// Settings from the manifest this crate was built with.
+pub fn bundled() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| Config::from_manifest(include_str!("../Cargo.toml")).expect("bundled manifest is valid"))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub facets: FacetConfig,
+    pub export: ExportConfig,
+    pub bulk: BulkConfig,
+    pub budget: BudgetConfig,
+    /// `[package.metadata.auth]`, a sibling of the app section.
+    #[serde(skip)]
+    pub auth: AuthConfig,
+}
+
+/// `[package.metadata.app.facets]`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FacetConfig {
+    /// Bucket edges per numeric field, e.g. `price = [25, 50, 100]`.
+    pub buckets: BTreeMap<String, Vec<f64>>,
+}
+
+/// `[package.metadata.app.export]`: how CSV cells hold array values such
+/// as `tags`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    pub arrays: ArrayStyle,
+    /// Separator for [`ArrayStyle::Join`].
+    pub separator: String,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        ExportConfig { arrays: ArrayStyle::Join, separator: "|".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArrayStyle {
+    /// `display|4k|popular`.
+    #[default]
+    Join,
+    /// `["display","4k","popular"]`.
+    Json,
+}
+
+impl ArrayStyle {
+    pub fn from_name(name: &str) -> Option<ArrayStyle> {
+        match name {
+            "join" => Some(ArrayStyle::Join),
+            "json" => Some(ArrayStyle::Json),
+            _ => None,
+        }
+    }
+}
+
+/// `[package.metadata.app.bulk]`: guard rails for PATCH/DELETE by filter.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BulkConfig {
+    /// Writes matching more records than this need `confirm=<count>`.
+    pub confirm_above: usize,
+}
+
+impl Default for BulkConfig {
+    fn default() -> Self {
+        BulkConfig { confirm_above: 10 }
+    }
+}
+
+/// `[package.metadata.app.budget]`: the most a single read may cost.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Queries estimated above this many cost units are rejected with 422;
+    /// one unit is about one comparison against one record.
+    pub max_cost: u64,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        BudgetConfig { max_cost: 2500 }
+    }
+}
+
+/// `[package.metadata.auth]`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Login methods offered by the platform, e.g. `["jwt"]`.
+    pub methods: Vec<String>,
+    pub jwt: Option<JwtConfig>,
+    /// Write rules by role name.
+    pub roles: BTreeMap<String, RoleRules>,
+}
+
+impl AuthConfig {
+    /// Whether any of `roles` may apply `action` to `table`.
+    pub fn allows(&self, roles: &[String], action: WriteAction, table: &str) -> bool {
+        roles.iter().filter_map(|r| self.roles.get(r)).any(|rules| {
+            let tables = match action {
+                WriteAction::Update => &rules.update,
+                WriteAction::Delete => &rules.delete,
+            };
+            tables.iter().any(|t| t == "*" || t == table)
+        })
+    }
+}
+
+/// HS256 bearer tokens.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct JwtConfig {
+    /// Environment variable holding the signing secret; never the secret
+    /// itself.
+    pub secret_env: String,
+    /// Claim listing the caller's roles, as a string or array of strings.
+    #[serde(default = "default_role_claim")]
+    pub role_claim: String,
+}
+
+fn default_role_claim() -> String {
+    "role".to_string()
+}
+
+/// Tables a role may write, by action; `"*"` means every table.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RoleRules {
+    pub update: Vec<String>,
+    pub delete: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteAction {
+    Update,
+    Delete,
+}
+
+impl WriteAction {
+    pub fn name(self) -> &'static str {
+        match self {
+            WriteAction::Update => "update",
+            WriteAction::Delete => "delete",
+        }
+    }
+}
+
+impl Config {
+    /// Reads `[package.metadata.app]` and `[package.metadata.auth]` out of a
+    /// `Cargo.toml`; missing sections fall back to defaults.
+    pub fn from_manifest(manifest: &str) -> Result<Config, toml::de::Error> {
+        #[derive(Deserialize)]
+        struct Manifest {
+            package: Package,
+        }
+        #[derive(Deserialize)]
+        struct Package {
+            #[serde(default)]
+            metadata: Metadata,
+        }
+        #[derive(Default, Deserialize)]
+        struct Metadata {
+            #[serde(default)]
+            app: Config,
+            #[serde(default)]
+            auth: AuthConfig,
+        }
+        let metadata = toml::from_str::<Manifest>(manifest)?.package.metadata;
+        Ok(Config { auth: metadata.auth, ..metadata.app })
+    }
+}
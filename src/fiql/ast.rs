@@ -0,0 +1,515 @@
+//! Typed syntax tree produced by the FIQL parser.
+//!
+//! Every node that came from the query string carries the byte [`Span`] it
+//! was parsed from, so diagnostics can point back into the original URL.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use serde::Serialize;
+
+use super::time::Time;
+
+/// Half-open byte range `start..end` into the parsed input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// A fully parsed query string: the filter expression plus control params.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub filter: Option<Expr>,
+    pub controls: Controls,
+}
+
+/// A parsed REST path such as `/Products/prod-001/` or `/Brand/?name==ViewTech`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathQuery {
+    pub table: String,
+    pub table_span: Span,
+    pub id: Option<String>,
+    pub query: Query,
+}
+
+/// Boolean filter tree. Parentheses are not kept as nodes; grouping is
+/// implied by nesting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Condition(Condition),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    /// Span covering every condition below this node.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Condition(c) => c.span,
+            Expr::And(children) | Expr::Or(children) => {
+                children.iter().map(Expr::span).reduce(Span::to).unwrap_or_default()
+            }
+            Expr::Not(inner) => inner.span(),
+        }
+    }
+
+    /// Visits every condition in source order.
+    pub fn conditions(&self) -> Vec<&Condition> {
+        let mut out = Vec::new();
+        self.collect_conditions(&mut out);
+        out
+    }
+
+    fn collect_conditions<'a>(&'a self, out: &mut Vec<&'a Condition>) {
+        match self {
+            Expr::Condition(c) => out.push(c),
+            Expr::And(children) | Expr::Or(children) => {
+                for child in children {
+                    child.collect_conditions(out);
+                }
+            }
+            Expr::Not(inner) => inner.collect_conditions(out),
+        }
+    }
+}
+
+/// A single `field<op>value` comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub field: FieldPath,
+    pub op: Operator,
+    pub value: Value,
+    /// True when the field was omitted and inherited from the previous
+    /// condition (`price=gt=50&lt=200`).
+    pub inherited: bool,
+    pub span: Span,
+    pub op_span: Span,
+}
+
+/// The most segments a [`FieldPath`] may have. Each relationship hop
+/// multiplies the records one condition reads, so longer chains such as
+/// `products.brand.products.brand.name` are refused when parsed.
+pub const MAX_PATH_DEPTH: usize = 4;
+
+/// Dotted attribute path: `price`, `manufacturer.country`, `brand.name`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldPath {
+    pub segments: Vec<String>,
+    pub span: Span,
+}
+
+impl FieldPath {
+    pub fn root(&self) -> &str {
+        &self.segments[0]
+    }
+
+    pub fn is_nested(&self) -> bool {
+        self.segments.len() > 1
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.segments.join("."))
+    }
+}
+
+/// Comparison operators understood by the platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    /// `==`, also used for `*` wildcards.
+    Eq,
+    /// `===`, equality without type coercion.
+    StrictEq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    /// `=gele=`, closed interval.
+    GeLe,
+    /// `=gelt=`, half-open interval.
+    GeLt,
+    /// `=gtle=`, half-open interval.
+    GtLe,
+    /// `=gtlt=`, open interval.
+    GtLt,
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// `=eqi=`, `==` under Unicode case folding.
+    EqI,
+    /// `=cti=`, `=ct=` under Unicode case folding.
+    ContainsI,
+    /// `=swi=`
+    StartsWithI,
+    /// `=ewi=`
+    EndsWithI,
+    FullText,
+    In,
+    Out,
+    /// `=~=`
+    Regex,
+    /// `=null=true`: the field is missing or null.
+    Null,
+    /// `=exists=true`: the field is present, even if null.
+    Exists,
+    /// `=empty=true`: an empty string, array or object, or a list
+    /// relationship with no records.
+    Empty,
+    /// `=all=a,b`: every value is among the elements; `=all=(filter)`:
+    /// every element matches the filter.
+    All,
+    /// `=any=a,b`: some value is among the elements; `=any=(filter)`: some
+    /// element matches the filter.
+    Any,
+    /// `=none=a,b`: no value is among the elements; `=none=(filter)`: no
+    /// element matches the filter.
+    None,
+    /// `=size=gt=2`: compares the number of elements.
+    Size(Comparison),
+    /// `=near=lat,lon,50km`: a `GeoPoint` within a distance of a point.
+    Near,
+    /// `=within=south,west,north,east`: a `GeoPoint` inside a box.
+    Within,
+    /// `=sim=text,k=10`: the `k` records whose embedding is nearest the
+    /// embedding of `text`.
+    Sim,
+}
+
+impl Operator {
+    pub const ALL: &'static [Operator] = &[
+        Operator::Eq,
+        Operator::StrictEq,
+        Operator::Ne,
+        Operator::Gt,
+        Operator::Ge,
+        Operator::Lt,
+        Operator::Le,
+        Operator::GeLe,
+        Operator::GeLt,
+        Operator::GtLe,
+        Operator::GtLt,
+        Operator::Contains,
+        Operator::StartsWith,
+        Operator::EndsWith,
+        Operator::EqI,
+        Operator::ContainsI,
+        Operator::StartsWithI,
+        Operator::EndsWithI,
+        Operator::FullText,
+        Operator::In,
+        Operator::Out,
+        Operator::Regex,
+        Operator::Null,
+        Operator::Exists,
+        Operator::Empty,
+        Operator::All,
+        Operator::Any,
+        Operator::None,
+        Operator::Size(Comparison::Eq),
+        Operator::Size(Comparison::Ne),
+        Operator::Size(Comparison::Gt),
+        Operator::Size(Comparison::Ge),
+        Operator::Size(Comparison::Lt),
+        Operator::Size(Comparison::Le),
+        Operator::Near,
+        Operator::Within,
+        Operator::Sim,
+    ];
+
+    /// Looks up the operator for the text between the outer `=` delimiters
+    /// of an `=name=` token, e.g. `gt`, `~` or `size=gt`.
+    pub fn from_name(name: &str) -> Option<Operator> {
+        Operator::ALL.iter().copied().find(|op| op.name() == name)
+    }
+
+    /// Short name as reported in resource JSON (`gt`, `in`, `==`).
+    pub fn name(self) -> &'static str {
+        match self {
+            Operator::Eq => "==",
+            Operator::StrictEq => "===",
+            Operator::Ne => "ne",
+            Operator::Gt => "gt",
+            Operator::Ge => "ge",
+            Operator::Lt => "lt",
+            Operator::Le => "le",
+            Operator::GeLe => "gele",
+            Operator::GeLt => "gelt",
+            Operator::GtLe => "gtle",
+            Operator::GtLt => "gtlt",
+            Operator::Contains => "ct",
+            Operator::StartsWith => "sw",
+            Operator::EndsWith => "ew",
+            Operator::EqI => "eqi",
+            Operator::ContainsI => "cti",
+            Operator::StartsWithI => "swi",
+            Operator::EndsWithI => "ewi",
+            Operator::FullText => "ft",
+            Operator::In => "in",
+            Operator::Out => "out",
+            Operator::Regex => "~",
+            Operator::Null => "null",
+            Operator::Exists => "exists",
+            Operator::Empty => "empty",
+            Operator::All => "all",
+            Operator::Any => "any",
+            Operator::None => "none",
+            Operator::Size(Comparison::Eq) => "size",
+            Operator::Size(Comparison::Ne) => "size=ne",
+            Operator::Size(Comparison::Gt) => "size=gt",
+            Operator::Size(Comparison::Ge) => "size=ge",
+            Operator::Size(Comparison::Lt) => "size=lt",
+            Operator::Size(Comparison::Le) => "size=le",
+            Operator::Near => "near",
+            Operator::Within => "within",
+            Operator::Sim => "sim",
+        }
+    }
+
+    /// Token as written in a URL (`=gt=`, `==`).
+    pub fn token(self) -> String {
+        match self {
+            Operator::Eq | Operator::StrictEq => self.name().to_string(),
+            _ => format!("={}=", self.name()),
+        }
+    }
+
+    /// Whether the operator takes a comma-separated list of values.
+    pub fn arity(self) -> Arity {
+        match self {
+            Operator::In | Operator::Out | Operator::All | Operator::Any | Operator::None => Arity::List,
+            Operator::GeLe | Operator::GeLt | Operator::GtLe | Operator::GtLt => Arity::Pair,
+            Operator::Near | Operator::Within | Operator::Sim => Arity::Tuple,
+            _ => Arity::One,
+        }
+    }
+
+    pub fn is_range(self) -> bool {
+        self.arity() == Arity::Pair
+    }
+
+    /// Operators that test whether a value is there rather than compare it;
+    /// their value is `true` or `false`.
+    pub fn takes_flag(self) -> bool {
+        matches!(self, Operator::Null | Operator::Exists | Operator::Empty)
+    }
+
+    /// The case-insensitive form of `==`, `=ct=`, `=sw=` and `=ew=`, which
+    /// an `i:` value prefix selects.
+    pub fn case_insensitive(self) -> Option<Operator> {
+        match self {
+            Operator::Eq => Some(Operator::EqI),
+            Operator::Contains => Some(Operator::ContainsI),
+            Operator::StartsWith => Some(Operator::StartsWithI),
+            Operator::EndsWith => Some(Operator::EndsWithI),
+            _ => None,
+        }
+    }
+
+    pub fn is_case_insensitive(self) -> bool {
+        matches!(self, Operator::EqI | Operator::ContainsI | Operator::StartsWithI | Operator::EndsWithI)
+    }
+
+    /// `=near=` and `=within=`, which match `GeoPoint` fields.
+    pub fn is_geo(self) -> bool {
+        matches!(self, Operator::Near | Operator::Within)
+    }
+
+    /// Operators over the elements of an array or list relationship as a
+    /// whole, which also accept a parenthesized per-element filter.
+    pub fn is_quantifier(self) -> bool {
+        matches!(self, Operator::All | Operator::Any | Operator::None)
+    }
+}
+
+/// How `=size=` compares an element count with its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Comparison {
+    /// Whether `ordering` (of the count against the value) satisfies the
+    /// comparison.
+    pub fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Eq => ordering == Ordering::Equal,
+            Comparison::Ne => ordering != Ordering::Equal,
+            Comparison::Gt => ordering == Ordering::Greater,
+            Comparison::Ge => ordering != Ordering::Less,
+            Comparison::Lt => ordering == Ordering::Less,
+            Comparison::Le => ordering != Ordering::Greater,
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.token())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    One,
+    Pair,
+    List,
+    /// Values whose order matters, such as the latitude, longitude and
+    /// distance of `=near=` or the text and options of `=sim=`.
+    Tuple,
+}
+
+/// Right-hand side of a condition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Single(Scalar),
+    List(Vec<Scalar>),
+    /// `(filter)` after a quantifier: a filter applied to each element, with
+    /// field paths relative to the element.
+    Filter(Box<Expr>),
+}
+
+impl Value {
+    /// The literal values; empty for a [`Value::Filter`].
+    pub fn scalars(&self) -> &[Scalar] {
+        match self {
+            Value::Single(s) => std::slice::from_ref(s),
+            Value::List(items) => items,
+            Value::Filter(_) => &[],
+        }
+    }
+
+    pub fn filter(&self) -> Option<&Expr> {
+        match self {
+            Value::Filter(expr) => Some(expr),
+            _ => None,
+        }
+    }
+}
+
+/// One literal value, decoded from its percent-encoded form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scalar {
+    /// Decoded text with any type prefix removed.
+    pub raw: String,
+    pub literal: Literal,
+    pub prefix: Option<TypePrefix>,
+    pub span: Span,
+}
+
+impl Scalar {
+    /// Untyped string values containing `*` are glob patterns under `==`.
+    pub fn is_wildcard(&self) -> bool {
+        self.prefix.is_none() && matches!(&self.literal, Literal::String(s) if s.contains('*'))
+    }
+}
+
+/// Interpreted value of a scalar after type coercion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    /// A `date:` value, or `now`/`now-7d` written bare.
+    Time(Time),
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::String(s) => f.write_str(s),
+            Literal::Number(n) => write!(f, "{n}"),
+            Literal::Bool(b) => write!(f, "{b}"),
+            Literal::Time(t) => write!(f, "{t}"),
+        }
+    }
+}
+
+/// Explicit `prefix:` coercion on a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypePrefix {
+    Number,
+    String,
+    Date,
+}
+
+impl TypePrefix {
+    pub fn from_name(name: &str) -> Option<TypePrefix> {
+        match name {
+            "number" => Some(TypePrefix::Number),
+            "string" => Some(TypePrefix::String),
+            "date" => Some(TypePrefix::Date),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TypePrefix::Number => "number",
+            TypePrefix::String => "string",
+            TypePrefix::Date => "date",
+        }
+    }
+}
+
+/// Non-filter parameters: projection, ordering, paging and introspection.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Controls {
+    pub select: Option<Vec<SelectField>>,
+    pub sort: Option<Vec<SortKey>>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+    pub pagination: Option<bool>,
+    pub explain: Option<bool>,
+    pub stream: Option<bool>,
+    /// Opaque keyset cursor from a previous page's `nextCursor`.
+    pub after: Option<String>,
+}
+
+/// One projected field, optionally with a nested `{...}` projection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectField {
+    pub name: String,
+    pub children: Vec<SelectField>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub field: FieldPath,
+    pub descending: bool,
+    pub span: Span,
+}
@@ -0,0 +1,53 @@
+//! Percent-encoding helpers for query-string components.
+
+use std::fmt::Write;
+
+use super::ast::Span;
+use super::error::{ErrorKind, ParseError};
+
+/// Decodes `%XX` escapes in `raw`, which starts at byte `offset` of the input.
+pub fn decode(raw: &str, offset: usize) -> Result<String, ParseError> {
+    if !raw.contains('%') {
+        return Ok(raw.to_string());
+    }
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            match hex {
+                Some(b) => {
+                    out.push(b);
+                    i += 3;
+                }
+                None => {
+                    let end = (i + 3).min(bytes.len());
+                    return Err(ParseError::new(ErrorKind::InvalidEscape, Span::new(offset + i, offset + end)));
+                }
+            }
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| ParseError::new(ErrorKind::InvalidUtf8, Span::new(offset, offset + raw.len())))
+}
+
+/// Percent-encodes everything except alphanumerics and `safe`, with
+/// uppercase hex digits.
+pub fn encode(text: &str, safe: &[u8]) -> String {
+    let mut out = String::with_capacity(text.len());
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || safe.contains(&b) {
+            out.push(b as char);
+        } else {
+            let _ = write!(out, "%{b:02X}");
+        }
+    }
+    out
+}
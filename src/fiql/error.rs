@@ -0,0 +1,120 @@
+use std::fmt;
+
+use super::ast::Span;
+
+/// A parse failure with the byte range of the offending input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ErrorKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    UnexpectedEnd,
+    UnexpectedChar(char),
+    UnknownOperator(String),
+    ExpectedOperator,
+    InvalidField(String),
+    /// A field path with more than [`MAX_PATH_DEPTH`](super::MAX_PATH_DEPTH)
+    /// segments.
+    PathTooDeep(String),
+    MissingField,
+    MissingValue,
+    EmptyTerm,
+    UnclosedParen,
+    UnmatchedParen,
+    /// Range operators take exactly two values.
+    WrongValueCount {
+        op: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    InvalidNumber(String),
+    InvalidBoolean(String),
+    InvalidDate(String),
+    /// A latitude outside ±90 or a longitude outside ±180.
+    InvalidCoordinate {
+        value: String,
+        axis: &'static str,
+    },
+    InvalidDistance(String),
+    /// A `=within=` box whose south edge is north of its north edge.
+    InvalidBox,
+    /// `=size=` takes a whole, non-negative number.
+    InvalidCount(String),
+    /// Anything after the text of a `=sim=` value other than one `k=`.
+    InvalidSimilarOption(String),
+    /// An `=ft=` search that cannot be read, with the reason.
+    InvalidSearch(&'static str),
+    InvalidEscape,
+    InvalidUtf8,
+    DuplicateParam(&'static str),
+    /// Control params may only appear in the top-level `&` chain.
+    MisplacedParam(&'static str),
+    UnknownFunction(String),
+    InvalidPath,
+}
+
+impl ParseError {
+    pub fn new(kind: ErrorKind, span: Span) -> Self {
+        ParseError { kind, span }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::UnexpectedEnd => f.write_str("unexpected end of query"),
+            ErrorKind::UnexpectedChar(c) => write!(f, "unexpected character `{c}`"),
+            ErrorKind::UnknownOperator(op) => write!(f, "unknown operator `={op}=`"),
+            ErrorKind::ExpectedOperator => f.write_str("expected an operator such as `==` or `=gt=`"),
+            ErrorKind::InvalidField(field) => write!(f, "`{field}` is not a valid field path"),
+            ErrorKind::PathTooDeep(field) => {
+                write!(f, "`{field}` has more than {} segments; shorten the path", super::MAX_PATH_DEPTH)
+            }
+            ErrorKind::MissingField => f.write_str("condition has no field and no previous field to inherit"),
+            ErrorKind::MissingValue => f.write_str("missing value"),
+            ErrorKind::EmptyTerm => f.write_str("empty term"),
+            ErrorKind::UnclosedParen => f.write_str("unclosed `(`"),
+            ErrorKind::UnmatchedParen => f.write_str("unmatched `)`"),
+            ErrorKind::WrongValueCount { op, expected, found } => {
+                write!(f, "`{op}` takes {expected} values, found {found}")
+            }
+            ErrorKind::InvalidNumber(s) => write!(f, "`{s}` is not a valid number"),
+            ErrorKind::InvalidBoolean(s) => write!(f, "`{s}` is not `true` or `false`"),
+            ErrorKind::InvalidDate(s) => {
+                write!(f, "`{s}` is not a date; write `2024-01-31`, `2024-01-31T09:30:00Z` or `now-7d`")
+            }
+            ErrorKind::InvalidCoordinate { value, axis } => {
+                let limit = if *axis == "latitude" { 90 } else { 180 };
+                write!(f, "`{value}` is not a {axis}; write a number of degrees from -{limit} to {limit}")
+            }
+            ErrorKind::InvalidDistance(s) => write!(f, "`{s}` is not a distance; write `50km`, `800m` or `10mi`"),
+            ErrorKind::InvalidBox => {
+                f.write_str("the box's south edge is north of its north edge; write `=within=south,west,north,east`")
+            }
+            ErrorKind::InvalidCount(s) => write!(f, "`{s}` is not a count; `=size=` takes a whole number like `2`"),
+            ErrorKind::InvalidSimilarOption(s) => write!(
+                f,
+                "`{s}` is not a `=sim=` option; write `k=` and a whole number from 1 to {}",
+                super::similar::MAX_K
+            ),
+            ErrorKind::InvalidSearch(why) => write!(f, "invalid `=ft=` search: {why}"),
+            ErrorKind::InvalidEscape => f.write_str("invalid percent-encoding"),
+            ErrorKind::InvalidUtf8 => f.write_str("percent-encoded bytes are not valid UTF-8"),
+            ErrorKind::DuplicateParam(p) => write!(f, "`{p}` given more than once"),
+            ErrorKind::MisplacedParam(p) => write!(f, "`{p}` must appear at the top level, not inside a group"),
+            ErrorKind::UnknownFunction(name) => write!(f, "unknown function `{name}()`"),
+            ErrorKind::InvalidPath => f.write_str("expected a path like `/Table/` or `/Table/id`"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.span)
+    }
+}
+
+impl std::error::Error for ParseError {}
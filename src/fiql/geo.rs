@@ -0,0 +1,108 @@
+//! Geographic values: the area a `=near=` or `=within=` condition covers.
+//!
+//! `location=near=35.68,139.69,50km` is a circle of 50 km around a
+//! latitude and longitude, measured along the Earth's surface.
+//! `location=within=45.8,5.9,47.8,10.5` is a box given as its south, west,
+//! north and east edges; a west edge east of the east edge crosses the
+//! antimeridian.
+
+use super::ast::{Literal, Operator, Scalar, Span};
+use super::error::{ErrorKind, ParseError};
+
+/// Mean Earth radius in meters (IUGG).
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Units a `=near=` radius is written in, with their length in meters.
+const UNITS: &[(&str, f64)] = &[("km", 1000.0), ("mi", 1609.344), ("m", 1.0)];
+
+/// A latitude and longitude in degrees, stored as `{ "lat": .., "lon": .. }`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Point {
+    /// Great-circle distance to `other` in meters, by the haversine formula.
+    pub fn distance(self, other: Point) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * h.sqrt().min(1.0).asin()
+    }
+}
+
+/// What a `=near=` or `=within=` condition matches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Area {
+    /// Points at most `radius` meters from `center`.
+    Near { center: Point, radius: f64 },
+    /// Points inside a box, edges included.
+    Within { south: f64, west: f64, north: f64, east: f64 },
+}
+
+impl Area {
+    /// Reads the values of a `=near=` (`lat,lon,distance`) or `=within=`
+    /// (`south,west,north,east`) condition written at `span`.
+    pub fn parse(op: Operator, values: &[Scalar], span: Span) -> Result<Area, ParseError> {
+        let expected = if op == Operator::Near { 3 } else { 4 };
+        if values.len() != expected {
+            return Err(ParseError::new(
+                ErrorKind::WrongValueCount { op: op.name(), expected, found: values.len() },
+                span,
+            ));
+        }
+        let degrees = |i: usize, axis: &'static str| {
+            let limit = if axis == "latitude" { 90.0 } else { 180.0 };
+            match values[i].literal {
+                Literal::Number(n) if n.abs() <= limit => Ok(n),
+                _ => Err(ParseError::new(
+                    ErrorKind::InvalidCoordinate { value: values[i].raw.clone(), axis },
+                    values[i].span,
+                )),
+            }
+        };
+        if op == Operator::Near {
+            let center = Point { lat: degrees(0, "latitude")?, lon: degrees(1, "longitude")? };
+            let radius = parse_distance(&values[2].raw)
+                .ok_or_else(|| ParseError::new(ErrorKind::InvalidDistance(values[2].raw.clone()), values[2].span))?;
+            return Ok(Area::Near { center, radius });
+        }
+        let (south, west) = (degrees(0, "latitude")?, degrees(1, "longitude")?);
+        let (north, east) = (degrees(2, "latitude")?, degrees(3, "longitude")?);
+        if south > north {
+            return Err(ParseError::new(ErrorKind::InvalidBox, values[0].span.to(values[2].span)));
+        }
+        Ok(Area::Within { south, west, north, east })
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        match *self {
+            Area::Near { center, radius } => center.distance(point) <= radius,
+            Area::Within { south, west, north, east } => {
+                let across = if west <= east {
+                    (west..=east).contains(&point.lon)
+                } else {
+                    point.lon >= west || point.lon <= east
+                };
+                (south..=north).contains(&point.lat) && across
+            }
+        }
+    }
+
+    /// The point distances are measured from, for `=near=`.
+    pub fn center(&self) -> Option<Point> {
+        match *self {
+            Area::Near { center, .. } => Some(center),
+            Area::Within { .. } => None,
+        }
+    }
+}
+
+/// `50km`, `800m` or `10mi` in meters.
+pub fn parse_distance(text: &str) -> Option<f64> {
+    let (unit, meters) = UNITS.iter().find(|(unit, _)| text.ends_with(unit))?;
+    let amount = super::parser::parse_number(&text[..text.len() - unit.len()])?;
+    (amount >= 0.0).then_some(amount * meters)
+}
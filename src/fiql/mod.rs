@@ -0,0 +1,30 @@
+//! FIQL query-string parsing.
+//!
+//! [`parse`] turns the part of a URL after `?` into a [`Query`]: a boolean
+//! [`Expr`] tree of [`Condition`]s plus the [`Controls`] (`select`, `sort`,
+//! `limit`, ...) that shape the response. [`parse_path`] additionally splits
+//! off the `/Table/id` prefix. [`print`] and [`canonical`] go the other
+//! way, back to a query string. Date values are read into a [`Time`],
+//! `=near=`/`=within=` values into an [`Area`], `=ft=` values into a
+//! [`Search`] and `=sim=` values into a [`Similar`].
+
+mod ast;
+mod encoding;
+mod error;
+mod geo;
+mod parser;
+mod printer;
+mod search;
+mod similar;
+mod time;
+
+pub use ast::*;
+pub use encoding::{decode, encode};
+pub use error::{ErrorKind, ParseError};
+pub use geo::{Area, Point, parse_distance};
+pub(crate) use parser::{field_path, parse_number};
+pub use parser::{parse, parse_path};
+pub use printer::{canonical, normalize, print, print_path};
+pub use search::{Search, Term};
+pub use similar::{DEFAULT_K, MAX_K, Similar};
+pub use time::Time;
@@ -0,0 +1,704 @@
+//! Recursive-descent parser for FIQL query strings.
+//!
+//! Grammar (`&` binds tighter than `|`):
+//!
+//! ```text
+//! or      := and ('|' and)*
+//! and     := unary ('&' unary)*
+//! unary   := '!' '(' or ')' | '(' or ')' | atom
+//! atom    := function | param | condition
+//! function:= ('select' | 'sort' | 'limit') '(' args ')'
+//! param   := control-key '=' value
+//! condition := field? op value | op-name '=' value   (inherited field)
+//! value   := literal (',' literal)* | '(' or ')'      (after =all= =any= =none=)
+//!          | 'i:' literal                            (after == =ct= =sw= =ew=)
+//!          | number ',' number ',' distance          (after =near=)
+//!          | search                                  (after =ft=)
+//!          | text (',' 'k=' count)?                  (after =sim=)
+//! search  := (term | term ('OR' term)+ | ('-' | 'NOT') term)+
+//! term    := word | word '*' | '"' words '"'
+//! ```
+//!
+//! Control params and functions are lifted out of the filter wherever they
+//! appear in an `&` chain outside parentheses. The parser works on the raw,
+//! still percent-encoded input so `%26` and `%7C` stay literal and every
+//! span is a byte offset into the string the caller passed in.
+
+use super::ast::*;
+use super::encoding::decode;
+use super::error::{ErrorKind, ParseError};
+use super::geo::Area;
+use super::search::Search;
+use super::similar::Similar;
+use super::time::Time;
+
+/// Parses the part of a URL after `?`.
+pub fn parse(query: &str) -> Result<Query, ParseError> {
+    Parser::new(query, 0).parse_query()
+}
+
+/// Parses a REST path such as `/Products/prod-001/` or
+/// `/Products/?category==books`. Spans are offsets into `path`.
+pub fn parse_path(path: &str) -> Result<PathQuery, ParseError> {
+    let (route, query_start) = match path.find('?') {
+        Some(i) => (&path[..i], i + 1),
+        None => (path, path.len()),
+    };
+    let segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+    if segments.is_empty() || segments.len() > 2 {
+        return Err(ParseError::new(ErrorKind::InvalidPath, Span::new(0, route.len())));
+    }
+    let segment_offset = |seg: &str| seg.as_ptr() as usize - path.as_ptr() as usize;
+    let table_start = segment_offset(segments[0]);
+    let table_span = Span::new(table_start, table_start + segments[0].len());
+    let table = decode(segments[0], table_start)?;
+    let id = match segments.get(1) {
+        Some(seg) => Some(decode(seg, segment_offset(seg))?),
+        None => None,
+    };
+    let query = Parser::new(path, query_start).parse_query()?;
+    Ok(PathQuery { table, table_span, id, query })
+}
+
+const CONTROL_KEYS: &[&str] = &["select", "sort", "limit", "offset", "pagination", "explain", "stream", "after"];
+const FUNCTIONS: &[&str] = &["select", "sort", "limit"];
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// Parenthesis nesting of the expression currently being parsed.
+    depth: usize,
+    controls: Controls,
+    /// Field of the most recent condition, for inherited attributes.
+    last_field: Option<FieldPath>,
+    /// Most recent control param, used to report one that left an `|`
+    /// branch empty.
+    last_param: Option<(&'static str, Span)>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str, start: usize) -> Self {
+        Parser { src, pos: start, depth: 0, controls: Controls::default(), last_field: None, last_param: None }
+    }
+
+    fn parse_query(mut self) -> Result<Query, ParseError> {
+        if self.at_end() {
+            return Ok(Query::default());
+        }
+        let filter = self.parse_or()?;
+        match self.peek() {
+            Some(b')') => return Err(self.error_here(ErrorKind::UnmatchedParen)),
+            Some(_) => return Err(self.unexpected_char()),
+            None => {}
+        }
+        Ok(Query { filter, controls: self.controls })
+    }
+
+    fn parse_or(&mut self) -> Result<Option<Expr>, ParseError> {
+        let mut branches = Vec::new();
+        let mut empty_branch = None;
+        loop {
+            self.last_param = None;
+            match self.parse_and()? {
+                Some(expr) => branches.push(expr),
+                None => empty_branch = empty_branch.or(self.last_param),
+            }
+            if self.eat(b'|') {
+                continue;
+            }
+            break;
+        }
+        match (branches.len(), empty_branch) {
+            (0, _) => Ok(None),
+            (1, None) => Ok(branches.pop()),
+            (_, Some((name, span))) => Err(ParseError::new(ErrorKind::MisplacedParam(name), span)),
+            _ => Ok(Some(Expr::Or(branches))),
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<Option<Expr>, ParseError> {
+        let mut terms = Vec::new();
+        loop {
+            if let Some(expr) = self.parse_unary()? {
+                terms.push(expr);
+            }
+            if self.eat(b'&') {
+                continue;
+            }
+            break;
+        }
+        Ok(match terms.len() {
+            0 => None,
+            1 => terms.pop(),
+            _ => Some(Expr::And(terms)),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Option<Expr>, ParseError> {
+        match self.peek() {
+            Some(b'!') => {
+                let start = self.pos;
+                self.pos += 1;
+                if self.peek() != Some(b'(') {
+                    return Err(ParseError::new(ErrorKind::UnexpectedChar('!'), Span::new(start, start + 1)));
+                }
+                let inner = self.parse_group()?;
+                Ok(Some(Expr::Not(Box::new(inner))))
+            }
+            Some(b'(') => self.parse_group().map(Some),
+            None | Some(b'&' | b'|' | b')') => Err(self.error_here(ErrorKind::EmptyTerm)),
+            Some(_) => self.parse_atom(),
+        }
+    }
+
+    /// Parses `( or )` with the cursor on the opening parenthesis.
+    fn parse_group(&mut self) -> Result<Expr, ParseError> {
+        let open = self.pos;
+        self.pos += 1;
+        self.depth += 1;
+        let inner = self.parse_or()?;
+        if !self.eat(b')') {
+            return Err(ParseError::new(ErrorKind::UnclosedParen, Span::new(open, open + 1)));
+        }
+        self.depth -= 1;
+        inner.ok_or_else(|| ParseError::new(ErrorKind::EmptyTerm, Span::new(open, self.pos)))
+    }
+
+    fn parse_atom(&mut self) -> Result<Option<Expr>, ParseError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if matches!(c, b'=' | b'&' | b'|' | b'(' | b')' | b'!') {
+                break;
+            }
+            self.pos += 1;
+        }
+        let key = &self.src[start..self.pos];
+        let key_span = Span::new(start, self.pos);
+
+        match self.peek() {
+            Some(b'(') if !key.is_empty() => {
+                self.parse_function(key, key_span)?;
+                Ok(None)
+            }
+            Some(b'=') => {
+                if let Some(name) = CONTROL_KEYS.iter().copied().find(|k| *k == key)
+                    && !self.rest().starts_with("==")
+                {
+                    self.pos += 1;
+                    let value_span = self.scan_value()?;
+                    self.apply_param(name, key_span, value_span)?;
+                    return Ok(None);
+                }
+                self.parse_condition(key, key_span).map(|c| Some(Expr::Condition(c)))
+            }
+            None => Err(ParseError::new(ErrorKind::ExpectedOperator, Span::new(start, self.pos))),
+            Some(_) => Err(self.unexpected_char()),
+        }
+    }
+
+    fn parse_condition(&mut self, key: &str, key_span: Span) -> Result<Condition, ParseError> {
+        let op_start = self.pos;
+        let (mut op, field, inherited, op_span) = match self.scan_operator()? {
+            Some(op) => {
+                let op_span = Span::new(op_start, self.pos);
+                if key.is_empty() {
+                    (op, self.inherit(op_span)?, true, op_span)
+                } else {
+                    (op, field_path(key, key_span)?, false, op_span)
+                }
+            }
+            // `key=value`: a chained comparison like `lt=200` after a previous
+            // condition, otherwise plain equality.
+            None => {
+                self.pos += 1;
+                match Operator::from_name(key) {
+                    Some(op) if self.last_field.is_some() => (op, self.inherit(key_span)?, true, key_span),
+                    _ if key.is_empty() => {
+                        return Err(ParseError::new(ErrorKind::MissingField, Span::new(op_start, self.pos)));
+                    }
+                    _ => (Operator::Eq, field_path(key, key_span)?, false, Span::new(op_start, self.pos)),
+                }
+            }
+        };
+        if op.is_quantifier() && self.peek() == Some(b'(') {
+            // An element filter: its fields are relative to the element, so
+            // nothing is inherited into or out of it.
+            self.last_field = None;
+            let filter = self.parse_group()?;
+            self.last_field = Some(field.clone());
+            return Ok(Condition {
+                field,
+                op,
+                value: Value::Filter(Box::new(filter)),
+                inherited,
+                span: Span::new(key_span.start.min(op_start), self.pos),
+                op_span,
+            });
+        }
+        let mut value_span = self.scan_value()?;
+        // `name==i:viewtech` is sugar for `name=eqi=viewtech`.
+        if let Some(folded) = op.case_insensitive()
+            && self.src[value_span.start..value_span.end].starts_with("i:")
+        {
+            op = folded;
+            value_span.start += 2;
+        }
+        if value_span.is_empty() {
+            return Err(ParseError::new(ErrorKind::MissingValue, value_span));
+        }
+        let value = self.parse_value(op, value_span)?;
+        self.last_field = Some(field.clone());
+        Ok(Condition {
+            field,
+            op,
+            value,
+            inherited,
+            span: Span::new(key_span.start.min(op_start), value_span.end),
+            op_span,
+        })
+    }
+
+    /// Reads `==`, `===`, `=name=` or `=name=sub=` (`=size=gt=`) at the
+    /// cursor. Returns `None` (without consuming) when the `=` is a plain
+    /// `key=value` separator.
+    fn scan_operator(&mut self) -> Result<Option<Operator>, ParseError> {
+        let rest = self.rest();
+        if rest.starts_with("===") {
+            self.pos += 3;
+            return Ok(Some(Operator::StrictEq));
+        }
+        if rest.starts_with("==") {
+            self.pos += 2;
+            return Ok(Some(Operator::Eq));
+        }
+        let name_len = rest[1..].bytes().take_while(|c| c.is_ascii_alphabetic() || *c == b'~').count();
+        if name_len == 0 || rest.as_bytes().get(1 + name_len) != Some(&b'=') {
+            return Ok(None);
+        }
+        let mut name = &rest[1..1 + name_len];
+        let sub_len = rest[name_len + 2..].bytes().take_while(u8::is_ascii_alphabetic).count();
+        let extended = &rest[1..name_len + 2 + sub_len];
+        if sub_len > 0
+            && rest.as_bytes().get(name_len + 2 + sub_len) == Some(&b'=')
+            && Operator::from_name(extended).is_some()
+        {
+            name = extended;
+        }
+        let span = Span::new(self.pos, self.pos + name.len() + 2);
+        let op = Operator::from_name(name)
+            .filter(|op| !matches!(op, Operator::Eq | Operator::StrictEq))
+            .ok_or_else(|| ParseError::new(ErrorKind::UnknownOperator(name.to_string()), span))?;
+        self.pos = span.end;
+        Ok(Some(op))
+    }
+
+    fn inherit(&self, span: Span) -> Result<FieldPath, ParseError> {
+        self.last_field.clone().ok_or_else(|| ParseError::new(ErrorKind::MissingField, span))
+    }
+
+    /// Advances over a value, stopping at `&`, `|` or an unbalanced `)`.
+    /// Balanced parentheses inside the value (`(?i)pro`) are kept, as are
+    /// escaped ones and those in a `[...]` class; one still open at the end
+    /// of the input is an error rather than a value that swallowed every
+    /// term after it.
+    fn scan_value(&mut self) -> Result<Span, ParseError> {
+        let start = self.pos;
+        let mut open = Vec::new();
+        let mut class = false;
+        while let Some(c) = self.peek() {
+            match c {
+                b'\\' if matches!(self.src.as_bytes().get(self.pos + 1), Some(b'(' | b')' | b'[' | b']')) => {
+                    self.pos += 1;
+                }
+                b'[' => class = true,
+                b']' => class = false,
+                b'(' | b')' if class => {}
+                b'(' => open.push(self.pos),
+                b')' if open.is_empty() => break,
+                b')' => {
+                    open.pop();
+                }
+                b'&' | b'|' if open.is_empty() => break,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        match open.first() {
+            Some(&paren) => Err(ParseError::new(ErrorKind::UnclosedParen, Span::new(paren, paren + 1))),
+            None => Ok(Span::new(start, self.pos)),
+        }
+    }
+
+    fn parse_value(&self, op: Operator, span: Span) -> Result<Value, ParseError> {
+        let raw = &self.src[span.start..span.end];
+        match op.arity() {
+            Arity::One if op.takes_flag() => match scalar(raw, span.start, op)? {
+                flag @ Scalar { literal: Literal::Bool(_), prefix: None, .. } => Ok(Value::Single(flag)),
+                other => Err(ParseError::new(ErrorKind::InvalidBoolean(other.raw), span)),
+            },
+            Arity::One if matches!(op, Operator::Size(_)) => match scalar(raw, span.start, op)? {
+                count @ Scalar { literal: Literal::Number(n), .. } if n >= 0.0 && n.fract() == 0.0 => {
+                    Ok(Value::Single(count))
+                }
+                other => Err(ParseError::new(ErrorKind::InvalidCount(other.raw), span)),
+            },
+            Arity::One if op == Operator::FullText => {
+                let text = scalar(raw, span.start, op)?;
+                Search::parse(&text.raw, span)?;
+                Ok(Value::Single(text))
+            }
+            Arity::One => Ok(Value::Single(scalar(raw, span.start, op)?)),
+            arity => {
+                let items = split_top_level(raw, span.start, b',')
+                    .into_iter()
+                    .map(|(part, offset)| {
+                        if part.is_empty() {
+                            Err(ParseError::new(ErrorKind::MissingValue, Span::new(offset, offset)))
+                        } else {
+                            scalar(part, offset, op)
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if arity == Arity::Pair && items.len() != 2 {
+                    return Err(ParseError::new(
+                        ErrorKind::WrongValueCount { op: op.name(), expected: 2, found: items.len() },
+                        span,
+                    ));
+                }
+                if op.is_geo() {
+                    Area::parse(op, &items, span)?;
+                } else if op == Operator::Sim {
+                    Similar::parse(&items, span)?;
+                }
+                Ok(Value::List(items))
+            }
+        }
+    }
+
+    fn parse_function(&mut self, name: &str, name_span: Span) -> Result<(), ParseError> {
+        let name = FUNCTIONS
+            .iter()
+            .copied()
+            .find(|f| *f == name)
+            .ok_or_else(|| ParseError::new(ErrorKind::UnknownFunction(name.to_string()), name_span))?;
+        let open = self.pos;
+        let close = self.src[open..]
+            .find(')')
+            .map(|i| open + i)
+            .ok_or_else(|| ParseError::new(ErrorKind::UnclosedParen, Span::new(open, open + 1)))?;
+        self.pos = close + 1;
+        let args = Span::new(open + 1, close);
+        if name == "limit" {
+            self.check_top_level("limit", name_span)?;
+            let parts = split_top_level(&self.src[args.start..args.end], args.start, b',');
+            let numbers = parts.iter().map(|(part, offset)| parse_u64(part, *offset)).collect::<Result<Vec<_>, _>>()?;
+            match numbers[..] {
+                [limit] => self.set(ControlSlot::Limit, limit, name_span)?,
+                [offset, limit] => {
+                    self.set(ControlSlot::Offset, offset, name_span)?;
+                    self.set(ControlSlot::Limit, limit, name_span)?;
+                }
+                _ => {
+                    return Err(ParseError::new(
+                        ErrorKind::WrongValueCount { op: "limit()", expected: 2, found: numbers.len() },
+                        args,
+                    ));
+                }
+            }
+            return Ok(());
+        }
+        self.apply_param(name, name_span, args)
+    }
+
+    fn apply_param(&mut self, name: &'static str, key_span: Span, value: Span) -> Result<(), ParseError> {
+        self.check_top_level(name, key_span)?;
+        let raw = &self.src[value.start..value.end];
+        let span = key_span.to(value);
+        match name {
+            "select" => {
+                if self.controls.select.is_some() {
+                    return Err(ParseError::new(ErrorKind::DuplicateParam("select"), span));
+                }
+                self.controls.select = Some(parse_select(raw, value.start)?);
+            }
+            "sort" => {
+                if self.controls.sort.is_some() {
+                    return Err(ParseError::new(ErrorKind::DuplicateParam("sort"), span));
+                }
+                self.controls.sort = Some(parse_sort(raw, value.start)?);
+            }
+            "after" => {
+                if self.controls.after.is_some() {
+                    return Err(ParseError::new(ErrorKind::DuplicateParam("after"), span));
+                }
+                self.controls.after = Some(decode(raw, value.start)?);
+            }
+            "limit" => self.set(ControlSlot::Limit, parse_u64(raw, value.start)?, span)?,
+            "offset" => self.set(ControlSlot::Offset, parse_u64(raw, value.start)?, span)?,
+            "pagination" | "explain" | "stream" => {
+                let flag = parse_bool(raw, value.start)?;
+                let slot = match name {
+                    "pagination" => &mut self.controls.pagination,
+                    "explain" => &mut self.controls.explain,
+                    _ => &mut self.controls.stream,
+                };
+                if slot.replace(flag).is_some() {
+                    return Err(ParseError::new(ErrorKind::DuplicateParam(name), span));
+                }
+            }
+            _ => unreachable!("unhandled control param `{name}`"),
+        }
+        self.last_param = Some((name, span));
+        Ok(())
+    }
+
+    fn set(&mut self, slot: ControlSlot, value: u64, span: Span) -> Result<(), ParseError> {
+        let (target, name) = match slot {
+            ControlSlot::Limit => (&mut self.controls.limit, "limit"),
+            ControlSlot::Offset => (&mut self.controls.offset, "offset"),
+        };
+        if target.replace(value).is_some() {
+            return Err(ParseError::new(ErrorKind::DuplicateParam(name), span));
+        }
+        self.last_param = Some((name, span));
+        Ok(())
+    }
+
+    fn check_top_level(&self, name: &'static str, span: Span) -> Result<(), ParseError> {
+        if self.depth > 0 {
+            return Err(ParseError::new(ErrorKind::MisplacedParam(name), span));
+        }
+        Ok(())
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.src.len()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error_here(&self, kind: ErrorKind) -> ParseError {
+        let end = (self.pos + 1).min(self.src.len());
+        ParseError::new(kind, Span::new(self.pos, end))
+    }
+
+    /// The character at the cursor as an [`ErrorKind::UnexpectedChar`],
+    /// spanning all of its bytes.
+    fn unexpected_char(&self) -> ParseError {
+        let c = self.rest().chars().next().unwrap_or('\u{fffd}');
+        ParseError::new(ErrorKind::UnexpectedChar(c), Span::new(self.pos, self.pos + c.len_utf8()))
+    }
+}
+
+enum ControlSlot {
+    Limit,
+    Offset,
+}
+
+/// Splits `raw` on `sep`, ignoring separators nested inside `{}`. Returns
+/// each part with its absolute byte offset.
+fn split_top_level(raw: &str, offset: usize, sep: u8) -> Vec<(&str, usize)> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in raw.bytes().enumerate() {
+        match c {
+            b'{' => depth += 1,
+            b'}' => depth = depth.saturating_sub(1),
+            c if c == sep && depth == 0 => {
+                parts.push((&raw[start..i], offset + start));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push((&raw[start..], offset + start));
+    parts
+}
+
+pub(crate) fn field_path(raw: &str, span: Span) -> Result<FieldPath, ParseError> {
+    let decoded = decode(raw, span.start)?;
+    if decoded.is_empty() || decoded.split('.').any(str::is_empty) {
+        return Err(ParseError::new(ErrorKind::InvalidField(decoded), span));
+    }
+    if decoded.split('.').count() > MAX_PATH_DEPTH {
+        return Err(ParseError::new(ErrorKind::PathTooDeep(decoded), span));
+    }
+    Ok(FieldPath { segments: decoded.split('.').map(str::to_string).collect(), span })
+}
+
+fn scalar(raw: &str, offset: usize, op: Operator) -> Result<Scalar, ParseError> {
+    let span = Span::new(offset, offset + raw.len());
+    let (prefix, body, body_offset) = match raw.split_once(':') {
+        Some((name, body)) => match TypePrefix::from_name(name) {
+            Some(prefix) => (Some(prefix), body, offset + name.len() + 1),
+            None => (None, raw, offset),
+        },
+        None => (None, raw, offset),
+    };
+    let text = decode(body, body_offset)?;
+    let literal = match prefix {
+        Some(TypePrefix::Number) => Literal::Number(
+            parse_number(&text).ok_or_else(|| ParseError::new(ErrorKind::InvalidNumber(text.clone()), span))?,
+        ),
+        Some(TypePrefix::String) => Literal::String(text.clone()),
+        Some(TypePrefix::Date) => Literal::Time(
+            Time::parse(&text).ok_or_else(|| ParseError::new(ErrorKind::InvalidDate(text.clone()), span))?,
+        ),
+        None => implicit_literal(&text, op),
+    };
+    Ok(Scalar { raw: text, literal, prefix, span })
+}
+
+/// Operators that always compare the string form of a value.
+fn is_string_operator(op: Operator) -> bool {
+    matches!(
+        op,
+        Operator::Contains
+            | Operator::StartsWith
+            | Operator::EndsWith
+            | Operator::FullText
+            | Operator::Regex
+            | Operator::Sim
+    ) || op.is_case_insensitive()
+}
+
+/// What an unprefixed value means under `op`: string operators always see
+/// text, everything else reads `true`/`false`, numbers and `now-7d` as
+/// such.
+pub(crate) fn implicit_literal(text: &str, op: Operator) -> Literal {
+    if is_string_operator(op) {
+        return Literal::String(text.to_string());
+    }
+    match text {
+        "true" => Literal::Bool(true),
+        "false" => Literal::Bool(false),
+        _ if text.starts_with("now")
+            && let Some(time) = Time::parse(text) =>
+        {
+            Literal::Time(time)
+        }
+        _ => parse_number(text).map_or_else(|| Literal::String(text.to_string()), Literal::Number),
+    }
+}
+
+/// Parses decimal numbers (`-12`, `4.5`, `1e3`), rejecting the extra forms
+/// `f64::from_str` accepts such as `inf`, `NaN` and `+1`.
+pub(crate) fn parse_number(text: &str) -> Option<f64> {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let starts_with_digit = digits.bytes().next().is_some_and(|c| c.is_ascii_digit());
+    let well_formed = digits.bytes().all(|c| c.is_ascii_digit() || matches!(c, b'.' | b'e' | b'E' | b'-' | b'+'));
+    if !starts_with_digit || !well_formed {
+        return None;
+    }
+    text.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+fn parse_u64(raw: &str, offset: usize) -> Result<u64, ParseError> {
+    let text = decode(raw, offset)?;
+    text.trim()
+        .parse()
+        .map_err(|_| ParseError::new(ErrorKind::InvalidNumber(text.clone()), Span::new(offset, offset + raw.len())))
+}
+
+fn parse_bool(raw: &str, offset: usize) -> Result<bool, ParseError> {
+    match raw {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(ParseError::new(ErrorKind::InvalidBoolean(raw.to_string()), Span::new(offset, offset + raw.len()))),
+    }
+}
+
+fn parse_sort(raw: &str, offset: usize) -> Result<Vec<SortKey>, ParseError> {
+    split_top_level(raw, offset, b',')
+        .into_iter()
+        .map(|(part, start)| {
+            let span = Span::new(start, start + part.len());
+            let (descending, name, name_start) = match part.as_bytes().first() {
+                Some(b'-') => (true, &part[1..], start + 1),
+                Some(b'+') => (false, &part[1..], start + 1),
+                _ => (false, part, start),
+            };
+            if name.is_empty() {
+                return Err(ParseError::new(ErrorKind::EmptyTerm, span));
+            }
+            let field = field_path(name, Span::new(name_start, span.end))?;
+            Ok(SortKey { field, descending, span })
+        })
+        .collect()
+}
+
+fn parse_select(raw: &str, offset: usize) -> Result<Vec<SelectField>, ParseError> {
+    let mut cursor = SelectCursor { src: raw, pos: 0, offset };
+    let fields = cursor.list()?;
+    if cursor.pos < raw.len() {
+        let c = raw[cursor.pos..].chars().next().unwrap_or('}');
+        return Err(ParseError::new(
+            ErrorKind::UnexpectedChar(c),
+            Span::new(offset + cursor.pos, offset + cursor.pos + 1),
+        ));
+    }
+    Ok(fields)
+}
+
+/// Cursor over `name,other{nested,fields}` projection lists.
+struct SelectCursor<'a> {
+    src: &'a str,
+    pos: usize,
+    offset: usize,
+}
+
+impl SelectCursor<'_> {
+    fn list(&mut self) -> Result<Vec<SelectField>, ParseError> {
+        let mut fields = vec![self.field()?];
+        while self.src.as_bytes().get(self.pos) == Some(&b',') {
+            self.pos += 1;
+            fields.push(self.field()?);
+        }
+        Ok(fields)
+    }
+
+    fn field(&mut self) -> Result<SelectField, ParseError> {
+        let start = self.pos;
+        while let Some(c) = self.src.as_bytes().get(self.pos) {
+            if matches!(c, b',' | b'{' | b'}') {
+                break;
+            }
+            self.pos += 1;
+        }
+        let span = Span::new(self.offset + start, self.offset + self.pos);
+        if start == self.pos {
+            return Err(ParseError::new(ErrorKind::EmptyTerm, span));
+        }
+        let name = decode(&self.src[start..self.pos], span.start)?;
+        let mut children = Vec::new();
+        if self.src.as_bytes().get(self.pos) == Some(&b'{') {
+            let open = self.pos;
+            self.pos += 1;
+            children = self.list()?;
+            if self.src.as_bytes().get(self.pos) != Some(&b'}') {
+                return Err(ParseError::new(
+                    ErrorKind::UnclosedParen,
+                    Span::new(self.offset + open, self.offset + open + 1),
+                ));
+            }
+            self.pos += 1;
+        }
+        Ok(SelectField { name, children, span: Span::new(span.start, self.offset + self.pos) })
+    }
+}
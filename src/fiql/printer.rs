@@ -0,0 +1,274 @@
+//! AST → query string.
+//!
+//! [`print`] writes a [`Query`] back out with consistent percent-encoding,
+//! the minimum parentheses the precedence rules need (`&` binds tighter
+//! than `|`), explicit field names instead of inherited ones, and control
+//! params in `key=value` form sorted by key. [`normalize`] rewrites a query
+//! into a canonical equivalent first, so [`canonical`] gives the same string
+//! for queries that differ only in spelling or operand order.
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+use super::ast::*;
+use super::encoding::encode;
+use super::parser::implicit_literal;
+
+/// Prints `query` without changing its structure.
+pub fn print(query: &Query) -> String {
+    let mut parts = Vec::new();
+    if let Some(filter) = &query.filter {
+        parts.push(print_expr(filter, Context::Top));
+    }
+    parts.extend(print_controls(&query.controls));
+    parts.join("&")
+}
+
+/// Prints a path query as `/Table/`, `/Table/id/` or `/Table/?query`.
+pub fn print_path(path: &PathQuery) -> String {
+    let mut out = format!("/{}/", encode(&path.table, PATH_SAFE));
+    if let Some(id) = &path.id {
+        let _ = write!(out, "{}/", encode(id, PATH_SAFE));
+    }
+    let query = print(&path.query);
+    if !query.is_empty() {
+        out.push('?');
+        out.push_str(&query);
+    }
+    out
+}
+
+/// `print(normalize(query))`: a stable key for semantically equal queries.
+pub fn canonical(query: &Query) -> String {
+    print(&normalize(query.clone()))
+}
+
+/// Rewrites `query` into canonical form:
+///
+/// - nested `&`/`|` chains are flattened, single-child groups unwrapped and
+///   `!(!(x))` reduced to `x`;
+/// - a lower and an upper bound on the same field in one `&` chain merge
+///   into a range (`price=gt=50&lt=200` → `price=gtlt=50,200`);
+/// - `=in=`/`=out=` values and the operands of every `&`/`|` are sorted and
+///   deduplicated;
+/// - redundant type prefixes (`number:499.99`) are dropped;
+/// - `offset=0` is removed.
+pub fn normalize(mut query: Query) -> Query {
+    query.filter = query.filter.map(normalize_expr);
+    if query.controls.offset == Some(0) {
+        query.controls.offset = None;
+    }
+    query
+}
+
+fn normalize_expr(expr: Expr) -> Expr {
+    match expr {
+        Expr::Condition(c) => Expr::Condition(normalize_condition(c)),
+        Expr::Not(inner) => match normalize_expr(*inner) {
+            Expr::Not(double) => *double,
+            other => Expr::Not(Box::new(other)),
+        },
+        Expr::And(children) => {
+            let mut flat = flatten(children, true);
+            flat = merge_ranges(flat);
+            rebuild(flat, Expr::And)
+        }
+        Expr::Or(children) => rebuild(flatten(children, false), Expr::Or),
+    }
+}
+
+fn flatten(children: Vec<Expr>, and: bool) -> Vec<Expr> {
+    let mut out = Vec::with_capacity(children.len());
+    for child in children.into_iter().map(normalize_expr) {
+        match child {
+            Expr::And(grand) if and => out.extend(grand),
+            Expr::Or(grand) if !and => out.extend(grand),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn rebuild(mut children: Vec<Expr>, make: fn(Vec<Expr>) -> Expr) -> Expr {
+    children.sort_by_cached_key(|e| print_expr(e, Context::Top));
+    children.dedup_by(|a, b| print_expr(a, Context::Top) == print_expr(b, Context::Top));
+    if children.len() == 1 { children.pop().expect("one child") } else { make(children) }
+}
+
+fn normalize_condition(mut c: Condition) -> Condition {
+    c.inherited = false;
+    let strip = |mut s: Scalar| {
+        if s.prefix.is_some() && implicit_literal(&s.raw, c.op) == s.literal {
+            s.prefix = None;
+        }
+        s
+    };
+    c.value = match c.value {
+        Value::Single(s) => Value::Single(strip(s)),
+        Value::List(items) => {
+            let mut items: Vec<Scalar> = items.into_iter().map(strip).collect();
+            if c.op.arity() == Arity::List {
+                items.sort_by(|a, b| compare_literals(&a.literal, &b.literal));
+                items.dedup_by(|a, b| a.literal == b.literal);
+            }
+            Value::List(items)
+        }
+        Value::Filter(filter) => Value::Filter(Box::new(normalize_expr(*filter))),
+    };
+    c
+}
+
+fn compare_literals(a: &Literal, b: &Literal) -> Ordering {
+    match (a, b) {
+        (Literal::Number(x), Literal::Number(y)) => x.total_cmp(y),
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+/// Merges exactly one lower bound (`gt`/`ge`) and one upper bound
+/// (`lt`/`le`) per field into a range condition at the lower bound's place.
+fn merge_ranges(mut terms: Vec<Expr>) -> Vec<Expr> {
+    let bound = |e: &Expr, lower: bool| match e {
+        Expr::Condition(c) if lower => matches!(c.op, Operator::Gt | Operator::Ge),
+        Expr::Condition(c) => matches!(c.op, Operator::Lt | Operator::Le),
+        _ => false,
+    };
+    let field_of = |e: &Expr| match e {
+        Expr::Condition(c) => Some(c.field.segments.clone()),
+        _ => None,
+    };
+    let mut i = 0;
+    while i < terms.len() {
+        if bound(&terms[i], true) {
+            let field = field_of(&terms[i]);
+            let same = |e: &Expr, lower| bound(e, lower) && field_of(e) == field;
+            let lowers = terms.iter().filter(|e| same(e, true)).count();
+            let uppers: Vec<usize> = (0..terms.len()).filter(|&j| same(&terms[j], false)).collect();
+            if lowers == 1 && uppers.len() == 1 {
+                let Expr::Condition(upper) = terms.remove(uppers[0]) else { unreachable!() };
+                if uppers[0] < i {
+                    i -= 1;
+                }
+                let Expr::Condition(lower) = &mut terms[i] else { unreachable!() };
+                lower.op = match (lower.op, upper.op) {
+                    (Operator::Ge, Operator::Le) => Operator::GeLe,
+                    (Operator::Ge, _) => Operator::GeLt,
+                    (_, Operator::Le) => Operator::GtLe,
+                    _ => Operator::GtLt,
+                };
+                let Value::Single(low) = lower.value.clone() else { unreachable!() };
+                let Value::Single(high) = upper.value else { unreachable!() };
+                lower.value = Value::List(vec![low, high]);
+                lower.span = lower.span.to(upper.span);
+            }
+        }
+        i += 1;
+    }
+    terms
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Context {
+    Top,
+    And,
+    Or,
+}
+
+fn print_expr(expr: &Expr, ctx: Context) -> String {
+    match expr {
+        Expr::Condition(c) => print_condition(c),
+        Expr::Not(inner) => format!("!({})", print_expr(inner, Context::Top)),
+        Expr::And(children) => children.iter().map(|c| print_expr(c, Context::And)).collect::<Vec<_>>().join("&"),
+        Expr::Or(children) => {
+            let body = children.iter().map(|c| print_expr(c, Context::Or)).collect::<Vec<_>>().join("|");
+            // `|` only needs grouping when it sits inside an `&` chain.
+            if ctx == Context::And { format!("({body})") } else { body }
+        }
+    }
+}
+
+fn print_condition(c: &Condition) -> String {
+    let field = c.field.segments.iter().map(|s| encode(s, FIELD_SAFE)).collect::<Vec<_>>().join(".");
+    let value = match c.value.filter() {
+        Some(filter) => format!("({})", print_expr(filter, Context::Top)),
+        None => c.value.scalars().iter().map(|s| print_scalar(s, c.op)).collect::<Vec<_>>().join(","),
+    };
+    format!("{field}{}{value}", c.op.token())
+}
+
+fn print_scalar(s: &Scalar, op: Operator) -> String {
+    let body = match &s.literal {
+        Literal::Number(n) => format_number(*n),
+        Literal::Bool(b) => b.to_string(),
+        Literal::String(text) => encode(text, VALUE_SAFE),
+        Literal::Time(time) => encode(&time.to_string(), VALUE_SAFE),
+    };
+    // A value needs a prefix if one was written, or if it would otherwise
+    // be read back as a different type (the string "true" under `==`).
+    let prefix = s.prefix.or_else(|| match &s.literal {
+        Literal::String(text) if implicit_literal(text, op) != s.literal => Some(TypePrefix::String),
+        Literal::Number(n) if implicit_literal(&format_number(*n), op) != s.literal => Some(TypePrefix::Number),
+        _ => None,
+    });
+    match prefix {
+        Some(p) => format!("{}:{body}", p.name()),
+        None => body,
+    }
+}
+
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 9.0e15 { format!("{}", n as i64) } else { format!("{n}") }
+}
+
+fn print_controls(c: &Controls) -> Vec<String> {
+    let mut out = Vec::new();
+    if let Some(after) = &c.after {
+        out.push(format!("after={}", encode(after, VALUE_SAFE)));
+    }
+    if let Some(explain) = c.explain {
+        out.push(format!("explain={explain}"));
+    }
+    if let Some(limit) = c.limit {
+        out.push(format!("limit={limit}"));
+    }
+    if let Some(offset) = c.offset {
+        out.push(format!("offset={offset}"));
+    }
+    if let Some(pagination) = c.pagination {
+        out.push(format!("pagination={pagination}"));
+    }
+    if let Some(select) = &c.select {
+        out.push(format!("select={}", print_select(select)));
+    }
+    if let Some(sort) = &c.sort {
+        let keys: Vec<String> = sort
+            .iter()
+            .map(|k| {
+                let field = k.field.segments.iter().map(|s| encode(s, FIELD_SAFE)).collect::<Vec<_>>().join(".");
+                if k.descending { format!("-{field}") } else { field }
+            })
+            .collect();
+        out.push(format!("sort={}", keys.join(",")));
+    }
+    if let Some(stream) = c.stream {
+        out.push(format!("stream={stream}"));
+    }
+    out
+}
+
+fn print_select(fields: &[SelectField]) -> String {
+    fields
+        .iter()
+        .map(|f| {
+            let name = encode(&f.name, FIELD_SAFE);
+            if f.children.is_empty() { name } else { format!("{name}{{{}}}", print_select(&f.children)) }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Bytes left unescaped in values besides ASCII alphanumerics. `*` stays
+/// literal because it is the wildcard.
+const VALUE_SAFE: &[u8] = b"-._~*";
+const FIELD_SAFE: &[u8] = b"-_~$";
+const PATH_SAFE: &[u8] = b"-._~";
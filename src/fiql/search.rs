@@ -0,0 +1,115 @@
+//! The value of an `=ft=` condition: which words a text must, may or must
+//! not contain.
+//!
+//! Words separated by spaces must all match. `OR` between two terms lets
+//! either do, `-word` or `NOT word` excludes a word, `"two words"` matches
+//! the words next to each other in that order and `monit*` matches any word
+//! starting with `monit`. `description=ft=wireless OR bluetooth -charging`
+//! finds wireless or bluetooth products that say nothing about charging.
+//!
+//! Words are kept as written; the store splits and lowercases them the way
+//! it splits the field's text.
+
+use super::ast::Span;
+use super::error::{ErrorKind, ParseError};
+
+/// One thing an `=ft=` search looks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Word(String),
+    /// `monit*`: any word starting with the text.
+    Prefix(String),
+    /// `"wireless charging"`: the words in order, next to each other.
+    Phrase(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    /// Every group needs one of its terms to match.
+    pub required: Vec<Vec<Term>>,
+    /// None of these may match.
+    pub excluded: Vec<Term>,
+}
+
+impl Search {
+    /// Reads the text of an `=ft=` value written at `span`.
+    pub fn parse(text: &str, span: Span) -> Result<Search, ParseError> {
+        let invalid = |why: &'static str| ParseError::new(ErrorKind::InvalidSearch(why), span);
+        let mut items = Vec::new();
+        let mut rest = text.trim_start();
+        while !rest.is_empty() {
+            if let Some(negated) = rest.strip_prefix('-').filter(|r| r.starts_with(|c: char| !c.is_whitespace())) {
+                items.push(Item::Not);
+                rest = negated;
+                continue;
+            }
+            let (item, after) = match rest.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted.find('"').ok_or_else(|| invalid("a phrase is missing its closing `\"`"))?;
+                    (Item::Term(Term::Phrase(quoted[..end].to_string())), &quoted[end + 1..])
+                }
+                None => {
+                    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                    (Item::read(&rest[..end]).ok_or_else(|| invalid("`*` can only end a word"))?, &rest[end..])
+                }
+            };
+            items.push(item);
+            rest = after.trim_start();
+        }
+
+        let mut search = Search { required: Vec::new(), excluded: Vec::new() };
+        let mut items = items.into_iter().peekable();
+        while let Some(item) = items.next() {
+            match item {
+                Item::Or => return Err(invalid("`OR` needs a term on each side")),
+                Item::Not => match items.next() {
+                    Some(Item::Term(term)) => search.excluded.push(term),
+                    _ => return Err(invalid("`NOT` and `-` need a term after them")),
+                },
+                Item::Term(term) => {
+                    let mut group = vec![term];
+                    while items.next_if_eq(&Item::Or).is_some() {
+                        match items.next() {
+                            Some(Item::Term(term)) => group.push(term),
+                            _ => return Err(invalid("`OR` needs a term on each side")),
+                        }
+                    }
+                    search.required.push(group);
+                }
+            }
+        }
+        if search.required.is_empty() && search.excluded.is_empty() {
+            return Err(ParseError::new(ErrorKind::MissingValue, span));
+        }
+        Ok(search)
+    }
+
+    /// Every term the search looks for, required or excluded.
+    pub fn terms(&self) -> impl Iterator<Item = &Term> {
+        self.required.iter().flatten().chain(&self.excluded)
+    }
+}
+
+/// A search split at spaces, before `OR` and `NOT` are applied.
+#[derive(Debug, PartialEq)]
+enum Item {
+    Term(Term),
+    Or,
+    Not,
+}
+
+impl Item {
+    /// `None` for a `*` anywhere but the end of a word.
+    fn read(word: &str) -> Option<Item> {
+        Some(match word {
+            "OR" => Item::Or,
+            "NOT" => Item::Not,
+            _ => match word.strip_suffix('*') {
+                Some(prefix) if prefix.is_empty() || prefix.contains('*') => return None,
+                Some(prefix) => Item::Term(Term::Prefix(prefix.to_string())),
+                None if word.contains('*') => return None,
+                None => Item::Term(Term::Word(word.to_string())),
+            },
+        })
+    }
+}
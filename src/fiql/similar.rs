@@ -0,0 +1,41 @@
+//! The value of a `=sim=` condition: the text to compare with and how many
+//! nearest records to keep.
+//!
+//! `description=sim=wireless%20audio,k=5` keeps the five records whose
+//! description embedding is nearest the embedding of "wireless audio".
+//! Without `k=` the ten nearest are kept.
+
+use super::ast::{Scalar, Span};
+use super::error::{ErrorKind, ParseError};
+
+/// Nearest records kept when the value gives no `k=`.
+pub const DEFAULT_K: usize = 10;
+
+/// Largest `k=` accepted.
+pub const MAX_K: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Similar {
+    pub text: String,
+    pub k: usize,
+}
+
+impl Similar {
+    /// Reads the values of a `=sim=` condition written at `span`: the text,
+    /// then options.
+    pub fn parse(values: &[Scalar], span: Span) -> Result<Similar, ParseError> {
+        let Some((text, options)) = values.split_first() else {
+            return Err(ParseError::new(ErrorKind::MissingValue, span));
+        };
+        let mut k = None;
+        for option in options {
+            let count = match option.raw.split_once('=') {
+                Some(("k", count)) if k.is_none() => count.parse::<usize>().ok().filter(|k| (1..=MAX_K).contains(k)),
+                _ => None,
+            };
+            let invalid = || ParseError::new(ErrorKind::InvalidSimilarOption(option.raw.clone()), option.span);
+            k = Some(count.ok_or_else(invalid)?);
+        }
+        Ok(Similar { text: text.raw.clone(), k: k.unwrap_or(DEFAULT_K) })
+    }
+}
@@ -0,0 +1,105 @@
+//! Date and time literals: `date:2024-01-31`, `date:2024-01-31T09:30:00Z`
+//! and the relative `now`, `now-7d`, `now+2h`.
+//!
+//! A day compares with an instant by the instant's UTC date, so
+//! `createdAt=le=date:2024-06-30` keeps everything written on the 30th.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, SecondsFormat, TimeDelta, Utc};
+
+/// A point in time as written in a query or stored in a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Time {
+    /// `2024-01-31`: a whole UTC day.
+    Day(NaiveDate),
+    /// `2024-01-31T09:30:00Z`, or any RFC 3339 offset, held in UTC.
+    At(DateTime<Utc>),
+    /// `now` shifted by this many seconds, fixed when the query runs.
+    Now(i64),
+}
+
+/// Units of a relative offset, largest first: weeks, days, hours, minutes
+/// and seconds.
+const UNITS: &[(char, i64)] = &[('w', 604_800), ('d', 86_400), ('h', 3_600), ('m', 60), ('s', 1)];
+
+impl Time {
+    /// Reads a day, an RFC 3339 instant or a `now` expression.
+    pub fn parse(text: &str) -> Option<Time> {
+        if let Some(offset) = text.strip_prefix("now") {
+            return relative(offset).map(Time::Now);
+        }
+        Time::parse_stored(text)
+    }
+
+    /// Reads a day or an RFC 3339 instant, the forms records hold.
+    pub fn parse_stored(text: &str) -> Option<Time> {
+        if text.len() == 10 {
+            return NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().map(Time::Day);
+        }
+        DateTime::parse_from_rfc3339(text).ok().map(|t| Time::At(t.with_timezone(&Utc)))
+    }
+
+    /// Fixes a `now` expression to an instant.
+    pub fn resolve(self, now: DateTime<Utc>) -> Time {
+        match self {
+            Time::Now(seconds) => Time::At(now + TimeDelta::seconds(seconds)),
+            fixed => fixed,
+        }
+    }
+
+    /// Orders two resolved times, by UTC date when either is a day.
+    pub fn compare(self, other: Time) -> Option<Ordering> {
+        match (self, other) {
+            (Time::Day(a), Time::Day(b)) => Some(a.cmp(&b)),
+            (Time::Day(a), Time::At(b)) => Some(a.cmp(&b.date_naive())),
+            (Time::At(a), Time::Day(b)) => Some(a.date_naive().cmp(&b)),
+            (Time::At(a), Time::At(b)) => Some(a.cmp(&b)),
+            (Time::Now(_), _) | (_, Time::Now(_)) => None,
+        }
+    }
+
+    /// Seconds since the Unix epoch, at the start of a day.
+    pub fn timestamp(self) -> Option<i64> {
+        match self {
+            Time::Day(day) => Some(day.and_hms_opt(0, 0, 0)?.and_utc().timestamp()),
+            Time::At(at) => Some(at.timestamp()),
+            Time::Now(_) => None,
+        }
+    }
+}
+
+/// `""`, `-7d` or `+2h` after `now`, in seconds.
+fn relative(offset: &str) -> Option<i64> {
+    if offset.is_empty() {
+        return Some(0);
+    }
+    let sign = match offset.as_bytes()[0] {
+        b'-' => -1,
+        b'+' => 1,
+        _ => return None,
+    };
+    let unit = offset.chars().last()?;
+    let (_, seconds) = UNITS.iter().find(|(u, _)| *u == unit)?;
+    let amount = &offset[1..offset.len() - 1];
+    if amount.is_empty() || !amount.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    amount.parse::<i64>().ok()?.checked_mul(*seconds)?.checked_mul(sign)
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Time::Day(day) => write!(f, "{}", day.format("%Y-%m-%d")),
+            Time::At(at) => f.write_str(&at.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            Time::Now(0) => f.write_str("now"),
+            Time::Now(seconds) => {
+                let sign = if *seconds < 0 { '-' } else { '+' };
+                let (unit, size) = UNITS.iter().find(|(_, size)| seconds % size == 0).unwrap_or(&('s', 1));
+                write!(f, "now{sign}{}{unit}", seconds.abs() / size)
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Arrow IPC and Parquet encodings.
+//!
+//! Column types come from the table's GraphQL definition, not from the rows,
+//! so every export of a table has the same schema: `Float!` is a non-null
+//! `Float64`, `Int` a nullable `Int32`, `ID` and `String` are `Utf8`, `Date`
+//! is `Date32`, `DateTime` a millisecond UTC `Timestamp`, `GeoPoint` a
+//! struct of `lat` and `lon` `Float64`s, a selected `@embedding` a list
+//! of `Float64`s, a selected `_score` a non-null `Float64`, and a selected
+//! relationship is a struct column (a list of structs for reverse joins).
+//! Values the schema does not describe, such as the `manufacturer` object
+//! behind a `String` field or `_highlight`, are written as JSON text.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, BooleanArray, Date32Array, Float64Array, Int32Array, ListArray, RecordBatch, StringArray, StructArray,
+    TimestampMillisecondArray,
+};
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Fields, Schema as ArrowSchema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use serde_json::Value as Json;
+
+use crate::fiql::{SelectField, Time};
+use crate::schema::{FieldDef, ScalarType, Schema, TypeDef};
+use crate::store::SCORE;
+
+/// Rows per record batch, and per Parquet row group, with `stream=true`.
+pub const BATCH_ROWS: usize = 1024;
+
+#[derive(Debug)]
+pub enum ExportError {
+    /// Writing to the output failed, e.g. the client went away.
+    Io(std::io::Error),
+    Arrow(ArrowError),
+    Parquet(ParquetError),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(err) => write!(f, "export failed: {err}"),
+            ExportError::Arrow(err) => write!(f, "arrow export failed: {err}"),
+            ExportError::Parquet(err) => write!(f, "parquet export failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<std::io::Error> for ExportError {
+    fn from(err: std::io::Error) -> Self {
+        ExportError::Io(err)
+    }
+}
+
+impl From<ArrowError> for ExportError {
+    fn from(err: ArrowError) -> Self {
+        ExportError::Arrow(err)
+    }
+}
+
+impl From<ParquetError> for ExportError {
+    fn from(err: ParquetError) -> Self {
+        ExportError::Parquet(err)
+    }
+}
+
+/// The Arrow schema of `table` rows under `select`; without a projection,
+/// every field stored in the record (not relationships or embeddings) in
+/// declaration order.
+pub fn arrow_schema(schema: &Schema, table: &TypeDef, select: Option<&[SelectField]>) -> SchemaRef {
+    Arc::new(ArrowSchema::new(columns(schema, table, select)))
+}
+
+/// Writes `rows` as an Arrow IPC stream. With `stream`, rows are read in
+/// [`BATCH_ROWS`] record batches, each encoded and written to `out` before
+/// the next is read; otherwise as one batch.
+pub fn write_arrow<W: Write>(
+    out: W,
+    schema: SchemaRef,
+    rows: impl IntoIterator<Item = Json>,
+    stream: bool,
+) -> Result<(), ExportError> {
+    let mut writer = StreamWriter::try_new(out, &schema)?;
+    for chunk in chunks(rows, stream) {
+        writer.write(&batch(&schema, &chunk)?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes `rows` as a Parquet file. With `stream`, each [`BATCH_ROWS`]
+/// batch is flushed to `out` as its own row group before the next is read.
+pub fn write_parquet<W: Write + Send>(
+    out: W,
+    schema: SchemaRef,
+    rows: impl IntoIterator<Item = Json>,
+    stream: bool,
+) -> Result<(), ExportError> {
+    let group = if stream { BATCH_ROWS } else { usize::MAX };
+    let props = WriterProperties::builder().set_max_row_group_size(group).build();
+    let mut writer = ArrowWriter::try_new(out, schema.clone(), Some(props))?;
+    for chunk in chunks(rows, stream) {
+        writer.write(&batch(&schema, &chunk)?)?;
+        if stream {
+            writer.flush()?;
+        }
+    }
+    writer.close()?;
+    Ok(())
+}
+
+/// `rows` in batches of [`BATCH_ROWS`] with `stream`, otherwise all in one;
+/// no batch at all when there are no rows.
+fn chunks(rows: impl IntoIterator<Item = Json>, stream: bool) -> impl Iterator<Item = Vec<Json>> {
+    let mut rows = rows.into_iter();
+    let size = if stream { BATCH_ROWS } else { usize::MAX };
+    std::iter::from_fn(move || Some(rows.by_ref().take(size).collect::<Vec<_>>()).filter(|chunk| !chunk.is_empty()))
+}
+
+fn columns(schema: &Schema, table: &TypeDef, select: Option<&[SelectField]>) -> Fields {
+    match select {
+        None => table
+            .fields
+            .iter()
+            .filter(|f| f.relationship().is_none() && f.embedding().is_none())
+            .map(|f| column(schema, f, &[]))
+            .collect(),
+        Some(select) => select
+            .iter()
+            .map(|s| match table.field(&s.name) {
+                Some(f) => column(schema, f, &s.children),
+                None if s.name == SCORE => Field::new(SCORE, DataType::Float64, false),
+                None => Field::new(&s.name, DataType::Utf8, true),
+            })
+            .collect(),
+    }
+}
+
+fn column(schema: &Schema, field: &FieldDef, children: &[SelectField]) -> Field {
+    if field.relationship().is_some() {
+        // A relationship without a sub-selection projects the whole target row.
+        let fields = schema
+            .table(&field.ty.name)
+            .map(|t| columns(schema, t, Some(children).filter(|c| !c.is_empty())))
+            .unwrap_or_else(Fields::empty);
+        let ty = DataType::Struct(fields);
+        let ty = if field.ty.list { DataType::new_list(ty, true) } else { ty };
+        return Field::new(&field.name, ty, true);
+    }
+    let ty = match field.scalar() {
+        _ if let Some(object) = schema.object(&field.ty.name) => {
+            DataType::Struct(columns(schema, object, Some(children).filter(|c| !c.is_empty())))
+        }
+        _ if !children.is_empty() => DataType::Utf8,
+        Some(ScalarType::Float) => DataType::Float64,
+        Some(ScalarType::Int) => DataType::Int32,
+        Some(ScalarType::Boolean) => DataType::Boolean,
+        Some(ScalarType::Date) => DataType::Date32,
+        Some(ScalarType::DateTime) => DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+        Some(ScalarType::GeoPoint) => {
+            DataType::Struct(["lat", "lon"].into_iter().map(|axis| Field::new(axis, DataType::Float64, true)).collect())
+        }
+        Some(ScalarType::Id | ScalarType::String) | None => DataType::Utf8,
+    };
+    let ty = if field.ty.list { DataType::new_list(ty, true) } else { ty };
+    Field::new(&field.name, ty, !field.ty.non_null)
+}
+
+fn batch(schema: &SchemaRef, rows: &[Json]) -> Result<RecordBatch, ArrowError> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|f| array(f.data_type(), &rows.iter().map(|r| r.get(f.name())).collect::<Vec<_>>()))
+        .collect::<Result<_, _>>()?;
+    RecordBatch::try_new(schema.clone(), columns)
+}
+
+/// One column from per-row values; `None`, `null` and values of the wrong
+/// type are nulls. A non-null field holding a null fails the batch.
+fn array(ty: &DataType, values: &[Option<&Json>]) -> Result<ArrayRef, ArrowError> {
+    let values: Vec<Option<&Json>> = values.iter().map(|v| v.filter(|v| !v.is_null())).collect();
+    Ok(match ty {
+        DataType::Float64 => Arc::new(values.iter().map(|v| v.and_then(Json::as_f64)).collect::<Float64Array>()),
+        DataType::Int32 => Arc::new(
+            values.iter().map(|v| v.and_then(Json::as_i64).and_then(|n| i32::try_from(n).ok())).collect::<Int32Array>(),
+        ),
+        DataType::Boolean => Arc::new(values.iter().map(|v| v.and_then(Json::as_bool)).collect::<BooleanArray>()),
+        DataType::Date32 => Arc::new(
+            values
+                .iter()
+                .map(|v| Some((stored_time(*v)?.timestamp()?).div_euclid(86_400) as i32))
+                .collect::<Date32Array>(),
+        ),
+        DataType::Timestamp(_, zone) => Arc::new(
+            values
+                .iter()
+                .map(|v| match stored_time(*v)? {
+                    Time::At(at) => Some(at.timestamp_millis()),
+                    day => day.timestamp().map(|s| s * 1000),
+                })
+                .collect::<TimestampMillisecondArray>()
+                .with_timezone_opt(zone.clone()),
+        ),
+        DataType::Struct(fields) => {
+            let children = fields
+                .iter()
+                .map(|f| {
+                    array(f.data_type(), &values.iter().map(|v| v.and_then(|v| v.get(f.name()))).collect::<Vec<_>>())
+                })
+                .collect::<Result<_, _>>()?;
+            let nulls = NullBuffer::from(values.iter().map(|v| v.is_some_and(Json::is_object)).collect::<Vec<_>>());
+            Arc::new(StructArray::try_new(fields.clone(), children, Some(nulls))?)
+        }
+        DataType::List(item) => {
+            let lists: Vec<Option<&Vec<Json>>> = values.iter().map(|v| v.and_then(Json::as_array)).collect();
+            let items: Vec<Option<&Json>> = lists.iter().flatten().flat_map(|l| l.iter().map(Some)).collect();
+            let offsets = OffsetBuffer::from_lengths(lists.iter().map(|l| l.map_or(0, Vec::len)));
+            let nulls = NullBuffer::from(lists.iter().map(Option::is_some).collect::<Vec<_>>());
+            Arc::new(ListArray::try_new(item.clone(), offsets, array(item.data_type(), &items)?, Some(nulls))?)
+        }
+        _ => Arc::new(
+            values
+                .iter()
+                .map(|v| {
+                    v.map(|v| match v {
+                        Json::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                })
+                .collect::<StringArray>(),
+        ),
+    })
+}
+
+fn stored_time(value: Option<&Json>) -> Option<Time> {
+    value.and_then(Json::as_str).and_then(Time::parse_stored)
+}
@@ -0,0 +1,245 @@
+//! Response encodings for table queries besides JSON.
+//!
+//! Arrow and Parquet are typed from the schema; see [`columnar`]. CSV
+//! flattens each row: nested objects and relationship projections become
+//! dotted columns (`manufacturer.country`, `brand.name`) and arrays are
+//! written per [`ExportConfig`]. Columns follow `select=` order; without a
+//! projection they follow the schema's declaration order, like the Arrow
+//! columns. NDJSON writes each row as is, one per line.
+//!
+//! [`write`] encodes straight to a writer. NDJSON, and Arrow and Parquet
+//! with `stream=true`, read their rows as they go, so a large export never
+//! holds more than a batch of rendered rows; JSON and CSV need every row
+//! first, CSV to find the columns of objects the schema does not describe.
+
+mod columnar;
+
+pub use columnar::{BATCH_ROWS, ExportError, arrow_schema, write_arrow, write_parquet};
+
+use std::io::Write;
+
+use serde_json::{Map, Value as Json};
+
+use crate::config::{ArrayStyle, ExportConfig};
+use crate::fiql::{Controls, SelectField};
+use crate::schema::{Schema, TypeDef};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+    Ndjson,
+    Arrow,
+    Parquet,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "ndjson" => Some(Format::Ndjson),
+            "arrow" => Some(Format::Arrow),
+            "parquet" => Some(Format::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Ndjson => "application/x-ndjson",
+            Format::Arrow => "application/vnd.apache.arrow.stream",
+            Format::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    /// `format=` wins; otherwise the first media type in `Accept` that has
+    /// an encoding, defaulting to JSON.
+    pub fn negotiate(param: Option<&str>, accept: Option<&str>) -> Result<Format, String> {
+        if let Some(name) = param {
+            return Format::from_name(name)
+                .ok_or_else(|| format!("unknown format `{name}`; expected json, csv, ndjson, arrow or parquet"));
+        }
+        let media = accept.unwrap_or("").split(',').map(|m| m.split(';').next().unwrap_or("").trim());
+        Ok(media
+            .filter_map(|m| match m {
+                "application/json" => Some(Format::Json),
+                "text/csv" => Some(Format::Csv),
+                "application/x-ndjson" => Some(Format::Ndjson),
+                "application/vnd.apache.arrow.stream" => Some(Format::Arrow),
+                "application/vnd.apache.parquet" => Some(Format::Parquet),
+                _ => None,
+            })
+            .next()
+            .unwrap_or(Format::Json))
+    }
+}
+
+/// Encodes the result rows of a `table` query in `format`.
+pub fn encode(
+    format: Format,
+    schema: &Schema,
+    table: &TypeDef,
+    rows: &[Json],
+    controls: &Controls,
+    export: &ExportConfig,
+) -> Result<Vec<u8>, ExportError> {
+    let mut out = Vec::new();
+    write(format, schema, table, rows.iter().cloned(), controls, export, &mut out)?;
+    Ok(out)
+}
+
+/// Writes the result rows of a `table` query to `out` in `format`, reading
+/// `rows` only as fast as the format encodes them.
+pub fn write<W: Write + Send>(
+    format: Format,
+    schema: &Schema,
+    table: &TypeDef,
+    rows: impl IntoIterator<Item = Json>,
+    controls: &Controls,
+    export: &ExportConfig,
+    mut out: W,
+) -> Result<(), ExportError> {
+    let select = controls.select.as_deref();
+    let stream = controls.stream == Some(true);
+    match format {
+        Format::Json => {
+            out.write_all(&serde_json::to_vec(&rows.into_iter().collect::<Vec<_>>()).expect("JSON values serialize"))?
+        }
+        Format::Csv => {
+            out.write_all(to_csv(schema, table, &rows.into_iter().collect::<Vec<_>>(), select, export).as_bytes())?
+        }
+        Format::Ndjson => {
+            for row in rows {
+                writeln!(out, "{row}")?;
+            }
+        }
+        Format::Arrow => write_arrow(out, arrow_schema(schema, table, select), rows, stream)?,
+        Format::Parquet => write_parquet(out, arrow_schema(schema, table, select), rows, stream)?,
+    }
+    Ok(())
+}
+
+pub fn to_ndjson(rows: &[Json]) -> String {
+    rows.iter().map(|r| format!("{r}\n")).collect()
+}
+
+/// RFC 4180 CSV with a header row.
+pub fn to_csv(
+    schema: &Schema,
+    table: &TypeDef,
+    rows: &[Json],
+    select: Option<&[SelectField]>,
+    export: &ExportConfig,
+) -> String {
+    let flat: Vec<Map<String, Json>> = rows
+        .iter()
+        .map(|r| {
+            let mut out = Map::new();
+            flatten(r, "", &mut out);
+            out
+        })
+        .collect();
+
+    let mut seen: Vec<&String> = Vec::new();
+    for key in flat.iter().flat_map(|row| row.keys()) {
+        if !seen.contains(&key) {
+            seen.push(key);
+        }
+    }
+    let mut order = Vec::new();
+    match select {
+        None => schema_columns(schema, table, "", &mut order),
+        Some(select) => selected_columns(select, "", &mut order),
+    }
+    let mut columns = Vec::new();
+    for column in order {
+        // An object the schema does not break down (`select=manufacturer`,
+        // a `GeoPoint`) expands into its flattened keys.
+        let nested = format!("{column}.");
+        let expanded: Vec<String> = seen.iter().filter(|k| k.starts_with(&nested)).map(|k| k.to_string()).collect();
+        if expanded.is_empty() { columns.push(column) } else { columns.extend(expanded) }
+    }
+
+    let mut out = String::new();
+    push_record(&mut out, columns.clone());
+    for row in &flat {
+        push_record(&mut out, columns.iter().map(|c| cell(row.get(c), export)).collect());
+    }
+    out
+}
+
+/// Dotted leaf paths of the fields stored in a `def` record (not
+/// relationships or embeddings), in declaration order.
+fn schema_columns(schema: &Schema, def: &TypeDef, prefix: &str, out: &mut Vec<String>) {
+    for f in def.fields.iter().filter(|f| f.relationship().is_none() && f.embedding().is_none()) {
+        let name = format!("{prefix}{}", f.name);
+        match schema.object(&f.ty.name) {
+            Some(object) => schema_columns(schema, object, &format!("{name}."), out),
+            None => out.push(name),
+        }
+    }
+}
+
+/// Dotted leaf paths of a projection, in order.
+fn selected_columns(fields: &[SelectField], prefix: &str, out: &mut Vec<String>) {
+    for f in fields {
+        let name = format!("{prefix}{}", f.name);
+        if f.children.is_empty() {
+            out.push(name);
+        } else {
+            selected_columns(&f.children, &format!("{name}."), out);
+        }
+    }
+}
+
+/// Objects flatten into dotted keys. An array of objects (a reverse join
+/// such as `products{name}`) flattens into one array per dotted key; a
+/// scalar already at that key joins the array.
+fn flatten(value: &Json, prefix: &str, out: &mut Map<String, Json>) {
+    match value {
+        Json::Object(map) => {
+            for (k, v) in map {
+                flatten(v, &format!("{prefix}{k}."), out);
+            }
+        }
+        Json::Array(items) if items.iter().any(Json::is_object) => {
+            for item in items {
+                let mut inner = Map::new();
+                flatten(item, prefix, &mut inner);
+                for (k, v) in inner {
+                    match out.entry(k).or_insert_with(|| Json::Array(Vec::new())) {
+                        Json::Array(values) => values.push(v),
+                        scalar => *scalar = Json::Array(vec![scalar.take(), v]),
+                    }
+                }
+            }
+        }
+        other => {
+            out.insert(prefix.trim_end_matches('.').to_string(), other.clone());
+        }
+    }
+}
+
+fn cell(value: Option<&Json>, export: &ExportConfig) -> String {
+    match value {
+        None | Some(Json::Null) => String::new(),
+        Some(Json::String(s)) => s.clone(),
+        Some(Json::Array(items)) => match export.arrays {
+            ArrayStyle::Json => Json::Array(items.clone()).to_string(),
+            ArrayStyle::Join => items.iter().map(|v| cell(Some(v), export)).collect::<Vec<_>>().join(&export.separator),
+        },
+        Some(other) => other.to_string(),
+    }
+}
+
+fn push_record(out: &mut String, cells: Vec<String>) {
+    let cells: Vec<String> = cells
+        .into_iter()
+        .map(|c| if c.contains([',', '"', '\n', '\r']) { format!("\"{}\"", c.replace('"', "\"\"")) } else { c })
+        .collect();
+    out.push_str(&cells.join(","));
+    out.push_str("\r\n");
+}
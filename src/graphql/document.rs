@@ -0,0 +1,577 @@
+//! Tokenizer and parser for GraphQL executable documents: operations,
+//! fragments, selections, arguments, variables and directives.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Line and column of a token, both from 1, as GraphQL errors report them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize)]
+pub struct Pos {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub pos: Pos,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "syntax error: {}", self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Document {
+    pub operations: Vec<Operation>,
+    pub fragments: Vec<Fragment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub kind: OperationKind,
+    pub name: Option<String>,
+    pub variables: Vec<VariableDef>,
+    pub selection: Vec<Selection>,
+    pub pos: Pos,
+}
+
+/// `$first: Int = 10`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableDef {
+    pub name: String,
+    pub ty: TypeRef,
+    pub default: Option<Value>,
+    pub pos: Pos,
+}
+
+/// A variable's type: `Int`, `[ID!]!`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeRef {
+    Named(String),
+    List(Box<TypeRef>),
+    NonNull(Box<TypeRef>),
+}
+
+impl fmt::Display for TypeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeRef::Named(name) => f.write_str(name),
+            TypeRef::List(inner) => write!(f, "[{inner}]"),
+            TypeRef::NonNull(inner) => write!(f, "{inner}!"),
+        }
+    }
+}
+
+/// `fragment Card on Products { ... }`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub name: String,
+    pub on: String,
+    pub selection: Vec<Selection>,
+    pub pos: Pos,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selection {
+    Field(Field),
+    /// `...Card`
+    Spread {
+        name: String,
+        directives: Vec<Directive>,
+        pos: Pos,
+    },
+    /// `... on Brand { ... }`, or `... @include(if: $x) { ... }` without a
+    /// type condition.
+    Inline {
+        on: Option<String>,
+        directives: Vec<Directive>,
+        selection: Vec<Selection>,
+        pos: Pos,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub alias: Option<String>,
+    pub name: String,
+    pub args: Vec<(String, Value)>,
+    pub directives: Vec<Directive>,
+    pub selection: Vec<Selection>,
+    pub pos: Pos,
+}
+
+impl Field {
+    /// The key the field's value goes under in the response.
+    pub fn key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn arg(&self, name: &str) -> Option<&Value> {
+        self.args.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+}
+
+/// `@include(if: $x)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    pub name: String,
+    pub args: Vec<(String, Value)>,
+    pub pos: Pos,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Variable(String),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    Null,
+    Enum(String),
+    List(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    /// `!`, `$`, `(`, `)`, `:`, `=`, `@`, `[`, `]`, `{`, `|` or `}`.
+    Punct(char),
+    /// `...`
+    Spread,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "`{name}`"),
+            Token::Int(n) => write!(f, "`{n}`"),
+            Token::Float(x) => write!(f, "`{x}`"),
+            Token::Str(_) => f.write_str("a string"),
+            Token::Punct(c) => write!(f, "`{c}`"),
+            Token::Spread => f.write_str("`...`"),
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<Document, SyntaxError> {
+    let tokens = tokenize(src)?;
+    let end = tokens.last().map_or(Pos { line: 1, column: 1 }, |(_, pos)| *pos);
+    let mut p = Parser { tokens, pos: 0, end };
+    let mut document = Document::default();
+    if p.at_end() {
+        return Err(p.error("the document holds no operation"));
+    }
+    while !p.at_end() {
+        match p.peek() {
+            Some(Token::Punct('{')) => {
+                let pos = p.here();
+                let selection = p.selection_set()?;
+                document.operations.push(Operation {
+                    kind: OperationKind::Query,
+                    name: None,
+                    variables: Vec::new(),
+                    selection,
+                    pos,
+                });
+            }
+            Some(Token::Name(name)) if name == "fragment" => document.fragments.push(p.fragment()?),
+            Some(Token::Name(_)) => document.operations.push(p.operation()?),
+            _ => return Err(p.unexpected("an operation or fragment")),
+        }
+    }
+    Ok(document)
+}
+
+fn tokenize(src: &str) -> Result<Vec<(Token, Pos)>, SyntaxError> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let (mut i, mut line, mut line_start) = (0, 1, 0);
+    while i < chars.len() {
+        let c = chars[i];
+        let pos = Pos { line, column: i - line_start + 1 };
+        let error = |message: &str| SyntaxError { message: message.to_string(), pos };
+        match c {
+            '\n' => {
+                i += 1;
+                line += 1;
+                line_start = i;
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            // Commas are insignificant in GraphQL, like whitespace.
+            c if c.is_whitespace() || c == ',' || c == '\u{feff}' => i += 1,
+            '.' => {
+                if chars[i..].starts_with(&['.', '.', '.']) {
+                    tokens.push((Token::Spread, pos));
+                    i += 3;
+                } else {
+                    return Err(error("expected `...`"));
+                }
+            }
+            '!' | '$' | '(' | ')' | ':' | '=' | '@' | '[' | ']' | '{' | '|' | '}' => {
+                tokens.push((Token::Punct(c), pos));
+                i += 1;
+            }
+            '"' if chars[i..].starts_with(&['"', '"', '"']) => {
+                let start = i + 3;
+                let mut end = start;
+                while end < chars.len() && !chars[end..].starts_with(&['"', '"', '"']) {
+                    if chars[end] == '\n' {
+                        line += 1;
+                        line_start = end + 1;
+                    }
+                    end += 1;
+                }
+                if end >= chars.len() {
+                    return Err(error("unterminated block string"));
+                }
+                let text: String = chars[start..end].iter().collect();
+                tokens.push((Token::Str(block_string(&text)), pos));
+                i = end + 3;
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => return Err(error("unterminated string")),
+                        Some('"') => break,
+                        Some('\\') => {
+                            let escaped = match chars.get(i + 1) {
+                                Some('"') => '"',
+                                Some('\\') => '\\',
+                                Some('/') => '/',
+                                Some('b') => '\u{8}',
+                                Some('f') => '\u{c}',
+                                Some('n') => '\n',
+                                Some('r') => '\r',
+                                Some('t') => '\t',
+                                Some('u') => {
+                                    let hex: String = chars.iter().skip(i + 2).take(4).collect();
+                                    let code = u32::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 4);
+                                    let Some(c) = code.and_then(char::from_u32) else {
+                                        return Err(error("invalid `\\u` escape in string"));
+                                    };
+                                    i += 4;
+                                    c
+                                }
+                                _ => return Err(error("invalid escape in string")),
+                            };
+                            text.push(escaped);
+                            i += 2;
+                        }
+                        Some(&c) => {
+                            text.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push((Token::Str(text), pos));
+                i += 1;
+            }
+            c if c == '-' || c.is_ascii_digit() => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '.' | '+' | '-')) {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let token = if text.contains(['.', 'e', 'E']) {
+                    text.parse().ok().filter(|x: &f64| x.is_finite()).map(Token::Float)
+                } else {
+                    text.parse().ok().map(Token::Int)
+                };
+                tokens.push((token.ok_or_else(|| error(&format!("invalid number `{text}`")))?, pos));
+            }
+            c if c == '_' || c.is_ascii_alphabetic() => {
+                let start = i;
+                while i < chars.len() && (chars[i] == '_' || chars[i].is_ascii_alphanumeric()) {
+                    i += 1;
+                }
+                tokens.push((Token::Name(chars[start..i].iter().collect()), pos));
+            }
+            c => return Err(error(&format!("unexpected character `{c}`"))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// A block string's lines with their common indentation and the blank
+/// lines around them removed.
+fn block_string(raw: &str) -> String {
+    let lines: Vec<&str> = raw.lines().collect();
+    let indent = lines
+        .iter()
+        .skip(1)
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> =
+        lines.iter().enumerate().map(|(i, l)| if i == 0 { *l } else { l.get(indent..).unwrap_or("") }).collect();
+    let first = lines.iter().position(|l| !l.trim().is_empty()).unwrap_or(lines.len());
+    let last = lines.iter().rposition(|l| !l.trim().is_empty()).map_or(first, |i| i + 1);
+    lines[first..last].join("\n").replace("\\\"\"\"", "\"\"\"")
+}
+
+struct Parser {
+    tokens: Vec<(Token, Pos)>,
+    pos: usize,
+    /// Where the last token starts, for errors at the end of input.
+    end: Pos,
+}
+
+impl Parser {
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn here(&self) -> Pos {
+        self.tokens.get(self.pos).map_or(self.end, |(_, pos)| *pos)
+    }
+
+    fn error(&self, message: impl Into<String>) -> SyntaxError {
+        SyntaxError { message: message.into(), pos: self.here() }
+    }
+
+    fn unexpected(&self, wanted: &str) -> SyntaxError {
+        match self.peek() {
+            Some(token) => self.error(format!("expected {wanted}, found {token}")),
+            None => self.error(format!("expected {wanted}, found the end of the document")),
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SyntaxError> {
+        if self.eat(c) { Ok(()) } else { Err(self.unexpected(&format!("`{c}`"))) }
+    }
+
+    fn name(&mut self) -> Result<String, SyntaxError> {
+        match self.peek() {
+            Some(Token::Name(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    fn operation(&mut self) -> Result<Operation, SyntaxError> {
+        let pos = self.here();
+        let kind = match self.name()?.as_str() {
+            "query" => OperationKind::Query,
+            "mutation" => OperationKind::Mutation,
+            "subscription" => OperationKind::Subscription,
+            other => {
+                return Err(SyntaxError {
+                    message: format!("expected `query`, `mutation`, `subscription` or `fragment`, found `{other}`"),
+                    pos,
+                });
+            }
+        };
+        let name = match self.peek() {
+            Some(Token::Name(_)) => Some(self.name()?),
+            _ => None,
+        };
+        let mut variables = Vec::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                variables.push(self.variable_def()?);
+            }
+        }
+        self.directives()?;
+        Ok(Operation { kind, name, variables, selection: self.selection_set()?, pos })
+    }
+
+    fn variable_def(&mut self) -> Result<VariableDef, SyntaxError> {
+        let pos = self.here();
+        self.expect('$')?;
+        let name = self.name()?;
+        self.expect(':')?;
+        let ty = self.type_ref()?;
+        let default = if self.eat('=') { Some(self.value(true)?) } else { None };
+        self.directives()?;
+        Ok(VariableDef { name, ty, default, pos })
+    }
+
+    fn type_ref(&mut self) -> Result<TypeRef, SyntaxError> {
+        let ty = if self.eat('[') {
+            let inner = self.type_ref()?;
+            self.expect(']')?;
+            TypeRef::List(Box::new(inner))
+        } else {
+            TypeRef::Named(self.name()?)
+        };
+        Ok(if self.eat('!') { TypeRef::NonNull(Box::new(ty)) } else { ty })
+    }
+
+    fn fragment(&mut self) -> Result<Fragment, SyntaxError> {
+        let pos = self.here();
+        self.name()?;
+        let name = self.name()?;
+        if name == "on" {
+            return Err(SyntaxError { message: "a fragment cannot be named `on`".to_string(), pos });
+        }
+        self.type_condition()?;
+        let on = self.name()?;
+        self.directives()?;
+        Ok(Fragment { name, on, selection: self.selection_set()?, pos })
+    }
+
+    fn type_condition(&mut self) -> Result<(), SyntaxError> {
+        match self.peek() {
+            Some(Token::Name(name)) if name == "on" => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.unexpected("`on`")),
+        }
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Selection>, SyntaxError> {
+        self.expect('{')?;
+        let mut selection = Vec::new();
+        while !self.eat('}') {
+            if self.at_end() {
+                return Err(self.unexpected("`}`"));
+            }
+            selection.push(self.selection()?);
+        }
+        if selection.is_empty() {
+            return Err(self.error("a selection set needs at least one field"));
+        }
+        Ok(selection)
+    }
+
+    fn selection(&mut self) -> Result<Selection, SyntaxError> {
+        let pos = self.here();
+        if self.peek() == Some(&Token::Spread) {
+            self.pos += 1;
+            return Ok(match self.peek() {
+                Some(Token::Name(name)) if name != "on" => {
+                    let name = self.name()?;
+                    Selection::Spread { name, directives: self.directives()?, pos }
+                }
+                _ => {
+                    let on = match self.peek() {
+                        Some(Token::Name(_)) => {
+                            self.type_condition()?;
+                            Some(self.name()?)
+                        }
+                        _ => None,
+                    };
+                    let directives = self.directives()?;
+                    Selection::Inline { on, directives, selection: self.selection_set()?, pos }
+                }
+            });
+        }
+        let mut name = self.name()?;
+        let mut alias = None;
+        if self.eat(':') {
+            alias = Some(name);
+            name = self.name()?;
+        }
+        let args = self.arguments(false)?;
+        let directives = self.directives()?;
+        let selection = if self.peek() == Some(&Token::Punct('{')) { self.selection_set()? } else { Vec::new() };
+        Ok(Selection::Field(Field { alias, name, args, directives, selection, pos }))
+    }
+
+    fn arguments(&mut self, constant: bool) -> Result<Vec<(String, Value)>, SyntaxError> {
+        let mut args = Vec::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                let name = self.name()?;
+                self.expect(':')?;
+                args.push((name, self.value(constant)?));
+            }
+        }
+        Ok(args)
+    }
+
+    fn directives(&mut self) -> Result<Vec<Directive>, SyntaxError> {
+        let mut directives = Vec::new();
+        while self.peek() == Some(&Token::Punct('@')) {
+            let pos = self.here();
+            self.pos += 1;
+            let name = self.name()?;
+            directives.push(Directive { name, args: self.arguments(false)?, pos });
+        }
+        Ok(directives)
+    }
+
+    /// A value; `constant` ones, such as variable defaults, cannot hold
+    /// variables.
+    fn value(&mut self, constant: bool) -> Result<Value, SyntaxError> {
+        let Some(token) = self.peek().cloned() else { return Err(self.unexpected("a value")) };
+        let value = match token {
+            Token::Punct('$') if !constant => {
+                self.pos += 1;
+                return Ok(Value::Variable(self.name()?));
+            }
+            Token::Punct('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                while !self.eat(']') {
+                    items.push(self.value(constant)?);
+                }
+                return Ok(Value::List(items));
+            }
+            Token::Punct('{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                while !self.eat('}') {
+                    let name = self.name()?;
+                    self.expect(':')?;
+                    fields.push((name, self.value(constant)?));
+                }
+                return Ok(Value::Object(fields));
+            }
+            Token::Int(n) => Value::Int(n),
+            Token::Float(x) => Value::Float(x),
+            Token::Str(s) => Value::String(s),
+            Token::Name(name) => match name.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                _ => Value::Enum(name),
+            },
+            _ => return Err(self.unexpected("a value")),
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+}
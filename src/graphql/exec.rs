@@ -0,0 +1,431 @@
+//! Running a validated operation against the store.
+//!
+//! Records are resolved a level at a time: every record a list returned is
+//! resolved together, so a relationship under it is loaded once for all of
+//! them rather than once per record. `Products.brand` fetches each distinct
+//! `brandId` once; `Brand.products` runs a single `brandId=in=(...)` query
+//! for every brand in the list, ANDed with its `filter`, and splits the
+//! rows back out per brand before `after` and `first` apply to each.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{Map, Value as Json, json};
+
+use super::Error;
+use super::document::{Directive, Field, Fragment, Operation, Selection, TypeRef, Value, VariableDef};
+use super::types::{self, CURSOR, Output, Parent, QUERY, TYPENAME};
+use super::validate::collect;
+use crate::config;
+use crate::fiql::{self, Condition, Controls, Expr, FieldPath, Literal, Operator, PathQuery, Query, Scalar, Span};
+use crate::schema::{FieldDef, Relationship, TypeDef};
+use crate::store::{
+    self, Cursor, HIGHLIGHT, QueryOutput, Relevance, SCORE, SortOrder, Store, key_string, primary_key_of,
+};
+
+pub struct Executor<'a> {
+    store: &'a Store,
+    fragments: HashMap<&'a str, &'a Fragment>,
+    variables: Map<String, Json>,
+    pub errors: Vec<Error>,
+    /// Table reads made: one per root field, and one per relationship per
+    /// level however many records it joins.
+    pub queries: usize,
+}
+
+impl<'a> Executor<'a> {
+    /// An executor for `operation` with its variables coerced from
+    /// `given`, or the errors of the variables that do not fit.
+    pub fn new(
+        store: &'a Store,
+        fragments: &'a [Fragment],
+        operation: &Operation,
+        given: &Map<String, Json>,
+    ) -> Result<Self, Vec<Error>> {
+        let mut variables = Map::new();
+        let mut errors = Vec::new();
+        for def in &operation.variables {
+            match coerce(def, given) {
+                Ok(value) => {
+                    variables.insert(def.name.clone(), value);
+                }
+                Err(message) => errors.push(Error::new(message, Some(def.pos))),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let fragments = fragments.iter().map(|f| (f.name.as_str(), f)).collect();
+        Ok(Executor { store, fragments, variables, errors: Vec::new(), queries: 0 })
+    }
+
+    pub fn run(&mut self, operation: &'a Operation) -> Json {
+        let mut data = Map::new();
+        for (key, fields) in self.collect(QUERY, &[&operation.selection]) {
+            let field = fields[0];
+            if field.name == TYPENAME {
+                data.insert(key, json!(QUERY));
+                continue;
+            }
+            let schema = self.store.schema();
+            let Some(Output::Table(table)) = types::field(schema, Parent::Query, &field.name).map(|f| f.output) else {
+                continue;
+            };
+            let path = vec![json!(key)];
+            let value = match self.root(table, field) {
+                Ok((rows, query)) => {
+                    let rows: Vec<&Json> = rows.iter().collect();
+                    let paths: Vec<Vec<Json>> = (0..rows.len()).map(|i| vec![json!(key), json!(i)]).collect();
+                    Json::Array(self.records(table, &rows, &query, &fields, &paths))
+                }
+                Err(message) => {
+                    self.errors.push(Error::new(message, Some(field.pos)).at(path));
+                    Json::Null
+                }
+            };
+            data.insert(key, value);
+        }
+        Json::Object(data)
+    }
+
+    /// The records a root field lists, through the same execution as the
+    /// table endpoint, with the query they came from.
+    fn root(&mut self, table: &TypeDef, field: &Field) -> Result<(Vec<Json>, Query), String> {
+        let mut query = self.list_query(field)?;
+        query.controls.limit = self.first(field)?;
+        query.controls.after = self.arg(field, "after").as_str().map(str::to_string);
+        within_budget(self.store, table, &query)?;
+        self.queries += 1;
+        let path = PathQuery { table: table.name.clone(), table_span: Span::default(), id: None, query };
+        match store::execute(self.store, &path) {
+            Ok(QueryOutput::Records(rows)) => Ok((rows, path.query)),
+            Ok(_) => Ok((Vec::new(), path.query)),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Resolves the fields `fields` select on each of `rows`, records of
+    /// `table` read by `origin`; `paths` are where each lands in the
+    /// response.
+    fn records(
+        &mut self,
+        table: &TypeDef,
+        rows: &[&Json],
+        origin: &Query,
+        fields: &[&'a Field],
+        paths: &[Vec<Json>],
+    ) -> Vec<Json> {
+        let store = self.store;
+        let mut out = vec![Map::new(); rows.len()];
+        let selections: Vec<_> = fields.iter().map(|f| f.selection.as_slice()).collect();
+        let relevance = Relevance::of(origin);
+        let order = SortOrder::of(origin);
+        for (key, selected) in self.collect(&table.name, &selections) {
+            let name = selected[0].name.as_str();
+            let values: Vec<Json> = match name {
+                TYPENAME => rows.iter().map(|_| json!(table.name)).collect(),
+                CURSOR => rows.iter().map(|r| json!(Cursor::of(store, table, r, &order).encode())).collect(),
+                SCORE => rows.iter().map(|r| json!(relevance.score(store, table, r))).collect(),
+                HIGHLIGHT => rows.iter().map(|r| relevance.highlight(table, r)).collect(),
+                _ => {
+                    let Some(def) = table.field(name) else { continue };
+                    match store.schema().type_of(def) {
+                        Some(target) if def.relationship().is_some() => {
+                            let paths: Vec<Vec<Json>> = paths.iter().map(|p| extend(p, [json!(key)])).collect();
+                            self.related(table, def, target, rows, &selected, &paths)
+                        }
+                        Some(object) => rows.iter().map(|r| self.object(object, &r[name], &selected)).collect(),
+                        None if def.embedding().is_some() => {
+                            let select = [fiql::SelectField {
+                                name: name.to_string(),
+                                children: Vec::new(),
+                                span: Span::default(),
+                            }];
+                            rows.iter().map(|r| store::project(store, table, r, &select)[name].clone()).collect()
+                        }
+                        None => rows.iter().map(|r| r[name].clone()).collect(),
+                    }
+                }
+            };
+            for (map, value) in out.iter_mut().zip(values) {
+                map.insert(key.clone(), value);
+            }
+        }
+        out.into_iter().map(Json::Object).collect()
+    }
+
+    /// The value of relationship `def` of `table` for each of `rows`,
+    /// loaded for all of them at once.
+    fn related(
+        &mut self,
+        table: &TypeDef,
+        def: &FieldDef,
+        target: &TypeDef,
+        rows: &[&Json],
+        fields: &[&'a Field],
+        paths: &[Vec<Json>],
+    ) -> Vec<Json> {
+        let store = self.store;
+        let Some(relationship) = def.relationship() else { return vec![Json::Null; rows.len()] };
+        self.queries += 1;
+        match relationship {
+            Relationship::From(fk) => {
+                // Each distinct key is read, and resolved, once.
+                let keys: Vec<Option<String>> = rows.iter().map(|r| r.get(&fk).and_then(key_string)).collect();
+                let mut loaded: BTreeMap<&str, usize> = BTreeMap::new();
+                let (mut targets, mut target_paths) = (Vec::new(), Vec::new());
+                for (key, path) in keys.iter().zip(paths) {
+                    let Some(key) = key.as_deref() else { continue };
+                    if loaded.contains_key(key) {
+                        continue;
+                    }
+                    if let Some(record) = store.get(&target.name, key) {
+                        loaded.insert(key, targets.len());
+                        targets.push(record);
+                        target_paths.push(path.clone());
+                    }
+                }
+                let resolved = self.records(target, &targets, &Query::default(), fields, &target_paths);
+                keys.iter()
+                    .map(|key| key.as_deref().and_then(|k| loaded.get(k)).map_or(Json::Null, |&i| resolved[i].clone()))
+                    .collect()
+            }
+            Relationship::To(fk) => match self.reverse(table, target, &fk, rows, fields[0]) {
+                Ok((groups, query)) => {
+                    let children: Vec<&Json> = groups.iter().flatten().copied().collect();
+                    let child_paths: Vec<Vec<Json>> = groups
+                        .iter()
+                        .zip(paths)
+                        .flat_map(|(group, path)| (0..group.len()).map(|i| extend(path, [json!(i)])))
+                        .collect();
+                    let mut resolved = self.records(target, &children, &query, fields, &child_paths).into_iter();
+                    groups.iter().map(|group| Json::Array(resolved.by_ref().take(group.len()).collect())).collect()
+                }
+                Err(message) => {
+                    let pos = fields[0].pos;
+                    self.errors.extend(paths.iter().map(|p| Error::new(message.clone(), Some(pos)).at(p.clone())));
+                    vec![Json::Null; rows.len()]
+                }
+            },
+        }
+    }
+
+    /// The records of `target` whose `fk` holds the key of each of `rows`,
+    /// in one query, grouped per row and paged by `after` and `first`; with
+    /// the query the rows' cursors and scores are relative to.
+    fn reverse(
+        &self,
+        table: &TypeDef,
+        target: &'a TypeDef,
+        fk: &str,
+        rows: &[&Json],
+        field: &Field,
+    ) -> Result<(Vec<Vec<&'a Json>>, Query), String> {
+        let query = self.list_query(field)?;
+        let first = self.first(field)?;
+        let order = SortOrder::of(&query);
+        let after = match self.arg(field, "after").as_str() {
+            Some(after) => Some(Cursor::decode(after, order.keys.len()).map_err(|err| err.to_string())?),
+            None => None,
+        };
+        let keys: Vec<Option<String>> = rows.iter().map(|r| primary_key_of(table, r)).collect();
+        let mut distinct: Vec<&str> = keys.iter().flatten().map(String::as_str).collect();
+        distinct.sort_unstable();
+        distinct.dedup();
+        if distinct.is_empty() {
+            return Ok((vec![Vec::new(); rows.len()], query));
+        }
+
+        let mut joined = query.clone();
+        let keyed = key_in(fk, &distinct);
+        joined.filter = Some(match joined.filter.take() {
+            Some(filter) => Expr::And(vec![keyed, filter]),
+            None => keyed,
+        });
+        within_budget(self.store, target, &joined)?;
+        let mut by_key: HashMap<String, Vec<&'a Json>> = HashMap::new();
+        for record in store::matching(self.store, target, &joined).map_err(|err| err.to_string())? {
+            if let Some(key) = record.get(fk).and_then(key_string) {
+                by_key.entry(key).or_default().push(record);
+            }
+        }
+        let groups = keys
+            .iter()
+            .map(|key| {
+                let group = key.as_ref().and_then(|k| by_key.get(k)).map_or(&[][..], Vec::as_slice);
+                let after = |r: &&&Json| {
+                    after
+                        .as_ref()
+                        .is_none_or(|c| c.compare(self.store, target, r, &order) == std::cmp::Ordering::Greater)
+                };
+                group.iter().filter(after).take(first.map_or(usize::MAX, |n| n as usize)).copied().collect()
+            })
+            .collect();
+        Ok((groups, query))
+    }
+
+    /// Objects stored inside a record, such as a product's `variants`.
+    fn object(&self, def: &TypeDef, value: &Json, fields: &[&'a Field]) -> Json {
+        match value {
+            Json::Array(items) => Json::Array(items.iter().map(|item| self.object(def, item, fields)).collect()),
+            Json::Object(map) => {
+                let selections: Vec<_> = fields.iter().map(|f| f.selection.as_slice()).collect();
+                let mut out = Map::new();
+                for (key, selected) in self.collect(&def.name, &selections) {
+                    let name = selected[0].name.as_str();
+                    let value = match def.field(name).and_then(|f| self.store.schema().object(&f.ty.name)) {
+                        _ if name == TYPENAME => json!(def.name),
+                        Some(inner) => self.object(inner, map.get(name).unwrap_or(&Json::Null), &selected),
+                        None => map.get(name).cloned().unwrap_or(Json::Null),
+                    };
+                    out.insert(key, value);
+                }
+                Json::Object(out)
+            }
+            _ => Json::Null,
+        }
+    }
+
+    /// The fields `selections` select on `parent` under each response key,
+    /// in order, leaving out what `@skip` and `@include` drop.
+    fn collect(&self, parent: &str, selections: &[&'a [Selection]]) -> Vec<(String, Vec<&'a Field>)> {
+        let mut out: Vec<(String, Vec<&'a Field>)> = Vec::new();
+        let include = |directives: &[Directive]| self.include(directives);
+        for selection in selections {
+            collect(&self.fragments, parent, selection, &include, &mut Vec::new(), &mut |field| match out
+                .iter_mut()
+                .find(|(key, _)| key == field.key())
+            {
+                Some((_, fields)) => fields.push(field),
+                None => out.push((field.key().to_string(), vec![field])),
+            });
+        }
+        out
+    }
+
+    fn include(&self, directives: &[Directive]) -> bool {
+        directives.iter().all(|d| {
+            let cond = d.args.iter().find(|(name, _)| name == "if").map(|(_, v)| self.value(v));
+            let cond = cond.and_then(|v| v.as_bool()).unwrap_or(true);
+            if d.name == "skip" { !cond } else { cond }
+        })
+    }
+
+    /// The `filter` and `sort` arguments of a list field as a query.
+    fn list_query(&self, field: &Field) -> Result<Query, String> {
+        let mut query = match self.arg(field, "filter").as_str() {
+            Some(filter) => fiql::parse(filter).map_err(|err| format!("filter: {err}"))?,
+            None => Query::default(),
+        };
+        if query.controls != Controls::default() {
+            return Err("filter: takes a FIQL filter only; order and page with `sort`, `first` and `after`".to_string());
+        }
+        if let Some(sort) = self.arg(field, "sort").as_str() {
+            let parsed = fiql::parse(&format!("sort={sort}")).map_err(|mut err| {
+                err.span = Span::new(err.span.start.saturating_sub(5), err.span.end.saturating_sub(5));
+                format!("sort: {err}")
+            })?;
+            if parsed.filter.is_some() || parsed.controls.sort.is_none() {
+                return Err(format!("sort: expected fields such as `-price,name`, found `{sort}`"));
+            }
+            query.controls.sort = parsed.controls.sort;
+        }
+        Ok(query)
+    }
+
+    fn first(&self, field: &Field) -> Result<Option<u64>, String> {
+        match self.arg(field, "first") {
+            Json::Null => Ok(None),
+            first => first.as_u64().map(Some).ok_or_else(|| format!("first: expected a whole number, found {first}")),
+        }
+    }
+
+    fn arg(&self, field: &Field, name: &str) -> Json {
+        field.arg(name).map_or(Json::Null, |v| self.value(v))
+    }
+
+    fn value(&self, value: &Value) -> Json {
+        match value {
+            Value::Variable(name) => self.variables.get(name).cloned().unwrap_or(Json::Null),
+            Value::Int(n) => json!(n),
+            Value::Float(x) => json!(x),
+            Value::String(s) | Value::Enum(s) => json!(s),
+            Value::Bool(b) => json!(b),
+            Value::Null => Json::Null,
+            Value::List(items) => Json::Array(items.iter().map(|v| self.value(v)).collect()),
+            Value::Object(fields) => Json::Object(fields.iter().map(|(k, v)| (k.clone(), self.value(v))).collect()),
+        }
+    }
+}
+
+/// `fk=in=(keys)`, built rather than parsed so no key needs escaping.
+fn key_in(fk: &str, keys: &[&str]) -> Expr {
+    let span = Span::default();
+    let scalars =
+        keys.iter().map(|k| Scalar { raw: k.to_string(), literal: Literal::String(k.to_string()), prefix: None, span });
+    Expr::Condition(Condition {
+        field: FieldPath { segments: vec![fk.to_string()], span },
+        op: Operator::In,
+        value: fiql::Value::List(scalars.collect()),
+        inherited: false,
+        span,
+        op_span: span,
+    })
+}
+
+fn within_budget(store: &Store, table: &TypeDef, query: &Query) -> Result<(), String> {
+    let estimate = store::plan(store, table, query).estimate;
+    estimate.within(config::bundled().budget.max_cost).map(|_| ()).map_err(|over| over.to_string())
+}
+
+fn extend(path: &[Json], more: impl IntoIterator<Item = Json>) -> Vec<Json> {
+    path.iter().cloned().chain(more).collect()
+}
+
+/// The value of variable `def` from the request, or its default.
+fn coerce(def: &VariableDef, given: &Map<String, Json>) -> Result<Json, String> {
+    let value = match given.get(&def.name) {
+        Some(value) => value.clone(),
+        None => match &def.default {
+            Some(default) => return Ok(literal(default)),
+            None => Json::Null,
+        },
+    };
+    if fits(&value, &def.ty) {
+        Ok(value)
+    } else if value.is_null() {
+        Err(format!("variable `${}` of type `{}` was not given", def.name, def.ty))
+    } else {
+        Err(format!("variable `${}` of type `{}` cannot be {value}", def.name, def.ty))
+    }
+}
+
+fn fits(value: &Json, ty: &TypeRef) -> bool {
+    match (value, ty) {
+        (Json::Null, TypeRef::NonNull(_)) => false,
+        (Json::Null, _) => true,
+        (value, TypeRef::NonNull(inner)) => fits(value, inner),
+        (Json::Array(items), TypeRef::List(inner)) => items.iter().all(|v| fits(v, inner)),
+        (value, TypeRef::List(inner)) => fits(value, inner),
+        (value, TypeRef::Named(name)) => match name.as_str() {
+            "String" => value.is_string(),
+            "ID" => value.is_string() || value.is_i64(),
+            "Int" => value.as_i64().is_some_and(|n| i32::try_from(n).is_ok()),
+            "Float" => value.is_number(),
+            "Boolean" => value.is_boolean(),
+            _ => false,
+        },
+    }
+}
+
+/// A variable's default, which holds no variables.
+fn literal(value: &Value) -> Json {
+    match value {
+        Value::Variable(_) | Value::Null => Json::Null,
+        Value::Int(n) => json!(n),
+        Value::Float(x) => json!(x),
+        Value::String(s) | Value::Enum(s) => json!(s),
+        Value::Bool(b) => json!(b),
+        Value::List(items) => Json::Array(items.iter().map(literal).collect()),
+        Value::Object(fields) => Json::Object(fields.iter().map(|(k, v)| (k.clone(), literal(v))).collect()),
+    }
+}
@@ -0,0 +1,106 @@
+//! A GraphQL query endpoint generated from the same SDL as the tables.
+//!
+//! Each table gets a root field listing its records, `products` for
+//! `Products` and `brands` for `Brand`, taking `filter` (FIQL, as the table
+//! endpoint reads it), `sort` (`-price,name`), `first` and `after` (a
+//! `_cursor` from an earlier row). Relationships are fields of the record
+//! types and are loaded in batches, so `brands { products { name } }`
+//! reads `Products` once however many brands there are. Only queries run;
+//! writes go through the REST endpoints.
+
+mod document;
+mod exec;
+mod types;
+mod validate;
+
+use serde::Serialize;
+use serde_json::{Map, Value as Json, json};
+
+use crate::schema::Schema;
+use crate::store::Store;
+
+pub use document::{Pos, SyntaxError};
+pub use types::root_field;
+
+/// One entry of a response's `errors`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Error {
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<Pos>,
+    /// Response keys and list indexes down to the field that failed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<Json>,
+}
+
+impl Error {
+    pub(crate) fn new(message: impl Into<String>, pos: Option<Pos>) -> Self {
+        Error { message: message.into(), locations: pos.into_iter().collect(), path: Vec::new() }
+    }
+
+    fn at(mut self, path: Vec<Json>) -> Self {
+        self.path = path;
+        self
+    }
+}
+
+/// The result of running a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    /// `None` when the document never ran: it did not parse or validate, or
+    /// its variables did not fit.
+    pub data: Option<Json>,
+    pub errors: Vec<Error>,
+    /// Table reads the operation made.
+    pub queries: usize,
+}
+
+impl Outcome {
+    fn failed(errors: Vec<Error>) -> Self {
+        Outcome { data: None, errors, queries: 0 }
+    }
+
+    /// 400 when the document never ran; a field that failed still answers
+    /// 200, with `null` in its place and an entry in `errors`.
+    pub fn status(&self) -> u16 {
+        if self.data.is_some() { 200 } else { 400 }
+    }
+
+    pub fn to_json(&self) -> Json {
+        let mut out = Map::new();
+        if let Some(data) = &self.data {
+            out.insert("data".to_string(), data.clone());
+        }
+        if !self.errors.is_empty() {
+            out.insert("errors".to_string(), json!(self.errors));
+        }
+        if self.data.is_some() {
+            out.insert("extensions".to_string(), json!({ "queries": self.queries }));
+        }
+        Json::Object(out)
+    }
+}
+
+/// Runs the GraphQL `document` against `store`: the operation named
+/// `operation`, or its only one, with `variables`.
+pub fn execute(store: &Store, document: &str, variables: &Map<String, Json>, operation: Option<&str>) -> Outcome {
+    let document = match document::parse(document) {
+        Ok(document) => document,
+        Err(err) => return Outcome::failed(vec![Error::new(err.to_string(), Some(err.pos))]),
+    };
+    let operation = match validate::validate(store.schema(), &document, operation) {
+        Ok(operation) => operation,
+        Err(errors) => return Outcome::failed(errors),
+    };
+    let mut executor = match exec::Executor::new(store, &document.fragments, operation, variables) {
+        Ok(executor) => executor,
+        Err(errors) => return Outcome::failed(errors),
+    };
+    let data = executor.run(operation);
+    Outcome { data: Some(data), errors: executor.errors, queries: executor.queries }
+}
+
+/// The GraphQL schema of `schema`'s tables, as SDL.
+pub fn sdl(schema: &Schema) -> String {
+    types::sdl(schema)
+}
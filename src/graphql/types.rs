@@ -0,0 +1,171 @@
+//! The GraphQL view of the table schema: a root field per table, what each
+//! field of a type resolves to and the arguments it takes, and the SDL the
+//! endpoint describes itself with.
+
+use std::fmt::Write;
+
+use crate::schema::{Schema, TypeDef, TypeRef};
+use crate::store::{HIGHLIGHT, SCORE};
+
+/// The type root fields live on.
+pub const QUERY: &str = "Query";
+pub const TYPENAME: &str = "__typename";
+/// A record's keyset cursor in the list it was read from, for `after:`.
+pub const CURSOR: &str = "_cursor";
+
+/// Arguments of the root fields and of list relationships, with their types.
+pub const LIST_ARGS: [(&str, &str); 4] =
+    [("filter", "String"), ("sort", "String"), ("first", "Int"), ("after", "String")];
+
+/// Directives a query may put on a selection, both taking `if: Boolean!`.
+pub const DIRECTIVES: [&str; 2] = ["include", "skip"];
+
+/// The root field reading `table`: `Products` is `products`, `Brand` is
+/// `brands`.
+pub fn root_field(table: &str) -> String {
+    let mut chars = table.chars();
+    let mut name: String = chars.next().map(|c| c.to_lowercase().chain(chars).collect()).unwrap_or_default();
+    if !name.ends_with('s') {
+        name.push('s');
+    }
+    name
+}
+
+/// The object type a selection set is on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parent<'s> {
+    Query,
+    Type(&'s TypeDef),
+}
+
+impl Parent<'_> {
+    pub fn name(&self) -> &str {
+        match self {
+            Parent::Query => QUERY,
+            Parent::Type(def) => &def.name,
+        }
+    }
+}
+
+/// What a field resolves to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output<'s> {
+    /// A scalar or a list of them: `String`, `[Float]`, `GeoPoint`.
+    Leaf,
+    /// Records of a table, from a root field or a relationship.
+    Table(&'s TypeDef),
+    /// Objects stored inside a record, such as `[Variant]`.
+    Object(&'s TypeDef),
+}
+
+impl<'s> Output<'s> {
+    /// The type a sub-selection is on; `None` for a leaf.
+    pub fn parent(self) -> Option<Parent<'s>> {
+        match self {
+            Output::Leaf => None,
+            Output::Table(def) | Output::Object(def) => Some(Parent::Type(def)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldType<'s> {
+    pub output: Output<'s>,
+    /// The GraphQL type, e.g. `[Products!]` or `Float!`.
+    pub ty: String,
+    pub args: &'static [(&'static str, &'static str)],
+}
+
+/// The field `name` of `parent`, or `None` when it has no such field.
+pub fn field<'s>(schema: &'s Schema, parent: Parent<'s>, name: &str) -> Option<FieldType<'s>> {
+    let leaf = |ty: &str| FieldType { output: Output::Leaf, ty: ty.to_string(), args: &[] };
+    if name == TYPENAME {
+        return Some(leaf("String!"));
+    }
+    let def = match parent {
+        Parent::Query => {
+            let table = schema.tables().find(|t| root_field(&t.name) == name)?;
+            return Some(FieldType {
+                output: Output::Table(table),
+                ty: format!("[{}!]", table.name),
+                args: &LIST_ARGS,
+            });
+        }
+        Parent::Type(def) => def,
+    };
+    if def.is_table() {
+        let fulltext = def.fields.iter().any(|f| f.is_fulltext());
+        match name {
+            CURSOR => return Some(leaf("String")),
+            SCORE if fulltext => return Some(leaf("Float")),
+            HIGHLIGHT if fulltext => return Some(leaf("JSON")),
+            _ => {}
+        }
+    }
+    let field = def.field(name)?;
+    if field.relationship().is_some() {
+        let table = schema.table(&field.ty.name)?;
+        return Some(if field.ty.list {
+            FieldType { output: Output::Table(table), ty: format!("[{}!]", table.name), args: &LIST_ARGS }
+        } else {
+            FieldType { output: Output::Table(table), ty: table.name.clone(), args: &[] }
+        });
+    }
+    let output = schema.object(&field.ty.name).map_or(Output::Leaf, Output::Object);
+    Some(FieldType { output, ty: field.ty.to_string(), args: &[] })
+}
+
+/// Every field of `parent`, in schema order, then the virtual ones.
+pub fn fields<'s>(schema: &'s Schema, parent: Parent<'s>) -> Vec<(String, FieldType<'s>)> {
+    let names: Vec<String> = match parent {
+        Parent::Query => schema.table_names().map(root_field).collect(),
+        Parent::Type(def) => {
+            def.fields.iter().map(|f| f.name.clone()).chain([CURSOR, SCORE, HIGHLIGHT].map(str::to_string)).collect()
+        }
+    };
+    names.into_iter().filter_map(|name| Some((name.clone(), field(schema, parent, &name)?))).collect()
+}
+
+/// The GraphQL schema the endpoint serves, as SDL.
+pub fn sdl(schema: &Schema) -> String {
+    let mut out = String::new();
+    for scalar in custom_scalars(schema) {
+        let _ = writeln!(out, "scalar {scalar}");
+    }
+    out.push('\n');
+    let parents = std::iter::once(Parent::Query).chain(schema.types.iter().map(Parent::Type));
+    for (i, parent) in parents.enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let _ = writeln!(out, "type {} {{", parent.name());
+        for (name, field) in fields(schema, parent) {
+            let args: Vec<String> = field.args.iter().map(|(name, ty)| format!("{name}: {ty}")).collect();
+            let args = if args.is_empty() { String::new() } else { format!("({})", args.join(", ")) };
+            let _ = writeln!(out, "  {name}{args}: {}", field.ty);
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+/// Scalars beyond GraphQL's own that some field has; `JSON` is the type
+/// of `_highlight`.
+fn custom_scalars(schema: &Schema) -> Vec<&str> {
+    let builtin = ["ID", "String", "Int", "Float", "Boolean"];
+    let mut out: Vec<&str> = Vec::new();
+    let named = schema.types.iter().flat_map(|t| &t.fields).map(|f| &f.ty).filter(|ty| is_scalar(schema, ty));
+    for ty in named {
+        if !builtin.contains(&ty.name.as_str()) && !out.contains(&ty.name.as_str()) {
+            out.push(&ty.name);
+        }
+    }
+    if schema.tables().any(|t| t.fields.iter().any(|f| f.is_fulltext())) {
+        out.push("JSON");
+    }
+    out
+}
+
+fn is_scalar(schema: &Schema, ty: &TypeRef) -> bool {
+    schema.table(&ty.name).is_none() && schema.object(&ty.name).is_none()
+}
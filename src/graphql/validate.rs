@@ -0,0 +1,336 @@
+//! Checks a document against the GraphQL view of the schema before any of
+//! it runs: the operation to run, fields and arguments that exist, leaf and
+//! object selections, fragments that are used, known and acyclic, and
+//! variables that are defined, used and of the argument's type.
+
+use std::collections::{BTreeSet, HashMap};
+
+use super::Error;
+use super::document::{
+    Directive, Document, Field, Fragment, Operation, OperationKind, Pos, Selection, TypeRef, Value, VariableDef,
+};
+use super::types::{self, DIRECTIVES, Parent};
+use crate::schema::Schema;
+use crate::validate::closest;
+
+/// Scalars a variable can be declared as.
+const INPUT_TYPES: [&str; 5] = ["String", "Int", "Float", "Boolean", "ID"];
+
+/// The operation to run, once the whole document is valid.
+pub fn validate<'d>(schema: &Schema, document: &'d Document, name: Option<&str>) -> Result<&'d Operation, Vec<Error>> {
+    let mut v = Validator {
+        schema,
+        fragments: HashMap::new(),
+        errors: Vec::new(),
+        spread: BTreeSet::new(),
+        variables: &[],
+        used: BTreeSet::new(),
+    };
+    for fragment in &document.fragments {
+        if v.fragments.insert(&fragment.name, fragment).is_some() {
+            v.error(format!("there are several fragments named `{}`", fragment.name), fragment.pos);
+        }
+        if schema.types.iter().all(|t| t.name != fragment.on) {
+            v.error(format!("fragment `{}` is on unknown type `{}`", fragment.name, fragment.on), fragment.pos);
+        }
+    }
+    let mut names = BTreeSet::new();
+    for operation in &document.operations {
+        if let Some(name) = &operation.name
+            && !names.insert(name)
+        {
+            v.error(format!("there are several operations named `{name}`"), operation.pos);
+        }
+        v.operation(operation);
+    }
+    for fragment in &document.fragments {
+        if !v.spread.contains(fragment.name.as_str()) {
+            v.error(format!("fragment `{}` is never used", fragment.name), fragment.pos);
+        }
+    }
+
+    let operation = match name {
+        Some(name) => document.operations.iter().find(|o| o.name.as_deref() == Some(name)).ok_or_else(|| {
+            vec![Error::new(format!("operationName: the document has no operation named `{name}`"), None)]
+        }),
+        None => match document.operations.as_slice() {
+            [operation] => Ok(operation),
+            [] => Err(vec![Error::new("the document holds only fragments; add a query", None)]),
+            _ => Err(vec![Error::new("the document holds several operations; pick one with `operationName`", None)]),
+        },
+    };
+    let mut errors = v.errors;
+    // A fragment spread in several places reports its errors once.
+    let mut seen = BTreeSet::new();
+    errors.retain(|e| seen.insert((e.message.clone(), e.locations.clone())));
+    match operation {
+        Ok(operation) if errors.is_empty() => Ok(operation),
+        Ok(_) => Err(errors),
+        Err(mut missing) => {
+            missing.extend(errors);
+            Err(missing)
+        }
+    }
+}
+
+struct Validator<'s, 'd> {
+    schema: &'s Schema,
+    fragments: HashMap<&'d str, &'d Fragment>,
+    errors: Vec<Error>,
+    /// Fragments some operation spreads.
+    spread: BTreeSet<&'d str>,
+    /// The variables of the operation being checked, and those it uses.
+    variables: &'d [VariableDef],
+    used: BTreeSet<&'d str>,
+}
+
+impl<'s, 'd> Validator<'s, 'd> {
+    fn error(&mut self, message: String, pos: Pos) {
+        self.errors.push(Error::new(message, Some(pos)));
+    }
+
+    fn operation(&mut self, operation: &'d Operation) {
+        if operation.kind != OperationKind::Query {
+            self.error("only queries are supported; write through the REST endpoints".to_string(), operation.pos);
+            return;
+        }
+        self.variables = &operation.variables;
+        self.used.clear();
+        let mut declared = BTreeSet::new();
+        for def in &operation.variables {
+            if !declared.insert(def.name.as_str()) {
+                self.error(format!("variable `${}` is declared more than once", def.name), def.pos);
+            }
+            if !INPUT_TYPES.contains(&base(&def.ty)) {
+                self.error(format!("variable `${}` cannot be of type `{}`", def.name, def.ty), def.pos);
+            }
+            if let Some(default) = &def.default
+                && !fits(default, &def.ty)
+            {
+                self.error(format!("variable `${}` defaults to a value that is not a `{}`", def.name, def.ty), def.pos);
+            }
+        }
+        self.selection(Parent::Query, &operation.selection, &mut Vec::new());
+        for def in &operation.variables {
+            if !self.used.contains(def.name.as_str()) {
+                self.error(format!("variable `${}` is never used", def.name), def.pos);
+            }
+        }
+    }
+
+    /// Checks `selection` on `parent`; `stack` holds the fragments being
+    /// spread, to stop at a cycle.
+    fn selection(&mut self, parent: Parent<'s>, selection: &'d [Selection], stack: &mut Vec<&'d str>) {
+        for item in selection {
+            match item {
+                Selection::Field(field) => {
+                    self.directives(&field.directives);
+                    self.field(parent, field, stack);
+                }
+                Selection::Spread { name, directives, pos } => {
+                    self.directives(directives);
+                    let Some(fragment) = self.fragments.get(name.as_str()).copied() else {
+                        self.error(format!("unknown fragment `{name}`"), *pos);
+                        continue;
+                    };
+                    self.spread.insert(&fragment.name);
+                    if stack.contains(&name.as_str()) {
+                        self.error(format!("fragment `{name}` spreads itself"), *pos);
+                        continue;
+                    }
+                    if fragment.on != parent.name() {
+                        self.error(
+                            format!(
+                                "fragment `{name}` is on `{}` and cannot be spread on `{}`",
+                                fragment.on,
+                                parent.name()
+                            ),
+                            *pos,
+                        );
+                        continue;
+                    }
+                    stack.push(&fragment.name);
+                    self.selection(parent, &fragment.selection, stack);
+                    stack.pop();
+                }
+                Selection::Inline { on, directives, selection, pos } => {
+                    self.directives(directives);
+                    if let Some(on) = on
+                        && on != parent.name()
+                    {
+                        self.error(format!("a fragment on `{on}` cannot be spread on `{}`", parent.name()), *pos);
+                        continue;
+                    }
+                    self.selection(parent, selection, stack);
+                }
+            }
+        }
+        self.conflicts(parent, selection);
+    }
+
+    fn field(&mut self, parent: Parent<'s>, field: &'d Field, stack: &mut Vec<&'d str>) {
+        let Some(ty) = types::field(self.schema, parent, &field.name) else {
+            let fields = types::fields(self.schema, parent);
+            let mut message = format!("`{}` has no field `{}`", parent.name(), field.name);
+            if let Some(suggestion) = closest(&field.name, fields.iter().map(|(name, _)| name.as_str())) {
+                message.push_str(&format!("; did you mean `{suggestion}`?"));
+            }
+            self.error(message, field.pos);
+            return;
+        };
+        let mut given = BTreeSet::new();
+        for (name, value) in &field.args {
+            if !given.insert(name) {
+                self.error(format!("argument `{name}` is given more than once"), field.pos);
+            }
+            match ty.args.iter().find(|(arg, _)| arg == name) {
+                Some((_, expected)) => self.argument(&field.name, name, value, expected, field.pos),
+                None if ty.args.is_empty() => {
+                    self.error(format!("`{}` takes no arguments, found `{name}`", field.name), field.pos)
+                }
+                None => {
+                    let names: Vec<&str> = ty.args.iter().map(|(arg, _)| *arg).collect();
+                    self.error(
+                        format!("`{}` has no argument `{name}`; arguments: {}", field.name, names.join(", ")),
+                        field.pos,
+                    );
+                }
+            }
+        }
+        match ty.output.parent() {
+            None if !field.selection.is_empty() => self
+                .error(format!("`{}` is a `{}` and cannot have a selection of fields", field.name, ty.ty), field.pos),
+            None => {}
+            Some(_) if field.selection.is_empty() => {
+                self.error(format!("`{}` is a `{}`; select some of its fields", field.name, ty.ty), field.pos)
+            }
+            Some(inner) => self.selection(inner, &field.selection, stack),
+        }
+    }
+
+    fn argument(&mut self, field: &str, name: &str, value: &'d Value, expected: &str, pos: Pos) {
+        match value {
+            Value::Variable(var) => {
+                self.used.insert(var);
+                match self.variables.iter().find(|d| &d.name == var) {
+                    None => self.error(format!("variable `${var}` is not defined by the operation"), pos),
+                    Some(def) if !matches!(unwrap(&def.ty), TypeRef::Named(n) if n == expected) => self.error(
+                        format!("variable `${var}` is a `{}` but `{name}` on `{field}` expects a `{expected}`", def.ty),
+                        pos,
+                    ),
+                    Some(_) => {}
+                }
+            }
+            value if !fits(value, &TypeRef::Named(expected.to_string())) => {
+                self.error(format!("argument `{name}` on `{field}` expects a value of type `{expected}`"), pos)
+            }
+            _ => {}
+        }
+    }
+
+    fn directives(&mut self, directives: &'d [Directive]) {
+        for directive in directives {
+            if !DIRECTIVES.contains(&directive.name.as_str()) {
+                self.error(
+                    format!("unknown directive `@{}`; directives: @include, @skip", directive.name),
+                    directive.pos,
+                );
+                continue;
+            }
+            let field = format!("@{}", directive.name);
+            match directive.args.as_slice() {
+                [(name, value)] if name == "if" => match value {
+                    Value::Null => self.error(format!("`if` on `{field}` cannot be null"), directive.pos),
+                    value => self.argument(&field, "if", value, "Boolean", directive.pos),
+                },
+                _ => self.error(format!("`{field}` takes a single `if: Boolean!` argument"), directive.pos),
+            }
+        }
+    }
+
+    /// Fields under one response key must be the same field with the same
+    /// arguments, or there is no telling which one answers.
+    fn conflicts(&mut self, parent: Parent<'s>, selection: &'d [Selection]) {
+        let mut by_key: Vec<(&str, &Field)> = Vec::new();
+        collect(&self.fragments, parent.name(), selection, &|_| true, &mut Vec::new(), &mut |field| {
+            by_key.push((field.key(), field));
+        });
+        for (i, (key, field)) in by_key.iter().enumerate() {
+            if let Some((_, other)) = by_key[..i].iter().find(|(k, _)| k == key)
+                && (other.name != field.name || other.args != field.args)
+            {
+                self.error(
+                    format!("`{key}` selects both `{}` and `{}`; give one of them an alias", other.name, field.name),
+                    field.pos,
+                );
+            }
+        }
+    }
+}
+
+/// Calls `each` with every field `selection` selects on `parent`,
+/// following fragments whose type condition is `parent` and whose
+/// directives `include` lets through, and skipping any already on `stack`.
+pub(super) fn collect<'d>(
+    fragments: &HashMap<&'d str, &'d Fragment>,
+    parent: &str,
+    selection: &'d [Selection],
+    include: &dyn Fn(&[Directive]) -> bool,
+    stack: &mut Vec<&'d str>,
+    each: &mut dyn FnMut(&'d Field),
+) {
+    for item in selection {
+        match item {
+            Selection::Field(field) if include(&field.directives) => each(field),
+            Selection::Field(_) => {}
+            Selection::Spread { name, directives, .. } => {
+                let Some(fragment) = fragments.get(name.as_str()) else { continue };
+                if fragment.on != parent || !include(directives) || stack.contains(&name.as_str()) {
+                    continue;
+                }
+                stack.push(&fragment.name);
+                collect(fragments, parent, &fragment.selection, include, stack, each);
+                stack.pop();
+            }
+            Selection::Inline { on, directives, selection, .. } => {
+                if on.as_deref().is_none_or(|on| on == parent) && include(directives) {
+                    collect(fragments, parent, selection, include, stack, each);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the literal `value` is a `ty`.
+fn fits(value: &Value, ty: &TypeRef) -> bool {
+    match (value, ty) {
+        (Value::Null, TypeRef::NonNull(_)) => false,
+        (Value::Null, _) => true,
+        (value, TypeRef::NonNull(inner)) => fits(value, inner),
+        (Value::List(items), TypeRef::List(inner)) => items.iter().all(|v| fits(v, inner)),
+        (value, TypeRef::List(inner)) => fits(value, inner),
+        (value, TypeRef::Named(name)) => matches!(
+            (value, name.as_str()),
+            (Value::String(_), "String" | "ID")
+                | (Value::Int(_), "Int" | "Float" | "ID")
+                | (Value::Float(_), "Float")
+                | (Value::Bool(_), "Boolean")
+        ),
+    }
+}
+
+/// `ty` without its outer `!`.
+fn unwrap(ty: &TypeRef) -> &TypeRef {
+    match ty {
+        TypeRef::NonNull(inner) => inner,
+        ty => ty,
+    }
+}
+
+/// The named type at the bottom of `ty`.
+fn base(ty: &TypeRef) -> &str {
+    match ty {
+        TypeRef::Named(name) => name,
+        TypeRef::List(inner) | TypeRef::NonNull(inner) => base(inner),
+    }
+}
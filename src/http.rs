@@ -0,0 +1,98 @@
+//! Minimal request/response types shared by the custom resources.
+
+use serde::Serialize;
+
+use crate::fiql::decode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+/// An incoming request, with `path` relative to the app mount
+/// (`/parse`, not `/demo-fiql/parse`) and `query` still percent-encoded.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: Method,
+    pub path: String,
+    pub query: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn new(method: Method, target: &str) -> Self {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Request { method, path: path.to_string(), query: query.to_string(), headers: Vec::new(), body: Vec::new() }
+    }
+
+    pub fn get(target: &str) -> Self {
+        Request::new(Method::Get, target)
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Case-insensitive header lookup.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    /// First `name=value` pair in the query string, percent-decoded.
+    pub fn param(&self, name: &str) -> Option<String> {
+        self.query.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (key == name).then(|| decode(value, 0).unwrap_or_else(|_| value.to_string()))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Response { status, headers: vec![("Content-Type".to_string(), content_type.to_string())], body: body.into() }
+    }
+
+    pub fn json(status: u16, value: &impl Serialize) -> Self {
+        let body = serde_json::to_vec_pretty(value).expect("response values serialize");
+        Response::new(status, "application/json", body)
+    }
+
+    /// JSON error body `{ "error": message }`.
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Response::json(status, &serde_json::json!({ "error": message.into() }))
+    }
+
+    pub fn not_found() -> Self {
+        Response::error(404, "not found")
+    }
+
+    pub fn method_not_allowed() -> Self {
+        Response::error(405, "method not allowed")
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    pub fn body_str(&self) -> &str {
+        std::str::from_utf8(&self.body).unwrap_or("")
+    }
+}
@@ -0,0 +1,21 @@
+//! Server-side support code for the demo-fiql application.
+//!
+//! The REST endpoints themselves are generated by the platform from
+//! `schemas/fiql.graphql`; this crate holds the pieces that sit next to
+//! them: an authoritative FIQL parser, an in-process [`store`] with the same
+//! query semantics as the table endpoints, and the custom resources built on
+//! both.
+
+pub mod app;
+pub mod auth;
+pub mod config;
+pub mod fiql;
+pub mod format;
+pub mod graphql;
+pub mod http;
+pub mod live;
+pub mod query;
+pub mod resources;
+pub mod schema;
+pub mod store;
+pub mod validate;
@@ -0,0 +1,269 @@
+//! Live queries: a FIQL table query kept current as records change.
+//!
+//! A [`Subscription`] remembers the rows it has reported. When polled it
+//! reads the store's change log, re-evaluates only the records a write can
+//! affect, and reports rows entering the result, changing within it, or
+//! leaving it. Relationship conditions are followed one hop: a write to a
+//! `Brand` row re-evaluates the products joined to it before and after the
+//! write, so `brand.country==JP` stays correct when a brand moves country.
+//! A longer path is only watched as far as that first hop: a product under
+//! `brand.products.price=gt=800` is re-checked when it or its brand is
+//! written, not when a sibling product's price changes.
+//!
+//! A filter with a `now` in it is compiled again on every poll, and every
+//! record re-evaluated, so rows enter and leave `updatedAt=gt=now-7d` as
+//! the clock moves, without a write.
+
+pub mod sse;
+pub mod ws;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde_json::{Value as Json, json};
+
+use crate::fiql::{Expr, Literal, Operator, PathQuery, SelectField, Time};
+use crate::schema::{FieldDef, Relationship, TypeDef};
+use crate::store::{
+    Change, ExecError, HIGHLIGHT, Predicate, SCORE, Store, key_string, matching, primary_key_of, render,
+};
+
+/// Something a subscriber is told.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The whole result when the subscription opens, in `sort=` order.
+    Snapshot(Vec<Json>),
+    /// A record now matches.
+    Insert { id: String, row: Json },
+    /// A matching record changed, or a joined row it projects did.
+    Update { id: String, row: Json },
+    /// A record no longer matches, or was deleted.
+    Delete { id: String },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Snapshot(_) => "snapshot",
+            Event::Insert { .. } => "insert",
+            Event::Update { .. } => "update",
+            Event::Delete { .. } => "delete",
+        }
+    }
+
+    /// `{ "data": [...] }` for a snapshot, `{ "id", "data" }` for a row
+    /// event and `{ "id" }` for a delete.
+    pub fn to_json(&self) -> Json {
+        match self {
+            Event::Snapshot(rows) => json!({ "data": rows }),
+            Event::Insert { id, row } | Event::Update { id, row } => json!({ "id": id, "data": row }),
+            Event::Delete { id } => json!({ "id": id }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiveError {
+    Exec(ExecError),
+    /// The request has no live meaning, e.g. it pages with `limit=`.
+    Unsupported(String),
+}
+
+impl LiveError {
+    pub fn status(&self) -> u16 {
+        match self {
+            LiveError::Exec(err) => err.status(),
+            LiveError::Unsupported(_) => 400,
+        }
+    }
+}
+
+impl fmt::Display for LiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LiveError::Exec(err) => err.fmt(f),
+            LiveError::Unsupported(why) => f.write_str(why),
+        }
+    }
+}
+
+impl std::error::Error for LiveError {}
+
+impl From<ExecError> for LiveError {
+    fn from(err: ExecError) -> Self {
+        LiveError::Exec(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    table: String,
+    filter: Option<Predicate>,
+    /// The filter, when it has a `now` that each poll fixes afresh.
+    clocked: Option<Expr>,
+    select: Option<Vec<SelectField>>,
+    /// Relationship fields the filter or projection reads; writes to their
+    /// target tables can change this result.
+    joins: Vec<FieldDef>,
+    /// Store version the reported rows reflect.
+    version: u64,
+    /// Reported rows by primary key, as last sent.
+    rows: BTreeMap<String, Json>,
+}
+
+impl Subscription {
+    /// Starts watching `path`, returning the subscription and its snapshot.
+    /// Paging and `explain` are rejected: a live result is the whole match.
+    /// So are `=sim=`, whose nearest records can change with any write to
+    /// the table, and `_score` and `_highlight`, since a write moves every
+    /// score.
+    pub fn open(store: &Store, path: &PathQuery) -> Result<(Subscription, Event), LiveError> {
+        if path.id.is_some() {
+            return Err(LiveError::Unsupported("subscriptions watch a table query, not a single record".into()));
+        }
+        let controls = &path.query.controls;
+        let paging = [
+            ("limit", controls.limit.is_some()),
+            ("offset", controls.offset.is_some()),
+            ("after", controls.after.is_some()),
+            ("pagination", controls.pagination == Some(true)),
+            ("explain", controls.explain == Some(true)),
+        ];
+        if let Some((name, _)) = paging.iter().find(|(_, set)| *set) {
+            return Err(LiveError::Unsupported(format!("`{name}` cannot be combined with a subscription")));
+        }
+        if path.query.filter.iter().flat_map(Expr::conditions).any(|c| c.op == Operator::Sim) {
+            return Err(LiveError::Unsupported("`=sim=` cannot be combined with a subscription".into()));
+        }
+        let sorted = controls.sort.iter().flatten().map(|k| k.field.to_string());
+        let selected = controls.select.iter().flatten().map(|f| f.name.clone());
+        if let Some(name) = sorted.chain(selected).find(|name| [SCORE, HIGHLIGHT].contains(&name.as_str())) {
+            return Err(LiveError::Unsupported(format!("`{name}` cannot be combined with a subscription")));
+        }
+        let table = store.schema().table(&path.table).ok_or_else(|| ExecError::UnknownTable(path.table.clone()))?;
+        let filter = path.query.filter.as_ref().map(|f| Predicate::compile(store, table, f)).transpose()?;
+        let select = controls.select.clone();
+        let clocked = path.query.filter.clone().filter(|f| {
+            f.conditions()
+                .into_iter()
+                .any(|c| c.value.scalars().iter().any(|s| matches!(s.literal, Literal::Time(Time::Now(_)))))
+        });
+
+        let mut used = BTreeSet::new();
+        if let Some(expr) = &path.query.filter {
+            roots(expr, &mut used);
+        }
+        used.extend(select.iter().flatten().map(|f| f.name.as_str()));
+        let joins: Vec<FieldDef> = table
+            .fields
+            .iter()
+            .filter(|f| f.relationship().is_some() && used.contains(f.name.as_str()))
+            .cloned()
+            .collect();
+
+        let mut rows = BTreeMap::new();
+        let mut snapshot = Vec::new();
+        for record in matching(store, table, &path.query)? {
+            let row = render(store, table, record, select.as_deref());
+            rows.insert(primary_key_of(table, record).unwrap_or_default(), row.clone());
+            snapshot.push(row);
+        }
+        let subscription =
+            Subscription { table: table.name.clone(), filter, clocked, select, joins, version: store.version(), rows };
+        Ok((subscription, Event::Snapshot(snapshot)))
+    }
+
+    /// Store version this subscription has caught up to.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Events for the writes since the last poll, in write order. Several
+    /// writes to one record between polls give one event for its latest
+    /// state. If the change log no longer reaches back that far, or the
+    /// filter has a `now` in it, every record of the table is re-evaluated
+    /// instead.
+    pub fn poll(&mut self, store: &Store) -> Vec<Event> {
+        let Some(table) = store.schema().table(&self.table) else { return Vec::new() };
+        let recompiled = self.clocked.as_ref().and_then(|f| Predicate::compile(store, table, f).ok());
+        let changes = store.changes_since(self.version).filter(|_| recompiled.is_none());
+        if recompiled.is_some() {
+            self.filter = recompiled;
+        }
+        let candidates: Vec<String> = match changes {
+            Some(changes) => changes.flat_map(|c| self.affected(store, c)).collect(),
+            None => {
+                let all = store.table(&self.table).into_iter().flat_map(|t| t.records.keys().cloned());
+                all.chain(self.rows.keys().cloned()).collect::<BTreeSet<_>>().into_iter().collect()
+            }
+        };
+        self.version = store.version();
+        candidates.iter().filter_map(|id| self.refresh(store, table, id)).collect()
+    }
+
+    /// Keys of watched records whose result a write may have changed.
+    fn affected(&self, store: &Store, change: &Change) -> Vec<String> {
+        let mut ids = Vec::new();
+        if change.table == self.table {
+            ids.push(change.id.clone());
+        }
+        for field in self.joins.iter().filter(|f| f.ty.name == change.table) {
+            match field.relationship() {
+                // Watched records hold the changed row's key.
+                Some(Relationship::From(fk)) => ids.extend(
+                    store
+                        .table(&self.table)
+                        .into_iter()
+                        .flat_map(|t| t.records.iter())
+                        .filter(|(_, r)| r.get(&fk).and_then(key_string).as_deref() == Some(change.id.as_str()))
+                        .map(|(id, _)| id.clone()),
+                ),
+                // The changed row holds a watched record's key, before
+                // and after the write.
+                Some(Relationship::To(fk)) => ids.extend(
+                    [&change.before, &change.after]
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|r| r.get(&fk).and_then(key_string)),
+                ),
+                None => {}
+            }
+        }
+        ids
+    }
+
+    /// Re-evaluates one record against the last reported row.
+    fn refresh(&mut self, store: &Store, table: &TypeDef, id: &str) -> Option<Event> {
+        let now = store
+            .get(&table.name, id)
+            .filter(|r| self.filter.as_ref().is_none_or(|p| p.matches(store, table, r)))
+            .map(|r| render(store, table, r, self.select.as_deref()));
+        let id = id.to_string();
+        match (self.rows.contains_key(&id), now) {
+            (false, Some(row)) => {
+                self.rows.insert(id.clone(), row.clone());
+                Some(Event::Insert { id, row })
+            }
+            (true, Some(row)) if self.rows[&id] != row => {
+                self.rows.insert(id.clone(), row.clone());
+                Some(Event::Update { id, row })
+            }
+            (true, None) => {
+                self.rows.remove(&id);
+                Some(Event::Delete { id })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Root field names of every condition in `expr`.
+fn roots<'a>(expr: &'a Expr, out: &mut BTreeSet<&'a str>) {
+    match expr {
+        Expr::Condition(c) => {
+            out.insert(c.field.root());
+        }
+        Expr::And(children) | Expr::Or(children) => children.iter().for_each(|e| roots(e, out)),
+        Expr::Not(inner) => roots(inner, out),
+    }
+}
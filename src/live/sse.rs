@@ -0,0 +1,24 @@
+//! Server-Sent Events framing for [`Event`]s.
+//!
+//! Each event is one `event:` / `data:` frame whose data is the event's JSON
+//! on a single line, so `EventSource` listeners can subscribe by event name.
+
+use super::Event;
+use crate::http::Response;
+
+pub const CONTENT_TYPE: &str = "text/event-stream";
+
+pub fn frame(event: &Event) -> String {
+    format!("event: {}\ndata: {}\n\n", event.name(), event.to_json())
+}
+
+pub fn frames(events: &[Event]) -> String {
+    events.iter().map(frame).collect()
+}
+
+/// The opening of an event stream: headers plus the snapshot frame.
+pub fn response(snapshot: &Event) -> Response {
+    let mut response = Response::new(200, CONTENT_TYPE, frame(snapshot));
+    response.headers.push(("Cache-Control".to_string(), "no-cache".to_string()));
+    response
+}
@@ -0,0 +1,175 @@
+//! WebSocket protocol: many live queries over one connection.
+//!
+//! After [`handshake`] upgrades `GET /live`, every text frame is one JSON
+//! message. The client opens and cancels subscriptions by an id of its
+//! choosing:
+//!
+//! ```text
+//! {"type":"subscribe","id":"oos","query":"/Products/?inStock==false"}
+//! {"type":"cancel","id":"oos"}
+//! ```
+//!
+//! The server answers a subscribe with a `query` echo (the parsed
+//! [`ResourceQuery`]) and a `snapshot`, then sends `insert`, `update` and
+//! `delete` deltas tagged with the subscription id, a `cancelled`
+//! acknowledgement, or an `error` with an HTTP-style `status`; a query over
+//! the cost budget is refused with `422` like on the table endpoints. The
+//! transport feeds frames to [`Session::receive`] and, after each write to
+//! the store, sends whatever [`Session::poll`] returns.
+
+use std::collections::BTreeMap;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Deserialize;
+use serde_json::{Map, Value as Json, json};
+use sha1_smol::Sha1;
+
+use super::{Event, Subscription};
+use crate::app;
+use crate::fiql;
+use crate::http::{Request, Response};
+use crate::query::ResourceQuery;
+use crate::store::Store;
+
+/// Open subscriptions one connection may hold.
+pub const MAX_SUBSCRIPTIONS: usize = 100;
+
+/// RFC 6455 key suffix for `Sec-WebSocket-Accept`.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// `101 Switching Protocols` for a WebSocket upgrade request, or `426` for
+/// a plain GET.
+pub fn handshake(req: &Request) -> Response {
+    let upgrade = req.header("Upgrade").is_some_and(|u| u.eq_ignore_ascii_case("websocket"));
+    let Some(key) = req.header("Sec-WebSocket-Key").filter(|_| upgrade) else {
+        let mut response = Response::error(426, "`/live` speaks WebSocket; connect with `Upgrade: websocket`");
+        response.headers.push(("Upgrade".to_string(), "websocket".to_string()));
+        return response;
+    };
+    let accept = STANDARD.encode(Sha1::from(format!("{}{ACCEPT_GUID}", key.trim())).digest().bytes());
+    Response {
+        status: 101,
+        headers: vec![
+            ("Upgrade".to_string(), "websocket".to_string()),
+            ("Connection".to_string(), "Upgrade".to_string()),
+            ("Sec-WebSocket-Accept".to_string(), accept),
+        ],
+        body: Vec::new(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum ClientMessage {
+    Subscribe { id: String, query: String },
+    Cancel { id: String },
+}
+
+/// The subscriptions of one connection, by client-chosen id.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    subscriptions: BTreeMap<String, Subscription>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session::default()
+    }
+
+    /// Ids of the open subscriptions.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.subscriptions.keys().map(String::as_str)
+    }
+
+    /// Handles one client text frame, returning the messages to send back.
+    pub fn receive(&mut self, store: &Store, text: &str) -> Vec<Json> {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(err) => return vec![error(None, 400, format!("invalid message: {err}"))],
+        };
+        match message {
+            ClientMessage::Subscribe { id, query } => self.subscribe(store, id, &query),
+            ClientMessage::Cancel { id } => match self.subscriptions.remove(&id) {
+                Some(_) => vec![json!({ "type": "cancelled", "id": id })],
+                None => vec![error(Some(&id), 404, format!("no subscription `{id}`"))],
+            },
+        }
+    }
+
+    fn subscribe(&mut self, store: &Store, id: String, query: &str) -> Vec<Json> {
+        if self.subscriptions.contains_key(&id) {
+            return vec![error(Some(&id), 409, format!("subscription `{id}` is already open"))];
+        }
+        if self.subscriptions.len() == MAX_SUBSCRIPTIONS {
+            return vec![error(Some(&id), 429, format!("at most {MAX_SUBSCRIPTIONS} subscriptions per connection"))];
+        }
+        let parsed = match fiql::parse_path(query) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                let mut message = error(Some(&id), 400, err.kind.to_string());
+                message["input"] = json!(query);
+                message["span"] = json!({ "start": err.span.start, "end": err.span.end });
+                return vec![message];
+            }
+        };
+        let echo = json!({ "type": "query", "id": id, "query": ResourceQuery::from_path(&parsed) });
+        if let Some(table) = store.schema().table(&parsed.table)
+            && let Err(over) = app::query_within_budget(store, table, &parsed.query)
+        {
+            let mut message = error(Some(&id), over.status(), over.to_string());
+            message["estimate"] = json!(over.estimate);
+            return vec![echo, message];
+        }
+        match Subscription::open(store, &parsed) {
+            Ok((subscription, snapshot)) => {
+                let snapshot = message(&id, &snapshot);
+                self.subscriptions.insert(id, subscription);
+                vec![echo, snapshot]
+            }
+            Err(err) => vec![echo, error(Some(&id), err.status(), err.to_string())],
+        }
+    }
+
+    /// Deltas for every open subscription since the last poll, grouped by
+    /// subscription id.
+    pub fn poll(&mut self, store: &Store) -> Vec<Json> {
+        let mut out = Vec::new();
+        for (id, subscription) in &mut self.subscriptions {
+            out.extend(subscription.poll(store).iter().map(|event| message(id, event)));
+        }
+        out
+    }
+}
+
+/// `event` tagged with its subscription: `{ type, id, data }` for a
+/// snapshot and `{ type, id, key, data? }` for a delta.
+fn message(id: &str, event: &Event) -> Json {
+    let mut out = Map::new();
+    out.insert("type".into(), json!(event.name()));
+    out.insert("id".into(), json!(id));
+    match event {
+        Event::Snapshot(rows) => {
+            out.insert("data".into(), json!(rows));
+        }
+        Event::Insert { id: key, row } | Event::Update { id: key, row } => {
+            out.insert("key".into(), json!(key));
+            out.insert("data".into(), row.clone());
+        }
+        Event::Delete { id: key } => {
+            out.insert("key".into(), json!(key));
+        }
+    }
+    Json::Object(out)
+}
+
+fn error(id: Option<&str>, status: u16, message: String) -> Json {
+    let mut out = Map::new();
+    out.insert("type".into(), json!("error"));
+    if let Some(id) = id {
+        out.insert("id".into(), json!(id));
+    }
+    out.insert("status".into(), json!(status));
+    out.insert("error".into(), json!(message));
+    Json::Object(out)
+}
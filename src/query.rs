@@ -0,0 +1,161 @@
+//! The structured `ResourceQuery` the server derives from a REST path.
+//!
+//! This is the JSON shown in the UI's "Resource JSON" pane. Top-level `&`
+//! terms land in `conditions`, a top-level `|` becomes `or` (one list per
+//! branch), and anything deeper is kept as a nested group so negation and
+//! grouping survive exactly as parsed.
+
+use serde::Serialize;
+use serde_json::Value as Json;
+
+use crate::fiql::{Condition, Expr, Literal, PathQuery, Scalar, SelectField, Value};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceQuery {
+    pub table: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<ConditionNode>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub or: Option<Vec<Vec<ConditionNode>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub select: Option<Vec<SelectItem>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Vec<SortItem>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explain: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<bool>,
+}
+
+/// A leaf condition or a nested `and`/`or` group.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ConditionNode {
+    Condition {
+        field: String,
+        op: &'static str,
+        value: Json,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        negate: bool,
+    },
+    Group {
+        operator: &'static str,
+        conditions: Vec<ConditionNode>,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        negate: bool,
+    },
+}
+
+/// A projected field; nested projections are `{ "field", "select" }`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum SelectItem {
+    Field(String),
+    Nested { field: String, select: Vec<SelectItem> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SortItem {
+    pub field: String,
+    pub descending: bool,
+}
+
+impl ResourceQuery {
+    pub fn from_path(parsed: &PathQuery) -> Self {
+        let controls = &parsed.query.controls;
+        let mut query = ResourceQuery {
+            table: parsed.table.clone(),
+            id: parsed.id.clone(),
+            conditions: None,
+            or: None,
+            select: controls.select.as_ref().map(|s| s.iter().map(select_item).collect()),
+            sort: controls.sort.as_ref().map(|keys| {
+                keys.iter().map(|k| SortItem { field: k.field.to_string(), descending: k.descending }).collect()
+            }),
+            limit: controls.limit,
+            offset: controls.offset,
+            after: controls.after.clone(),
+            explain: controls.explain.filter(|e| *e),
+            pagination: controls.pagination.filter(|p| *p),
+        };
+        match &parsed.query.filter {
+            None => {}
+            Some(Expr::Or(branches)) => query.or = Some(branches.iter().map(conjuncts).collect()),
+            Some(expr) => query.conditions = Some(conjuncts(expr)),
+        }
+        query
+    }
+}
+
+/// Flattens one level of `&` into a list of nodes.
+pub(crate) fn conjuncts(expr: &Expr) -> Vec<ConditionNode> {
+    match expr {
+        Expr::And(children) => children.iter().map(|c| node(c, false)).collect(),
+        other => vec![node(other, false)],
+    }
+}
+
+fn node(expr: &Expr, negate: bool) -> ConditionNode {
+    match expr {
+        Expr::Condition(c) => condition(c, negate),
+        Expr::And(children) => ConditionNode::Group {
+            operator: "and",
+            conditions: children.iter().map(|c| node(c, false)).collect(),
+            negate,
+        },
+        Expr::Or(children) => ConditionNode::Group {
+            operator: "or",
+            conditions: children.iter().map(|c| node(c, false)).collect(),
+            negate,
+        },
+        // `!(!(x))` keeps both negations visible as a negated group.
+        Expr::Not(inner) if negate => {
+            ConditionNode::Group { operator: "and", conditions: vec![node(inner, true)], negate: true }
+        }
+        Expr::Not(inner) => node(inner, true),
+    }
+}
+
+fn condition(c: &Condition, negate: bool) -> ConditionNode {
+    ConditionNode::Condition { field: c.field.to_string(), op: c.op.name(), value: value_json(&c.value), negate }
+}
+
+pub(crate) fn value_json(value: &Value) -> Json {
+    match value {
+        Value::Single(s) => scalar_json(s),
+        Value::List(items) => Json::Array(items.iter().map(scalar_json).collect()),
+        // An element filter is shown like a top-level one.
+        Value::Filter(filter) => serde_json::to_value(conjuncts(filter)).unwrap_or_default(),
+    }
+}
+
+pub(crate) fn scalar_json(scalar: &Scalar) -> Json {
+    literal_json(&scalar.literal)
+}
+
+/// Integral numbers serialize without a fractional part (`100`, not `100.0`).
+pub(crate) fn literal_json(literal: &Literal) -> Json {
+    match literal {
+        Literal::String(s) => Json::String(s.clone()),
+        Literal::Bool(b) => Json::Bool(*b),
+        Literal::Number(n) if n.fract() == 0.0 && n.abs() < 9.0e15 => Json::from(*n as i64),
+        Literal::Number(n) => Json::from(*n),
+        Literal::Time(t) => Json::String(t.to_string()),
+    }
+}
+
+fn select_item(field: &SelectField) -> SelectItem {
+    if field.children.is_empty() {
+        SelectItem::Field(field.name.clone())
+    } else {
+        SelectItem::Nested { field: field.name.clone(), select: field.children.iter().map(select_item).collect() }
+    }
+}
@@ -0,0 +1,169 @@
+//! `GET /<Table>/aggregate?<fiql>&groupBy=a,b&metrics=count,avg(price)`:
+//! group counts and metrics over the records a FIQL filter selects.
+//!
+//! The filter is held to the same cost budget as the table endpoint and
+//! evaluated by a scan of the table, like an unpaged table read;
+//! `explain=true` includes the plan the budget was estimated from. The
+//! other controls shape rows, which an aggregate has none of, so they are
+//! a 400.
+//! Group fields follow relationships (`groupBy=brand.country`).
+
+use serde_json::json;
+
+use super::{Resource, parse_error, split_params};
+use crate::app::App;
+use crate::fiql::{self, FieldPath, Span};
+use crate::http::{Request, Response};
+use crate::schema::{Schema, TypeDef};
+use crate::store::{self, Function, Metric};
+use crate::validate::{Diagnostic, Resolved, resolve};
+
+pub struct Aggregate {
+    table: String,
+    path: String,
+}
+
+impl Aggregate {
+    pub fn new(table: &str) -> Self {
+        Aggregate { table: table.to_string(), path: format!("/{table}/aggregate") }
+    }
+}
+
+impl Resource for Aggregate {
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn get(&self, app: &App, req: &Request) -> Response {
+        let store = app.store();
+        let Some(table) = store.schema().table(&self.table) else { return Response::not_found() };
+        let (filter, params) = split_params(&req.query, &["groupBy", "metrics"]);
+        let param = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+
+        let query = match fiql::parse(&filter) {
+            Ok(query) => query,
+            Err(err) => return parse_error(&filter, &err),
+        };
+        let controls = &query.controls;
+        let set = [
+            ("select", controls.select.is_some()),
+            ("sort", controls.sort.is_some()),
+            ("limit", controls.limit.is_some()),
+            ("offset", controls.offset.is_some()),
+            ("after", controls.after.is_some()),
+            ("pagination", controls.pagination.is_some()),
+            ("stream", controls.stream.is_some()),
+        ];
+        if let Some((name, _)) = set.iter().find(|(_, set)| *set) {
+            return Response::error(400, format!("`{name}` does not apply to an aggregate"));
+        }
+        let group_by = match param("groupBy").map(|g| group_fields(store.schema(), table, g)).transpose() {
+            Ok(fields) => fields.unwrap_or_default(),
+            Err(d) => return bad_param("groupBy", d),
+        };
+        let metrics = match parse_metrics(store.schema(), table, param("metrics").unwrap_or("count")) {
+            Ok(metrics) => metrics,
+            Err(d) => return bad_param("metrics", d),
+        };
+
+        if let Err(response) = app.check_budget(table, &query) {
+            return response;
+        }
+        let records = match store::matching(store, table, &query) {
+            Ok(records) => records,
+            Err(err) => return Response::error(err.status(), err.to_string()),
+        };
+        let mut body = json!({
+            "table": table.name,
+            "groupBy": group_by.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "metrics": metrics.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "total": records.len(),
+            "groups": store::aggregate(store, table, &records, &group_by, &metrics),
+        });
+        if query.controls.explain == Some(true) {
+            body["plan"] = serde_json::to_value(store::plan(store, table, &query)).expect("plans serialize");
+        }
+        Response::json(200, &body)
+    }
+}
+
+fn bad_param(name: &str, diagnostic: Diagnostic) -> Response {
+    Response::json(400, &json!({ "error": format!("{name}: {}", diagnostic.message), "diagnostic": diagnostic }))
+}
+
+/// `brand.country,inStock`: each field must resolve to a value, not to a
+/// bare relationship.
+fn group_fields(schema: &Schema, table: &TypeDef, text: &str) -> Result<Vec<FieldPath>, Diagnostic> {
+    let mut fields = Vec::new();
+    let mut offset = 0;
+    for raw in text.split(',') {
+        let start = offset + raw.len() - raw.trim_start().len();
+        offset += raw.len() + 1;
+        let raw = raw.trim();
+        let span = Span::new(start, start + raw.len());
+        let path = field_path(raw, span)?;
+        if let Resolved::Relationship(..) = resolve(schema, table, &path)? {
+            return Err(Diagnostic::error(
+                "relationship-without-field",
+                format!("cannot group by relationship `{path}`; name a field such as `{path}.id`"),
+                span,
+            ));
+        }
+        fields.push(path);
+    }
+    Ok(fields)
+}
+
+/// `count,avg(price),max(price)`. `sum` and `avg` need a numeric field.
+fn parse_metrics(schema: &Schema, table: &TypeDef, text: &str) -> Result<Vec<Metric>, Diagnostic> {
+    let mut metrics = Vec::new();
+    let mut offset = 0;
+    for raw in text.split(',') {
+        let start = offset + raw.len() - raw.trim_start().len();
+        offset += raw.len() + 1;
+        let raw = raw.trim();
+        let span = Span::new(start, start + raw.len());
+        let (name, arg) = match raw.strip_suffix(')').and_then(|r| r.split_once('(')) {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (raw, None),
+        };
+        let unknown = || {
+            Diagnostic::error(
+                "unknown-metric",
+                format!("unknown metric `{raw}`; expected count, sum, avg, min or max"),
+                span,
+            )
+        };
+        let function = Function::from_name(name).ok_or_else(unknown)?;
+        let field = match arg {
+            None if function == Function::Count => None,
+            None => return Err(unknown()),
+            Some(arg) => Some(field_path(arg, span)?),
+        };
+        if let Some(path) = &field {
+            match resolve(schema, table, path)? {
+                Resolved::Scalar(_, ty) if function.is_numeric() && !ty.is_numeric() => {
+                    return Err(Diagnostic::error(
+                        "type-mismatch",
+                        format!("`{}` needs a numeric field; `{path}` is {ty:?}", function.name()),
+                        span,
+                    ));
+                }
+                Resolved::Relationship(..) => {
+                    return Err(Diagnostic::error(
+                        "relationship-without-field",
+                        format!("`{raw}` aggregates a relationship; name one of its fields"),
+                        span,
+                    ));
+                }
+                _ => {}
+            }
+        }
+        metrics.push(Metric { function, field });
+    }
+    Ok(metrics)
+}
+
+fn field_path(raw: &str, span: Span) -> Result<FieldPath, Diagnostic> {
+    fiql::field_path(raw, span).map_err(|e| Diagnostic::error("syntax", e.kind.to_string(), e.span))
+}
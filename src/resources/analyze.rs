@@ -0,0 +1,96 @@
+//! `GET /analyze?field=Products.name&text=...`: how a full-text field's
+//! analyzer splits a text, for working out why an `=ft=` does or does not
+//! match. `analyzer=english` tries an analyzer without a field.
+//!
+//! `tokens` are what the index holds for the text, one per word position;
+//! `search` is what an `=ft=` phrase of the same text looks for.
+
+use serde_json::json;
+
+use super::Resource;
+use crate::app::App;
+use crate::fiql::Term;
+use crate::http::{Request, Response};
+use crate::schema::{Analyzer, Schema};
+use crate::store::{analyze, query_terms};
+
+pub struct Analyze;
+
+impl Resource for Analyze {
+    fn path(&self) -> &str {
+        "/analyze"
+    }
+
+    fn get(&self, app: &App, req: &Request) -> Response {
+        let Some(text) = req.param("text") else {
+            return Response::error(400, "missing `text` parameter, e.g. /analyze?field=Products.name&text=Headphones");
+        };
+        let field = req.param("field");
+        let analyzer = match (req.param("analyzer"), &field) {
+            (Some(name), _) => match Analyzer::from_name(&name) {
+                Some(analyzer) => analyzer,
+                None => {
+                    let names: Vec<&str> = Analyzer::ALL.iter().map(|a| a.name()).collect();
+                    return Response::error(
+                        400,
+                        format!("analyzer: `{name}` is not an analyzer; available: {}", names.join(", ")),
+                    );
+                }
+            },
+            (None, Some(field)) => match fulltext_field(app.store().schema(), field) {
+                Some(analyzer) => analyzer,
+                None => {
+                    let fields = fulltext_fields(app.store().schema());
+                    return Response::error(
+                        400,
+                        format!("field: `{field}` is not a full-text field; full-text fields: {}", fields.join(", ")),
+                    );
+                }
+            },
+            (None, None) => {
+                return Response::error(
+                    400,
+                    "missing `field` or `analyzer` parameter, e.g. /analyze?field=Products.name&text=Headphones",
+                );
+            }
+        };
+
+        let tokens: Vec<_> = analyze(analyzer, &text)
+            .into_iter()
+            .enumerate()
+            .map(|(position, token)| {
+                json!({
+                    "token": &text[token.span.clone()],
+                    "start": token.span.start,
+                    "end": token.span.end,
+                    "position": position,
+                    "terms": token.terms,
+                })
+            })
+            .collect();
+        let (search, _) = query_terms(analyzer, &Term::Phrase(text.clone()));
+        Response::json(
+            200,
+            &json!({
+                "field": field,
+                "analyzer": analyzer.name(),
+                "text": text,
+                "tokens": tokens,
+                "search": search,
+            }),
+        )
+    }
+}
+
+/// The analyzer of `Table.field`, when that is a full-text field.
+fn fulltext_field(schema: &Schema, path: &str) -> Option<Analyzer> {
+    let (table, field) = path.split_once('.')?;
+    schema.table(table)?.field(field).filter(|f| f.is_fulltext()).map(|f| f.analyzer())
+}
+
+fn fulltext_fields(schema: &Schema) -> Vec<String> {
+    schema
+        .tables()
+        .flat_map(|t| t.fields.iter().filter(|f| f.is_fulltext()).map(move |f| format!("{}.{}", t.name, f.name)))
+        .collect()
+}
@@ -0,0 +1,94 @@
+//! `GET /<Table>/facets?<fiql>`: facet counts for every indexed field.
+//!
+//! Numeric fields are bucketed by the edges in
+//! `[package.metadata.app.facets.buckets]`; `buckets.price=0,100,500`
+//! overrides them for one request and `facets=category,price` limits the
+//! response to the named facets.
+
+use serde_json::json;
+
+use super::{Resource, parse_error, split_params};
+use crate::app::App;
+use crate::config;
+use crate::fiql::{self, parse_number};
+use crate::http::{Request, Response};
+use crate::store::{self, Facet};
+
+pub struct Facets {
+    table: String,
+    path: String,
+}
+
+impl Facets {
+    pub fn new(table: &str) -> Self {
+        Facets { table: table.to_string(), path: format!("/{table}/facets") }
+    }
+}
+
+impl Resource for Facets {
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn get(&self, app: &App, req: &Request) -> Response {
+        let store = app.store();
+        let Some(table) = store.schema().table(&self.table) else { return Response::not_found() };
+        let numeric: Vec<&str> = table
+            .fields
+            .iter()
+            .filter(|f| f.scalar().is_some_and(|s| s.is_numeric()))
+            .map(|f| f.name.as_str())
+            .collect();
+        let bucket_params: Vec<String> = numeric.iter().map(|f| format!("buckets.{f}")).collect();
+        let mut names = vec!["facets"];
+        names.extend(bucket_params.iter().map(String::as_str));
+        let (filter, params) = split_params(&req.query, &names);
+
+        let query = match fiql::parse(&filter) {
+            Ok(query) => query,
+            Err(err) => return parse_error(&filter, &err),
+        };
+        let mut buckets = config::bundled().facets.buckets.clone();
+        for (name, value) in &params {
+            let Some(field) = name.strip_prefix("buckets.") else { continue };
+            let edges: Option<Vec<f64>> = value.split(',').map(parse_number).collect();
+            match edges {
+                Some(edges) if !edges.is_empty() && edges.windows(2).all(|w| w[0] < w[1]) => {
+                    buckets.insert(field.to_string(), edges);
+                }
+                _ => return Response::error(400, format!("{name}: expected increasing numbers, e.g. 0,50,100")),
+            }
+        }
+
+        let mut selected: Vec<Facet> = store::default_facets(table, &buckets);
+        if let Some((_, only)) = params.iter().find(|(k, _)| k == "facets") {
+            let wanted: Vec<&str> = only.split(',').map(str::trim).collect();
+            if let Some(unknown) = wanted.iter().find(|w| !selected.iter().any(|f| f.field == **w)) {
+                let available: Vec<&str> = selected.iter().map(|f| f.field.as_str()).collect();
+                return Response::error(
+                    400,
+                    format!(
+                        "facets: `{unknown}` is not a facet of `{}`; available: {}",
+                        table.name,
+                        available.join(", ")
+                    ),
+                );
+            }
+            selected.retain(|f| wanted.contains(&f.field.as_str()));
+        }
+
+        // Each facet re-runs the filter without its own conditions, so every
+        // one of those queries counts against the budget.
+        if let Err(response) = app.check_estimate(store::facets_estimate(store, table, &query, &selected)) {
+            return response;
+        }
+        let total = match store::matching(store, table, &query) {
+            Ok(records) => records.len(),
+            Err(err) => return Response::error(err.status(), err.to_string()),
+        };
+        match store::facets(store, table, &query, &selected) {
+            Ok(facets) => Response::json(200, &json!({ "table": table.name, "total": total, "facets": facets })),
+            Err(err) => Response::error(err.status(), err.to_string()),
+        }
+    }
+}
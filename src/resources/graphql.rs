@@ -0,0 +1,72 @@
+//! `POST /graphql` with `{"query": ..., "variables": {...}, "operationName":
+//! ...}`, or `GET /graphql?query=...&variables=...`: the tables read
+//! through GraphQL, with FIQL in each list's `filter` argument.
+//!
+//! `GET /graphql/schema` serves the GraphQL schema the endpoint answers
+//! to, as SDL.
+
+use serde_json::{Map, Value as Json, json};
+
+use super::{Resource, subpath};
+use crate::app::App;
+use crate::graphql::{self, Outcome};
+use crate::http::{Request, Response};
+
+pub struct Graphql;
+
+impl Resource for Graphql {
+    fn path(&self) -> &str {
+        "/graphql"
+    }
+
+    fn get(&self, app: &App, req: &Request) -> Response {
+        match subpath(req, self) {
+            "" | "/" => {}
+            "/schema" => return Response::new(200, "text/plain; charset=utf-8", graphql::sdl(app.store().schema())),
+            _ => return Response::not_found(),
+        }
+        let Some(query) = req.param("query") else {
+            return request_error("missing `query` parameter, e.g. /graphql?query={products(first:3){name}}");
+        };
+        let variables = match req.param("variables").map(|v| serde_json::from_str::<Json>(&v)) {
+            None => Map::new(),
+            Some(Ok(Json::Object(variables))) => variables,
+            Some(Ok(Json::Null)) => Map::new(),
+            Some(_) => return request_error("variables: expected a JSON object"),
+        };
+        respond(&graphql::execute(app.store(), &query, &variables, req.param("operationName").as_deref()))
+    }
+
+    fn post(&self, app: &App, req: &Request) -> Response {
+        if !matches!(subpath(req, self), "" | "/") {
+            return Response::method_not_allowed();
+        }
+        let body: Map<String, Json> = match serde_json::from_slice(&req.body) {
+            Ok(Json::Object(body)) => body,
+            _ => return request_error("expected a JSON body such as {\"query\": \"{ products { name } }\"}"),
+        };
+        let Some(query) = body.get("query").and_then(Json::as_str) else {
+            return request_error("missing `query` in the request body");
+        };
+        let variables = match body.get("variables") {
+            None | Some(Json::Null) => Map::new(),
+            Some(Json::Object(variables)) => variables.clone(),
+            Some(_) => return request_error("variables: expected a JSON object"),
+        };
+        let operation = match body.get("operationName") {
+            None | Some(Json::Null) => None,
+            Some(Json::String(name)) => Some(name.as_str()),
+            Some(_) => return request_error("operationName: expected a string"),
+        };
+        respond(&graphql::execute(app.store(), query, &variables, operation))
+    }
+}
+
+fn respond(outcome: &Outcome) -> Response {
+    Response::json(outcome.status(), &outcome.to_json())
+}
+
+/// A request that never reached the document, in GraphQL's error shape.
+fn request_error(message: &str) -> Response {
+    Response::json(400, &json!({ "errors": [{ "message": message }] }))
+}
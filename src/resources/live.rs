@@ -0,0 +1,19 @@
+//! `GET /live`: the WebSocket endpoint for live queries. See [`crate::live::ws`]
+//! for the message protocol.
+
+use super::Resource;
+use crate::app::App;
+use crate::http::{Request, Response};
+use crate::live::ws;
+
+pub struct Live;
+
+impl Resource for Live {
+    fn path(&self) -> &str {
+        "/live"
+    }
+
+    fn get(&self, _app: &App, req: &Request) -> Response {
+        ws::handshake(req)
+    }
+}
@@ -0,0 +1,122 @@
+//! Custom resources served alongside the generated table endpoints.
+//!
+//! Each resource owns a path prefix under the app mount. [`route`] picks
+//! the resource with the longest matching prefix and dispatches on method;
+//! paths no resource claims fall through to the table endpoints.
+
+mod aggregate;
+mod analyze;
+mod facets;
+mod graphql;
+mod live;
+mod parse;
+mod suggest;
+mod validate;
+
+use crate::app::App;
+use crate::fiql::decode;
+use crate::http::{Method, Request, Response};
+use crate::schema::Schema;
+
+pub use aggregate::Aggregate;
+pub use analyze::Analyze;
+pub use facets::Facets;
+pub use graphql::Graphql;
+pub use live::Live;
+pub use parse::Parse;
+pub(crate) use parse::parse_error;
+pub use suggest::Suggest;
+pub use validate::Validate;
+
+pub trait Resource: Send + Sync {
+    /// Path prefix relative to the app mount, e.g. `/parse`.
+    fn path(&self) -> &str;
+
+    fn get(&self, _app: &App, _req: &Request) -> Response {
+        Response::method_not_allowed()
+    }
+
+    fn post(&self, _app: &App, _req: &Request) -> Response {
+        Response::method_not_allowed()
+    }
+}
+
+/// Every custom resource exposed by the app, including the per-table ones
+/// (`/Products/aggregate`, `/Products/suggest`) for each table in `schema`.
+/// The store refuses records keyed by a per-table resource's name; see
+/// [`RESERVED_KEYS`](crate::store::RESERVED_KEYS).
+pub fn resources(schema: &Schema) -> Vec<Box<dyn Resource>> {
+    let mut all: Vec<Box<dyn Resource>> =
+        vec![Box::new(Parse), Box::new(Validate), Box::new(Live), Box::new(Analyze), Box::new(Graphql)];
+    for table in schema.table_names() {
+        all.push(Box::new(Aggregate::new(table)));
+        all.push(Box::new(Facets::new(table)));
+        all.push(Box::new(Suggest::new(table)));
+    }
+    all
+}
+
+/// Routes `req` to the matching resource, or `None` if no resource claims
+/// its path.
+pub fn route(app: &App, req: &Request) -> Option<Response> {
+    let all = resources(app.store().schema());
+    let resource = all.iter().filter(|r| matches_prefix(&req.path, r.path())).max_by_key(|r| r.path().len())?;
+    Some(match req.method {
+        Method::Get => resource.get(app, req),
+        Method::Post => resource.post(app, req),
+        _ => Response::method_not_allowed(),
+    })
+}
+
+fn matches_prefix(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// The request path after the resource's own prefix, e.g. `/Products/` for
+/// `/parse/Products/`.
+pub(crate) fn subpath<'a>(req: &'a Request, resource: &dyn Resource) -> &'a str {
+    req.path.strip_prefix(resource.path()).unwrap_or("")
+}
+
+/// The REST path a debugging resource operates on: the decoded `q` param, or
+/// the request's own subpath and query (`/parse/Products/?a==1`).
+pub(crate) fn target_path(req: &Request, resource: &dyn Resource) -> Option<String> {
+    match req.param("q") {
+        Some(q) => Some(q),
+        None if subpath(req, resource).len() > 1 => Some(format!("{}?{}", subpath(req, resource), req.query)),
+        None => None,
+    }
+}
+
+/// Splits the named resource params (`groupBy=...`) out of a raw query
+/// string, leaving the FIQL for the parser. Only top-level `&` terms are
+/// considered, so a parenthesized group is never cut apart.
+pub(crate) fn split_params(query: &str, names: &[&str]) -> (String, Vec<(String, String)>) {
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut terms = Vec::new();
+    for (i, c) in query.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '&' if depth == 0 => {
+                terms.push(&query[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    terms.push(&query[start..]);
+
+    let mut rest = Vec::new();
+    let mut params = Vec::new();
+    for term in terms {
+        match term.split_once('=') {
+            Some((name, value)) if names.contains(&name) && !value.starts_with('=') => {
+                params.push((name.to_string(), decode(value, 0).unwrap_or_else(|_| value.to_string())));
+            }
+            _ => rest.push(term),
+        }
+    }
+    (rest.join("&"), params)
+}
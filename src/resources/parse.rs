@@ -0,0 +1,40 @@
+//! `GET /parse?q=<path>` (or `GET /parse/<Table>/?<fiql>`): the structured
+//! [`ResourceQuery`] the server derives from a REST path.
+
+use serde_json::json;
+
+use super::{Resource, target_path};
+use crate::app::App;
+use crate::fiql::{self, ParseError};
+use crate::http::{Request, Response};
+use crate::query::ResourceQuery;
+
+pub struct Parse;
+
+impl Resource for Parse {
+    fn path(&self) -> &str {
+        "/parse"
+    }
+
+    fn get(&self, _app: &App, req: &Request) -> Response {
+        let Some(target) = target_path(req, self) else {
+            return Response::error(400, "missing `q` parameter, e.g. /parse?q=/Products/%3Fprice=gt=100");
+        };
+        match fiql::parse_path(&target) {
+            Ok(parsed) => Response::json(200, &ResourceQuery::from_path(&parsed)),
+            Err(err) => parse_error(&target, &err),
+        }
+    }
+}
+
+/// 400 with the message and the byte span within `input`.
+pub(crate) fn parse_error(input: &str, err: &ParseError) -> Response {
+    Response::json(
+        400,
+        &json!({
+            "error": err.kind.to_string(),
+            "input": input,
+            "span": { "start": err.span.start, "end": err.span.end },
+        }),
+    )
+}
@@ -0,0 +1,86 @@
+//! `GET /<Table>/suggest?field=name&prefix=ult&limit=8&<fiql>`: typeahead
+//! suggestions for a search box.
+//!
+//! Full-text fields complete words from their index; enumerable fields
+//! (`category`, `brand.name`) suggest values starting with the prefix. The
+//! FIQL filter scopes the records counted, so `inStock==true` only
+//! suggests what can be bought.
+
+use serde_json::json;
+
+use super::{Resource, parse_error, split_params};
+use crate::app::App;
+use crate::fiql;
+use crate::http::{Request, Response};
+use crate::store::{self, DEFAULT_SUGGESTIONS};
+
+/// The most suggestions one request can ask for.
+const MAX_SUGGESTIONS: usize = 100;
+
+pub struct Suggest {
+    table: String,
+    path: String,
+}
+
+impl Suggest {
+    pub fn new(table: &str) -> Self {
+        Suggest { table: table.to_string(), path: format!("/{table}/suggest") }
+    }
+}
+
+impl Resource for Suggest {
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn get(&self, app: &App, req: &Request) -> Response {
+        let store = app.store();
+        let Some(table) = store.schema().table(&self.table) else { return Response::not_found() };
+        let (filter, params) = split_params(&req.query, &["field", "prefix", "limit"]);
+        let param = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+
+        let suggestable = store::suggestable(store.schema(), table);
+        let Some(field) = param("field") else {
+            return Response::error(
+                400,
+                format!("missing `field` parameter; suggestable fields: {}", suggestable.join(", ")),
+            );
+        };
+        if !suggestable.iter().any(|f| f == field) {
+            return Response::error(
+                400,
+                format!(
+                    "field: `{field}` has no suggestions on `{}`; suggestable fields: {}",
+                    table.name,
+                    suggestable.join(", ")
+                ),
+            );
+        }
+        let Some(prefix) = param("prefix") else {
+            return Response::error(400, "missing `prefix` parameter, e.g. prefix=ult");
+        };
+        let limit = match param("limit").map(str::parse::<usize>) {
+            None => DEFAULT_SUGGESTIONS,
+            Some(Ok(n)) if (1..=MAX_SUGGESTIONS).contains(&n) => n,
+            Some(_) => {
+                return Response::error(400, format!("limit: expected a whole number from 1 to {MAX_SUGGESTIONS}"));
+            }
+        };
+
+        let query = match fiql::parse(&filter) {
+            Ok(query) => query,
+            Err(err) => return parse_error(&filter, &err),
+        };
+        if let Err(response) = app.check_budget(table, &query) {
+            return response;
+        }
+        let path: Vec<String> = field.split('.').map(str::to_string).collect();
+        match store::suggest(store, table, &query, &path, prefix, limit) {
+            Ok(suggestions) => Response::json(
+                200,
+                &json!({ "table": table.name, "field": field, "prefix": prefix, "suggestions": suggestions }),
+            ),
+            Err(err) => Response::error(err.status(), err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! `GET /validate?q=<path>`: schema diagnostics for a REST path.
+
+use serde_json::json;
+
+use super::{Resource, target_path};
+use crate::app::App;
+use crate::fiql;
+use crate::http::{Request, Response};
+use crate::validate::{Diagnostic, Severity, validate};
+
+pub struct Validate;
+
+impl Resource for Validate {
+    fn path(&self) -> &str {
+        "/validate"
+    }
+
+    fn get(&self, app: &App, req: &Request) -> Response {
+        let Some(target) = target_path(req, self) else {
+            return Response::error(400, "missing `q` parameter, e.g. /validate?q=/Products/%3Fprcie=gt=100");
+        };
+        let diagnostics = match fiql::parse_path(&target) {
+            Ok(parsed) => validate(app.store().schema(), &parsed),
+            Err(err) => vec![Diagnostic {
+                severity: Severity::Error,
+                code: "syntax",
+                message: err.kind.to_string(),
+                span: err.span,
+                suggestion: None,
+            }],
+        };
+        let valid = diagnostics.iter().all(|d| d.severity != Severity::Error);
+        Response::json(200, &json!({ "input": target, "valid": valid, "diagnostics": diagnostics }))
+    }
+}
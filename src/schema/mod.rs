@@ -0,0 +1,358 @@
+//! Table definitions read from the app's GraphQL SDL (`schemas/fiql.graphql`).
+//!
+//! Only the subset of SDL the platform uses for tables is understood: object
+//! types with directives, scalar/list/non-null field types and directive
+//! arguments. Types marked `@table` are tables; the others describe the
+//! objects stored inside a field, such as the elements of `[Variant]`.
+
+mod sdl;
+
+use std::fmt;
+use std::sync::OnceLock;
+
+pub use sdl::SchemaError;
+
+/// The schema bundled with this app.
+pub fn bundled() -> &'static Schema {
+    static SCHEMA: OnceLock<Schema> = OnceLock::new();
+    SCHEMA.get_or_init(|| Schema::parse(include_str!("../../schemas/fiql.graphql")).expect("bundled schema is valid"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub types: Vec<TypeDef>,
+}
+
+impl Schema {
+    pub fn parse(src: &str) -> Result<Schema, SchemaError> {
+        sdl::parse(src)
+    }
+
+    pub fn table(&self, name: &str) -> Option<&TypeDef> {
+        self.tables().find(|t| t.name == name)
+    }
+
+    pub fn table_names(&self) -> impl Iterator<Item = &str> {
+        self.tables().map(|t| t.name.as_str())
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &TypeDef> {
+        self.types.iter().filter(|t| t.is_table())
+    }
+
+    /// An object type without `@table`, stored inside records.
+    pub fn object(&self, name: &str) -> Option<&TypeDef> {
+        self.types.iter().find(|t| t.name == name && !t.is_table())
+    }
+
+    /// The table or object type a field holds, for relationships and
+    /// object fields.
+    pub fn type_of(&self, field: &FieldDef) -> Option<&TypeDef> {
+        self.table(&field.ty.name).or_else(|| self.object(&field.ty.name))
+    }
+
+    /// The field a dotted path ends at, walking from `table` through
+    /// relationships and object fields.
+    pub fn field_at<'a>(&'a self, table: &'a TypeDef, path: &[String]) -> Option<&'a FieldDef> {
+        let (last, parents) = path.split_last()?;
+        let mut owner = table;
+        for segment in parents {
+            owner = self.type_of(owner.field(segment)?)?;
+        }
+        owner.field(last)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDef {
+    pub name: String,
+    pub fields: Vec<FieldDef>,
+    pub directives: Vec<Directive>,
+}
+
+impl TypeDef {
+    pub fn field(&self, name: &str) -> Option<&FieldDef> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    pub fn primary_key(&self) -> Option<&FieldDef> {
+        self.fields.iter().find(|f| f.directive("primaryKey").is_some())
+    }
+
+    pub fn directive(&self, name: &str) -> Option<&Directive> {
+        self.directives.iter().find(|d| d.name == name)
+    }
+
+    pub fn is_table(&self) -> bool {
+        self.directive("table").is_some()
+    }
+
+    /// The `@embedding` field a `=sim=` on `name` searches: `name` itself
+    /// when it is one, or the field embedding `name`'s text.
+    pub fn embedding_for(&self, name: &str) -> Option<&FieldDef> {
+        self.fields
+            .iter()
+            .filter(|f| f.embedding().is_some_and(|e| f.name == name || e.from == name))
+            .min_by_key(|f| f.name != name)
+    }
+
+    /// Field lists of every `@compositeIndex(fields: "a,b")`.
+    pub fn composite_indexes(&self) -> Vec<Vec<&str>> {
+        self.directives
+            .iter()
+            .filter(|d| d.name == "compositeIndex")
+            .filter_map(|d| d.arg_str("fields"))
+            .map(|fields| fields.split(',').map(str::trim).collect())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDef {
+    pub name: String,
+    pub ty: TypeRef,
+    pub directives: Vec<Directive>,
+}
+
+impl FieldDef {
+    pub fn directive(&self, name: &str) -> Option<&Directive> {
+        self.directives.iter().find(|d| d.name == name)
+    }
+
+    /// Index kind from `@indexed` (`"btree"` when no `type:` is given).
+    pub fn index_type(&self) -> Option<&str> {
+        self.directive("indexed").map(|d| d.arg_str("type").unwrap_or("btree"))
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.index_type().is_some() || self.directive("primaryKey").is_some()
+    }
+
+    pub fn is_fulltext(&self) -> bool {
+        self.index_type() == Some("fulltext")
+    }
+
+    /// What a match in this full-text field adds to `_score` against one in
+    /// another, from `@indexed(type: "fulltext", boost: 2)`; 1 by default.
+    pub fn boost(&self) -> f64 {
+        match self.directive("indexed").and_then(|d| d.arg("boost")) {
+            Some(DirectiveValue::Int(n)) if *n > 0 => *n as f64,
+            Some(DirectiveValue::Float(x)) if *x > 0.0 => *x,
+            _ => 1.0,
+        }
+    }
+
+    /// How a full-text field splits its text into terms, from
+    /// `@indexed(type: "fulltext", analyzer: "english")`; `standard` by
+    /// default.
+    pub fn analyzer(&self) -> Analyzer {
+        self.directive("indexed").and_then(|d| d.arg_str("analyzer")).and_then(Analyzer::from_name).unwrap_or_default()
+    }
+
+    /// `@indexed(type: "geo")` on a `GeoPoint` field.
+    pub fn is_geo(&self) -> bool {
+        self.index_type() == Some("geo")
+    }
+
+    /// `@indexed(type: "hnsw")` on an `@embedding` field.
+    pub fn is_vector(&self) -> bool {
+        self.index_type() == Some("hnsw")
+    }
+
+    /// `@embedding(from: "description", dimensions: 64)`: a vector the
+    /// store computes from another field's text.
+    pub fn embedding(&self) -> Option<Embedding<'_>> {
+        let d = self.directive("embedding")?;
+        let dimensions = match d.arg("dimensions") {
+            Some(DirectiveValue::Int(n)) if *n > 0 => *n as usize,
+            _ => DEFAULT_DIMENSIONS,
+        };
+        Some(Embedding { from: d.arg_str("from")?, dimensions })
+    }
+
+    /// Set by the store when the record is first written (`@createdTime`).
+    pub fn is_created_time(&self) -> bool {
+        self.directive("createdTime").is_some()
+    }
+
+    /// Set by the store on every write (`@updatedTime`).
+    pub fn is_updated_time(&self) -> bool {
+        self.directive("updatedTime").is_some()
+    }
+
+    /// How the index compares text, from `@indexed(collation: "ci")`.
+    pub fn collation(&self) -> Option<Collation> {
+        match self.directive("indexed")?.arg_str("collation")? {
+            "ci" => Some(Collation::CaseInsensitive),
+            "ci_ai" => Some(Collation::AccentInsensitive),
+            _ => None,
+        }
+    }
+
+    pub fn relationship(&self) -> Option<Relationship> {
+        let d = self.directive("relationship")?;
+        if let Some(from) = d.arg_str("from") {
+            Some(Relationship::From(from.to_string()))
+        } else {
+            d.arg_str("to").map(|to| Relationship::To(to.to_string()))
+        }
+    }
+
+    pub fn scalar(&self) -> Option<ScalarType> {
+        ScalarType::from_name(&self.ty.name)
+    }
+}
+
+/// Vector length of an `@embedding` without `dimensions:`.
+pub const DEFAULT_DIMENSIONS: usize = 64;
+
+/// Where an `@embedding` field's vector comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Embedding<'a> {
+    /// The `String` field whose text is embedded.
+    pub from: &'a str,
+    pub dimensions: usize,
+}
+
+/// How an `@indexed` string field compares text: both ignore case, using
+/// Unicode case folding, and `ci_ai` also ignores accents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collation {
+    /// `ci`
+    CaseInsensitive,
+    /// `ci_ai`
+    AccentInsensitive,
+}
+
+/// How a full-text field's text and the `=ft=` searches on it are split
+/// into terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Analyzer {
+    /// `standard`: lowercased alphanumeric words.
+    #[default]
+    Standard,
+    /// `english`: standard words without stopwords, with synonyms added and
+    /// every word stemmed, so `programs` finds `programming`.
+    English,
+    /// `ngram`: every run of three or more letters within a word, so a
+    /// search finds words that contain it.
+    Ngram,
+    /// `edge_ngram`: every prefix of a word, so a search finds words that
+    /// start with it.
+    EdgeNgram,
+    /// `keyword`: the whole lowercased text as one term.
+    Keyword,
+}
+
+impl Analyzer {
+    pub const ALL: [Analyzer; 5] =
+        [Analyzer::Standard, Analyzer::English, Analyzer::Ngram, Analyzer::EdgeNgram, Analyzer::Keyword];
+
+    pub fn from_name(name: &str) -> Option<Analyzer> {
+        Analyzer::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Analyzer::Standard => "standard",
+            Analyzer::English => "english",
+            Analyzer::Ngram => "ngram",
+            Analyzer::EdgeNgram => "edge_ngram",
+            Analyzer::Keyword => "keyword",
+        }
+    }
+}
+
+/// How a relationship field joins to its target table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Relationship {
+    /// Forward join: this table's `from` field holds the target's key.
+    From(String),
+    /// Reverse join: the target table's `to` field holds this table's key.
+    To(String),
+}
+
+/// A field type such as `Float!` or `[Products]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeRef {
+    pub name: String,
+    pub list: bool,
+    pub non_null: bool,
+}
+
+impl fmt::Display for TypeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bang = if self.non_null { "!" } else { "" };
+        if self.list { write!(f, "[{}]{bang}", self.name) } else { write!(f, "{}{bang}", self.name) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScalarType {
+    Id,
+    String,
+    Int,
+    Float,
+    Boolean,
+    /// A calendar day, stored as `2024-01-31`.
+    Date,
+    /// An instant, stored as RFC 3339 in UTC: `2024-01-31T09:30:00Z`.
+    DateTime,
+    /// A latitude and longitude in degrees, stored as
+    /// `{ "lat": 35.68, "lon": 139.69 }`.
+    GeoPoint,
+}
+
+impl ScalarType {
+    pub fn from_name(name: &str) -> Option<ScalarType> {
+        match name {
+            "ID" => Some(ScalarType::Id),
+            "String" => Some(ScalarType::String),
+            "Int" => Some(ScalarType::Int),
+            "Float" => Some(ScalarType::Float),
+            "Boolean" => Some(ScalarType::Boolean),
+            "Date" => Some(ScalarType::Date),
+            "DateTime" => Some(ScalarType::DateTime),
+            "GeoPoint" => Some(ScalarType::GeoPoint),
+            _ => None,
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(self, ScalarType::Int | ScalarType::Float)
+    }
+
+    pub fn is_temporal(self) -> bool {
+        matches!(self, ScalarType::Date | ScalarType::DateTime)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    pub name: String,
+    pub args: Vec<(String, DirectiveValue)>,
+}
+
+impl Directive {
+    pub fn arg(&self, name: &str) -> Option<&DirectiveValue> {
+        self.args.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+
+    pub fn arg_str(&self, name: &str) -> Option<&str> {
+        match self.arg(name)? {
+            DirectiveValue::String(s) | DirectiveValue::Enum(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DirectiveValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    /// Bare identifier, e.g. `read` in `public: [read]`.
+    Enum(String),
+    List(Vec<DirectiveValue>),
+}
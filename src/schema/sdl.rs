@@ -0,0 +1,201 @@
+//! Tokenizer and parser for the table subset of GraphQL SDL.
+
+use std::fmt;
+
+use super::{Directive, DirectiveValue, FieldDef, Schema, TypeDef, TypeRef};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    pub message: String,
+    pub line: usize,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "schema line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Str(String),
+    Num(String),
+    Punct(char),
+}
+
+pub(super) fn parse(src: &str) -> Result<Schema, SchemaError> {
+    let tokens = tokenize(src)?;
+    let mut p = SdlParser { tokens, pos: 0 };
+    let mut types = Vec::new();
+    while !p.at_end() {
+        match p.next_name()?.as_str() {
+            "type" => types.push(p.type_def()?),
+            other => return Err(p.error(format!("expected `type`, found `{other}`"))),
+        }
+    }
+    Ok(Schema { types })
+}
+
+fn tokenize(src: &str) -> Result<Vec<(Token, usize)>, SchemaError> {
+    let mut tokens = Vec::new();
+    for (index, line) in src.lines().enumerate() {
+        let line_no = index + 1;
+        let mut chars = line.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '#' => break,
+                // Commas are insignificant in GraphQL.
+                c if c.is_whitespace() || c == ',' => {}
+                '"' => {
+                    let rest = &line[i + 1..];
+                    let end = rest
+                        .find('"')
+                        .ok_or(SchemaError { message: "unterminated string".to_string(), line: line_no })?;
+                    tokens.push((Token::Str(rest[..end].to_string()), line_no));
+                    let close = i + 1 + end;
+                    while chars.next_if(|&(j, _)| j <= close).is_some() {}
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    let mut end = i + c.len_utf8();
+                    while let Some(&(j, d)) = chars.peek() {
+                        if !(d.is_ascii_alphanumeric() || d == '_') {
+                            break;
+                        }
+                        end = j + d.len_utf8();
+                        chars.next();
+                    }
+                    tokens.push((Token::Name(line[i..end].to_string()), line_no));
+                }
+                c if c.is_ascii_digit() || c == '-' => {
+                    let mut end = i + 1;
+                    while let Some(&(j, d)) = chars.peek() {
+                        if !(d.is_ascii_digit() || d == '.') {
+                            break;
+                        }
+                        end = j + 1;
+                        chars.next();
+                    }
+                    tokens.push((Token::Num(line[i..end].to_string()), line_no));
+                }
+                '{' | '}' | '(' | ')' | '[' | ']' | ':' | '!' | '@' => tokens.push((Token::Punct(c), line_no)),
+                other => {
+                    return Err(SchemaError { message: format!("unexpected character `{other}`"), line: line_no });
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct SdlParser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl SdlParser {
+    fn type_def(&mut self) -> Result<TypeDef, SchemaError> {
+        let name = self.next_name()?;
+        let directives = self.directives()?;
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        while !self.eat('}') {
+            let field_name = self.next_name()?;
+            self.expect(':')?;
+            let ty = self.type_ref()?;
+            let directives = self.directives()?;
+            fields.push(FieldDef { name: field_name, ty, directives });
+        }
+        Ok(TypeDef { name, fields, directives })
+    }
+
+    fn type_ref(&mut self) -> Result<TypeRef, SchemaError> {
+        let list = self.eat('[');
+        let name = self.next_name()?;
+        if list {
+            // Element nullability is not tracked.
+            self.eat('!');
+            self.expect(']')?;
+        }
+        let non_null = self.eat('!');
+        Ok(TypeRef { name, list, non_null })
+    }
+
+    fn directives(&mut self) -> Result<Vec<Directive>, SchemaError> {
+        let mut directives = Vec::new();
+        while self.eat('@') {
+            let name = self.next_name()?;
+            let mut args = Vec::new();
+            if self.eat('(') {
+                while !self.eat(')') {
+                    let key = self.next_name()?;
+                    self.expect(':')?;
+                    args.push((key, self.value()?));
+                }
+            }
+            directives.push(Directive { name, args });
+        }
+        Ok(directives)
+    }
+
+    fn value(&mut self) -> Result<DirectiveValue, SchemaError> {
+        if self.eat('[') {
+            let mut items = Vec::new();
+            while !self.eat(']') {
+                items.push(self.value()?);
+            }
+            return Ok(DirectiveValue::List(items));
+        }
+        match self.bump() {
+            Some(Token::Str(s)) => Ok(DirectiveValue::String(s)),
+            Some(Token::Num(n)) => match n.parse::<i64>() {
+                Ok(i) => Ok(DirectiveValue::Int(i)),
+                Err(_) => {
+                    n.parse::<f64>().map(DirectiveValue::Float).map_err(|_| self.error(format!("invalid number `{n}`")))
+                }
+            },
+            Some(Token::Name(n)) if n == "true" => Ok(DirectiveValue::Bool(true)),
+            Some(Token::Name(n)) if n == "false" => Ok(DirectiveValue::Bool(false)),
+            Some(Token::Name(n)) => Ok(DirectiveValue::Enum(n)),
+            Some(Token::Punct(c)) => Err(self.error(format!("unexpected `{c}`"))),
+            None => Err(self.error("unexpected end of schema".to_string())),
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if matches!(self.tokens.get(self.pos), Some((Token::Punct(p), _)) if *p == c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SchemaError> {
+        if self.eat(c) { Ok(()) } else { Err(self.error(format!("expected `{c}`"))) }
+    }
+
+    fn next_name(&mut self) -> Result<String, SchemaError> {
+        match self.bump() {
+            Some(Token::Name(n)) => Ok(n),
+            _ => Err(self.error("expected a name".to_string())),
+        }
+    }
+
+    fn error(&self, message: String) -> SchemaError {
+        let line = self.tokens.get(self.pos.min(self.tokens.len().saturating_sub(1))).map_or(0, |(_, line)| *line);
+        SchemaError { message, line }
+    }
+}
@@ -0,0 +1,141 @@
+//! Group-by and metrics over a filtered record set.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::{Map, Value as Json};
+
+use super::Store;
+use super::eval::{Ordered, compare_json, field_values};
+use crate::fiql::FieldPath;
+use crate::schema::TypeDef;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "count" => Some(Function::Count),
+            "sum" => Some(Function::Sum),
+            "avg" => Some(Function::Avg),
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Count => "count",
+            Function::Sum => "sum",
+            Function::Avg => "avg",
+            Function::Min => "min",
+            Function::Max => "max",
+        }
+    }
+
+    /// `sum` and `avg` only make sense over numbers.
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Function::Sum | Function::Avg)
+    }
+}
+
+/// `count` or `fn(field)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    pub function: Function,
+    pub field: Option<FieldPath>,
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}({field})", self.function.name()),
+            None => f.write_str(self.function.name()),
+        }
+    }
+}
+
+/// Groups `records` by the values at `group_by` and computes `metrics` per
+/// group. A record whose group field is an array counts once in the group
+/// of each element; a missing field groups under `null`. Groups come back
+/// ordered by key, `null` last. With no `group_by` there is always exactly
+/// one group, so a filter that matches nothing still reports `count: 0`.
+pub fn aggregate(
+    store: &Store,
+    table: &TypeDef,
+    records: &[&Json],
+    group_by: &[FieldPath],
+    metrics: &[Metric],
+) -> Vec<Json> {
+    let mut groups: BTreeMap<Vec<Ordered>, Vec<&Json>> = BTreeMap::new();
+    if group_by.is_empty() {
+        groups.insert(Vec::new(), Vec::new());
+    }
+    for &record in records {
+        for key in keys(store, table, record, group_by) {
+            groups.entry(key).or_default().push(record);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(key, members)| {
+            let mut row = Map::new();
+            for (field, value) in group_by.iter().zip(key) {
+                row.insert(field.to_string(), value.0);
+            }
+            for metric in metrics {
+                row.insert(metric.to_string(), compute(store, table, &members, metric));
+            }
+            Json::Object(row)
+        })
+        .collect()
+}
+
+/// Every combination of group values for one record.
+fn keys(store: &Store, table: &TypeDef, record: &Json, group_by: &[FieldPath]) -> Vec<Vec<Ordered>> {
+    let mut out = vec![Vec::new()];
+    for field in group_by {
+        let mut values: Vec<Ordered> =
+            field_values(store, table, record, &field.segments).into_iter().map(|v| Ordered(v.clone())).collect();
+        values.sort();
+        values.dedup();
+        if values.is_empty() {
+            values.push(Ordered(Json::Null));
+        }
+        out = out
+            .into_iter()
+            .flat_map(|prefix| values.iter().map(move |v| [prefix.clone(), vec![v.clone()]].concat()))
+            .collect();
+    }
+    out
+}
+
+fn compute(store: &Store, table: &TypeDef, members: &[&Json], metric: &Metric) -> Json {
+    let Some(field) = &metric.field else { return Json::from(members.len()) };
+    let values: Vec<&Json> =
+        members.iter().flat_map(|r| field_values(store, table, r, &field.segments)).filter(|v| !v.is_null()).collect();
+    let numbers = || values.iter().filter_map(|v| v.as_f64());
+    match metric.function {
+        Function::Count => Json::from(values.len()),
+        Function::Sum => Json::from(tidy(numbers().sum::<f64>())),
+        Function::Avg => {
+            let count = numbers().count();
+            if count == 0 { Json::Null } else { Json::from(tidy(numbers().sum::<f64>() / count as f64)) }
+        }
+        Function::Min => values.iter().min_by(|a, b| compare_json(a, b)).map_or(Json::Null, |v| (*v).clone()),
+        Function::Max => values.iter().max_by(|a, b| compare_json(a, b)).map_or(Json::Null, |v| (*v).clone()),
+    }
+}
+
+/// Rounds away binary noise from summing decimal prices (`44.989999999999995`).
+fn tidy(n: f64) -> f64 {
+    (n * 1e9).round() / 1e9
+}
@@ -0,0 +1,245 @@
+//! Text analysis for full-text fields.
+//!
+//! An [`Analyzer`] splits a field's text into [`Token`]s, one per word
+//! position, each holding the terms a search can find the word by. The
+//! `english` analyzer drops stopwords, adds each word's stem and the stems
+//! of its synonyms, and keeps the word itself so that a prefix search still
+//! sees it. The n-gram analyzers hold every gram of the word at its
+//! position, so phrases keep working on them.
+//!
+//! Searches go through [`query_terms`], which splits a term of an `=ft=`
+//! the way the field's analyzer would but without expanding it: under
+//! `english` a searched word is stemmed and meets the stems in the text,
+//! and under the n-gram analyzers it is compared with the grams whole
+//! rather than cut into grams itself.
+//!
+//! The stopwords and synonyms come from `data/analysis.json`.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::fiql::Term;
+use crate::schema::Analyzer;
+
+/// The shortest gram the `ngram` analyzer keeps. Shorter words are kept
+/// whole.
+pub const MIN_GRAM: usize = 3;
+
+/// One word position of an analyzed text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    /// Byte range of the word in the text.
+    pub span: Range<usize>,
+    /// Every term the word is indexed under.
+    pub terms: Vec<String>,
+}
+
+/// `text` split into tokens by `analyzer`.
+pub fn analyze(analyzer: Analyzer, text: &str) -> Vec<Token> {
+    if analyzer == Analyzer::Keyword {
+        let trimmed = text.trim();
+        let start = text.len() - text.trim_start().len();
+        return (!trimmed.is_empty())
+            .then(|| Token { span: start..start + trimmed.len(), terms: vec![trimmed.to_lowercase()] })
+            .into_iter()
+            .collect();
+    }
+    words(text)
+        .into_iter()
+        .filter_map(|(span, word)| {
+            let terms = match analyzer {
+                Analyzer::Standard | Analyzer::Keyword => vec![word],
+                Analyzer::English if dictionary().stopwords.contains(&word) => return None,
+                Analyzer::English => {
+                    let stem = stem(&word);
+                    let mut terms = vec![word];
+                    for term in dictionary().synonyms.get(&stem).into_iter().flatten().chain([&stem]) {
+                        if !terms.contains(term) {
+                            terms.push(term.clone());
+                        }
+                    }
+                    terms
+                }
+                Analyzer::Ngram => grams(&word, MIN_GRAM, false),
+                Analyzer::EdgeNgram => grams(&word, 1, true),
+            };
+            Some(Token { span, terms })
+        })
+        .collect()
+}
+
+/// The terms of a search term under `analyzer`, in order, and whether the
+/// last is only a prefix. A prefix is lowercased but never stemmed.
+pub fn query_terms(analyzer: Analyzer, term: &Term) -> (Vec<String>, bool) {
+    let (text, prefix) = match term {
+        Term::Word(text) | Term::Phrase(text) => (text, false),
+        Term::Prefix(text) => (text, true),
+    };
+    let terms = match analyzer {
+        Analyzer::Keyword => {
+            let trimmed = text.trim();
+            if trimmed.is_empty() { Vec::new() } else { vec![trimmed.to_lowercase()] }
+        }
+        Analyzer::English if !prefix => {
+            tokenize(text).into_iter().filter(|w| !dictionary().stopwords.contains(w)).map(|w| stem(&w)).collect()
+        }
+        _ => tokenize(text),
+    };
+    (terms, prefix)
+}
+
+/// Lowercased alphanumeric words with their byte ranges in `text`.
+pub(crate) fn words(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                out.push((s..i, text[s..i].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Lowercased alphanumeric words.
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    words(text).into_iter().map(|(_, w)| w).collect()
+}
+
+/// Every gram of `word` at least `min` characters long, or only those that
+/// start it when `edge` is set. A word shorter than `min` is its own gram.
+fn grams(word: &str, min: usize, edge: bool) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    if chars.len() <= min {
+        return vec![word.to_string()];
+    }
+    let starts = if edge { 0..1 } else { 0..chars.len() - min + 1 };
+    let mut out = Vec::new();
+    for start in starts {
+        for end in start + min..=chars.len() {
+            out.push(chars[start..end].iter().collect());
+        }
+    }
+    out
+}
+
+/// Stopwords and synonyms from `data/analysis.json`, with every synonym
+/// group keyed and held by stem.
+#[derive(Debug)]
+struct Dictionary {
+    stopwords: Vec<String>,
+    synonyms: BTreeMap<String, Vec<String>>,
+}
+
+fn dictionary() -> &'static Dictionary {
+    #[derive(Deserialize)]
+    struct File {
+        stopwords: Vec<String>,
+        synonyms: Vec<Vec<String>>,
+    }
+    static DICTIONARY: OnceLock<Dictionary> = OnceLock::new();
+    DICTIONARY.get_or_init(|| {
+        let file: File =
+            serde_json::from_str(include_str!("../../data/analysis.json")).expect("bundled analysis data is valid");
+        let mut synonyms = BTreeMap::new();
+        for group in file.synonyms {
+            let stems: Vec<String> = group.iter().map(|w| stem(&w.to_lowercase())).collect();
+            for key in &stems {
+                synonyms.insert(key.clone(), stems.clone());
+            }
+        }
+        Dictionary { stopwords: file.stopwords, synonyms }
+    })
+}
+
+/// The first step of the Porter stemmer: plurals, `-ed` and `-ing`, and a
+/// final `y`. That is enough for `programs` and `programming` to meet at
+/// `program` without the later steps' surprises. Words with anything but
+/// ASCII letters in them are left alone.
+pub fn stem(word: &str) -> String {
+    if word.len() <= 2 || !word.bytes().all(|b| b.is_ascii_lowercase()) {
+        return word.to_string();
+    }
+    let mut w = word.as_bytes().to_vec();
+
+    // Step 1a.
+    if w.ends_with(b"sses") || w.ends_with(b"ies") {
+        w.truncate(w.len() - 2);
+    } else if w.ends_with(b"s") && !w.ends_with(b"ss") {
+        w.pop();
+    }
+
+    // Step 1b.
+    if w.ends_with(b"eed") {
+        if measure(&w[..w.len() - 3]) > 0 {
+            w.pop();
+        }
+    } else if let Some(suffix) = [&b"ed"[..], b"ing"].into_iter().find(|s| w.ends_with(s))
+        && has_vowel(&w[..w.len() - suffix.len()])
+    {
+        w.truncate(w.len() - suffix.len());
+        if w.ends_with(b"at") || w.ends_with(b"bl") || w.ends_with(b"iz") {
+            w.push(b'e');
+        } else if ends_double_consonant(&w) && !matches!(w[w.len() - 1], b'l' | b's' | b'z') {
+            w.pop();
+        } else if measure(&w) == 1 && ends_cvc(&w) {
+            w.push(b'e');
+        }
+    }
+
+    // Step 1c.
+    if w.ends_with(b"y") && has_vowel(&w[..w.len() - 1]) {
+        *w.last_mut().expect("ends with y") = b'i';
+    }
+    String::from_utf8(w).expect("ASCII in, ASCII out")
+}
+
+fn is_consonant(w: &[u8], i: usize) -> bool {
+    match w[i] {
+        b'a' | b'e' | b'i' | b'o' | b'u' => false,
+        b'y' => i == 0 || !is_consonant(w, i - 1),
+        _ => true,
+    }
+}
+
+/// How many vowel-consonant sequences `w` has after its leading
+/// consonants.
+fn measure(w: &[u8]) -> usize {
+    let mut count = 0;
+    let mut vowel = false;
+    for i in 0..w.len() {
+        let consonant = is_consonant(w, i);
+        if consonant && vowel {
+            count += 1;
+        }
+        vowel = !consonant;
+    }
+    count
+}
+
+fn has_vowel(w: &[u8]) -> bool {
+    (0..w.len()).any(|i| !is_consonant(w, i))
+}
+
+fn ends_double_consonant(w: &[u8]) -> bool {
+    let n = w.len();
+    n >= 2 && w[n - 1] == w[n - 2] && is_consonant(w, n - 1)
+}
+
+/// Consonant, vowel, consonant, the last not `w`, `x` or `y`: `hop`, not
+/// `show`.
+fn ends_cvc(w: &[u8]) -> bool {
+    let n = w.len();
+    n >= 3
+        && is_consonant(w, n - 3)
+        && !is_consonant(w, n - 2)
+        && is_consonant(w, n - 1)
+        && !matches!(w[n - 1], b'w' | b'x' | b'y')
+}
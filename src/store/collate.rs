@@ -0,0 +1,23 @@
+//! Text folding for the case-insensitive operators and collated indexes.
+//!
+//! `=eqi=`, `=cti=`, `=swi=` and `=ewi=` compare both sides after Unicode
+//! default case folding, so `STRASSE` matches `straße` and `ΣΊΣΥΦΟΣ`
+//! matches `σίσυφος`. A field indexed with `collation: "ci_ai"` also drops
+//! accents: text is decomposed (NFD) and its combining marks removed, so
+//! `Müller` matches `muller`.
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+use crate::schema::Collation;
+
+/// The form of `text` two values are compared in: case-folded, with
+/// accents stripped under [`Collation::AccentInsensitive`], and composed so
+/// precomposed and combining spellings of the same letter agree.
+pub fn fold(text: &str, collation: Collation) -> String {
+    let decomposed: String = match collation {
+        Collation::CaseInsensitive => text.nfd().collect(),
+        Collation::AccentInsensitive => text.nfd().filter(|c| !is_combining_mark(*c)).collect(),
+    };
+    caseless::default_case_fold_str(&decomposed).nfc().collect()
+}
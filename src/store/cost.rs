@@ -0,0 +1,475 @@
+//! Query cost estimates for the per-request budget.
+//!
+//! The estimate starts from the access path [`plan`](super::plan) picked and
+//! the statistics of the index it reads: row counts, distinct values per
+//! indexed field (folded keys for a collated one), the numeric range of
+//! indexed numbers, the points of a geo index and how many records hold
+//! each full-text term; a vector index reads the `k` records of its
+//! `=sim=`. Every condition is then charged once per scanned row, weighted
+//! by how much work one check takes; a regex with unbounded repeats or a
+//! condition that walks a reverse join costs far more than a comparison.
+//! Sorting the scanned rows adds `n log n` comparisons unless the index
+//! already yields them in order.
+//!
+//! One cost unit is roughly one plain comparison against one record.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{Value as Json, json};
+
+use super::analysis::query_terms;
+use super::collate::fold;
+use super::cursor::{Origin, SortOrder};
+use super::eval::{Ordered, point};
+use super::{Store, TextIndex};
+use crate::fiql::{
+    self, Area, Condition, Expr, Literal, Operator, Point, Query, Search, Similar, SortKey, Span, Term, Time, Value,
+};
+use crate::schema::{Collation, Relationship, ScalarType, TypeDef};
+
+/// Rows read and work done by a query, with the parts that cost it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Estimate {
+    pub rows_scanned: usize,
+    pub cost: u64,
+    pub parts: Vec<Part>,
+}
+
+/// One line of an [`Estimate`]: the scan, a condition or a sort key.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Part {
+    /// `scan`, a condition as written (`name=~=.*x`) or `sort=description`.
+    pub part: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    pub cost: u64,
+    pub reason: String,
+}
+
+impl Estimate {
+    /// The part that costs the most; conditions win ties over the scan.
+    pub fn most_expensive(&self) -> Option<&Part> {
+        self.parts.iter().max_by_key(|p| p.cost)
+    }
+
+    /// Adds the estimate of another query run for the same request, noting
+    /// `why` in the reason of each of its parts.
+    pub fn and(mut self, other: Estimate, why: &str) -> Estimate {
+        self.rows_scanned = self.rows_scanned.saturating_add(other.rows_scanned);
+        self.cost = self.cost.saturating_add(other.cost);
+        self.parts.extend(other.parts.into_iter().map(|p| Part { reason: format!("{}, {why}", p.reason), ..p }));
+        self
+    }
+
+    pub fn within(self, budget: u64) -> Result<Estimate, OverBudget> {
+        if self.cost > budget { Err(OverBudget { estimate: self, budget }) } else { Ok(self) }
+    }
+}
+
+/// A query whose estimate is over the configured budget.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverBudget {
+    pub estimate: Estimate,
+    pub budget: u64,
+}
+
+impl OverBudget {
+    pub fn status(&self) -> u16 {
+        422
+    }
+
+    /// `{ error, budget, estimate, span? }`, with the span of the most
+    /// expensive condition.
+    pub fn to_json(&self) -> Json {
+        let mut body = json!({ "error": self.to_string(), "budget": self.budget, "estimate": self.estimate });
+        if let Some(span) = self.estimate.most_expensive().and_then(|p| p.span) {
+            body["span"] = json!(span);
+        }
+        body
+    }
+}
+
+impl fmt::Display for OverBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "query is too expensive: estimated cost {} is over the budget of {}",
+            self.estimate.cost, self.budget
+        )?;
+        if let Some(part) = self.estimate.most_expensive() {
+            write!(f, "; `{}` costs {} ({})", part.part, part.cost, part.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Figures kept by a table's indexes, borrowed for one estimate.
+struct Statistics<'s> {
+    rows: usize,
+    fields: &'s [FieldStats],
+    texts: &'s [TextIndex],
+}
+
+/// What the index of one field knows about its values, kept up to date on
+/// every write so an estimate never reads the records themselves.
+#[derive(Debug, Clone)]
+pub(crate) struct FieldStats {
+    pub field: String,
+    temporal: bool,
+    geo: bool,
+    /// Records holding each non-null value.
+    values: BTreeMap<Ordered, usize>,
+    /// Records without the field or with `null`: the index's null markers.
+    nulls: usize,
+    /// Records holding each numeric value, or each date in seconds since
+    /// the epoch; the first and last keys are the range.
+    numbers: BTreeMap<Ordered, usize>,
+    /// Records holding each folded key of a field indexed with a collation.
+    folded: Option<(Collation, BTreeMap<String, usize>)>,
+    /// The point of each record with one, for a geo-indexed field.
+    points: BTreeMap<String, Point>,
+}
+
+impl FieldStats {
+    /// Statistics for every indexed, stored field of `table`.
+    pub(crate) fn for_table(table: &TypeDef) -> Vec<FieldStats> {
+        table
+            .fields
+            .iter()
+            .filter(|f| f.is_indexed() && f.relationship().is_none())
+            .map(|field| FieldStats {
+                field: field.name.clone(),
+                temporal: field.scalar().is_some_and(ScalarType::is_temporal),
+                geo: field.is_geo(),
+                values: BTreeMap::new(),
+                nulls: 0,
+                numbers: BTreeMap::new(),
+                folded: field.collation().map(|c| (c, BTreeMap::new())),
+                points: BTreeMap::new(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, record: &Json) {
+        self.apply(key, record, true);
+    }
+
+    pub(crate) fn remove(&mut self, key: &str, record: &Json) {
+        self.apply(key, record, false);
+    }
+
+    fn apply(&mut self, key: &str, record: &Json, insert: bool) {
+        fn count<K: Ord>(counts: &mut BTreeMap<K, usize>, value: K, insert: bool) {
+            if insert {
+                *counts.entry(value).or_default() += 1;
+            } else if let Some(n) = counts.get_mut(&value) {
+                *n -= 1;
+                if *n == 0 {
+                    counts.remove(&value);
+                }
+            }
+        }
+        let value = match record.get(&self.field) {
+            None | Some(Json::Null) => {
+                self.nulls = if insert { self.nulls + 1 } else { self.nulls.saturating_sub(1) };
+                return;
+            }
+            Some(value) => value,
+        };
+        count(&mut self.values, Ordered(value.clone()), insert);
+        let number = match value.as_str() {
+            Some(text) if self.temporal => Time::parse_stored(text).and_then(Time::timestamp).map(|s| s as f64),
+            _ => value.as_f64(),
+        };
+        if let Some(n) = number {
+            count(&mut self.numbers, Ordered(json!(n)), insert);
+        }
+        if let (Some((collation, keys)), Some(text)) = (&mut self.folded, value.as_str()) {
+            count(keys, fold(text, *collation), insert);
+        }
+        if self.geo {
+            match point(value) {
+                Some(p) if insert => self.points.insert(key.to_string(), p),
+                _ => self.points.remove(key),
+            };
+        }
+    }
+
+    fn distinct(&self) -> usize {
+        self.folded.as_ref().map_or(self.values.len(), |(_, keys)| keys.len())
+    }
+
+    /// Whether some record holds a string passing `test`, checked once per
+    /// distinct value.
+    pub(crate) fn any_text(&self, test: impl Fn(&str) -> bool) -> bool {
+        self.values.keys().any(|v| v.0.as_str().is_some_and(&test))
+    }
+
+    /// Smallest and largest number (or date) held.
+    fn range(&self) -> Option<(f64, f64)> {
+        let low = self.numbers.keys().next()?.0.as_f64()?;
+        let high = self.numbers.keys().next_back()?.0.as_f64()?;
+        Some((low, high))
+    }
+}
+
+impl<'s> Statistics<'s> {
+    fn of(store: &'s Store, table: &TypeDef) -> Statistics<'s> {
+        match store.table(&table.name) {
+            Some(t) => Statistics { rows: t.records.len(), fields: &t.stats, texts: &t.texts },
+            None => Statistics { rows: 0, fields: &[], texts: &[] },
+        }
+    }
+
+    /// Expected fraction of the table passing `c`, for a condition on an
+    /// indexed top-level field.
+    fn selectivity(&self, c: &Condition) -> Option<f64> {
+        if c.field.is_nested() {
+            return None;
+        }
+        let stats = self.fields.iter().find(|f| f.field == c.field.root())?;
+        let values = c.value.scalars();
+        let number = |i: usize| match values.get(i).map(|s| &s.literal) {
+            Some(Literal::Number(n)) => Some(*n),
+            Some(Literal::Time(t)) => t.resolve(Utc::now()).timestamp().map(|s| s as f64),
+            Some(Literal::String(text)) => Time::parse_stored(text).and_then(Time::timestamp).map(|s| s as f64),
+            _ => None,
+        };
+        // Share of the indexed range between `low` and `high`.
+        let between = |low: Option<f64>, high: Option<f64>| {
+            let (min, max) = stats.range()?;
+            if max <= min {
+                return Some(1.0);
+            }
+            let low = low.unwrap_or(min).max(min);
+            let high = high.unwrap_or(max).min(max);
+            Some(((high - low) / (max - min)).clamp(0.0, 1.0))
+        };
+        let point = 1.0 / stats.distinct().max(1) as f64;
+        match c.op {
+            Operator::Eq | Operator::StrictEq if !values[0].is_wildcard() => Some(point),
+            Operator::In => Some((point * values.len() as f64).min(1.0)),
+            Operator::EqI if stats.folded.is_some() => Some(point),
+            Operator::StartsWithI => {
+                let (collation, keys) = stats.folded.as_ref()?;
+                let prefix = fold(&values[0].raw, *collation);
+                let hits = keys.range(prefix.clone()..).take_while(|(k, _)| k.starts_with(&prefix)).count();
+                Some(hits as f64 / keys.len().max(1) as f64)
+            }
+            Operator::Gt | Operator::Ge => between(number(0), None).or(Some(1.0 / 3.0)),
+            Operator::Lt | Operator::Le => between(None, number(0)).or(Some(1.0 / 3.0)),
+            Operator::GeLe | Operator::GeLt | Operator::GtLe | Operator::GtLt => {
+                between(number(0), number(1)).or(Some(1.0 / 3.0))
+            }
+            Operator::Null => {
+                let nulls = stats.nulls as f64 / self.rows.max(1) as f64;
+                Some(if values[0].literal == Literal::Bool(true) { nulls } else { 1.0 - nulls })
+            }
+            Operator::Near | Operator::Within => {
+                let area = Area::parse(c.op, values, c.span).ok()?;
+                let hits = stats.points.values().filter(|p| area.contains(**p)).count();
+                Some(hits as f64 / self.rows.max(1) as f64).filter(|_| !stats.points.is_empty())
+            }
+            Operator::FullText => {
+                let index = self.texts.iter().find(|t| t.field == stats.field)?;
+                let search = Search::parse(&values[0].raw, c.span).ok()?;
+                // The rarest word of a term bounds the records holding it;
+                // a prefix holds the records of every term it starts.
+                let docs = |term: &Term| {
+                    let (words, prefix) = query_terms(index.analyzer, term);
+                    let held = |(i, word): (usize, &String)| {
+                        if prefix && i + 1 == words.len() {
+                            index.prefix_frequency(word)
+                        } else {
+                            index.frequency(word)
+                        }
+                    };
+                    words.iter().enumerate().map(held).min().unwrap_or(self.rows)
+                };
+                let docs = search.required.iter().map(|group| group.iter().map(docs).sum::<usize>()).min();
+                Some(docs.unwrap_or(self.rows).min(self.rows) as f64 / self.rows.max(1) as f64)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Estimates `query` read through `strategy` (and `index`, when it has one)
+/// as chosen by the planner. `keyset` is set when the index yields rows in
+/// sort order.
+pub(crate) fn estimate(
+    store: &Store,
+    table: &TypeDef,
+    query: &Query,
+    strategy: &str,
+    index: Option<&str>,
+    keyset: bool,
+) -> Estimate {
+    let stats = Statistics::of(store, table);
+    let top = super::plan::top_level(query);
+    let narrowest = |field: &str| {
+        top.iter()
+            .filter(|c| !c.field.is_nested() && c.field.root() == field)
+            .filter_map(|c| stats.selectivity(c))
+            .reduce(f64::min)
+    };
+    let rows = stats.rows as f64;
+    let scanned = match (strategy, index) {
+        ("primary_key", Some(pk)) => top
+            .iter()
+            .find(|c| c.field.root() == pk && matches!(c.op, Operator::Eq | Operator::StrictEq | Operator::In))
+            .map_or(1.0, |c| c.value.scalars().len() as f64),
+        ("composite_index", Some(index)) => {
+            index.split('+').take(2).map(|f| narrowest(f).unwrap_or(1.0)).product::<f64>() * rows
+        }
+        ("index" | "fulltext" | "geo", Some(field)) => narrowest(field).unwrap_or(1.0) * rows,
+        ("vector", Some(_)) => top
+            .iter()
+            .filter(|c| c.op == Operator::Sim)
+            .find_map(|c| Similar::parse(c.value.scalars(), c.span).ok())
+            .map_or(rows, |s| s.k as f64),
+        _ => rows,
+    };
+    let scanned = (scanned.ceil() as usize).min(stats.rows);
+
+    let mut parts = vec![Part {
+        part: "scan".to_string(),
+        span: None,
+        cost: scanned as u64,
+        reason: match index {
+            Some(index) => format!("~{scanned} of {} rows through `{index}`", stats.rows),
+            None => format!("all {} rows of `{}`", stats.rows, table.name),
+        },
+    }];
+    for c in query.filter.iter().flat_map(Expr::conditions) {
+        let (weight, why) = condition_weight(c);
+        let (joins, via) = join_factor(store, table, &c.field.segments);
+        parts.push(Part {
+            part: format!("{}{}{}", c.field, c.op.token(), value_label(&c.value)),
+            span: Some(c.span),
+            cost: (scanned as u64).saturating_mul(weight).saturating_mul(joins),
+            reason: format!("{why}{via}, checked against ~{scanned} rows"),
+        });
+    }
+    if let Some(keys) = query.controls.sort.as_deref().filter(|_| !keyset) {
+        let comparisons = (scanned as u64).saturating_mul((scanned.max(2) as f64).log2().ceil() as u64);
+        let order = SortOrder::of(query);
+        for (i, key) in keys.iter().enumerate() {
+            let (weight, why) = sort_weight(table, key, order.origin(i));
+            let (joins, via) = join_factor(store, table, &key.field.segments);
+            parts.push(Part {
+                part: format!("sort={}{}", if key.descending { "-" } else { "" }, key.field),
+                span: Some(key.span),
+                cost: comparisons.saturating_mul(weight).saturating_mul(joins),
+                reason: format!("{why}{via}, ~{comparisons} comparisons"),
+            });
+        }
+    }
+    Estimate { rows_scanned: scanned, cost: parts.iter().fold(0, |sum, p| sum.saturating_add(p.cost)), parts }
+}
+
+/// The raw values of a condition, or its element filter in parentheses.
+fn value_label(value: &Value) -> String {
+    match value.filter() {
+        Some(filter) => format!("({})", fiql::print(&Query { filter: Some(filter.clone()), ..Query::default() })),
+        None => value.scalars().iter().map(|s| s.raw.as_str()).collect::<Vec<_>>().join(","),
+    }
+}
+
+/// Work for one check of `c` against one value, and why.
+fn condition_weight(c: &Condition) -> (u64, String) {
+    if let Some(filter) = c.value.filter() {
+        let inner = filter.conditions().into_iter().fold(0u64, |sum, c| sum.saturating_add(condition_weight(c).0));
+        return (inner, "element filter, run on every element".to_string());
+    }
+    let value = &c.value.scalars()[0].raw;
+    match c.op {
+        Operator::Regex => {
+            let repeats = unbounded_repeats(value);
+            let weight = 4u64.saturating_mul((1 + repeats).saturating_pow(2));
+            match repeats {
+                0 => (weight, "regex".to_string()),
+                1 => (weight, "regex with an unbounded repeat".to_string()),
+                n => (weight, format!("regex with {n} unbounded repeats")),
+            }
+        }
+        Operator::Eq if c.value.scalars()[0].is_wildcard() => {
+            (2 + value.matches('*').count() as u64, "wildcard match".to_string())
+        }
+        Operator::FullText => (4, "full-text match".to_string()),
+        op if op.takes_flag() => (1, "presence check".to_string()),
+        Operator::All | Operator::Any | Operator::None => (2, "element membership".to_string()),
+        Operator::Contains | Operator::StartsWith | Operator::EndsWith => (2, "substring match".to_string()),
+        op if op.is_case_insensitive() => (2, "case-insensitive match".to_string()),
+        Operator::Near => (3, "distance check".to_string()),
+        Operator::Within => (1, "bounding-box check".to_string()),
+        Operator::Sim => (1, "nearest-neighbour lookup".to_string()),
+        _ => (1, "comparison".to_string()),
+    }
+}
+
+/// `*`, `+` and `{n,}` quantifiers in `pattern`, outside escapes and
+/// character classes. Each one nested after another multiplies the ways a
+/// backtracking engine can split the input.
+fn unbounded_repeats(pattern: &str) -> u64 {
+    let mut count = 0;
+    let mut chars = pattern.chars().peekable();
+    let mut class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !class => class = true,
+            ']' if class => class = false,
+            '*' | '+' if !class => count += 1,
+            '{' if !class => {
+                let body: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                if body.ends_with(',') {
+                    count += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    count
+}
+
+fn sort_weight(table: &TypeDef, key: &SortKey, origin: Option<&Origin>) -> (u64, String) {
+    let field = table.field(key.field.root()).filter(|_| !key.field.is_nested());
+    match field {
+        None if matches!(origin, Some(Origin::Relevance(_))) => (2, "sort by full-text relevance".to_string()),
+        Some(_) if matches!(origin, Some(Origin::Text { .. })) => (2, format!("sort by similarity on `{}`", key.field)),
+        Some(f) if f.scalar() == Some(ScalarType::GeoPoint) => (3, format!("sort by distance on `{}`", key.field)),
+        Some(f) if f.is_indexed() && !f.is_fulltext() => (1, format!("sort on indexed `{}`", key.field)),
+        _ => (2, format!("sort on unindexed `{}`", key.field)),
+    }
+}
+
+/// How many records one value of `path` reads: forward joins look up one
+/// record, reverse joins scan the target table. Costs saturate rather than
+/// wrap, so a long chain is priced over any budget.
+fn join_factor(store: &Store, table: &TypeDef, path: &[String]) -> (u64, String) {
+    let mut factor = 1u64;
+    let mut via = String::new();
+    let mut def = Some(table);
+    for segment in path {
+        let Some(field) = def.and_then(|d| d.field(segment)) else { break };
+        let target = store.schema().table(&field.ty.name);
+        match field.relationship() {
+            Some(Relationship::From(_)) => factor = factor.saturating_mul(2),
+            Some(Relationship::To(_)) => {
+                let rows = store.table(&field.ty.name).map_or(0, |t| t.records.len());
+                factor = factor.saturating_mul(rows.max(1) as u64);
+                via = format!(" through `{segment}` ({rows} `{}` rows each)", field.ty.name);
+            }
+            None => break,
+        }
+        def = target;
+    }
+    (factor, via)
+}
@@ -0,0 +1,189 @@
+//! Opaque keyset cursors for `after=`.
+//!
+//! A cursor is the base64url of the JSON array `[sort values..., id]` taken
+//! from the last row of a page. The next page starts strictly after that
+//! position in `sort=` order, with the primary key breaking ties, so rows
+//! inserted or removed between requests never shift a page the way
+//! `offset=` does. A cursor issued with `pagination=true` also carries the
+//! total counted for the first page, as a number after the id, so later
+//! pages report it without counting the result again.
+//!
+//! A `GeoPoint` sort key orders by distance from the `=near=` point on the
+//! same field, so its cursor value is a distance in meters. A key with a
+//! `=sim=` on the same field orders by how far each record's embedding is
+//! from the `=sim=` text's, nearest first, and its cursor value is that
+//! cosine distance. A `_score` key orders by relevance to the query's
+//! `=ft=` conditions, and its cursor value is the score.
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde_json::{Value as Json, json};
+
+use super::eval::{compare_json, field_values, point};
+use super::exec::ExecError;
+use super::plan::top_level;
+use super::text::{Relevance, SCORE};
+use super::vector::distance;
+use super::{Store, primary_key_of};
+use crate::fiql::{Area, Operator, Point, Query, Similar, SortKey};
+use crate::schema::TypeDef;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    /// One value per sort key; `null` where the row had none.
+    pub keys: Vec<Json>,
+    pub id: String,
+    /// The result's size when the first page was read.
+    pub total: Option<usize>,
+}
+
+impl Cursor {
+    /// The position of `record` under `order`.
+    pub fn of(store: &Store, table: &TypeDef, record: &Json, order: &SortOrder) -> Cursor {
+        let keys = sort_values(store, table, record, order)
+            .into_iter()
+            .map(|v| v.map_or(Json::Null, Cow::into_owned))
+            .collect();
+        Cursor { keys, id: primary_key_of(table, record).unwrap_or_default(), total: None }
+    }
+
+    pub fn encode(&self) -> String {
+        let mut parts = self.keys.clone();
+        parts.push(Json::String(self.id.clone()));
+        parts.extend(self.total.map(|total| json!(total)));
+        URL_SAFE_NO_PAD.encode(Json::Array(parts).to_string())
+    }
+
+    /// Decodes a cursor issued for a query with `sort_len` sort keys.
+    pub fn decode(text: &str, sort_len: usize) -> Result<Cursor, ExecError> {
+        let invalid = |why: &str| ExecError::InvalidCursor(why.to_string());
+        let bytes = URL_SAFE_NO_PAD.decode(text).map_err(|_| invalid("not a cursor issued by this endpoint"))?;
+        let Ok(Json::Array(mut parts)) = serde_json::from_slice(&bytes) else {
+            return Err(invalid("not a cursor issued by this endpoint"));
+        };
+        let total = match parts.last() {
+            Some(Json::Number(n)) if parts.len() == sort_len + 2 => n.as_u64().map(|n| n as usize),
+            _ => None,
+        };
+        if parts.len() != sort_len + 1 + usize::from(total.is_some()) {
+            return Err(invalid("cursor was issued for a different `sort`"));
+        }
+        parts.truncate(sort_len + 1);
+        let Some(Json::String(id)) = parts.pop() else { return Err(invalid("not a cursor issued by this endpoint")) };
+        Ok(Cursor { keys: parts, id, total })
+    }
+
+    /// Where `record` falls relative to this cursor under `order`.
+    pub fn compare(&self, store: &Store, table: &TypeDef, record: &Json, order: &SortOrder) -> Ordering {
+        let values = sort_values(store, table, record, order);
+        let keys: Vec<Option<Cow<Json>>> =
+            self.keys.iter().map(|k| Some(Cow::Borrowed(k)).filter(|k| !k.is_null())).collect();
+        compare_keys(order.keys, &values, &keys)
+            .then_with(|| primary_key_of(table, record).unwrap_or_default().as_str().cmp(&self.id))
+    }
+}
+
+/// The `sort=` keys of a query, each with the origin distances along it are
+/// measured from.
+#[derive(Debug, Clone, Default)]
+pub struct SortOrder<'q> {
+    pub keys: &'q [SortKey],
+    origins: Vec<Option<Origin>>,
+}
+
+/// What a sort key measures distance from.
+#[derive(Debug, Clone)]
+pub enum Origin {
+    /// The centre of a `=near=`.
+    Point(Point),
+    /// The text of a `=sim=`, embedded the first time a record is measured.
+    Text { text: String, vector: OnceCell<Vec<f32>> },
+    /// The `=ft=` conditions a `_score` key ranks by.
+    Relevance(Relevance),
+}
+
+impl<'q> SortOrder<'q> {
+    /// A key's origin is the centre of a top-level `=near=` or the text of
+    /// a top-level `=sim=` on the same field. A `_score` key's is the
+    /// query's `=ft=` conditions, when it has any.
+    pub fn of(query: &'q Query) -> SortOrder<'q> {
+        let keys = query.controls.sort.as_deref().unwrap_or_default();
+        let top = top_level(query);
+        let origins = keys
+            .iter()
+            .map(|key| {
+                if key.field.segments == [SCORE] {
+                    return Some(Relevance::of(query)).filter(|r| !r.is_empty()).map(Origin::Relevance);
+                }
+                top.iter().filter(|c| c.field.segments == key.field.segments).find_map(|c| match c.op {
+                    Operator::Near => Some(Origin::Point(Area::parse(c.op, c.value.scalars(), c.span).ok()?.center()?)),
+                    Operator::Sim => {
+                        let text = Similar::parse(c.value.scalars(), c.span).ok()?.text;
+                        Some(Origin::Text { text, vector: OnceCell::new() })
+                    }
+                    _ => None,
+                })
+            })
+            .collect();
+        SortOrder { keys, origins }
+    }
+
+    /// What the `index`th key measures distance from, if anything.
+    pub fn origin(&self, index: usize) -> Option<&Origin> {
+        self.origins.get(index)?.as_ref()
+    }
+}
+
+/// The first value at each sort key, `None` when missing or `null`. A key
+/// with an origin gives the distance from it instead: meters from a point,
+/// or the cosine distance of the record's embedding from a text's. A
+/// `_score` key gives the record's relevance.
+pub(crate) fn sort_values<'a>(
+    store: &'a Store,
+    table: &'a TypeDef,
+    record: &'a Json,
+    order: &SortOrder,
+) -> Vec<Option<Cow<'a, Json>>> {
+    order
+        .keys
+        .iter()
+        .zip(&order.origins)
+        .map(|(k, origin)| {
+            if let Some(Origin::Relevance(relevance)) = origin {
+                return Some(Cow::Owned(json!(relevance.score(store, table, record))));
+            }
+            if let Some(Origin::Text { text, vector }) = origin {
+                let field = table.embedding_for(k.field.root())?;
+                let index = store.vectors(&table.name, &field.name)?;
+                let stored = index.get(&primary_key_of(table, record)?)?;
+                let query = vector.get_or_init(|| store.embedder().embed(text, index.dimensions));
+                return Some(Cow::Owned(json!(f64::from(distance(query, stored)))));
+            }
+            let value = field_values(store, table, record, &k.field.segments).into_iter().find(|v| !v.is_null())?;
+            match origin {
+                Some(Origin::Point(origin)) => point(value).map(|p| Cow::Owned(json!(origin.distance(p)))),
+                _ => Some(Cow::Borrowed(value)),
+            }
+        })
+        .collect()
+}
+
+/// Compares two rows' sort values. Missing values sort last in either
+/// direction.
+pub(crate) fn compare_keys(sort: &[SortKey], a: &[Option<Cow<Json>>], b: &[Option<Cow<Json>>]) -> Ordering {
+    sort.iter()
+        .zip(a.iter().zip(b))
+        .map(|(key, (x, y))| match (x, y) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(x), Some(y)) if key.descending => compare_json(y, x),
+            (Some(x), Some(y)) => compare_json(x, y),
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
@@ -0,0 +1,65 @@
+//! Text embeddings for `@embedding` fields.
+//!
+//! An [`Embedder`] turns text into a vector of unit length, so that the dot
+//! product of two texts' vectors says how alike they are. The store embeds
+//! the `from:` field of every record it writes and keeps the vector in the
+//! field's [`VectorIndex`](super::VectorIndex) rather than in the record.
+//!
+//! [`HashingEmbedder`] is the default. It needs no model and no network and
+//! gives the same vector for the same text on every run, which is what
+//! tests and the bundled demo want; a deployment with a real model plugs
+//! it in with [`Store::with_embedder`](super::Store::with_embedder).
+
+use std::fmt;
+
+use super::analysis::tokenize;
+
+pub trait Embedder: fmt::Debug + Send + Sync {
+    /// A vector of `dimensions` numbers for `text`, of length 1, or all
+    /// zeros when the text has nothing to embed.
+    fn embed(&self, text: &str, dimensions: usize) -> Vec<f32>;
+}
+
+/// Words too common to say anything about a text.
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "by", "for", "from", "in", "into", "is", "of", "on", "or", "the", "to", "with",
+];
+
+/// Feature hashing: every word and every character trigram of a word adds
+/// a signed weight to the slot its hash picks. Trigrams make `programming`
+/// and `programmable` close even though the words differ.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HashingEmbedder;
+
+impl Embedder for HashingEmbedder {
+    fn embed(&self, text: &str, dimensions: usize) -> Vec<f32> {
+        let mut vector = vec![0.0; dimensions];
+        if dimensions == 0 {
+            return vector;
+        }
+        for word in tokenize(text).into_iter().filter(|w| !STOPWORDS.contains(&w.as_str())) {
+            let marked: Vec<char> = format!("<{word}>").chars().collect();
+            add(&mut vector, &marked, 1.0);
+            for gram in marked.windows(3) {
+                add(&mut vector, gram, 0.5);
+            }
+        }
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+fn add(vector: &mut [f32], feature: &[char], weight: f32) {
+    let hash = fnv1a(feature.iter().collect::<String>().as_bytes());
+    let slot = (hash % vector.len() as u64) as usize;
+    vector[slot] += if hash >> 63 == 0 { weight } else { -weight };
+}
+
+/// 64-bit FNV-1a, which is stable across runs and platforms, unlike the
+/// standard library's hasher.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3))
+}
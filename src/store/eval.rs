@@ -0,0 +1,548 @@
+//! Evaluation of a FIQL filter against a single record.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde_json::Value as Json;
+
+use super::analysis::{Token, analyze};
+use super::collate::fold;
+use super::exec::ExecError;
+use super::pattern;
+use super::text::{ALL_TEXT, Analyzed, searched_fields};
+use super::{Store, primary_key_of};
+use crate::fiql::{Area, Condition, Expr, Literal, Operator, Point, Scalar, Search, Similar, Time};
+use crate::schema::{Analyzer, Collation, FieldDef, ScalarType, TypeDef};
+
+/// A filter compiled once per query: regexes built, full-text searches read,
+/// case-insensitive values folded, dates and areas read, `now` fixed and
+/// the nearest records of each `=sim=` looked up.
+#[derive(Debug, Clone)]
+pub struct Predicate {
+    node: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Condition(Compiled),
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+}
+
+#[derive(Debug, Clone)]
+struct Compiled {
+    path: Vec<String>,
+    op: Operator,
+    values: Vec<Scalar>,
+    matcher: Matcher,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Plain,
+    Glob,
+    Regex(Regex),
+    FullText(Analyzed),
+    /// The value of a case-insensitive operator, folded under the field's
+    /// collation (case only when the field has none).
+    Folded(String, Collation),
+    /// The circle or box of `=near=` and `=within=`.
+    Area(Area),
+    /// The primary keys of the records a `=sim=` keeps.
+    Similar(BTreeSet<String>),
+    /// The `(filter)` of a quantifier, run against each element.
+    Elements(Box<Node>),
+}
+
+impl Predicate {
+    /// Compiles a filter over `table`, whose field definitions decide how
+    /// case-insensitive operators fold text and which values are dates.
+    /// A `=sim=` is answered here, from the store's vector index, so the
+    /// predicate reflects the records stored when it was compiled.
+    pub fn compile(store: &Store, table: &TypeDef, expr: &Expr) -> Result<Predicate, ExecError> {
+        Ok(Predicate { node: Scope { store, now: Utc::now() }.node(table, expr, None)? })
+    }
+
+    pub fn matches(&self, store: &Store, table: &TypeDef, record: &Json) -> bool {
+        self.node.matches(store, table, record)
+    }
+
+    /// The first `=~=` value in the filter, if it has one.
+    pub(crate) fn regex(&self) -> Option<&Scalar> {
+        self.node.regex()
+    }
+}
+
+/// What compiling a filter needs besides the filter: the store, for its
+/// schema and vector indexes, and the instant `now` stands for.
+struct Scope<'a> {
+    store: &'a Store,
+    now: DateTime<Utc>,
+}
+
+impl Scope<'_> {
+    /// Compiles `expr`. A `=sim=` keeps the nearest records that pass
+    /// `siblings`, the rest of the `&` chain it is in, so
+    /// `category==books&description=sim=rust,k=3` is the three books
+    /// nearest "rust" rather than the books among the three nearest.
+    fn node(&self, table: &TypeDef, expr: &Expr, siblings: Option<&Node>) -> Result<Node, ExecError> {
+        let children =
+            |children: &[Expr]| children.iter().map(|c| self.node(table, c, None)).collect::<Result<Vec<_>, _>>();
+        Ok(match expr {
+            Expr::Condition(c) => Node::Condition(self.condition(table, c, siblings)?),
+            Expr::And(nodes) => {
+                let is_similar = |e: &&Expr| matches!(e, Expr::Condition(c) if c.op == Operator::Sim);
+                let rest: Vec<Expr> = nodes.iter().filter(|e| !is_similar(e)).cloned().collect();
+                let mut compiled = children(&rest)?;
+                let filter = Node::And(compiled.clone());
+                for similar in nodes.iter().filter(is_similar) {
+                    compiled.push(self.node(table, similar, Some(&filter))?);
+                }
+                Node::And(compiled)
+            }
+            Expr::Or(nodes) => Node::Or(children(nodes)?),
+            Expr::Not(inner) => Node::Not(Box::new(self.node(table, inner, None)?)),
+        })
+    }
+
+    fn condition(&self, table: &TypeDef, c: &Condition, siblings: Option<&Node>) -> Result<Compiled, ExecError> {
+        let schema = self.store.schema();
+        let field = schema.field_at(table, &c.field.segments);
+        let values: Vec<Scalar> = c.value.scalars().iter().map(|s| self.value(s, c.op, field)).collect();
+        let matcher = match c.op {
+            _ if let Some(filter) = c.value.filter() => {
+                let element = field.and_then(|f| schema.type_of(f)).unwrap_or(&ELEMENT);
+                Matcher::Elements(Box::new(self.node(element, filter, None)?))
+            }
+            Operator::Sim => Matcher::Similar(self.similar(table, c, siblings)),
+            Operator::Regex => Matcher::Regex(pattern::compile(&values[0])?),
+            Operator::FullText => match Search::parse(&values[0].raw, c.span) {
+                Ok(search) => Matcher::FullText(Analyzed::new(&search, &analyzers(table, &c.field.segments, field))),
+                Err(_) => Matcher::Plain,
+            },
+            Operator::Eq if values[0].is_wildcard() => Matcher::Glob,
+            op if op.is_geo() => match Area::parse(op, &values, c.span) {
+                Ok(area) => Matcher::Area(area),
+                Err(_) => Matcher::Plain,
+            },
+            op if op.is_case_insensitive() => {
+                let collation = field.and_then(|f| f.collation()).unwrap_or(Collation::CaseInsensitive);
+                Matcher::Folded(fold(&values[0].raw, collation), collation)
+            }
+            _ => Matcher::Plain,
+        };
+        Ok(Compiled { path: c.field.segments.clone(), op: c.op, values, matcher })
+    }
+
+    /// The records a `=sim=` on a top-level field keeps: the `k` whose
+    /// embedding is nearest the value's that also pass `siblings`. None
+    /// for a field without an embedding.
+    fn similar(&self, table: &TypeDef, c: &Condition, siblings: Option<&Node>) -> BTreeSet<String> {
+        let store = self.store;
+        let Ok(similar) = Similar::parse(c.value.scalars(), c.span) else { return BTreeSet::new() };
+        let index = table
+            .embedding_for(c.field.root())
+            .filter(|_| !c.field.is_nested())
+            .and_then(|field| store.vectors(&table.name, &field.name));
+        let Some(index) = index else { return BTreeSet::new() };
+        let query = store.embedder().embed(&similar.text, index.dimensions);
+        let keep = |id: &str| {
+            siblings.is_none_or(|filter| store.get(&table.name, id).is_some_and(|r| filter.matches(store, table, r)))
+        };
+        index.nearest(&query, similar.k, keep).into_iter().map(|(id, _)| id.to_string()).collect()
+    }
+
+    /// Fixes `now` to this query's instant, and reads an unprefixed value
+    /// as a date exactly when its field is a `Date` or `DateTime`: a bare
+    /// `now` compared with a `String` field is text.
+    fn value(&self, scalar: &Scalar, op: Operator, field: Option<&FieldDef>) -> Scalar {
+        let temporal = field.and_then(FieldDef::scalar).is_some_and(ScalarType::is_temporal);
+        let time = match &scalar.literal {
+            Literal::Time(_) if scalar.prefix.is_none() && field.is_some() && !temporal => {
+                return Scalar { literal: Literal::String(scalar.raw.clone()), ..scalar.clone() };
+            }
+            Literal::Time(time) => Some(*time),
+            Literal::String(text) if temporal && scalar.prefix.is_none() && !op.is_case_insensitive() => {
+                Time::parse_stored(text)
+            }
+            _ => None,
+        };
+        match time {
+            Some(time) => Scalar { literal: Literal::Time(time.resolve(self.now)), ..scalar.clone() },
+            None => scalar.clone(),
+        }
+    }
+}
+
+impl Node {
+    fn regex(&self) -> Option<&Scalar> {
+        match self {
+            Node::Condition(c) => match &c.matcher {
+                Matcher::Regex(_) => Some(&c.values[0]),
+                Matcher::Elements(filter) => filter.regex(),
+                _ => None,
+            },
+            Node::And(children) | Node::Or(children) => children.iter().find_map(Node::regex),
+            Node::Not(inner) => inner.regex(),
+        }
+    }
+
+    fn matches(&self, store: &Store, table: &TypeDef, record: &Json) -> bool {
+        match self {
+            Node::Condition(c) => c.matches(store, table, record),
+            Node::And(children) => children.iter().all(|n| n.matches(store, table, record)),
+            Node::Or(children) => children.iter().any(|n| n.matches(store, table, record)),
+            Node::Not(inner) => !inner.matches(store, table, record),
+        }
+    }
+}
+
+impl Compiled {
+    fn matches(&self, store: &Store, table: &TypeDef, record: &Json) -> bool {
+        if let Matcher::Similar(keys) = &self.matcher {
+            return primary_key_of(table, record).is_some_and(|key| keys.contains(&key));
+        }
+        if self.op.takes_flag() {
+            return self.presence(store, table, record);
+        }
+        if let Matcher::FullText(search) = &self.matcher {
+            return search.matches(&self.texts(store, table, record));
+        }
+        if self.op.is_quantifier() || matches!(self.op, Operator::Size(_)) {
+            return self.quantify(store, table, record);
+        }
+        let candidates = field_values(store, table, record, &self.path);
+        match self.op {
+            // Negated operators hold when no element matches, including when
+            // the field is missing.
+            Operator::Ne => !candidates.iter().any(|c| loose_eq(&self.values[0], c)),
+            Operator::Out => !candidates.iter().any(|c| self.values.iter().any(|v| loose_eq(v, c))),
+            _ => candidates.iter().any(|c| self.test(c)),
+        }
+    }
+
+    fn test(&self, candidate: &Json) -> bool {
+        let first = &self.values[0];
+        match (&self.matcher, self.op) {
+            (Matcher::Glob, _) => text_of(candidate).is_some_and(|t| glob_match(&first.raw, &t)),
+            (Matcher::Regex(re), _) => text_of(candidate).is_some_and(|t| re.is_match(&t)),
+            (Matcher::Folded(value, collation), _) => text_of(candidate).is_some_and(|t| {
+                let text = fold(&t, *collation);
+                match self.op {
+                    Operator::EqI => text == *value,
+                    Operator::ContainsI => text.contains(value.as_str()),
+                    Operator::StartsWithI => text.starts_with(value.as_str()),
+                    _ => text.ends_with(value.as_str()),
+                }
+            }),
+            (Matcher::Area(area), _) => point(candidate).is_some_and(|p| area.contains(p)),
+            // Values the parser would have rejected cover no area.
+            (_, Operator::Near | Operator::Within) => false,
+            (_, Operator::Eq) => loose_eq(first, candidate),
+            (_, Operator::StrictEq) => strict_eq(&first.literal, candidate),
+            (_, Operator::In) => self.values.iter().any(|v| loose_eq(v, candidate)),
+            (_, Operator::Gt) => compare(candidate, first) == Some(Ordering::Greater),
+            (_, Operator::Ge) => matches!(compare(candidate, first), Some(Ordering::Greater | Ordering::Equal)),
+            (_, Operator::Lt) => compare(candidate, first) == Some(Ordering::Less),
+            (_, Operator::Le) => matches!(compare(candidate, first), Some(Ordering::Less | Ordering::Equal)),
+            (_, Operator::GeLe | Operator::GeLt | Operator::GtLe | Operator::GtLt) => {
+                let low = compare(candidate, &self.values[0]);
+                let high = compare(candidate, &self.values[1]);
+                let above = match self.op {
+                    Operator::GeLe | Operator::GeLt => matches!(low, Some(Ordering::Greater | Ordering::Equal)),
+                    _ => low == Some(Ordering::Greater),
+                };
+                let below = match self.op {
+                    Operator::GeLe | Operator::GtLe => matches!(high, Some(Ordering::Less | Ordering::Equal)),
+                    _ => high == Some(Ordering::Less),
+                };
+                above && below
+            }
+            (_, Operator::Contains) => text_of(candidate).is_some_and(|t| t.contains(first.raw.as_str())),
+            (_, Operator::StartsWith) => text_of(candidate).is_some_and(|t| t.starts_with(first.raw.as_str())),
+            (_, Operator::EndsWith) => text_of(candidate).is_some_and(|t| t.ends_with(first.raw.as_str())),
+            (
+                _,
+                Operator::Ne
+                | Operator::Out
+                | Operator::Regex
+                | Operator::FullText
+                | Operator::EqI
+                | Operator::ContainsI
+                | Operator::StartsWithI
+                | Operator::EndsWithI
+                | Operator::Sim,
+            ) => {
+                unreachable!("handled by matcher or negation")
+            }
+            (_, Operator::Null | Operator::Exists | Operator::Empty) => unreachable!("handled by presence"),
+            (_, Operator::All | Operator::Any | Operator::None | Operator::Size(_)) => {
+                unreachable!("handled by quantify")
+            }
+        }
+    }
+
+    /// Every text an `=ft=` searches, split by its field's analyzer: each
+    /// full-text field for `_text`, otherwise each value at the path.
+    fn texts(&self, store: &Store, table: &TypeDef, record: &Json) -> Vec<(Analyzer, Vec<Token>)> {
+        if let [name] = self.path.as_slice()
+            && name == ALL_TEXT
+        {
+            let fields = searched_fields(table, &self.path);
+            let texts = fields.iter().filter_map(|f| Some((f.analyzer(), text_of(record.get(&f.name)?)?)));
+            return texts.map(|(analyzer, t)| (analyzer, analyze(analyzer, &t))).collect();
+        }
+        let field = store.schema().field_at(table, &self.path);
+        let analyzer = field.map(FieldDef::analyzer).unwrap_or_default();
+        let values = field_values(store, table, record, &self.path).into_iter().filter_map(text_of);
+        values.map(|t| (analyzer, analyze(analyzer, &t))).collect()
+    }
+
+    /// `=null=`, `=exists=` and `=empty=`. The `false` forms are the
+    /// opposite of the `true` ones, except that `=empty=false` needs a
+    /// non-empty value: a missing or null field is neither.
+    fn presence(&self, store: &Store, table: &TypeDef, record: &Json) -> bool {
+        let want = matches!(self.values[0].literal, Literal::Bool(true));
+        let (values, list_relationship) = resolve(store, table, record, &self.path);
+        let values: Vec<&Json> = values.into_iter().map(|(_, v)| v).collect();
+        let empty = |v: &&Json| match v {
+            Json::String(s) => s.is_empty(),
+            Json::Array(items) => items.is_empty(),
+            Json::Object(map) => map.is_empty(),
+            _ => false,
+        };
+        match self.op {
+            Operator::Null => values.iter().all(|v| v.is_null()) == want,
+            Operator::Exists => values.is_empty() != want,
+            _ if want => (list_relationship && values.is_empty()) || values.iter().any(empty),
+            _ => values.iter().any(|v| !v.is_null() && !empty(v)),
+        }
+    }
+
+    /// `=all=`, `=any=`, `=none=` and `=size=` over the elements of the
+    /// arrays at the path, or the records of a list relationship. `=all=`
+    /// needs at least one element, so it never holds for an empty array.
+    fn quantify(&self, store: &Store, table: &TypeDef, record: &Json) -> bool {
+        let (values, list_relationship) = resolve(store, table, record, &self.path);
+        if let Operator::Size(comparison) = self.op {
+            let Literal::Number(count) = self.values[0].literal else { return false };
+            let sizes: Vec<usize> = if list_relationship {
+                vec![values.len()]
+            } else {
+                values.iter().filter_map(|(_, v)| v.as_array().map(Vec::len)).collect()
+            };
+            return sizes.into_iter().any(|size| comparison.holds((size as f64).total_cmp(&count)));
+        }
+        let elements: Vec<(Option<&TypeDef>, &Json)> = values
+            .into_iter()
+            .flat_map(|(def, v)| match v {
+                Json::Array(items) => items.iter().map(|item| (None, item)).collect(),
+                Json::Null => vec![],
+                other => vec![(def, other)],
+            })
+            .collect();
+        let Matcher::Elements(filter) = &self.matcher else {
+            let contains = |value: &Scalar| elements.iter().any(|(_, e)| loose_eq(value, e));
+            return match self.op {
+                Operator::All => self.values.iter().all(contains),
+                Operator::Any => self.values.iter().any(contains),
+                _ => !self.values.iter().any(contains),
+            };
+        };
+        let hit = |(def, element): &(Option<&TypeDef>, &Json)| filter.matches(store, def.unwrap_or(&ELEMENT), element);
+        match self.op {
+            Operator::All => !elements.is_empty() && elements.iter().all(hit),
+            Operator::Any => elements.iter().any(hit),
+            _ => !elements.iter().any(hit),
+        }
+    }
+}
+
+/// The table definition element filters see for array items, which have
+/// no schema of their own to follow relationships through.
+static ELEMENT: TypeDef = TypeDef { name: String::new(), fields: Vec::new(), directives: Vec::new() };
+
+/// Every value at `path` in `record`, following relationships and
+/// flattening arrays, so a condition holds if any of them matches.
+pub fn field_values<'a>(store: &'a Store, table: &'a TypeDef, record: &'a Json, path: &[String]) -> Vec<&'a Json> {
+    resolve(store, table, record, path)
+        .0
+        .into_iter()
+        .flat_map(|(_, v)| match v {
+            Json::Array(items) => items.iter().collect(),
+            other => vec![other],
+        })
+        .collect()
+}
+
+/// The values at `path`, each with its table when it is a related record,
+/// with arrays at the end left whole, and whether the path ends in a list
+/// relationship (whose records are the values). A missing key contributes
+/// nothing; an explicit `null` is kept.
+fn resolve<'a>(
+    store: &'a Store,
+    table: &'a TypeDef,
+    record: &'a Json,
+    path: &[String],
+) -> (Vec<(Option<&'a TypeDef>, &'a Json)>, bool) {
+    let mut frontier: Vec<(Option<&'a TypeDef>, &'a Json)> = vec![(Some(table), record)];
+    let mut list_relationship = false;
+    for (i, segment) in path.iter().enumerate() {
+        let last = i + 1 == path.len();
+        let mut next = Vec::new();
+        for (def, value) in frontier {
+            let relationship =
+                def.and_then(|d| d.field(segment).filter(|f| f.relationship().is_some()).map(|f| (d, f)));
+            if let Some((def, field)) = relationship {
+                let target = store.schema().table(&field.ty.name);
+                list_relationship = last && field.ty.list;
+                next.extend(store.related(def, field, value).into_iter().map(|r| (target, r)));
+                continue;
+            }
+            match value.get(segment) {
+                Some(Json::Array(items)) if !last => next.extend(items.iter().map(|item| (None, item))),
+                Some(v) => next.push((None, v)),
+                None => {}
+            }
+        }
+        frontier = next;
+    }
+    (frontier, list_relationship)
+}
+
+/// `==` semantics: untyped values coerce between strings, numbers and
+/// booleans; a `number:` or `string:` prefix makes the comparison strict.
+fn loose_eq(value: &Scalar, candidate: &Json) -> bool {
+    if value.prefix.is_some() {
+        return strict_eq(&value.literal, candidate);
+    }
+    match (&value.literal, candidate) {
+        (Literal::Number(n), Json::Number(c)) => c.as_f64() == Some(*n),
+        (Literal::Number(n), Json::String(s)) => crate::fiql::parse_number(s) == Some(*n),
+        (Literal::Bool(b), Json::Bool(c)) => b == c,
+        (Literal::Bool(b), Json::String(s)) => s == if *b { "true" } else { "false" },
+        (Literal::String(s), c) => text_of(c).is_some_and(|t| t == s.as_str()),
+        (Literal::Time(_), _) => compare(candidate, value) == Some(Ordering::Equal),
+        _ => false,
+    }
+}
+
+/// `===` semantics: the JSON type must match the literal's type.
+fn strict_eq(literal: &Literal, candidate: &Json) -> bool {
+    match (literal, candidate) {
+        (Literal::String(s), Json::String(c)) => s == c,
+        (Literal::Number(n), Json::Number(c)) => c.as_f64() == Some(*n),
+        (Literal::Bool(b), Json::Bool(c)) => b == c,
+        (Literal::Time(t), Json::String(c)) => {
+            Time::parse_stored(c).and_then(|c| c.compare(*t)) == Some(Ordering::Equal)
+        }
+        _ => false,
+    }
+}
+
+/// Orders `candidate` against a literal of the same kind; `None` when the
+/// kinds differ.
+fn compare(candidate: &Json, value: &Scalar) -> Option<Ordering> {
+    match (&value.literal, candidate) {
+        (Literal::Number(n), Json::Number(c)) => c.as_f64()?.partial_cmp(n),
+        (Literal::String(s), Json::String(c)) => Some(c.as_str().cmp(s.as_str())),
+        (Literal::Time(t), Json::String(c)) => Time::parse_stored(c)?.compare(*t),
+        _ => None,
+    }
+}
+
+/// Total order used for sorting: numbers, then strings, then booleans, then
+/// everything else.
+pub fn compare_json(a: &Json, b: &Json) -> Ordering {
+    fn rank(v: &Json) -> u8 {
+        match v {
+            Json::Number(_) => 0,
+            Json::String(_) => 1,
+            Json::Bool(_) => 2,
+            _ => 3,
+        }
+    }
+    match (a, b) {
+        (Json::Number(x), Json::Number(y)) => x.as_f64().unwrap_or(0.0).total_cmp(&y.as_f64().unwrap_or(0.0)),
+        (Json::String(x), Json::String(y)) => x.cmp(y),
+        (Json::Bool(x), Json::Bool(y)) => x.cmp(y),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// A JSON value ordered by [`compare_json`] with `null` last, for index
+/// keys, cursors and group keys.
+#[derive(Debug, Clone)]
+pub(crate) struct Ordered(pub Json);
+
+impl PartialEq for Ordered {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ordered {}
+
+impl PartialOrd for Ordered {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ordered {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (&self.0, &other.0) {
+            (Json::Null, Json::Null) => Ordering::Equal,
+            (Json::Null, _) => Ordering::Greater,
+            (_, Json::Null) => Ordering::Less,
+            (a, b) => compare_json(a, b),
+        }
+    }
+}
+
+/// A stored `GeoPoint`: an object with numeric `lat` and `lon`.
+pub(crate) fn point(value: &Json) -> Option<Point> {
+    Some(Point { lat: value.get("lat")?.as_f64()?, lon: value.get("lon")?.as_f64()? })
+}
+
+/// The analyzers an `=ft=` on `path` splits its search by: those of every
+/// full-text field for `_text`, otherwise that of `field`.
+fn analyzers(table: &TypeDef, path: &[String], field: Option<&FieldDef>) -> Vec<Analyzer> {
+    match path {
+        [name] if name == ALL_TEXT => searched_fields(table, path).into_iter().map(FieldDef::analyzer).collect(),
+        _ => vec![field.map(FieldDef::analyzer).unwrap_or_default()],
+    }
+}
+
+fn text_of(value: &Json) -> Option<Cow<'_, str>> {
+    match value {
+        Json::String(s) => Some(Cow::Borrowed(s)),
+        Json::Number(n) => Some(Cow::Owned(n.to_string())),
+        Json::Bool(b) => Some(Cow::Borrowed(if *b { "true" } else { "false" })),
+        _ => None,
+    }
+}
+
+/// `*` matches any run of characters; everything else is literal.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || !text[first.len()..].ends_with(last) {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for middle in &parts[1..parts.len() - 1] {
+        match rest.find(middle) {
+            Some(i) => rest = &rest[i + middle.len()..],
+            None => return false,
+        }
+    }
+    true
+}
@@ -0,0 +1,388 @@
+//! Running a parsed path query against the store: filter, sort, page and
+//! project, or return the plan for `explain=true`.
+//!
+//! Pages come from `offset=` or from an `after=` cursor. A page is fetched
+//! one row long so the envelope can tell whether a `nextCursor` exists.
+//! When a composite index yields the `sort=` order, every page, the first
+//! included, is read from the index rather than by sorting the table, and
+//! the `pagination=true` total counts the index range the query pins.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Bound;
+use std::time::Instant;
+
+use serde_json::{Map, Value as Json, json};
+
+use super::Store;
+use super::cursor::{Cursor, SortOrder, compare_keys, sort_values};
+use super::eval::Predicate;
+use super::pattern::REGEX_TIME_LIMIT;
+use super::plan::{Plan, keyset_index, plan};
+use super::text::{HIGHLIGHT, Relevance, SCORE};
+use crate::fiql::{PathQuery, Query, SelectField, Span};
+use crate::schema::TypeDef;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecError {
+    UnknownTable(String),
+    NotFound {
+        table: String,
+        id: String,
+    },
+    InvalidRegex {
+        pattern: String,
+        message: String,
+        span: Span,
+    },
+    /// Matching `=~=` ran past [`REGEX_TIME_LIMIT`].
+    RegexTimeout {
+        pattern: String,
+        span: Span,
+    },
+    InvalidCursor(String),
+}
+
+impl ExecError {
+    /// HTTP status the REST layer answers with.
+    pub fn status(&self) -> u16 {
+        match self {
+            ExecError::UnknownTable(_) | ExecError::NotFound { .. } => 404,
+            ExecError::InvalidRegex { .. } | ExecError::InvalidCursor(_) => 400,
+            ExecError::RegexTimeout { .. } => 422,
+        }
+    }
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::UnknownTable(t) => write!(f, "unknown table `{t}`"),
+            ExecError::NotFound { table, id } => write!(f, "no `{table}` record with id `{id}`"),
+            ExecError::InvalidRegex { pattern, message, span } => {
+                write!(f, "invalid regex `{pattern}` at {span}: {message}")
+            }
+            ExecError::RegexTimeout { pattern, span } => write!(
+                f,
+                "regex `{pattern}` at {span} ran past the {}ms evaluation limit; narrow the filter or simplify the pattern",
+                REGEX_TIME_LIMIT.as_millis()
+            ),
+            ExecError::InvalidCursor(why) => write!(f, "invalid `after` cursor: {why}"),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+/// What a table endpoint responds with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryOutput {
+    /// `GET /Table/id`.
+    Record(Json),
+    Records(Vec<Json>),
+    /// `pagination=true`.
+    Page {
+        data: Vec<Json>,
+        total: usize,
+        offset: u64,
+        limit: Option<u64>,
+        /// Cursor for the page after this one, if there is one.
+        next_cursor: Option<String>,
+    },
+    /// `explain=true`.
+    Plan(Plan),
+}
+
+impl QueryOutput {
+    pub fn to_json(&self) -> Json {
+        match self {
+            QueryOutput::Record(record) => record.clone(),
+            QueryOutput::Records(records) => Json::Array(records.clone()),
+            QueryOutput::Page { data, total, offset, limit, next_cursor } => json!({
+                "data": data,
+                "pagination": { "total": total, "offset": offset, "limit": limit, "nextCursor": next_cursor },
+            }),
+            QueryOutput::Plan(plan) => serde_json::to_value(plan).expect("plans serialize"),
+        }
+    }
+}
+
+pub fn execute(store: &Store, path: &PathQuery) -> Result<QueryOutput, ExecError> {
+    let table = store.schema().table(&path.table).ok_or_else(|| ExecError::UnknownTable(path.table.clone()))?;
+    let controls = &path.query.controls;
+    if controls.explain == Some(true) {
+        return Ok(QueryOutput::Plan(plan(store, table, &path.query)));
+    }
+    let mut rows = rows(store, path)?;
+    if path.id.is_some() {
+        return Ok(QueryOutput::Record(rows.next().expect("a read by id yields its record")));
+    }
+    let (total, next_cursor) = (rows.total, rows.next_cursor.take());
+    let data: Vec<Json> = rows.collect();
+    match total {
+        Some(total) => Ok(QueryOutput::Page {
+            data,
+            total,
+            offset: controls.offset.unwrap_or(0),
+            limit: controls.limit,
+            next_cursor,
+        }),
+        None => Ok(QueryOutput::Records(data)),
+    }
+}
+
+/// The rows of a table query, rendered one at a time as they are read.
+/// Only references to the matching records are held up front, so an export
+/// can encode each batch before the next is rendered.
+pub struct Rows<'a> {
+    /// Records before paging, with `pagination=true`.
+    pub total: Option<usize>,
+    /// Cursor for the page after this one, with `pagination=true`.
+    pub next_cursor: Option<String>,
+    records: std::vec::IntoIter<&'a Json>,
+    show: Box<dyn Fn(&Json) -> Json + 'a>,
+}
+
+impl Iterator for Rows<'_> {
+    type Item = Json;
+
+    fn next(&mut self) -> Option<Json> {
+        self.records.next().map(|r| (self.show)(r))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.records.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Rows<'_> {}
+
+/// The rows `path` selects: filtered, sorted and paged, with `select=`
+/// applied as each is read. `explain=` is ignored; see [`execute`].
+pub fn rows<'a>(store: &'a Store, path: &PathQuery) -> Result<Rows<'a>, ExecError> {
+    let table = store.schema().table(&path.table).ok_or_else(|| ExecError::UnknownTable(path.table.clone()))?;
+    let controls = &path.query.controls;
+    let relevance = Relevance::of(&path.query);
+    let select = controls.select.clone();
+    let show = Box::new(move |record: &Json| {
+        let select = select.as_deref();
+        render(store, table, &scored(store, table, record, &relevance, select), select)
+    });
+    if let Some(id) = &path.id {
+        let record = store
+            .get(&table.name, id)
+            .ok_or_else(|| ExecError::NotFound { table: table.name.clone(), id: id.clone() })?;
+        return Ok(Rows { total: None, next_cursor: None, records: vec![record].into_iter(), show });
+    }
+
+    let order = SortOrder::of(&path.query);
+    let cursor = controls.after.as_deref().map(|c| Cursor::decode(c, order.keys.len())).transpose()?;
+    if cursor.is_some() && controls.offset.is_some() {
+        return Err(ExecError::InvalidCursor("`after` replaces `offset`; use one or the other".to_string()));
+    }
+    let offset = controls.offset.unwrap_or(0);
+    let limit = controls.limit.map(|l| l as usize);
+    let want = limit.map_or(usize::MAX, |l| l.saturating_add(1));
+    let paginated = controls.pagination == Some(true);
+    let mut total = cursor.as_ref().and_then(|c| c.total);
+    let mut records = match &cursor {
+        Some(cursor) => page_after(store, table, &path.query, cursor, want)?,
+        None => {
+            // The total needs the whole result; otherwise stop at the page.
+            let read = if paginated { usize::MAX } else { want.saturating_add(offset as usize) };
+            let matched = match seek(store, table, &path.query, None, read)? {
+                Some(records) => records,
+                None => matching(store, table, &path.query)?,
+            };
+            total = Some(matched.len());
+            matched.into_iter().skip(offset as usize).take(want).collect()
+        }
+    };
+    let more = limit.is_some_and(|l| records.len() > l);
+    records.truncate(limit.unwrap_or(usize::MAX));
+
+    if !paginated {
+        return Ok(Rows { total: None, next_cursor: None, records: records.into_iter(), show });
+    }
+    let total = match total {
+        Some(total) => total,
+        None => match seek(store, table, &path.query, None, usize::MAX)? {
+            Some(records) => records.len(),
+            None => matching(store, table, &path.query)?.len(),
+        },
+    };
+    let next_cursor = records
+        .last()
+        .filter(|_| more)
+        .map(|r| Cursor { total: Some(total), ..Cursor::of(store, table, r, &order) }.encode());
+    Ok(Rows { total: Some(total), next_cursor, records: records.into_iter(), show })
+}
+
+/// Up to `want` rows strictly after `cursor`. Unsorted queries walk the
+/// primary key from the cursor and index-ordered ones walk the composite
+/// index; anything else filters the sorted result.
+fn page_after<'a>(
+    store: &'a Store,
+    table: &'a TypeDef,
+    query: &Query,
+    cursor: &Cursor,
+    want: usize,
+) -> Result<Vec<&'a Json>, ExecError> {
+    if let Some(records) = seek(store, table, query, Some(cursor), want)? {
+        return Ok(records);
+    }
+    let order = SortOrder::of(query);
+    let predicate = query.filter.as_ref().map(|f| Predicate::compile(store, table, f)).transpose()?;
+    let passes = |r: &&Json| predicate.as_ref().is_none_or(|p| p.matches(store, table, r));
+    if order.keys.is_empty() {
+        let Some(records) = store.table(&table.name) else { return Ok(Vec::new()) };
+        let after = (Bound::Excluded(cursor.id.clone()), Bound::Unbounded);
+        return Ok(records.records.range(after).map(|(_, r)| r).filter(passes).take(want).collect());
+    }
+    Ok(matching(store, table, query)?
+        .into_iter()
+        .filter(|r| cursor.compare(store, table, r, &order) == Ordering::Greater)
+        .take(want)
+        .collect())
+}
+
+/// Up to `want` rows read in order from the composite index whose first
+/// field the query pins, starting after `cursor` when given; `None` when no
+/// index yields the `sort=` order.
+fn seek<'a>(
+    store: &'a Store,
+    table: &'a TypeDef,
+    query: &Query,
+    cursor: Option<&Cursor>,
+    want: usize,
+) -> Result<Option<Vec<&'a Json>>, ExecError> {
+    let Some((index, first)) = keyset_index(table, query).and_then(|(name, first)| {
+        let index = store.table(&table.name)?.indexes.iter().find(|i| i.name() == name)?;
+        Some((index, first))
+    }) else {
+        return Ok(None);
+    };
+    let predicate = query.filter.as_ref().map(|f| Predicate::compile(store, table, f)).transpose()?;
+    Ok(Some(
+        index
+            .seek(&first, cursor.map(|c| (&c.keys[0], c.id.as_str())))
+            .filter_map(|id| store.get(&table.name, id))
+            .filter(|r| predicate.as_ref().is_none_or(|p| p.matches(store, table, r)))
+            .take(want)
+            .collect(),
+    ))
+}
+
+/// Records of `table` passing the filter, in `sort=` order (primary key
+/// order when unsorted), before paging.
+pub fn matching<'a>(store: &'a Store, table: &'a TypeDef, query: &Query) -> Result<Vec<&'a Json>, ExecError> {
+    let predicate = query.filter.as_ref().map(|f| Predicate::compile(store, table, f)).transpose()?;
+    let Some(records) = store.table(&table.name) else { return Ok(Vec::new()) };
+    let regex = predicate.as_ref().and_then(Predicate::regex);
+    let started = Instant::now();
+    let mut matched = Vec::new();
+    for record in records.records.values() {
+        if let Some(value) = regex
+            && started.elapsed() > REGEX_TIME_LIMIT
+        {
+            return Err(ExecError::RegexTimeout { pattern: value.raw.clone(), span: value.span });
+        }
+        if predicate.as_ref().is_none_or(|p| p.matches(store, table, record)) {
+            matched.push(record);
+        }
+    }
+
+    if query.controls.sort.is_some() {
+        let order = SortOrder::of(query);
+        let mut keyed: Vec<(Vec<Option<Cow<Json>>>, &Json)> =
+            matched.into_iter().map(|r| (sort_values(store, table, r, &order), r)).collect();
+        // Stable, so ties stay in primary key order.
+        keyed.sort_by(|(a, _), (b, _)| compare_keys(order.keys, a, b));
+        matched = keyed.into_iter().map(|(_, r)| r).collect();
+    }
+    Ok(matched)
+}
+
+/// `record` with the `_score` and `_highlight` a `select=` asks for, which
+/// no record stores.
+fn scored<'a>(
+    store: &Store,
+    table: &TypeDef,
+    record: &'a Json,
+    relevance: &Relevance,
+    select: Option<&[SelectField]>,
+) -> Cow<'a, Json> {
+    let wants = |name: &str| select.is_some_and(|fields| fields.iter().any(|f| f.name == name));
+    if !wants(SCORE) && !wants(HIGHLIGHT) {
+        return Cow::Borrowed(record);
+    }
+    let mut scored = record.clone();
+    if wants(SCORE) {
+        scored[SCORE] = json!(relevance.score(store, table, record));
+    }
+    if wants(HIGHLIGHT) {
+        scored[HIGHLIGHT] = relevance.highlight(table, record);
+    }
+    Cow::Owned(scored)
+}
+
+pub(crate) fn render(store: &Store, table: &TypeDef, record: &Json, select: Option<&[SelectField]>) -> Json {
+    match select {
+        Some(fields) => project(store, table, record, fields),
+        None => record.clone(),
+    }
+}
+
+/// Applies `select=`: relationship fields are joined in (an object for a
+/// forward join, an array for a reverse one), `@embedding` fields are read
+/// from their vector index, nested selections pick keys out of JSON
+/// objects, and fields the record lacks are left out.
+pub fn project(store: &Store, table: &TypeDef, record: &Json, fields: &[SelectField]) -> Json {
+    let mut out = Map::new();
+    for f in fields {
+        if let Some(field) = table.field(&f.name).filter(|d| d.relationship().is_some()) {
+            let target = store.schema().table(&field.ty.name);
+            let join = |r: &Json| match target {
+                Some(t) if !f.children.is_empty() => project(store, t, r, &f.children),
+                _ => r.clone(),
+            };
+            let related = store.related(table, field, record);
+            let value = if field.ty.list {
+                Json::Array(related.into_iter().map(join).collect())
+            } else {
+                related.first().map_or(Json::Null, |r| join(r))
+            };
+            out.insert(f.name.clone(), value);
+            continue;
+        }
+        if table.field(&f.name).is_some_and(|d| d.embedding().is_some()) {
+            let vector = super::primary_key_of(table, record).and_then(|id| {
+                // Six decimals is all an `f32` holds; printed as `f64`, the
+                // rest would be noise.
+                Some(store.vectors(&table.name, &f.name)?.get(&id)?.iter().map(|x| (f64::from(*x) * 1e6).round() / 1e6))
+            });
+            if let Some(vector) = vector {
+                out.insert(f.name.clone(), json!(vector.collect::<Vec<_>>()));
+            }
+            continue;
+        }
+        if let Some(value) = record.get(&f.name) {
+            out.insert(f.name.clone(), pick(value, &f.children));
+        }
+    }
+    Json::Object(out)
+}
+
+fn pick(value: &Json, children: &[SelectField]) -> Json {
+    if children.is_empty() {
+        return value.clone();
+    }
+    match value {
+        Json::Object(map) => Json::Object(
+            children.iter().filter_map(|c| map.get(&c.name).map(|v| (c.name.clone(), pick(v, &c.children)))).collect(),
+        ),
+        Json::Array(items) => Json::Array(items.iter().map(|v| pick(v, children)).collect()),
+        other => other.clone(),
+    }
+}
@@ -0,0 +1,160 @@
+//! Facet counts for a catalog UI.
+//!
+//! Each facet is counted over the records matching the filter *minus the
+//! facet's own top-level conditions*, so selecting `category==books` still
+//! shows how many results the other categories would give. Those scoped
+//! queries can read far more than the filter itself, so
+//! [`facets_estimate`] prices every one of them.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value as Json, json};
+
+use super::Store;
+use super::cost::Estimate;
+use super::eval::{compare_json, field_values};
+use super::exec::{ExecError, matching};
+use super::plan::plan;
+use crate::fiql::{Expr, Literal, Query};
+use crate::query::literal_json;
+use crate::schema::{ScalarType, TypeDef};
+
+/// One facet: distinct values with counts, or counts per numeric bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Facet {
+    pub field: String,
+    /// Bucket edges; `None` counts distinct values.
+    pub edges: Option<Vec<f64>>,
+}
+
+/// Facets for every `@indexed` field of `table` that is not the primary key,
+/// a full-text, geo or vector index or a date, plus any numeric field with
+/// bucket edges.
+/// Fields with edges are bucketed; the rest count distinct values.
+pub fn default_facets(table: &TypeDef, buckets: &BTreeMap<String, Vec<f64>>) -> Vec<Facet> {
+    table
+        .fields
+        .iter()
+        .filter(|f| f.relationship().is_none() && f.directive("primaryKey").is_none())
+        .filter(|f| !f.scalar().is_some_and(ScalarType::is_temporal))
+        .filter(|f| {
+            (f.is_indexed() && !f.is_fulltext() && !f.is_geo() && !f.is_vector()) || buckets.contains_key(&f.name)
+        })
+        .map(|f| Facet { field: f.name.clone(), edges: buckets.get(&f.name).cloned() })
+        .collect()
+}
+
+/// The estimate of counting `facets` for `query`: the query itself plus
+/// the scoped query of each facet.
+pub fn facets_estimate(store: &Store, table: &TypeDef, query: &Query, facets: &[Facet]) -> Estimate {
+    facets.iter().fold(plan(store, table, query).estimate, |sum, facet| {
+        let scoped = plan(store, table, &scoped(query, &facet.field)).estimate;
+        sum.and(scoped, &format!("for the `{}` facet", facet.field))
+    })
+}
+
+/// Counts `facets` for `query`, returning `{ field: { type, ... } }`.
+pub fn facets(store: &Store, table: &TypeDef, query: &Query, facets: &[Facet]) -> Result<Json, ExecError> {
+    let mut out = Map::new();
+    for facet in facets {
+        let records = matching(store, table, &scoped(query, &facet.field))?;
+        let values: Vec<Vec<&Json>> =
+            records.iter().map(|r| field_values(store, table, r, std::slice::from_ref(&facet.field))).collect();
+        let counted = match &facet.edges {
+            Some(edges) => json!({ "type": "range", "buckets": bucket_counts(&values, edges) }),
+            None => json!({ "type": "terms", "values": term_counts(&values) }),
+        };
+        out.insert(facet.field.clone(), counted);
+    }
+    Ok(Json::Object(out))
+}
+
+/// `query` without the terms that only constrain `field`.
+fn scoped(query: &Query, field: &str) -> Query {
+    let mut scoped = query.clone();
+    scoped.filter = query.filter.clone().and_then(|f| without_field(f, field));
+    scoped
+}
+
+/// Drops top-level `&` terms that only constrain `field`: a condition on
+/// it, or a multi-select `|` whose branches all are
+/// (`category==electronics|category==books`). Other terms under `|` or `!`
+/// stay because removing them would change what the rest means.
+fn without_field(expr: Expr, field: &str) -> Option<Expr> {
+    fn on_field(e: &Expr, field: &str) -> bool {
+        match e {
+            Expr::Condition(c) => c.field.segments.len() == 1 && c.field.root() == field,
+            Expr::Or(branches) => branches.iter().all(|b| on_field(b, field)),
+            _ => false,
+        }
+    }
+    match expr {
+        e if on_field(&e, field) => None,
+        Expr::And(children) => {
+            let mut kept: Vec<Expr> = children.into_iter().filter(|e| !on_field(e, field)).collect();
+            match kept.len() {
+                0 => None,
+                1 => kept.pop(),
+                _ => Some(Expr::And(kept)),
+            }
+        }
+        other => Some(other),
+    }
+}
+
+/// Distinct values by descending count, ties in value order. A record with
+/// an array value counts once for each distinct element.
+fn term_counts(values: &[Vec<&Json>]) -> Vec<Json> {
+    let mut counts: Vec<(&Json, usize)> = Vec::new();
+    for record in values {
+        let mut seen: Vec<&Json> = Vec::new();
+        for &value in record {
+            if seen.contains(&value) {
+                continue;
+            }
+            seen.push(value);
+            match counts.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((value, 1)),
+            }
+        }
+    }
+    counts.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| compare_json(a, b)));
+    counts.into_iter().map(|(value, count)| json!({ "value": value, "count": count })).collect()
+}
+
+/// Half-open buckets `[edge[i], edge[i+1])`, plus one below the first edge
+/// and one from the last edge up. Empty buckets are kept so the UI can show
+/// a stable list.
+fn bucket_counts(values: &[Vec<&Json>], edges: &[f64]) -> Vec<Json> {
+    let mut counts = vec![0usize; edges.len() + 1];
+    for record in values {
+        let mut hit = vec![false; counts.len()];
+        for n in record.iter().filter_map(|v| v.as_f64()) {
+            let index = edges.iter().position(|&e| n < e).unwrap_or(edges.len());
+            hit[index] = true;
+        }
+        for (count, hit) in counts.iter_mut().zip(hit) {
+            *count += usize::from(hit);
+        }
+    }
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let mut bucket = Map::new();
+            if i > 0 {
+                bucket.insert("from".into(), number(edges[i - 1]));
+            }
+            if i < edges.len() {
+                bucket.insert("to".into(), number(edges[i]));
+            }
+            bucket.insert("count".into(), json!(count));
+            Json::Object(bucket)
+        })
+        .collect()
+}
+
+fn number(n: f64) -> Json {
+    literal_json(&Literal::Number(n))
+}
@@ -0,0 +1,420 @@
+//! In-process table store with the platform's FIQL semantics.
+//!
+//! Records are kept as JSON objects ordered by primary key, which is the
+//! order the platform returns unsorted results in. The store is seeded from
+//! the same `data/*.json` loader files the app ships with, so it can stand in
+//! for the generated table endpoints in tests and back the custom resources.
+//!
+//! Every `@compositeIndex` is maintained as an ordered set of
+//! `(field values..., primary key)`, which is what makes keyset pagination a
+//! seek rather than a scan.
+//!
+//! Fields marked `@createdTime` and `@updatedTime` are stamped on write, as
+//! RFC 3339 UTC strings.
+//!
+//! Every full-text field keeps a [`TextIndex`] of its term statistics,
+//! which `_score` ranks `=ft=` matches by, counted over the terms its
+//! `analyzer:` splits the text into.
+//!
+//! An `@embedding` field is computed on write too: its `from:` text goes
+//! through the store's [`Embedder`] and the vector lands in the field's
+//! [`VectorIndex`], not in the record, so it never bloats a response that
+//! did not ask for it.
+//!
+//! Every indexed field also keeps counts of the values it holds, its
+//! numeric range and, where it applies, its folded keys and points. Cost
+//! estimates read those instead of the records.
+//!
+//! Writes are also appended to a bounded change log, which live queries
+//! read to find out what changed since they last looked.
+
+mod aggregate;
+mod analysis;
+mod collate;
+mod cost;
+mod cursor;
+mod embed;
+mod eval;
+mod exec;
+mod facets;
+mod patch;
+mod pattern;
+mod plan;
+mod suggest;
+mod text;
+mod vector;
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::Value as Json;
+
+use crate::schema::{self, FieldDef, Relationship, Schema, TypeDef};
+use cost::FieldStats;
+use eval::Ordered;
+
+pub use aggregate::{Function, Metric, aggregate};
+pub use analysis::{MIN_GRAM, Token, analyze, query_terms, stem};
+pub use cost::{Estimate, OverBudget, Part};
+pub use cursor::{Cursor, Origin, SortOrder};
+pub use embed::{Embedder, HashingEmbedder};
+pub use eval::{Predicate, compare_json, field_values};
+pub(crate) use exec::render;
+pub use exec::{ExecError, QueryOutput, Rows, execute, matching, project, rows};
+pub use facets::{Facet, default_facets, facets, facets_estimate};
+pub use patch::{conform, merge_patch};
+pub use pattern::{
+    MAX_PATTERN_LEN, REGEX_CACHE_CAPACITY, REGEX_NEST_LIMIT, REGEX_SIZE_LIMIT, REGEX_TIME_LIMIT, is_cached,
+};
+pub use plan::{Plan, plan};
+pub use suggest::{DEFAULT_SUGGESTIONS, Suggestion, suggest, suggestable};
+pub use text::{ALL_TEXT, HIGHLIGHT, Relevance, SCORE, SNIPPET_LEN, TextIndex};
+pub use vector::VectorIndex;
+
+/// Primary keys no record may have: `/<Table>/aggregate`, `/<Table>/facets`
+/// and `/<Table>/suggest` are resources, so a record under one of these
+/// keys could never be read or written by id.
+pub const RESERVED_KEYS: &[&str] = &["aggregate", "facets", "suggest"];
+
+/// How many writes the change log keeps. A reader further behind than this
+/// has to re-read the tables it watches.
+pub const CHANGE_LOG_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct Store {
+    schema: Schema,
+    embedder: Arc<dyn Embedder>,
+    tables: BTreeMap<String, Table>,
+    changes: VecDeque<Change>,
+    version: u64,
+}
+
+/// One committed write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// Position in the change log, starting at 1.
+    pub seq: u64,
+    pub table: String,
+    pub id: String,
+    /// The record before the write; `None` for an insert.
+    pub before: Option<Json>,
+    /// The record after the write; `None` for a delete.
+    pub after: Option<Json>,
+}
+
+/// Records of one table keyed by primary key.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    pub records: BTreeMap<String, Json>,
+    pub indexes: Vec<CompositeIndex>,
+    /// One per full-text field.
+    pub texts: Vec<TextIndex>,
+    /// One per `@embedding` field.
+    pub vectors: Vec<VectorIndex>,
+    /// One per indexed field, for cost estimates.
+    pub(crate) stats: Vec<FieldStats>,
+}
+
+/// An `@compositeIndex`: records ordered by the listed fields, then by key.
+#[derive(Debug, Clone, Default)]
+pub struct CompositeIndex {
+    pub fields: Vec<String>,
+    entries: BTreeSet<(Vec<Ordered>, String)>,
+}
+
+impl CompositeIndex {
+    fn new(fields: Vec<String>) -> Self {
+        CompositeIndex { fields, entries: BTreeSet::new() }
+    }
+
+    /// `category+price`, as shown in query plans.
+    pub fn name(&self) -> String {
+        self.fields.join("+")
+    }
+
+    fn key(&self, record: &Json) -> Vec<Ordered> {
+        self.fields.iter().map(|f| Ordered(record.get(f).cloned().unwrap_or(Json::Null))).collect()
+    }
+
+    /// Keys of the records whose first field equals `first`, in index order,
+    /// starting after `(second, id)` when given.
+    pub(crate) fn seek<'a>(&'a self, first: &Json, after: Option<(&Json, &str)>) -> impl Iterator<Item = &'a str> + 'a {
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+        let first = Ordered(first.clone());
+        let lower = match after {
+            Some((second, id)) => Excluded((vec![first.clone(), Ordered(second.clone())], id.to_string())),
+            None => Included((vec![first.clone()], String::new())),
+        };
+        self.entries.range((lower, Unbounded)).take_while(move |(key, _)| key[0] == first).map(|(_, id)| id.as_str())
+    }
+}
+
+/// How a write treats `@updatedTime` fields: API writes set them to now,
+/// loader files keep the ones they give.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stamp {
+    Now,
+    IfMissing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreError {
+    InvalidJson(String),
+    UnknownTable(String),
+    MissingKey {
+        table: String,
+    },
+    /// See [`RESERVED_KEYS`].
+    ReservedKey {
+        table: String,
+        key: String,
+    },
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::InvalidJson(e) => write!(f, "invalid loader file: {e}"),
+            StoreError::UnknownTable(t) => write!(f, "unknown table `{t}`"),
+            StoreError::MissingKey { table } => write!(f, "`{table}` record without a primary key"),
+            StoreError::ReservedKey { table, key } => {
+                write!(f, "`{key}` cannot be a `{table}` primary key; `/{table}/{key}` is a resource")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl Store {
+    /// An empty store with one table per `@table` type in `schema`.
+    pub fn new(schema: Schema) -> Self {
+        let tables = schema
+            .tables()
+            .map(|t| {
+                let indexes = t
+                    .composite_indexes()
+                    .into_iter()
+                    .map(|fields| CompositeIndex::new(fields.into_iter().map(str::to_string).collect()))
+                    .collect();
+                let vectors = t
+                    .fields
+                    .iter()
+                    .filter_map(|f| f.embedding().map(|e| VectorIndex::new(&f.name, e.from, e.dimensions)))
+                    .collect();
+                let texts = t
+                    .fields
+                    .iter()
+                    .filter(|f| f.is_fulltext())
+                    .map(|f| TextIndex::new(&f.name, f.boost(), f.analyzer()))
+                    .collect();
+                let stats = FieldStats::for_table(t);
+                (t.name.clone(), Table { records: BTreeMap::new(), indexes, texts, vectors, stats })
+            })
+            .collect();
+        Store { schema, embedder: Arc::new(HashingEmbedder), tables, changes: VecDeque::new(), version: 0 }
+    }
+
+    /// Embeds `@embedding` fields with `embedder` from now on, re-embedding
+    /// the records already stored.
+    pub fn with_embedder(mut self, embedder: impl Embedder + 'static) -> Self {
+        self.embedder = Arc::new(embedder);
+        for table in self.tables.values_mut() {
+            for index in &mut table.vectors {
+                for (key, record) in &table.records {
+                    embed(self.embedder.as_ref(), index, key, record);
+                }
+            }
+        }
+        self
+    }
+
+    /// The bundled schema loaded with the bundled seed data.
+    pub fn seeded() -> Self {
+        let mut store = Store::new(schema::bundled().clone());
+        for file in [include_str!("../../data/products.json"), include_str!("../../data/brands.json")] {
+            store.load(file).expect("bundled data is valid");
+        }
+        store
+    }
+
+    /// Loads a `{ "table": ..., "records": [...] }` loader file.
+    pub fn load(&mut self, json: &str) -> Result<(), StoreError> {
+        let file: Json = serde_json::from_str(json).map_err(|e| StoreError::InvalidJson(e.to_string()))?;
+        let table = file["table"].as_str().ok_or_else(|| StoreError::InvalidJson("missing `table`".into()))?;
+        let records = file["records"].as_array().ok_or_else(|| StoreError::InvalidJson("missing `records`".into()))?;
+        for record in records {
+            self.write(table, record.clone(), Stamp::IfMissing)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts or replaces a record by primary key. `@createdTime` fields
+    /// keep the stored record's value (or the given one, for a new record)
+    /// and `@updatedTime` fields are set to now.
+    pub fn put(&mut self, table: &str, record: Json) -> Result<(), StoreError> {
+        self.write(table, record, Stamp::Now)
+    }
+
+    fn write(&mut self, table: &str, mut record: Json, stamp: Stamp) -> Result<(), StoreError> {
+        let def = self.schema.table(table).ok_or_else(|| StoreError::UnknownTable(table.to_string()))?;
+        let key = primary_key_of(def, &record).ok_or_else(|| StoreError::MissingKey { table: table.to_string() })?;
+        if RESERVED_KEYS.contains(&key.as_str()) {
+            return Err(StoreError::ReservedKey { table: table.to_string(), key });
+        }
+        let stored = self.tables.get(table).and_then(|t| t.records.get(&key));
+        let now = Json::String(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+        if let Json::Object(fields) = &mut record {
+            for field in def.fields.iter().filter(|f| f.embedding().is_some()) {
+                fields.remove(&field.name);
+            }
+            for field in &def.fields {
+                let kept = if field.is_created_time() {
+                    stored.and_then(|r| r.get(&field.name)).or(fields.get(&field.name))
+                } else if field.is_updated_time() {
+                    fields.get(&field.name).filter(|_| stamp == Stamp::IfMissing)
+                } else {
+                    continue;
+                };
+                let value = kept.cloned().unwrap_or_else(|| now.clone());
+                fields.insert(field.name.clone(), value);
+            }
+        }
+        let name = table.to_string();
+        let table = self.tables.entry(name.clone()).or_default();
+        for index in &mut table.indexes {
+            if let Some(old) = table.records.get(&key) {
+                index.entries.remove(&(index.key(old), key.clone()));
+            }
+            index.entries.insert((index.key(&record), key.clone()));
+        }
+        for index in &mut table.texts {
+            if let Some(old) = table.records.get(&key) {
+                index.remove(old);
+            }
+            index.insert(&record);
+        }
+        for index in &mut table.vectors {
+            embed(self.embedder.as_ref(), index, &key, &record);
+        }
+        for stats in &mut table.stats {
+            if let Some(old) = table.records.get(&key) {
+                stats.remove(&key, old);
+            }
+            stats.insert(&key, &record);
+        }
+        let before = table.records.insert(key.clone(), record.clone());
+        self.log(name, key, before, Some(record));
+        Ok(())
+    }
+
+    /// Removes a record by primary key, returning it if it existed.
+    pub fn delete(&mut self, table: &str, id: &str) -> Result<Option<Json>, StoreError> {
+        let table_def = self.tables.get_mut(table).ok_or_else(|| StoreError::UnknownTable(table.to_string()))?;
+        let Some(old) = table_def.records.remove(id) else { return Ok(None) };
+        for index in &mut table_def.indexes {
+            index.entries.remove(&(index.key(&old), id.to_string()));
+        }
+        for index in &mut table_def.texts {
+            index.remove(&old);
+        }
+        for index in &mut table_def.vectors {
+            index.remove(id);
+        }
+        for stats in &mut table_def.stats {
+            stats.remove(id, &old);
+        }
+        self.log(table.to_string(), id.to_string(), Some(old.clone()), None);
+        Ok(Some(old))
+    }
+
+    fn log(&mut self, table: String, id: String, before: Option<Json>, after: Option<Json>) {
+        self.version += 1;
+        if self.changes.len() == CHANGE_LOG_CAPACITY {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(Change { seq: self.version, table, id, before, after });
+    }
+
+    /// Sequence number of the latest write; 0 before any.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Writes after `version`, oldest first, or `None` when some of them
+    /// have already been dropped from the log.
+    pub fn changes_since(&self, version: u64) -> Option<impl Iterator<Item = &Change>> {
+        let oldest = self.changes.front().map_or(self.version + 1, |c| c.seq);
+        (version + 1 >= oldest).then(|| self.changes.iter().skip_while(move |c| c.seq <= version))
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+
+    pub fn get(&self, table: &str, id: &str) -> Option<&Json> {
+        self.tables.get(table)?.records.get(id)
+    }
+
+    pub fn embedder(&self) -> &dyn Embedder {
+        self.embedder.as_ref()
+    }
+
+    /// The term statistics of full-text field `field`.
+    pub fn text_index(&self, table: &str, field: &str) -> Option<&TextIndex> {
+        self.tables.get(table)?.texts.iter().find(|t| t.field == field)
+    }
+
+    /// The index of `@embedding` field `field`.
+    pub fn vectors(&self, table: &str, field: &str) -> Option<&VectorIndex> {
+        self.tables.get(table)?.vectors.iter().find(|v| v.field == field)
+    }
+
+    /// Records reached from `record` (of `table`) through relationship `field`.
+    pub fn related<'a>(&'a self, table: &TypeDef, field: &FieldDef, record: &Json) -> Vec<&'a Json> {
+        let Some(target) = self.schema.table(&field.ty.name) else { return Vec::new() };
+        match field.relationship() {
+            Some(Relationship::From(fk)) => {
+                record.get(&fk).and_then(key_string).and_then(|id| self.get(&target.name, &id)).into_iter().collect()
+            }
+            Some(Relationship::To(fk)) => {
+                let Some(id) = primary_key_of(table, record) else { return Vec::new() };
+                self.tables
+                    .get(&target.name)
+                    .into_iter()
+                    .flat_map(|t| t.records.values())
+                    .filter(|r| r.get(&fk).and_then(key_string).as_deref() == Some(id.as_str()))
+                    .collect()
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Stores the embedding of `record`'s source text, or drops the record's
+/// vector when it has no text.
+fn embed(embedder: &dyn Embedder, index: &mut VectorIndex, key: &str, record: &Json) {
+    match record.get(&index.source).and_then(Json::as_str) {
+        Some(text) => index.insert(key, embedder.embed(text, index.dimensions)),
+        None => index.remove(key),
+    }
+}
+
+pub(crate) fn primary_key_of(table: &TypeDef, record: &Json) -> Option<String> {
+    let pk = table.primary_key().map_or("id", |f| f.name.as_str());
+    record.get(pk).and_then(key_string)
+}
+
+pub(crate) fn key_string(value: &Json) -> Option<String> {
+    match value {
+        Json::String(s) => Some(s.clone()),
+        Json::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
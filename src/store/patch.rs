@@ -0,0 +1,103 @@
+//! JSON Merge Patch (RFC 7396), and the schema check a patched record has
+//! to pass before it is written.
+
+use serde_json::Value as Json;
+
+use super::eval::point;
+use super::{RESERVED_KEYS, primary_key_of};
+use crate::fiql::Time;
+use crate::schema::{FieldDef, ScalarType, Schema, TypeDef};
+
+/// Applies `patch` to `target`: object members merge recursively, `null`
+/// removes a member, and anything else replaces the target outright.
+pub fn merge_patch(target: &mut Json, patch: &Json) {
+    let Json::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Json::Object(Default::default());
+    }
+    let Json::Object(target) = target else { unreachable!("made an object above") };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Json::Null), value);
+        }
+    }
+}
+
+/// Checks a record about to be written against its type: every key is a
+/// stored field, every value has the field's type and no non-null field is
+/// missing. `@createdTime` and `@updatedTime` fields are left out, since the
+/// store stamps them itself. A table record's key must not be one of
+/// [`RESERVED_KEYS`].
+pub fn conform(schema: &Schema, def: &TypeDef, record: &Json) -> Result<(), String> {
+    let Json::Object(fields) = record else {
+        return Err(format!("a `{}` record must be an object", def.name));
+    };
+    if schema.table(&def.name).is_some()
+        && let Some(key) = primary_key_of(def, record).filter(|k| RESERVED_KEYS.contains(&k.as_str()))
+    {
+        return Err(format!("`{key}` cannot be a primary key; `/{}/{key}` is a resource", def.name));
+    }
+    if let Some(key) = fields.keys().find(|k| def.field(k).is_none()) {
+        return Err(format!("`{}` has no field `{key}`", def.name));
+    }
+    for field in &def.fields {
+        let value = fields.get(&field.name).unwrap_or(&Json::Null);
+        if field.relationship().is_some() || field.embedding().is_some() {
+            if !value.is_null() {
+                return Err(format!("`{}` is not stored on `{}` records and cannot be written", field.name, def.name));
+            }
+            continue;
+        }
+        if field.is_created_time() || field.is_updated_time() {
+            continue;
+        }
+        if value.is_null() {
+            if field.ty.non_null {
+                return Err(format!("`{}` is {} and cannot be null", field.name, field.ty));
+            }
+            continue;
+        }
+        let values = match value {
+            Json::Array(items) if field.ty.list => items.as_slice(),
+            _ if field.ty.list => return Err(mismatch(field, value)),
+            _ => std::slice::from_ref(value),
+        };
+        for value in values {
+            let ok = match (schema.object(&field.ty.name), field.scalar()) {
+                (Some(object), _) => {
+                    conform(schema, object, value).map_err(|e| format!("`{}`: {e}", field.name))?;
+                    true
+                }
+                (None, Some(ty)) => fits(ty, value),
+                (None, None) => true,
+            };
+            if !ok {
+                return Err(mismatch(field, value));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn fits(ty: ScalarType, value: &Json) -> bool {
+    match ty {
+        ScalarType::Id => value.is_string(),
+        // A `String` field may hold a JSON object, as `manufacturer` does.
+        ScalarType::String => value.is_string() || value.is_object(),
+        ScalarType::Int => value.is_i64() || value.is_u64(),
+        ScalarType::Float => value.is_number(),
+        ScalarType::Boolean => value.is_boolean(),
+        ScalarType::Date => matches!(value.as_str().and_then(Time::parse_stored), Some(Time::Day(_))),
+        ScalarType::DateTime => matches!(value.as_str().and_then(Time::parse_stored), Some(Time::At(_))),
+        ScalarType::GeoPoint => point(value).is_some(),
+    }
+}
+
+fn mismatch(field: &FieldDef, value: &Json) -> String {
+    format!("`{}` is {}, not `{value}`", field.name, field.ty)
+}
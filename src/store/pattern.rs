@@ -0,0 +1,162 @@
+//! Compiling `=~=` patterns safely.
+//!
+//! Patterns come straight from public URLs, so they run on the `regex`
+//! crate's automata, which match in time linear in the input: there is no
+//! backtracking for a pattern like `(a+)+$` to blow up. The constructs that
+//! need backtracking (backreferences, lookaround, atomic groups) are
+//! rejected up front with an error naming the construct, rather than with
+//! the parser's generic message. Patterns are also capped in length, in
+//! nesting and in compiled size, and each query's regex evaluation is cut
+//! off after [`REGEX_TIME_LIMIT`] (see [`matching`](super::matching)).
+//!
+//! Compiled patterns are shared through a small process-wide cache, so a
+//! popular query such as `name=~=(?i)pro` is compiled once.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use regex::{Regex, RegexBuilder};
+
+use super::exec::ExecError;
+use crate::fiql::{Scalar, Span};
+
+/// Longest `=~=` pattern accepted, in characters.
+pub const MAX_PATTERN_LEN: usize = 256;
+
+/// Most bytes a compiled pattern may take; `\w{500}` is over.
+pub const REGEX_SIZE_LIMIT: usize = 256 * 1024;
+
+/// Deepest nesting of groups and repetitions.
+pub const REGEX_NEST_LIMIT: u32 = 32;
+
+/// Time one query may spend matching regexes against records.
+pub const REGEX_TIME_LIMIT: Duration = Duration::from_millis(250);
+
+/// Compiled patterns kept for reuse.
+pub const REGEX_CACHE_CAPACITY: usize = 256;
+
+#[derive(Default)]
+struct Cache {
+    regexes: HashMap<String, Regex>,
+    /// Insertion order, oldest first, for eviction.
+    order: VecDeque<String>,
+}
+
+fn cache() -> &'static Mutex<Cache> {
+    static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+    CACHE.get_or_init(Mutex::default)
+}
+
+/// Whether `pattern` is in the compiled-pattern cache.
+pub fn is_cached(pattern: &str) -> bool {
+    cache().lock().is_ok_and(|c| c.regexes.contains_key(pattern))
+}
+
+/// Compiles the value of a `=~=` condition, from the cache when it can.
+pub(crate) fn compile(value: &Scalar) -> Result<Regex, ExecError> {
+    let pattern = value.raw.as_str();
+    if let Some(regex) = cache().lock().ok().and_then(|c| c.regexes.get(pattern).cloned()) {
+        return Ok(regex);
+    }
+    let invalid = |message: String, span: Span| ExecError::InvalidRegex { pattern: pattern.to_string(), message, span };
+
+    let length = pattern.chars().count();
+    if length > MAX_PATTERN_LEN {
+        return Err(invalid(
+            format!("pattern is {length} characters long; `=~=` patterns are limited to {MAX_PATTERN_LEN}"),
+            value.span,
+        ));
+    }
+    if let Some((offset, construct)) = unsupported(pattern) {
+        let found = &pattern[offset..offset + construct.token.len()];
+        // Point at the construct itself when the value was written unencoded.
+        let span = if value.span.len() == pattern.len() {
+            Span::new(value.span.start + offset, value.span.start + offset + found.len())
+        } else {
+            value.span
+        };
+        return Err(invalid(
+            format!(
+                "{} (`{found}`) are not supported; `=~=` runs on a linear-time engine without backtracking",
+                construct.name
+            ),
+            span,
+        ));
+    }
+    let regex = RegexBuilder::new(pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_SIZE_LIMIT)
+        .nest_limit(REGEX_NEST_LIMIT)
+        .build()
+        .map_err(|err| match err {
+            regex::Error::CompiledTooBig(limit) => invalid(
+                format!("pattern compiles to more than {limit} bytes; use fewer or smaller repetitions"),
+                value.span,
+            ),
+            other => invalid(other.to_string(), value.span),
+        })?;
+
+    if let Ok(mut cache) = cache().lock() {
+        if cache.order.len() == REGEX_CACHE_CAPACITY
+            && let Some(oldest) = cache.order.pop_front()
+        {
+            cache.regexes.remove(&oldest);
+        }
+        cache.order.push_back(pattern.to_string());
+        cache.regexes.insert(pattern.to_string(), regex.clone());
+    }
+    Ok(regex)
+}
+
+struct Construct {
+    token: &'static str,
+    name: &'static str,
+}
+
+const UNSUPPORTED: &[Construct] = &[
+    Construct { token: "(?=", name: "lookaheads" },
+    Construct { token: "(?!", name: "negative lookaheads" },
+    Construct { token: "(?<=", name: "lookbehinds" },
+    Construct { token: "(?<!", name: "negative lookbehinds" },
+    Construct { token: "(?>", name: "atomic groups" },
+    Construct { token: "(?(", name: "conditionals" },
+    Construct { token: "\\k", name: "named backreferences" },
+    Construct { token: "\\g", name: "backreferences" },
+];
+
+/// The first construct that needs backtracking, with its byte offset.
+/// Escapes and character classes are skipped, so `\(?=` and `[(?=]` are
+/// fine.
+fn unsupported(pattern: &str) -> Option<(usize, &'static Construct)> {
+    const NUMBERED: Construct = Construct { token: "\\1", name: "backreferences" };
+    let mut class = 0usize;
+    let mut chars = pattern.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let rest = &pattern[i..];
+        match c {
+            '\\' if class == 0 => {
+                if let Some(c) = UNSUPPORTED.iter().find(|c| c.token.starts_with('\\') && rest.starts_with(c.token)) {
+                    return Some((i, c));
+                }
+                if rest[1..].starts_with(|c: char| ('1'..='9').contains(&c)) {
+                    return Some((i, &NUMBERED));
+                }
+                // The escaped character, however many bytes it takes.
+                chars.next();
+            }
+            '\\' => {
+                chars.next();
+            }
+            '[' => class += 1,
+            ']' if class > 0 => class -= 1,
+            '(' if class == 0 => {
+                if let Some(c) = UNSUPPORTED.iter().find(|c| c.token.starts_with('(') && rest.starts_with(c.token)) {
+                    return Some((i, c));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
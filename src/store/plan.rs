@@ -0,0 +1,164 @@
+//! Index selection for `explain=true`.
+//!
+//! The planner looks only at the top-level `&` chain: a condition under `|`
+//! or `!` cannot narrow the candidate set on its own. Preference order is
+//! primary key, vector index, composite index, single-field index,
+//! full-text or geo index, and finally a full scan. A `=sim=` on a field
+//! with an HNSW index reads its `k` records from the graph, checking the
+//! rest of the chain on the way. An index with a collation holds folded
+//! keys, so it also answers `=eqi=` with a seek and `=swi=` with a prefix
+//! range; a geo index answers `=near=` and `=within=`. A query that pins
+//! the first field of a composite index and sorts by the second reads pages
+//! straight out of that index.
+//!
+//! Each plan carries a cost [`Estimate`] for the path it picked.
+
+use serde::Serialize;
+use serde_json::Value as Json;
+
+use super::Store;
+use super::cost::{Estimate, estimate};
+use crate::fiql::{Condition, Expr, Literal, Operator, Query};
+use crate::query::{ConditionNode, conjuncts};
+use crate::schema::{ScalarType, TypeDef};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Plan {
+    pub strategy: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    pub conditions: Vec<ConditionNode>,
+    /// Rows come out of the index already in `sort=` order, so every page
+    /// starts with a seek.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub keyset: bool,
+    pub estimated_cost: &'static str,
+    pub estimate: Estimate,
+}
+
+pub fn plan(store: &Store, table: &TypeDef, query: &Query) -> Plan {
+    let conditions = query.filter.as_ref().map(conjuncts).unwrap_or_default();
+    let (strategy, index, keyset, estimated_cost) = match keyset_index(table, query) {
+        Some((index, _)) => ("composite_index", Some(index), true, "low"),
+        None => {
+            let (strategy, index, cost) = choose(table, &top_level(query));
+            (strategy, index, false, cost)
+        }
+    };
+    let estimate = estimate(store, table, query, strategy, index.as_deref(), keyset);
+    Plan { strategy, index, conditions, keyset, estimated_cost, estimate }
+}
+
+/// A composite index that yields `query`'s rows in order: its first field
+/// is pinned to one string by `==` and `sort=` is its second field,
+/// ascending. Returns the index name and the pinned value.
+pub(crate) fn keyset_index(table: &TypeDef, query: &Query) -> Option<(String, Json)> {
+    let [key] = query.controls.sort.as_deref()? else { return None };
+    if key.descending || key.field.is_nested() {
+        return None;
+    }
+    let top = top_level(query);
+    table.composite_indexes().into_iter().find_map(|fields| {
+        let [first, second, ..] = fields[..] else { return None };
+        if key.field.root() != second
+            || !table.field(first)?.scalar().is_some_and(|s| matches!(s, ScalarType::String | ScalarType::Id))
+        {
+            return None;
+        }
+        top.iter().find_map(|c| match c.value.scalars().first().map(|s| &s.literal) {
+            Some(Literal::String(s)) if c.field.segments == [first] && is_point(c) && c.op != Operator::In => {
+                Some((fields.join("+"), Json::String(s.clone())))
+            }
+            _ => None,
+        })
+    })
+}
+
+pub(crate) fn top_level(query: &Query) -> Vec<&Condition> {
+    match &query.filter {
+        Some(Expr::Condition(c)) => vec![c],
+        Some(Expr::And(children)) => children
+            .iter()
+            .filter_map(|e| match e {
+                Expr::Condition(c) => Some(c),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn choose(table: &TypeDef, top: &[&Condition]) -> (&'static str, Option<String>, &'static str) {
+    let any_on = |name: &str, test: fn(&Condition) -> bool| {
+        top.iter().any(|c| c.field.segments.len() == 1 && c.field.root() == name && test(c))
+    };
+
+    if let Some(pk) = table.primary_key()
+        && any_on(&pk.name, is_point)
+    {
+        return ("primary_key", Some(pk.name.clone()), "low");
+    }
+    let vector = top.iter().find_map(|c| {
+        let field = table.embedding_for(c.field.root()).filter(|f| f.is_vector())?;
+        (c.op == Operator::Sim && !c.field.is_nested()).then(|| field.name.clone())
+    });
+    if let Some(field) = vector {
+        return ("vector", Some(field), "medium");
+    }
+    for fields in table.composite_indexes() {
+        if let [first, second, ..] = fields[..]
+            && any_on(first, is_point)
+            && any_on(second, |c| is_point(c) || c.op.is_range() || is_bound(c))
+        {
+            return ("composite_index", Some(fields.join("+")), "low");
+        }
+    }
+    let indexed = |c: &&&Condition| {
+        c.field.segments.len() == 1
+            && table
+                .field(c.field.root())
+                .is_some_and(|f| f.is_indexed() && !f.is_fulltext() && !f.is_geo() && !f.is_vector())
+    };
+    let collated = |c: &&&Condition, op: Operator| {
+        c.op == op && table.field(c.field.root()).is_some_and(|f| f.collation().is_some())
+    };
+    if let Some(c) = top.iter().filter(indexed).find(|c| is_point(c) || collated(c, Operator::EqI)) {
+        return ("index", Some(c.field.to_string()), "low");
+    }
+    if let Some(c) =
+        top.iter().filter(indexed).find(|c| c.op.is_range() || is_bound(c) || collated(c, Operator::StartsWithI))
+    {
+        return ("index", Some(c.field.to_string()), "medium");
+    }
+    let fulltext = top.iter().find(|c| {
+        c.op == Operator::FullText
+            && c.field.segments.len() == 1
+            && table.field(c.field.root()).is_some_and(|f| f.is_fulltext())
+    });
+    if let Some(c) = fulltext {
+        return ("fulltext", Some(c.field.to_string()), "medium");
+    }
+    let geo = top.iter().find(|c| {
+        c.op.is_geo() && c.field.segments.len() == 1 && table.field(c.field.root()).is_some_and(|f| f.is_geo())
+    });
+    if let Some(c) = geo {
+        return ("geo", Some(c.field.to_string()), "medium");
+    }
+    ("full_scan", None, "high")
+}
+
+/// Equality lookups an index can answer with a point seek. Indexes keep a
+/// null marker for records without the field, so `=null=true` is one too.
+fn is_point(c: &Condition) -> bool {
+    match c.op {
+        Operator::Eq | Operator::StrictEq => !c.value.scalars()[0].is_wildcard(),
+        Operator::In => true,
+        Operator::Null => c.value.scalars()[0].literal == Literal::Bool(true),
+        _ => false,
+    }
+}
+
+fn is_bound(c: &Condition) -> bool {
+    matches!(c.op, Operator::Gt | Operator::Ge | Operator::Lt | Operator::Le)
+}
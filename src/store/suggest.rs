@@ -0,0 +1,246 @@
+//! Typeahead suggestions for a search box.
+//!
+//! A full-text field completes the last word of the prefix from the words
+//! its analyzer indexed, with the words before it matched whole and next to
+//! each other, so `ultra h` suggests `Ultra HD`. An enumerable field, an
+//! `@indexed` `String` such as `category` or `brand.name`, suggests its
+//! values that start with the prefix, compared under its collation or
+//! case-insensitively.
+//!
+//! Before any record is read, the field's [`TextIndex`], or for an
+//! enumerable field the distinct values its index statistics hold, is
+//! checked for something the prefix starts, so a prefix nothing starts
+//! costs no record scan. That holds for a joined field too, whose index
+//! lives on the table the relationship reaches; a field inside an object
+//! has no index and is always scanned.
+//!
+//! Suggestions count the records matching the filter that hold them, most
+//! first, and carry the span of the suggestion the prefix matched.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use serde::Serialize;
+
+use super::analysis::{analyze, query_terms, tokenize};
+use super::collate::fold;
+use super::eval::field_values;
+use super::exec::{ExecError, matching};
+use super::text::Pattern;
+use super::{Store, TextIndex};
+use crate::fiql::{Query, Span, Term};
+use crate::schema::{Analyzer, Collation, FieldDef, ScalarType, Schema, TypeDef};
+
+/// Suggestions returned when the request gives no `limit`.
+pub const DEFAULT_SUGGESTIONS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub text: String,
+    /// Matching records holding the suggestion.
+    pub count: usize,
+    /// Byte range of `text` the prefix matched.
+    pub span: Span,
+}
+
+/// Where a field's suggestions come from.
+enum Source {
+    /// The words of a full-text field.
+    Words(Analyzer),
+    /// The values of an enumerable field.
+    Values(Collation),
+}
+
+fn source(field: &FieldDef) -> Option<Source> {
+    if field.is_fulltext() {
+        return Some(Source::Words(field.analyzer()));
+    }
+    let enumerable = field.is_indexed()
+        && field.scalar() == Some(ScalarType::String)
+        && !field.ty.list
+        && field.directive("primaryKey").is_none();
+    enumerable.then(|| Source::Values(field.collation().unwrap_or(Collation::CaseInsensitive)))
+}
+
+/// The fields of `table` with suggestions: its full-text and enumerable
+/// fields, then those of the tables its relationships join, as
+/// `brand.name`.
+pub fn suggestable(schema: &Schema, table: &TypeDef) -> Vec<String> {
+    let mut out: Vec<String> = table.fields.iter().filter(|f| source(f).is_some()).map(|f| f.name.clone()).collect();
+    for field in table.fields.iter().filter(|f| f.relationship().is_some()) {
+        let Some(target) = schema.type_of(field) else { continue };
+        let joined = target.fields.iter().filter(|f| source(f).is_some());
+        out.extend(joined.map(|f| format!("{}.{}", field.name, f.name)));
+    }
+    out
+}
+
+/// Up to `limit` suggestions for `prefix` from the field at `path`, counted
+/// over the records matching `query`; none when the field has no
+/// suggestions.
+pub fn suggest(
+    store: &Store,
+    table: &TypeDef,
+    query: &Query,
+    path: &[String],
+    prefix: &str,
+    limit: usize,
+) -> Result<Vec<Suggestion>, ExecError> {
+    let Some(source) = store.schema().field_at(table, path).and_then(source) else { return Ok(Vec::new()) };
+    let owner = indexed_in(store.schema(), table, path);
+    let field = path.last().expect("`field_at` found a field");
+    let matcher = match source {
+        Source::Words(analyzer) => {
+            let Some(completion) = Completion::new(analyzer, prefix) else { return Ok(Vec::new()) };
+            if let Some(owner) = owner
+                && let Some(index) = store.text_index(&owner.name, field)
+                && !completion.indexed(index)
+            {
+                return Ok(Vec::new());
+            }
+            Matcher::Words(completion)
+        }
+        Source::Values(collation) => {
+            let folded = fold(prefix, collation);
+            if let Some(stats) = owner
+                .and_then(|owner| store.table(&owner.name))
+                .and_then(|t| t.stats.iter().find(|s| &s.field == field))
+                && !stats.any_text(|v| fold(v, collation).starts_with(folded.as_str()))
+            {
+                return Ok(Vec::new());
+            }
+            Matcher::Values { folded, collation, chars: prefix.chars().count() }
+        }
+    };
+
+    // Keyed by the lowercased text, holding the first spelling seen.
+    let mut counted: BTreeMap<String, Suggestion> = BTreeMap::new();
+    for record in matching(store, table, query)? {
+        let mut seen = BTreeSet::new();
+        for text in field_values(store, table, record, path).into_iter().filter_map(|v| v.as_str()) {
+            for (text, span) in matcher.candidates(text) {
+                let key = text.to_lowercase();
+                if seen.insert(key.clone()) {
+                    counted.entry(key).or_insert(Suggestion { text, count: 0, span }).count += 1;
+                }
+            }
+        }
+    }
+    let mut ranked: Vec<Suggestion> = counted.into_values().collect();
+    // Stable, so ties stay in text order.
+    ranked.sort_by_key(|s| Reverse(s.count));
+    ranked.truncate(limit);
+    Ok(ranked)
+}
+
+/// The table whose indexes cover the field at `path`: `table`, or the one
+/// its relationships lead to. `None` when the path enters an object.
+fn indexed_in<'a>(schema: &'a Schema, table: &'a TypeDef, path: &[String]) -> Option<&'a TypeDef> {
+    let (_, parents) = path.split_last()?;
+    let mut owner = table;
+    for segment in parents {
+        let field = owner.field(segment)?;
+        field.relationship()?;
+        owner = schema.table(&field.ty.name)?;
+    }
+    Some(owner)
+}
+
+/// What a text suggests for the prefix.
+enum Matcher {
+    Words(Completion),
+    /// The whole value, when its folded form starts with the folded prefix,
+    /// which is `chars` characters long.
+    Values {
+        folded: String,
+        collation: Collation,
+        chars: usize,
+    },
+}
+
+impl Matcher {
+    fn candidates(&self, text: &str) -> Vec<(String, Span)> {
+        match self {
+            Matcher::Words(completion) => completion.complete(text),
+            Matcher::Values { folded, collation, chars } => {
+                if !fold(text, *collation).starts_with(folded.as_str()) {
+                    return Vec::new();
+                }
+                let end = text.char_indices().nth(*chars).map_or(text.len(), |(i, _)| i);
+                vec![(text.to_string(), Span::new(0, end))]
+            }
+        }
+    }
+}
+
+/// A prefix split by a full-text field's analyzer: the terms of its whole
+/// words and, unless it ends in a space, the last word as a prefix.
+struct Completion {
+    analyzer: Analyzer,
+    terms: Vec<String>,
+    prefix: bool,
+    pattern: Pattern,
+    /// The first and last words as typed, to find in the text.
+    first: String,
+    last: String,
+}
+
+impl Completion {
+    fn new(analyzer: Analyzer, prefix: &str) -> Option<Completion> {
+        let typed = tokenize(prefix);
+        let open = !prefix.ends_with(char::is_whitespace);
+        let (terms, open) = if analyzer == Analyzer::Keyword {
+            (query_terms(analyzer, &Term::Prefix(prefix.to_string())).0, true)
+        } else {
+            let (whole, last) = match typed.split_last() {
+                Some((last, whole)) if open => (whole, Some(last)),
+                _ => (typed.as_slice(), None),
+            };
+            let mut terms = query_terms(analyzer, &Term::Phrase(whole.join(" "))).0;
+            if let Some(last) = last {
+                terms.extend(query_terms(analyzer, &Term::Prefix(last.clone())).0);
+            }
+            (terms, last.is_some())
+        };
+        let pattern = Pattern::new(terms.clone(), open)?;
+        let (first, last) = match analyzer {
+            Analyzer::Keyword => (prefix.trim().to_lowercase(), prefix.trim().to_lowercase()),
+            _ => (typed.first()?.clone(), typed.last()?.clone()),
+        };
+        Some(Completion { analyzer, terms, prefix: open, pattern, first, last })
+    }
+
+    /// Whether the index holds the last term, or a term it starts.
+    fn indexed(&self, index: &TextIndex) -> bool {
+        let last = self.terms.last().expect("a pattern has terms");
+        if self.prefix { index.prefix_frequency(last) > 0 } else { index.frequency(last) > 0 }
+    }
+
+    /// Each run of whole words in `text` the prefix matches, with the span
+    /// of the run the typed words cover.
+    fn complete(&self, text: &str) -> Vec<(String, Span)> {
+        let tokens = analyze(self.analyzer, text);
+        self.pattern
+            .occurrences(&tokens)
+            .into_iter()
+            .map(|(run, _)| {
+                let (first, last) = (tokens[run.start].span.clone(), tokens[run.end - 1].span.clone());
+                let start = locate(&text[first.clone()], &self.first).map_or(0, |m| m.start);
+                let end = locate(&text[last.clone()], &self.last).map_or(last.len(), |m| m.end);
+                (text[first.start..last.end].to_string(), Span::new(start, last.start - first.start + end))
+            })
+            .collect()
+    }
+}
+
+/// Where `needle`, lowercased, occurs in `word`; `None` when it does not or
+/// lowercasing moves the byte offsets.
+fn locate(word: &str, needle: &str) -> Option<Range<usize>> {
+    let lower = word.to_lowercase();
+    if lower.len() != word.len() {
+        return None;
+    }
+    let start = lower.find(needle)?;
+    Some(start..start + needle.len())
+}
@@ -0,0 +1,383 @@
+//! Full-text indexes and relevance.
+//!
+//! Every `@indexed(type: "fulltext")` field has a [`TextIndex`] counting how
+//! many records hold each term and how long their texts are. That is what
+//! BM25 needs to weigh a match: a term few records hold counts for more
+//! than a common one, and a match in a short text for more than one in a
+//! long text. The store updates the index on every write.
+//!
+//! [`Relevance`] scores a record against the `=ft=` conditions of a query,
+//! for `_score`, and marks the words they matched, for `_highlight`.
+//! `_text=ft=...` searches every full-text field of the table at once, and
+//! a field's `boost:` scales what its matches add to the score.
+//!
+//! Both sides go through the field's [`Analyzer`]: the index counts the
+//! terms of each word position, and a search matches when its terms occur
+//! at consecutive positions.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde_json::{Map, Value as Json};
+
+use super::Store;
+use super::analysis::{Token, analyze, query_terms};
+use crate::fiql::{Expr, Operator, Query, Search, Term};
+use crate::schema::{Analyzer, FieldDef, TypeDef};
+
+/// The virtual field holding a record's relevance to the query's `=ft=`
+/// conditions, for `sort=` and `select=`.
+pub const SCORE: &str = "_score";
+
+/// The virtual field holding the matched text of each searched field, for
+/// `select=`.
+pub const HIGHLIGHT: &str = "_highlight";
+
+/// The virtual field standing for every full-text field of a table in an
+/// `=ft=` condition.
+pub const ALL_TEXT: &str = "_text";
+
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 length normalization.
+const B: f64 = 0.75;
+
+/// Texts longer than this many bytes are cut to a snippet around the first
+/// match in `_highlight`.
+pub const SNIPPET_LEN: usize = 160;
+
+/// Term statistics of one full-text field.
+#[derive(Debug, Clone)]
+pub struct TextIndex {
+    pub field: String,
+    /// What a match in this field is worth against one in another.
+    pub boost: f64,
+    pub analyzer: Analyzer,
+    /// How many records hold each term.
+    terms: BTreeMap<String, usize>,
+    records: usize,
+    /// Word positions in every record's text together.
+    length: usize,
+}
+
+impl TextIndex {
+    pub fn new(field: &str, boost: f64, analyzer: Analyzer) -> Self {
+        TextIndex { field: field.to_string(), boost, analyzer, terms: BTreeMap::new(), records: 0, length: 0 }
+    }
+
+    /// Records with text in the field.
+    pub fn len(&self) -> usize {
+        self.records
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+
+    /// Records whose text holds `term`.
+    pub fn frequency(&self, term: &str) -> usize {
+        self.terms.get(term).copied().unwrap_or(0)
+    }
+
+    /// Records holding a term that starts with `prefix`. A record holding
+    /// two such terms counts twice, so this is an upper bound.
+    pub fn prefix_frequency(&self, prefix: &str) -> usize {
+        self.terms.range(prefix.to_string()..).take_while(|(t, _)| t.starts_with(prefix)).map(|(_, n)| n).sum()
+    }
+
+    fn average_length(&self) -> f64 {
+        if self.records == 0 { 0.0 } else { self.length as f64 / self.records as f64 }
+    }
+
+    /// BM25 inverse document frequency, which stays positive for a term
+    /// every record holds.
+    fn idf(&self, term: &str) -> f64 {
+        let n = self.frequency(term) as f64;
+        (1.0 + (self.records as f64 - n + 0.5) / (n + 0.5)).ln()
+    }
+
+    pub(crate) fn insert(&mut self, record: &Json) {
+        self.count(record, true);
+    }
+
+    pub(crate) fn remove(&mut self, record: &Json) {
+        self.count(record, false);
+    }
+
+    fn count(&mut self, record: &Json, add: bool) {
+        let Some(text) = record.get(&self.field).and_then(Json::as_str) else { return };
+        let tokens = analyze(self.analyzer, text);
+        let mut distinct: Vec<&String> = tokens.iter().flat_map(|t| &t.terms).collect();
+        distinct.sort();
+        distinct.dedup();
+        let step = |n: &mut usize, by: usize| *n = if add { *n + by } else { n.saturating_sub(by) };
+        step(&mut self.records, 1);
+        step(&mut self.length, tokens.len());
+        for term in distinct {
+            let count = self.terms.entry(term.clone()).or_default();
+            step(count, 1);
+            if *count == 0 {
+                self.terms.remove(term);
+            }
+        }
+    }
+}
+
+/// An `=ft=` search with its terms split by each analyzer of the fields it
+/// searches.
+#[derive(Debug, Clone)]
+pub(crate) struct Analyzed {
+    by: Vec<(Analyzer, Patterns)>,
+}
+
+/// A search's terms under one analyzer. A term is `None` when it has no
+/// terms in it, such as `"!!"` or a stopword, and neither requires nor
+/// excludes anything.
+#[derive(Debug, Clone)]
+struct Patterns {
+    required: Vec<Vec<Option<Pattern>>>,
+    excluded: Vec<Option<Pattern>>,
+}
+
+/// Terms that must occur at consecutive word positions, the last one only
+/// as a prefix when `prefix` is set. A single word is a phrase of one.
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    terms: Vec<String>,
+    prefix: bool,
+}
+
+impl Pattern {
+    pub(crate) fn new(terms: Vec<String>, prefix: bool) -> Option<Pattern> {
+        (!terms.is_empty()).then_some(Pattern { terms, prefix })
+    }
+
+    fn of(term: &Term, analyzer: Analyzer) -> Option<Pattern> {
+        let (terms, prefix) = query_terms(analyzer, term);
+        Pattern::new(terms, prefix)
+    }
+
+    /// The token ranges where the pattern occurs in `tokens`, each with
+    /// the terms it matched there.
+    pub(crate) fn occurrences<'t>(&self, tokens: &'t [Token]) -> Vec<(Range<usize>, Vec<&'t str>)> {
+        let n = self.terms.len();
+        let last = n - 1;
+        (0..(tokens.len() + 1).saturating_sub(n))
+            .filter_map(|start| {
+                let run = self.terms.iter().enumerate().map(|(i, want)| {
+                    let held = tokens[start + i].terms.iter();
+                    let mut held =
+                        held.filter(|t| if self.prefix && i == last { t.starts_with(want) } else { *t == want });
+                    held.next().map(String::as_str)
+                });
+                Some((start..start + n, run.collect::<Option<Vec<_>>>()?))
+            })
+            .collect()
+    }
+
+    fn occurs(&self, tokens: &[Token]) -> bool {
+        !self.occurrences(tokens).is_empty()
+    }
+}
+
+impl Analyzed {
+    /// `search` as the given analyzers split it. A group of alternatives
+    /// none of them finds a term in is dropped.
+    pub(crate) fn new(search: &Search, analyzers: &[Analyzer]) -> Analyzed {
+        let mut by: Vec<(Analyzer, Patterns)> = Vec::new();
+        let unique = analyzers.iter().enumerate().filter(|(i, a)| !analyzers[..*i].contains(a)).map(|(_, a)| *a);
+        by.extend(unique.map(|a| {
+            let group = |g: &Vec<Term>| g.iter().map(|t| Pattern::of(t, a)).collect();
+            let excluded = search.excluded.iter().map(|t| Pattern::of(t, a)).collect();
+            (a, Patterns { required: search.required.iter().map(group).collect(), excluded })
+        }));
+        let empty: Vec<bool> = (0..search.required.len())
+            .map(|g| by.iter().all(|(_, p)| p.required[g].iter().all(Option::is_none)))
+            .collect();
+        for (_, patterns) in &mut by {
+            let mut empty = empty.iter();
+            patterns.required.retain(|_| !empty.next().expect("one flag per group"));
+        }
+        Analyzed { by }
+    }
+
+    fn patterns(&self, analyzer: Analyzer) -> Option<&Patterns> {
+        self.by.iter().find(|(a, _)| *a == analyzer).map(|(_, p)| p)
+    }
+
+    /// Whether a record whose searched texts split into `texts` matches:
+    /// every required group occurs in one of them and no excluded term in
+    /// any.
+    pub(crate) fn matches(&self, texts: &[(Analyzer, Vec<Token>)]) -> bool {
+        let Some((_, shape)) = self.by.first() else { return false };
+        // The `k`th term of required group `g`, or of the excluded terms.
+        let occurs = |g: Option<usize>, k: usize| {
+            texts.iter().any(|(analyzer, tokens)| {
+                let Some(patterns) = self.patterns(*analyzer) else { return false };
+                let pattern = match g {
+                    Some(g) => &patterns.required[g][k],
+                    None => &patterns.excluded[k],
+                };
+                pattern.as_ref().is_some_and(|p| p.occurs(tokens))
+            })
+        };
+        let required = shape.required.iter().enumerate().all(|(g, group)| (0..group.len()).any(|k| occurs(Some(g), k)));
+        required && !(0..shape.excluded.len()).any(|k| occurs(None, k))
+    }
+
+    /// BM25 of one text against the required terms. Each distinct run of
+    /// terms a pattern matched is scored as one term, with the IDF of its
+    /// terms summed, so a phrase is worth more than its rarest word.
+    fn score(&self, index: &TextIndex, tokens: &[Token]) -> f64 {
+        let Some(patterns) = self.patterns(index.analyzer) else { return 0.0 };
+        let average = index.average_length();
+        let norm = if average > 0.0 { K1 * (1.0 - B + B * tokens.len() as f64 / average) } else { K1 };
+        let mut runs: BTreeMap<Vec<&str>, usize> = BTreeMap::new();
+        for pattern in patterns.required.iter().flatten().flatten() {
+            for (_, run) in pattern.occurrences(tokens) {
+                *runs.entry(run).or_default() += 1;
+            }
+        }
+        runs.into_iter()
+            .map(|(run, tf)| {
+                let idf: f64 = run.iter().map(|t| index.idf(t)).sum();
+                let tf = tf as f64;
+                idf * tf * (K1 + 1.0) / (tf + norm)
+            })
+            .sum()
+    }
+
+    /// Token ranges of every required term in `tokens`.
+    fn marks(&self, analyzer: Analyzer, tokens: &[Token]) -> Vec<Range<usize>> {
+        let Some(patterns) = self.patterns(analyzer) else { return Vec::new() };
+        patterns.required.iter().flatten().flatten().flat_map(|p| p.occurrences(tokens)).map(|(r, _)| r).collect()
+    }
+}
+
+/// The `=ft=` conditions of a query that make up its `_score`: every one
+/// not under a `!`.
+#[derive(Debug, Clone, Default)]
+pub struct Relevance {
+    clauses: Vec<(Vec<String>, Analyzed)>,
+}
+
+impl Relevance {
+    pub fn of(query: &Query) -> Relevance {
+        fn walk(expr: &Expr, out: &mut Vec<(Vec<String>, Analyzed)>) {
+            match expr {
+                Expr::Condition(c) if c.op == Operator::FullText => {
+                    let scalar = &c.value.scalars()[0];
+                    if let Ok(search) = Search::parse(&scalar.raw, c.span) {
+                        out.push((c.field.segments.clone(), Analyzed::new(&search, &Analyzer::ALL)));
+                    }
+                }
+                Expr::Condition(_) | Expr::Not(_) => {}
+                Expr::And(children) | Expr::Or(children) => children.iter().for_each(|c| walk(c, out)),
+            }
+        }
+        let mut clauses = Vec::new();
+        if let Some(filter) = &query.filter {
+            walk(filter, &mut clauses);
+        }
+        Relevance { clauses }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    /// BM25 of `record` summed over the `=ft=` conditions and the fields
+    /// each searches, every field's share times its boost. Rounded to six
+    /// decimals so a cursor holding it compares equal to a fresh score.
+    pub fn score(&self, store: &Store, table: &TypeDef, record: &Json) -> f64 {
+        let mut score = 0.0;
+        for (path, search) in &self.clauses {
+            for field in searched_fields(table, path) {
+                let Some(index) = store.text_index(&table.name, &field.name) else { continue };
+                let Some(text) = record.get(&field.name).and_then(Json::as_str) else { continue };
+                score += index.boost * search.score(index, &analyze(index.analyzer, text));
+            }
+        }
+        (score * 1e6).round() / 1e6
+    }
+
+    /// Each searched field whose text a condition matched, with the
+    /// matched words wrapped in `<em>`, cut to a snippet when long.
+    pub fn highlight(&self, table: &TypeDef, record: &Json) -> Json {
+        let mut marked: BTreeMap<usize, Vec<Range<usize>>> = BTreeMap::new();
+        for (path, search) in &self.clauses {
+            for field in searched_fields(table, path) {
+                let Some(text) = record.get(&field.name).and_then(Json::as_str) else { continue };
+                let tokens = analyze(field.analyzer(), text);
+                let marks = search.marks(field.analyzer(), &tokens);
+                let spans = marks.into_iter().map(|r| tokens[r.start].span.start..tokens[r.end - 1].span.end);
+                let position = table.fields.iter().position(|f| f.name == field.name).unwrap_or_default();
+                marked.entry(position).or_default().extend(spans);
+            }
+        }
+        let mut out = Map::new();
+        for (position, marks) in marked.into_iter().filter(|(_, marks)| !marks.is_empty()) {
+            let field = &table.fields[position];
+            if let Some(text) = record.get(&field.name).and_then(Json::as_str) {
+                out.insert(field.name.clone(), Json::String(snippet(text, marks)));
+            }
+        }
+        Json::Object(out)
+    }
+}
+
+/// The full-text fields an `=ft=` on `path` searches: all of them for
+/// `_text`, otherwise the top-level field itself.
+pub(crate) fn searched_fields<'a>(table: &'a TypeDef, path: &[String]) -> Vec<&'a FieldDef> {
+    match path {
+        [name] if name == ALL_TEXT => table.fields.iter().filter(|f| f.is_fulltext()).collect(),
+        [name] => table.field(name).filter(|f| f.is_fulltext()).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `text` with `<em>` around each marked byte range. A text longer than
+/// [`SNIPPET_LEN`] is cut at word boundaries to a window that starts a
+/// little before the first mark, with `…` where it was cut.
+fn snippet(text: &str, mut marks: Vec<Range<usize>>) -> String {
+    // A phrase wins over a word it starts with.
+    marks.sort_by_key(|m| (m.start, Reverse(m.end)));
+    let (mut start, mut end) = (0, text.len());
+    if text.len() > SNIPPET_LEN {
+        start = marks[0].start.saturating_sub(SNIPPET_LEN / 4);
+        while !text.is_char_boundary(start) {
+            start -= 1;
+        }
+        if start > 0 {
+            start = text[start..marks[0].start].find(char::is_whitespace).map_or(marks[0].start, |i| start + i + 1);
+        }
+        end = (start + SNIPPET_LEN).min(text.len());
+        while !text.is_char_boundary(end) {
+            end += 1;
+        }
+        if end < text.len() {
+            end = text[start..end].rfind(char::is_whitespace).map_or(end, |i| start + i);
+        }
+    }
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    let mut at = start;
+    for mark in marks {
+        if mark.start < at || mark.end > end {
+            continue;
+        }
+        out.push_str(&text[at..mark.start]);
+        out.push_str("<em>");
+        out.push_str(&text[mark.clone()]);
+        out.push_str("</em>");
+        at = mark.end;
+    }
+    out.push_str(&text[at..end]);
+    if end < text.len() {
+        out.push('…');
+    }
+    out
+}
@@ -0,0 +1,245 @@
+//! Vector indexes for `@embedding` fields: approximate nearest neighbours
+//! through a hierarchical navigable small world (HNSW) graph.
+//!
+//! Every record gets a node on layer 0 and, with falling probability, on
+//! the layers above; on each layer a node links to its nearest neighbours.
+//! A search walks greedily down from the entry point on the top layer, then
+//! keeps the `ef` best candidates it meets on layer 0. A node's layer comes
+//! from a hash of its primary key rather than a random draw, so the same
+//! writes always build the same graph. Distance is cosine distance,
+//! `1 - a·b` over unit vectors.
+//!
+//! Rewriting or deleting a record retires its node: retired nodes still
+//! route searches but are never returned.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap};
+
+use super::embed::fnv1a;
+
+/// Links per node on the upper layers; layer 0 keeps twice as many.
+const M: usize = 8;
+
+/// Candidates kept while linking a new node.
+const EF_CONSTRUCTION: usize = 32;
+
+/// Candidates kept by a search, at least.
+const EF_SEARCH: usize = 32;
+
+/// The vectors of one `@embedding` field, by primary key.
+#[derive(Debug, Clone)]
+pub struct VectorIndex {
+    /// The `@embedding` field.
+    pub field: String,
+    /// The field whose text is embedded.
+    pub source: String,
+    pub dimensions: usize,
+    nodes: Vec<Node>,
+    /// The current node of each record.
+    live: BTreeMap<String, usize>,
+    /// The node on the highest layer, where searches start.
+    entry: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    id: String,
+    vector: Vec<f32>,
+    /// Neighbours on each layer the node is on, from layer 0 up.
+    links: Vec<Vec<usize>>,
+    retired: bool,
+}
+
+/// A node and its distance from the vector being searched for, ordered
+/// nearest first with ties broken by insertion order.
+#[derive(Debug, Clone, Copy)]
+struct Scored(f32, usize);
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+impl VectorIndex {
+    pub fn new(field: &str, source: &str, dimensions: usize) -> Self {
+        VectorIndex {
+            field: field.to_string(),
+            source: source.to_string(),
+            dimensions,
+            nodes: Vec::new(),
+            live: BTreeMap::new(),
+            entry: None,
+        }
+    }
+
+    /// Records with a vector.
+    pub fn len(&self) -> usize {
+        self.live.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live.is_empty()
+    }
+
+    /// The vector stored for record `id`.
+    pub fn get(&self, id: &str) -> Option<&[f32]> {
+        self.live.get(id).map(|&n| self.nodes[n].vector.as_slice())
+    }
+
+    /// Stores `vector` for record `id`, replacing the one it had.
+    pub fn insert(&mut self, id: &str, vector: Vec<f32>) {
+        self.remove(id);
+        let index = self.nodes.len();
+        let level = level_of(id);
+        self.nodes.push(Node { id: id.to_string(), vector, links: vec![Vec::new(); level + 1], retired: false });
+        self.live.insert(id.to_string(), index);
+        let Some(mut entry) = self.entry else {
+            self.entry = Some(index);
+            return;
+        };
+        let top = self.nodes[entry].links.len() - 1;
+        let query = self.nodes[index].vector.clone();
+        for layer in (level + 1..=top).rev() {
+            entry = self.search_layer(&query, &[entry], 1, layer)[0].1;
+        }
+        let mut entries = vec![entry];
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, &entries, EF_CONSTRUCTION, layer);
+            let neighbours: Vec<usize> = found.iter().take(M).map(|s| s.1).collect();
+            for &neighbour in &neighbours {
+                self.nodes[neighbour].links[layer].push(index);
+                self.prune(neighbour, layer);
+            }
+            self.nodes[index].links[layer] = neighbours;
+            entries = found.iter().map(|s| s.1).collect();
+        }
+        if level > top {
+            self.entry = Some(index);
+        }
+    }
+
+    /// Retires the vector of record `id`, if it has one.
+    pub fn remove(&mut self, id: &str) {
+        if let Some(node) = self.live.remove(id) {
+            self.nodes[node].retired = true;
+        }
+    }
+
+    /// Up to `k` records nearest `query` that pass `keep`, nearest first,
+    /// with their distances. The search widens until it finds `k` or has
+    /// looked at every record, so a filter that rejects most records still
+    /// gets its `k`.
+    pub fn nearest(&self, query: &[f32], k: usize, keep: impl Fn(&str) -> bool) -> Vec<(&str, f32)> {
+        let mut ef = k.max(EF_SEARCH);
+        loop {
+            let exhaustive = ef >= self.nodes.len();
+            let found = if exhaustive { self.scan(query) } else { self.search(query, ef) };
+            let hits: Vec<(&str, f32)> = found
+                .into_iter()
+                .filter(|s| !self.nodes[s.1].retired)
+                .map(|s| (self.nodes[s.1].id.as_str(), s.0))
+                .filter(|(id, _)| keep(id))
+                .take(k)
+                .collect();
+            if hits.len() == k || exhaustive {
+                return hits;
+            }
+            ef *= 4;
+        }
+    }
+
+    /// The `ef` nodes nearest `query` the graph leads to.
+    fn search(&self, query: &[f32], ef: usize) -> Vec<Scored> {
+        let Some(mut entry) = self.entry else { return Vec::new() };
+        for layer in (1..self.nodes[entry].links.len()).rev() {
+            entry = self.search_layer(query, &[entry], 1, layer)[0].1;
+        }
+        self.search_layer(query, &[entry], ef, 0)
+    }
+
+    /// Every node by distance from `query`.
+    fn scan(&self, query: &[f32]) -> Vec<Scored> {
+        let mut all: Vec<Scored> = (0..self.nodes.len()).map(|n| Scored(self.distance(query, n), n)).collect();
+        all.sort();
+        all
+    }
+
+    /// Best-first search of one layer from `entries`, keeping the `ef`
+    /// nearest nodes seen, nearest first.
+    fn search_layer(&self, query: &[f32], entries: &[usize], ef: usize, layer: usize) -> Vec<Scored> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut candidates = BinaryHeap::new();
+        let mut found = BinaryHeap::new();
+        for &entry in entries {
+            visited[entry] = true;
+            let scored = Scored(self.distance(query, entry), entry);
+            candidates.push(Reverse(scored));
+            found.push(scored);
+        }
+        while let Some(Reverse(Scored(distance, node))) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|worst: &Scored| distance > worst.0) {
+                break;
+            }
+            for &next in self.nodes[node].links.get(layer).into_iter().flatten() {
+                if std::mem::replace(&mut visited[next], true) {
+                    continue;
+                }
+                let scored = Scored(self.distance(query, next), next);
+                if found.len() < ef || found.peek().is_some_and(|worst| scored < *worst) {
+                    candidates.push(Reverse(scored));
+                    found.push(scored);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    /// Keeps only the nearest links of `node` on `layer` once it has more
+    /// than the layer allows.
+    fn prune(&mut self, node: usize, layer: usize) {
+        let limit = if layer == 0 { 2 * M } else { M };
+        if self.nodes[node].links[layer].len() <= limit {
+            return;
+        }
+        let origin = &self.nodes[node].vector;
+        let mut scored: Vec<Scored> =
+            self.nodes[node].links[layer].iter().map(|&n| Scored(self.distance(origin, n), n)).collect();
+        scored.sort();
+        self.nodes[node].links[layer] = scored.into_iter().take(limit).map(|s| s.1).collect();
+    }
+
+    fn distance(&self, query: &[f32], node: usize) -> f32 {
+        distance(query, &self.nodes[node].vector)
+    }
+}
+
+/// Cosine distance between unit vectors: 0 for the same direction, 1 for
+/// unrelated and 2 for opposite ones.
+pub(crate) fn distance(a: &[f32], b: &[f32]) -> f32 {
+    1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>()
+}
+
+/// The top layer of the node for `id`: layer `l` or above with probability
+/// `M^-l`, drawn from a hash of the key.
+fn level_of(id: &str) -> usize {
+    let unit = ((fnv1a(id.as_bytes()) >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+    ((-unit.ln() / (M as f64).ln()) as usize).min(16)
+}
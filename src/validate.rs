@@ -0,0 +1,508 @@
+//! Checks a parsed query against the table schema.
+//!
+//! The platform answers a misspelled field or an impossible comparison with
+//! an empty list; [`validate`] reports those as [`Diagnostic`]s pointing at
+//! the offending bytes, with a "did you mean" suggestion where one is close.
+
+use serde::Serialize;
+
+use crate::fiql::{Condition, FieldPath, Literal, Operator, PathQuery, SelectField, Span, Time, TypePrefix};
+use crate::schema::{FieldDef, ScalarType, Schema, TypeDef};
+use crate::store::{ALL_TEXT, HIGHLIGHT, SCORE, SortOrder};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(code: &'static str, message: String, span: Span) -> Self {
+        Diagnostic { severity: Severity::Error, code, message, span, suggestion: None }
+    }
+
+    fn warning(code: &'static str, message: String, span: Span) -> Self {
+        Diagnostic { severity: Severity::Warning, code, message, span, suggestion: None }
+    }
+
+    fn suggest(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+}
+
+/// Validates every condition, projection and sort key of `parsed`.
+pub fn validate(schema: &Schema, parsed: &PathQuery) -> Vec<Diagnostic> {
+    let Some(table) = schema.table(&parsed.table) else {
+        let suggestion = closest(&parsed.table, schema.table_names());
+        return vec![
+            Diagnostic::error("unknown-table", format!("unknown table `{}`", parsed.table), parsed.table_span)
+                .suggest(suggestion),
+        ];
+    };
+    let mut out = Vec::new();
+    let checker = Checker { schema, table };
+    if let Some(filter) = &parsed.query.filter {
+        for condition in filter.conditions() {
+            checker.condition(condition, &mut out);
+        }
+    }
+    if let Some(select) = &parsed.query.controls.select {
+        checker.select(table, select, &mut out);
+    }
+    let order = SortOrder::of(&parsed.query);
+    for (i, key) in order.keys.iter().enumerate() {
+        if key.field.segments == [SCORE] {
+            if order.origin(i).is_none() {
+                out.push(Diagnostic::warning(
+                    "sort-without-origin",
+                    format!("sorting by `{SCORE}` ranks by relevance to `=ft=` conditions, and the filter has none"),
+                    key.field.span,
+                ));
+            }
+            continue;
+        }
+        match checker.resolve(&key.field) {
+            Ok(Resolved::Relationship(field, _)) => out.push(Diagnostic::error(
+                "invalid-sort",
+                format!("cannot sort by relationship `{}`; sort by one of its fields", field.name),
+                key.field.span,
+            )),
+            Ok(Resolved::Scalar(field, ScalarType::GeoPoint)) if order.origin(i).is_none() => {
+                out.push(Diagnostic::warning(
+                    "sort-without-origin",
+                    format!(
+                        "sorting by `{}` orders by distance from a `{}=near=` point, and the filter has none",
+                        field.name, key.field
+                    ),
+                    key.field.span,
+                ))
+            }
+            Ok(Resolved::Scalar(field, _)) if field.embedding().is_some() && order.origin(i).is_none() => {
+                out.push(Diagnostic::warning(
+                    "sort-without-origin",
+                    format!(
+                        "sorting by `{}` orders by similarity to a `{}=sim=` text, and the filter has none",
+                        field.name, key.field
+                    ),
+                    key.field.span,
+                ))
+            }
+            Ok(_) => {}
+            Err(d) => out.push(d),
+        }
+    }
+    out
+}
+
+/// What a field path points at.
+pub(crate) enum Resolved<'s> {
+    Scalar(&'s FieldDef, ScalarType),
+    /// Inside a `String` field that stores JSON (`manufacturer.country`);
+    /// the schema says nothing about its type.
+    Json,
+    Relationship(&'s FieldDef, &'s TypeDef),
+    /// A field holding objects of a type without `@table`, such as
+    /// `variants: [Variant]`.
+    Object(&'s FieldDef, &'s TypeDef),
+}
+
+struct Checker<'s> {
+    schema: &'s Schema,
+    table: &'s TypeDef,
+}
+
+impl<'s> Checker<'s> {
+    fn resolve(&self, path: &FieldPath) -> Result<Resolved<'s>, Diagnostic> {
+        resolve(self.schema, self.table, path)
+    }
+
+    fn condition(&self, c: &Condition, out: &mut Vec<Diagnostic>) {
+        if c.field.segments == [ALL_TEXT] {
+            let fulltext = fulltext_fields(self.table);
+            if c.op != Operator::FullText {
+                out.push(Diagnostic::error(
+                    "operator-not-supported",
+                    format!("`{ALL_TEXT}` stands for every fulltext field and only takes `=ft=`"),
+                    c.op_span,
+                ));
+            } else if fulltext.is_empty() {
+                out.push(Diagnostic::error(
+                    "operator-not-supported",
+                    format!("`{}` has no fulltext fields to search", self.table.name),
+                    c.field.span,
+                ));
+            }
+            return;
+        }
+        let resolved = match self.resolve(&c.field) {
+            Ok(r) => r,
+            Err(d) => return out.push(d),
+        };
+        if let Some(d) = self.elements(c, &resolved, out) {
+            return out.push(d);
+        }
+        match resolved {
+            Resolved::Relationship(..) | Resolved::Object(..) if c.op.is_quantifier() || is_size(c.op) => {}
+            Resolved::Relationship(field, _) if c.op == Operator::Empty && !field.ty.list => {
+                out.push(Diagnostic::error(
+                    "operator-not-supported",
+                    format!("`{}` joins one record; use `=null=` to find records without one", field.name),
+                    c.op_span,
+                ));
+            }
+            // `brand=null=true`: products without a brand.
+            Resolved::Relationship(..) if c.op.takes_flag() => {}
+            Resolved::Relationship(field, target) => out.push(
+                Diagnostic::error(
+                    "relationship-without-field",
+                    format!("`{}` is a relationship to {}; filter on one of its fields", field.name, target.name),
+                    c.field.span,
+                )
+                .suggest(target.fields.iter().find(|f| f.name == "name").map(|f| format!("{}.{}", c.field, f.name))),
+            ),
+            Resolved::Object(..) if c.op.takes_flag() => {}
+            Resolved::Object(field, object) => out.push(Diagnostic::error(
+                "operator-not-supported",
+                format!(
+                    "`{}` holds {} objects; match them with a filter such as `{}=any=({}==...)`",
+                    field.name,
+                    object.name,
+                    c.field,
+                    object.fields.first().map_or("field", |f| f.name.as_str())
+                ),
+                c.op_span,
+            )),
+            Resolved::Json if c.op == Operator::FullText => {
+                out.push(not_fulltext(c, fulltext_fields(self.table)));
+            }
+            Resolved::Json if c.op == Operator::Sim => out.push(not_embedded(c, self.table)),
+            Resolved::Json => {}
+            Resolved::Scalar(field, ty) => {
+                let owner = owner_of(self.schema, self.table, &c.field);
+                if let Some(d) = check_operator(c, field, ty, owner) {
+                    out.push(d);
+                } else if !c.op.takes_flag() && !is_size(c.op) && c.op != Operator::Sim {
+                    check_values(c, field, ty, out);
+                }
+            }
+        }
+    }
+
+    /// Checks a quantifier or `=size=` against what the field holds, and an
+    /// element filter against the element type. Returns the diagnostic that
+    /// ends checking of `c`, if any.
+    fn elements(&self, c: &Condition, resolved: &Resolved<'s>, out: &mut Vec<Diagnostic>) -> Option<Diagnostic> {
+        if !c.op.is_quantifier() && !is_size(c.op) {
+            return None;
+        }
+        let unsupported = |why: String| Some(Diagnostic::error("operator-not-supported", why, c.op_span));
+        let (field, element) = match resolved {
+            Resolved::Json => return None,
+            Resolved::Scalar(field, _) => (field, None),
+            Resolved::Relationship(field, target) | Resolved::Object(field, target) => (field, Some(*target)),
+        };
+        if !field.ty.list {
+            return unsupported(format!("`{}` applies to lists but `{}` is {}", c.op, field.name, field.ty));
+        }
+        match (c.value.filter(), element) {
+            (Some(filter), Some(element)) => {
+                let checker = Checker { schema: self.schema, table: element };
+                for inner in filter.conditions() {
+                    checker.condition(inner, out);
+                }
+                None
+            }
+            (Some(_), None) => unsupported(format!(
+                "`{}` holds {} values, which have no fields to filter; list values instead, as in `{}{}a,b`",
+                field.name, field.ty.name, c.field, c.op
+            )),
+            (None, Some(element)) if c.op.is_quantifier() => unsupported(format!(
+                "`{}` holds {} records, which never equal a value; use a filter such as `{}{}(...)`",
+                field.name, element.name, c.field, c.op
+            )),
+            (None, _) => None,
+        }
+    }
+
+    fn select(&self, table: &'s TypeDef, fields: &[SelectField], out: &mut Vec<Diagnostic>) {
+        for selected in fields {
+            if table.name == self.table.name && [SCORE, HIGHLIGHT].contains(&selected.name.as_str()) {
+                continue;
+            }
+            let Some(field) = table.field(&selected.name) else {
+                let suggestion = closest(&selected.name, table.fields.iter().map(|f| f.name.as_str()));
+                out.push(
+                    Diagnostic::error(
+                        "unknown-field",
+                        format!("`{}` has no field `{}`", table.name, selected.name),
+                        selected.span,
+                    )
+                    .suggest(suggestion),
+                );
+                continue;
+            };
+            if selected.children.is_empty() {
+                continue;
+            }
+            if field.relationship().is_some() {
+                if let Some(target) = self.schema.table(&field.ty.name) {
+                    self.select(target, &selected.children, out);
+                }
+            } else if let Some(object) = self.schema.object(&field.ty.name) {
+                self.select(object, &selected.children, out);
+            } else if !matches!(field.scalar(), Some(ScalarType::String | ScalarType::GeoPoint)) {
+                out.push(Diagnostic::error(
+                    "invalid-select",
+                    format!("`{}` is {} and has no nested fields to select", field.name, field.ty),
+                    selected.span,
+                ));
+            }
+        }
+    }
+}
+
+/// Walks `path` from `table`, following relationships and object types and
+/// stopping at JSON string fields.
+pub(crate) fn resolve<'s>(
+    schema: &'s Schema,
+    table: &'s TypeDef,
+    path: &FieldPath,
+) -> Result<Resolved<'s>, Diagnostic> {
+    let mut current = table;
+    let mut offset = path.span.start;
+    for (i, segment) in path.segments.iter().enumerate() {
+        let span = Span::new(offset, offset + segment.len());
+        offset = span.end + 1;
+        let last = i + 1 == path.segments.len();
+        let Some(field) = current.field(segment) else {
+            let suggestion = closest(segment, current.fields.iter().map(|f| f.name.as_str()));
+            return Err(Diagnostic::error(
+                "unknown-field",
+                format!("`{}` has no field `{segment}`", current.name),
+                span,
+            )
+            .suggest(suggestion));
+        };
+        if field.relationship().is_some() {
+            let Some(target) = schema.table(&field.ty.name) else {
+                return Err(Diagnostic::error(
+                    "unknown-table",
+                    format!("relationship `{segment}` points at unknown table `{}`", field.ty.name),
+                    span,
+                ));
+            };
+            if last {
+                return Ok(Resolved::Relationship(field, target));
+            }
+            current = target;
+            continue;
+        }
+        if let Some(object) = schema.object(&field.ty.name) {
+            if last {
+                return Ok(Resolved::Object(field, object));
+            }
+            current = object;
+            continue;
+        }
+        let ty = field.scalar().unwrap_or(ScalarType::String);
+        if last {
+            return Ok(Resolved::Scalar(field, ty));
+        }
+        if ty == ScalarType::String {
+            return Ok(Resolved::Json);
+        }
+        return Err(Diagnostic::error(
+            "not-nested",
+            format!("`{segment}` is {} and has no nested field `{}`", field.ty, path.segments[i + 1]),
+            Span::new(span.end + 1, path.span.end),
+        ));
+    }
+    unreachable!("field paths have at least one segment")
+}
+
+/// Table that owns the last segment of `path` (the relationship target for
+/// `brand.name`).
+fn owner_of<'s>(schema: &'s Schema, table: &'s TypeDef, path: &FieldPath) -> &'s TypeDef {
+    let mut current = table;
+    for segment in &path.segments[..path.segments.len() - 1] {
+        match current.field(segment).and_then(|f| schema.table(&f.ty.name)) {
+            Some(next) => current = next,
+            None => break,
+        }
+    }
+    current
+}
+
+fn is_size(op: Operator) -> bool {
+    matches!(op, Operator::Size(_))
+}
+
+fn check_operator(c: &Condition, field: &FieldDef, ty: ScalarType, owner: &TypeDef) -> Option<Diagnostic> {
+    let unsupported = |why: String| Some(Diagnostic::error("operator-not-supported", why, c.op_span));
+    match c.op {
+        Operator::Empty if ty != ScalarType::String && !field.ty.list => {
+            unsupported(format!("`=empty=` applies to strings and lists but `{}` is {}", field.name, field.ty))
+        }
+        Operator::Null | Operator::Exists if field.ty.non_null && !c.field.is_nested() => Some(Diagnostic::warning(
+            "never-null",
+            format!("`{}` is {} and always present, so `{}` has a fixed answer", field.name, field.ty, c.op),
+            c.op_span,
+        )),
+        _ if c.op.takes_flag() || is_size(c.op) => None,
+        Operator::Sim if owner.embedding_for(&field.name).is_none() || c.field.is_nested() => {
+            Some(not_embedded(c, owner))
+        }
+        Operator::Sim => None,
+        op if field.embedding().is_some() => {
+            unsupported(format!("`{op}` is not supported on embedding field `{}`; use `=sim=`", field.name))
+        }
+        op if op.is_geo() && ty != ScalarType::GeoPoint => {
+            unsupported(format!("`{op}` needs a GeoPoint field but `{}` is {}", field.name, field.ty))
+        }
+        op if ty == ScalarType::GeoPoint && !op.is_geo() => unsupported(format!(
+            "`{op}` is not supported on GeoPoint field `{}`; use `=near=` or `=within=`",
+            field.name
+        )),
+        Operator::FullText if !field.is_fulltext() => Some(not_fulltext(c, fulltext_fields(owner))),
+        Operator::FullText => None,
+        op if ty == ScalarType::Boolean
+            && !matches!(op, Operator::Eq | Operator::StrictEq | Operator::Ne | Operator::In | Operator::Out) =>
+        {
+            unsupported(format!("`{op}` is not supported on Boolean field `{}`; use `==` or `=ne=`", field.name))
+        }
+        op if ty.is_numeric()
+            && (op.is_case_insensitive()
+                || matches!(op, Operator::Contains | Operator::StartsWith | Operator::EndsWith | Operator::Regex)) =>
+        {
+            unsupported(format!("`{}` is a string operator but `{}` is {}", c.op, field.name, field.ty))
+        }
+        _ => None,
+    }
+}
+
+fn not_fulltext(c: &Condition, fulltext: Vec<&str>) -> Diagnostic {
+    let hint = if fulltext.is_empty() {
+        "this table has no fulltext fields".to_string()
+    } else {
+        format!("`=ft=` works on {}", fulltext.join(", "))
+    };
+    Diagnostic::error("operator-not-supported", format!("`{}` has no fulltext index; {hint}", c.field), c.op_span)
+        .suggest(Some(format!("{}=ct={}", c.field, c.value.scalars()[0].raw)))
+}
+
+fn not_embedded(c: &Condition, table: &TypeDef) -> Diagnostic {
+    let embedded: Vec<&str> =
+        table.fields.iter().filter_map(|f| Some([f.embedding()?.from, f.name.as_str()])).flatten().collect();
+    let hint = if embedded.is_empty() {
+        "this table has no embeddings".to_string()
+    } else {
+        format!("`=sim=` works on {}", embedded.join(", "))
+    };
+    Diagnostic::error("operator-not-supported", format!("`{}` has no embedding; {hint}", c.field), c.op_span)
+}
+
+fn fulltext_fields(table: &TypeDef) -> Vec<&str> {
+    table.fields.iter().filter(|f| f.is_fulltext()).map(|f| f.name.as_str()).collect()
+}
+
+fn check_values(c: &Condition, field: &FieldDef, ty: ScalarType, out: &mut Vec<Diagnostic>) {
+    for scalar in c.value.scalars() {
+        let mismatch = |what: &str| {
+            Diagnostic::error(
+                "type-mismatch",
+                format!("`{}` is not {what}; `{}` is {}", scalar.raw, field.name, field.ty),
+                scalar.span,
+            )
+        };
+        match (&scalar.literal, ty) {
+            (Literal::String(_), _) if scalar.is_wildcard() => {}
+            (Literal::String(_), ScalarType::Int | ScalarType::Float) => out.push(mismatch("a number")),
+            (Literal::Bool(_), ScalarType::Int | ScalarType::Float) => out.push(mismatch("a number")),
+            (Literal::Number(n), ScalarType::Int) if n.fract() != 0.0 => out.push(Diagnostic::warning(
+                "type-mismatch",
+                format!("`{}` is Int and never equals the fraction {n}", field.name),
+                scalar.span,
+            )),
+            (Literal::String(_), ScalarType::Boolean) if scalar.prefix == Some(TypePrefix::String) => {
+                out.push(Diagnostic::warning(
+                    "type-mismatch",
+                    format!("`string:{}` compares as a string, but `{}` stores booleans", scalar.raw, field.name),
+                    scalar.span,
+                ))
+            }
+            (Literal::String(_) | Literal::Number(_), ScalarType::Boolean) => {
+                out.push(mismatch("`true` or `false`").suggest(Some(format!("{}==true", c.field))))
+            }
+            // `createdAt=sw=2024-03` matches the stored text.
+            (Literal::String(_), _) if ty.is_temporal() && compares_text(c.op) => {}
+            (Literal::String(text), _)
+                if ty.is_temporal() && scalar.prefix.is_none() && Time::parse_stored(text).is_none() =>
+            {
+                out.push(mismatch("a date"))
+            }
+            (Literal::Number(_) | Literal::Bool(_), _) if ty.is_temporal() => out.push(mismatch("a date")),
+            (Literal::Time(_), _) if !ty.is_temporal() && scalar.prefix == Some(TypePrefix::Date) => {
+                out.push(Diagnostic::error(
+                    "type-mismatch",
+                    format!("`date:{}` is a date but `{}` is {}", scalar.raw, field.name, field.ty),
+                    scalar.span,
+                ))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Operators that match the text of a value rather than what it means.
+fn compares_text(op: Operator) -> bool {
+    op.is_case_insensitive()
+        || matches!(op, Operator::Contains | Operator::StartsWith | Operator::EndsWith | Operator::Regex)
+}
+
+/// The candidate within edit distance of `name`, if any. Matching ignores
+/// case so `instock` suggests `inStock`.
+pub(crate) fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<String> {
+    let lower = name.to_lowercase();
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .map(|c| (edit_distance(&lower, &c.to_lowercase()), c))
+        .filter(|(d, _)| *d <= limit)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c.to_string())
+}
+
+/// Optimal string alignment distance: Levenshtein plus adjacent transpositions,
+/// so `prcie` is one edit from `price`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
@@ -0,0 +1,58 @@
+//! `/<Table>/aggregate` over the seed data.
+
+mod common;
+
+use common::get;
+use demo_fiql::app::App;
+use serde_json::json;
+
+#[test]
+fn groups_across_the_brand_relationship() {
+    let app = App::seeded();
+    let (status, body) = get(
+        &app,
+        "/Products/aggregate?category==electronics&groupBy=brand.country&metrics=count,avg(price),max(price)",
+    );
+    assert_eq!(status, 200);
+    let jp = body["groups"].as_array().unwrap().iter().find(|g| g["brand.country"] == "JP").unwrap();
+    assert_eq!(jp, &json!({ "brand.country": "JP", "count": 5, "avg(price)": 383.99, "max(price)": 899.99 }));
+    let counted: u64 = body["groups"].as_array().unwrap().iter().map(|g| g["count"].as_u64().unwrap()).sum();
+    assert_eq!(counted, body["total"].as_u64().unwrap());
+}
+
+#[test]
+fn counts_match_the_table_endpoint() {
+    let app = App::seeded();
+    let (_, body) = get(&app, "/Products/aggregate?inStock==true&groupBy=category");
+    for group in body["groups"].as_array().unwrap() {
+        let category = group["category"].as_str().unwrap();
+        let (_, rows) = get(&app, &format!("/Products/?inStock==true&category=={category}"));
+        assert_eq!(group["count"], rows.as_array().unwrap().len(), "{category}");
+    }
+
+    // Without `groupBy` there is one group, even when nothing matches.
+    let (_, body) = get(&app, "/Products/aggregate?price=lt=0&metrics=count,avg(price)");
+    assert_eq!(body["total"], 0);
+    assert_eq!(body["groups"], json!([{ "count": 0, "avg(price)": null }]));
+}
+
+#[test]
+fn rejects_unknown_fields_and_non_numeric_metrics() {
+    let app = App::seeded();
+    let (status, body) = get(&app, "/Products/aggregate?groupBy=brnd.country");
+    assert_eq!(status, 400);
+    assert_eq!(body["diagnostic"]["suggestion"], "brand");
+    let (status, body) = get(&app, "/Products/aggregate?metrics=avg(name)");
+    assert_eq!(status, 400);
+    assert_eq!(body["diagnostic"]["code"], "type-mismatch");
+    let (status, body) = get(&app, "/Products/aggregate?groupBy=inStock,%20brnd.country");
+    assert_eq!(status, 400);
+    assert_eq!(body["diagnostic"]["span"], json!({ "start": 9, "end": 13 }));
+
+    for control in ["select=name", "sort=price", "limit=5", "offset=5", "pagination=true", "stream=true"] {
+        let (status, body) = get(&app, &format!("/Products/aggregate?inStock==true&{control}&groupBy=category"));
+        assert_eq!(status, 400, "{control}");
+        assert!(body["error"].as_str().unwrap().ends_with("does not apply to an aggregate"), "{body}");
+    }
+    assert_eq!(get(&app, "/Products/aggregate?inStock==true&explain=true&groupBy=category").0, 200);
+}
@@ -0,0 +1,112 @@
+//! `analyzer:` on full-text indexes and the `/analyze` resource.
+
+mod common;
+
+use common::{app_with, get, ids};
+use demo_fiql::app::App;
+use demo_fiql::store::stem;
+use serde_json::{Value as Json, json};
+
+const SDL: &str = r#"
+type Item @table {
+    id: ID! @primaryKey
+    plain: String @indexed(type: "fulltext")
+    grams: String @indexed(type: "fulltext", analyzer: "ngram")
+    edges: String @indexed(type: "fulltext", analyzer: "edge_ngram")
+    exact: String @indexed(type: "fulltext", analyzer: "keyword")
+}
+"#;
+
+fn items() -> App {
+    let items = [("i1", "Noise Cancelling Headphones"), ("i2", "Wireless Earbuds"), ("i3", "Headphone Stand")];
+    let record =
+        |(id, text): (&str, &str)| json!({ "id": id, "plain": text, "grams": text, "edges": text, "exact": text });
+    app_with(SDL, "Item", items.map(record))
+}
+
+#[test]
+fn english_stems_drops_stopwords_and_expands_synonyms() {
+    for (word, stemmed) in [
+        ("programs", "program"),
+        ("programming", "program"),
+        ("headphones", "headphone"),
+        ("batteries", "batteri"),
+        ("hopping", "hop"),
+        ("filing", "file"),
+        ("agreed", "agree"),
+        ("glass", "glass"),
+        ("4k", "4k"),
+    ] {
+        assert_eq!(stem(word), stemmed, "{word}");
+    }
+
+    let app = App::seeded();
+    assert_eq!(ids(&app, "/Products/?description=ft=programs"), ["prod-004", "prod-044"]);
+    assert_eq!(ids(&app, "/Products/?name=ft=headphone"), ["prod-020"]);
+    // `headset` shares a synonym group with `headphones`, `charger` with
+    // `charging`.
+    assert_eq!(ids(&app, "/Products/?name=ft=headset"), ["prod-020"]);
+    assert_eq!(ids(&app, "/Products/?description=ft=charger"), ["prod-022", "prod-035"]);
+    // Stopwords are left out of both sides, so a phrase skips over them.
+    assert_eq!(ids(&app, "/Products/?description=ft=%22guide%20kernel%22"), ["prod-044"]);
+    assert!(ids(&app, "/Products/?description=ft=the").len() > 40);
+    // A prefix is never stemmed, and still sees the words as written.
+    assert_eq!(ids(&app, "/Products/?description=ft=programm*"), ["prod-002", "prod-004", "prod-044"]);
+
+    let (_, rows) = get(&app, "/Products/?name=ft=headset&select=_highlight");
+    assert_eq!(rows[0]["_highlight"], json!({ "name": "Noise Cancelling <em>Headphones</em>" }));
+}
+
+#[test]
+fn ngram_edge_ngram_and_keyword_fields_match_their_own_way() {
+    let app = items();
+    assert_eq!(ids(&app, "/Item/?plain=ft=headphone"), ["i3"]);
+    assert_eq!(ids(&app, "/Item/?grams=ft=phone"), ["i1", "i3"]);
+    assert_eq!(ids(&app, "/Item/?grams=ft=%22cancel%20phone%22"), ["i1"]);
+    assert!(ids(&app, "/Item/?grams=ft=ph").is_empty());
+    assert_eq!(ids(&app, "/Item/?edges=ft=head"), ["i1", "i3"]);
+    assert!(ids(&app, "/Item/?edges=ft=phone").is_empty());
+    assert_eq!(ids(&app, "/Item/?exact=ft=%22wireless%20EARBUDS%22"), ["i2"]);
+    assert!(ids(&app, "/Item/?exact=ft=wireless").is_empty());
+    assert_eq!(ids(&app, "/Item/?exact=ft=head*"), ["i3"]);
+    // `_text` splits the search by each field's own analyzer.
+    assert_eq!(ids(&app, "/Item/?_text=ft=phone"), ["i1", "i3"]);
+
+    let (_, rows) = get(&app, "/Item/?grams=ft=phone&sort=id&select=id,_score,_highlight");
+    assert_eq!(rows[0]["_highlight"], json!({ "grams": "Noise Cancelling <em>Headphones</em>" }));
+    assert!(rows[0]["_score"].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn analyze_shows_tokens_terms_and_search() {
+    let app = App::seeded();
+    let (status, body) = get(&app, "/analyze?field=Products.name&text=The%20Headphones");
+    assert_eq!(status, 200);
+    assert_eq!(body["analyzer"], "english");
+    assert_eq!(
+        body["tokens"],
+        json!([{
+            "token": "Headphones",
+            "start": 4,
+            "end": 14,
+            "position": 0,
+            "terms": ["headphones", "headphone", "earphone", "headset"],
+        }])
+    );
+    assert_eq!(body["search"], json!(["headphone"]));
+
+    let (_, body) = get(&app, "/analyze?analyzer=edge_ngram&text=Ult");
+    assert_eq!(body["field"], Json::Null);
+    assert_eq!(body["tokens"][0]["terms"], json!(["u", "ul", "ult"]));
+
+    for (path, error) in [
+        ("/analyze?field=Products.name", "missing `text` parameter"),
+        ("/analyze?text=x", "missing `field` or `analyzer` parameter"),
+        ("/analyze?field=Products.price&text=x", "`Products.price` is not a full-text field"),
+        ("/analyze?analyzer=porter&text=x", "`porter` is not an analyzer; available: standard, english"),
+    ] {
+        let (status, body) = get(&app, path);
+        assert_eq!(status, 400, "{path}");
+        assert!(body["error"].as_str().unwrap().contains(error), "{path}: {body}");
+    }
+}
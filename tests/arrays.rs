@@ -0,0 +1,73 @@
+//! `=all=`, `=any=`, `=none=`, `=size=` and element filters.
+
+mod common;
+
+use common::{get, ids};
+use demo_fiql::app::App;
+use demo_fiql::fiql;
+use serde_json::json;
+
+#[test]
+fn value_quantifiers_and_sizes_on_tags() {
+    let app = App::seeded();
+    assert_eq!(ids(&app, "/Products/?tags=all=4k,popular"), ["prod-001"]);
+    assert_eq!(ids(&app, "/Products/?tags=any=4k,anc"), ["prod-001", "prod-020", "prod-026"]);
+    assert_eq!(ids(&app, "/Products/?tags=size=gt=3"), ["prod-020", "prod-040", "prod-043"]);
+    assert_eq!(ids(&app, "/Products/?tags=size=3").len(), 47);
+    // Like `=out=`, `=none=` holds when nothing matches, even with no tags.
+    assert_eq!(ids(&app, "/Products/?tags=none=clearance").len(), 50);
+    assert_eq!(ids(&app, "/Products/?category==electronics&tags=none=popular,wireless,portable").len(), 5);
+
+    // Sizes count related records too.
+    let (_, brands) = get(&app, "/Brand/?products=size=ge=5&select=name");
+    assert_eq!(brands, json!([{ "name": "FlexForm" }]));
+
+    let mut app = App::seeded();
+    let record = json!({ "id": "x-1", "name": "Bare", "price": 1, "category": "x", "inStock": true, "tags": [] });
+    app.store_mut().put("Products", record).unwrap();
+    assert_eq!(ids(&app, "/Products/?tags=size=0"), ["x-1"]);
+    assert!(!ids(&app, "/Products/?tags=all=(x==1)|tags=all=a").contains(&"x-1".to_string()));
+}
+
+#[test]
+fn element_filters_match_within_one_element() {
+    let app = App::seeded();
+    // Across elements, navy with no stock and grey with stock both count.
+    assert!(ids(&app, "/Products/?variants.color==navy&variants.stock=gt=0").contains(&"prod-005".to_string()));
+    assert_eq!(ids(&app, "/Products/?variants=any=(color==navy&stock=gt=0)"), ["prod-013", "prod-028", "prod-042"]);
+    assert_eq!(
+        ids(&app, "/Products/?variants=all=(stock=gt=0)"),
+        ["prod-013", "prod-021", "prod-038", "prod-042", "prod-048"]
+    );
+    assert_eq!(ids(&app, "/Products/?category==clothing&variants=none=(stock=gt=0)"), ["prod-018"]);
+    assert_eq!(ids(&app, "/Products/?variants=any=(size==one-size|stock=ge=20)"), ["prod-021", "prod-034", "prod-048"]);
+
+    // Over a list relationship, the elements are the related records.
+    let (_, brands) = get(&app, "/Brand/?products=all=(category==clothing)&select=name");
+    assert_eq!(brands.as_array().unwrap().len(), 4);
+    let (_, brands) = get(&app, "/Brand/?products=any=(price=gt=150&inStock==false)&select=name");
+    assert_eq!(brands, json!([{ "name": "PortPlus" }, { "name": "TimberLine" }]));
+}
+
+#[test]
+fn filters_round_trip_and_are_checked_against_the_element_type() {
+    let parsed = fiql::parse("variants=any=(stock=gt=0&color==navy)&tags=size=le=3").unwrap();
+    assert_eq!(fiql::print(&parsed), "variants=any=(stock=gt=0&color==navy)&tags=size=le=3");
+    assert_eq!(fiql::canonical(&parsed), "tags=size=le=3&variants=any=(color==navy&stock=gt=0)");
+    // Fields inside the filter are not inherited from outside it.
+    assert!(fiql::parse("price=gt=1&variants=any=(=lt=5)").is_err());
+    assert_eq!(get(&App::seeded(), "/Products/?tags=size=2.5").0, 400);
+
+    let app = App::seeded();
+    for (path, message) in [
+        ("/validate/Products/?variants=any=(colour==navy)", "`Variant` has no field `colour`"),
+        ("/validate/Products/?name=all=a,b", "`=all=` applies to lists but `name` is String!"),
+        ("/validate/Products/?tags=any=(x==1)", "`tags` holds String values"),
+        ("/validate/Products/?variants==navy", "`variants` holds Variant objects"),
+        ("/validate/Brand/?products=none=x", "`products` holds Products records"),
+    ] {
+        let (_, body) = get(&app, path);
+        let error = body["diagnostics"][0]["message"].as_str().unwrap();
+        assert!(error.contains(message), "{path}: {error}");
+    }
+}
@@ -0,0 +1,129 @@
+//! Authenticated bulk PATCH and DELETE by FIQL filter.
+
+use demo_fiql::app::App;
+use demo_fiql::http::{Method, Request};
+use serde_json::{Value as Json, json};
+
+const SECRET: &[u8] = b"test-secret";
+
+fn app() -> App {
+    App::seeded().with_jwt_secret(SECRET)
+}
+
+fn token(app: &App, role: &str) -> String {
+    format!("Bearer {}", app.auth().sign(&json!({ "sub": "tester", "role": role })).unwrap())
+}
+
+fn send(app: &mut App, method: Method, target: &str, role: &str, body: &str) -> (u16, Json) {
+    let auth = token(app, role);
+    let response = app.handle_mut(&Request::new(method, target).with_header("Authorization", &auth).with_body(body));
+    (response.status, serde_json::from_slice(&response.body).unwrap())
+}
+
+#[test]
+fn writes_need_a_valid_token_and_a_role_that_allows_them() {
+    let mut app = app();
+    let target = "/Products/?price=lt=20";
+    let response = app.handle_mut(&Request::new(Method::Delete, target));
+    assert_eq!(response.status, 401);
+    assert_eq!(response.header("WWW-Authenticate"), Some("Bearer"));
+
+    let forged = App::seeded().with_jwt_secret(b"other").auth().sign(&json!({ "role": "admin" })).unwrap();
+    let request = Request::new(Method::Delete, target).with_header("Authorization", &format!("Bearer {forged}"));
+    assert_eq!(app.handle_mut(&request).status, 401);
+    let expired = app.auth().sign(&json!({ "role": "admin", "exp": 1 })).unwrap();
+    let request = Request::new(Method::Delete, target).with_header("Authorization", &format!("Bearer {expired}"));
+    assert_eq!(app.handle_mut(&request).status, 401);
+
+    assert_eq!(send(&mut app, Method::Delete, target, "merchandiser", "").0, 403);
+    assert_eq!(send(&mut app, Method::Patch, "/Brand/?country==JP", "merchandiser", "{}").0, 403);
+    assert_eq!(app.store().table("Products").unwrap().records.len(), 50);
+
+    // Reads stay public, and `handle` never writes.
+    assert_eq!(
+        app.handle(&Request::new(Method::Delete, target).with_header("Authorization", &token(&app, "admin"))).status,
+        405
+    );
+    assert_eq!(app.handle(&Request::get(target)).status, 200);
+}
+
+#[test]
+fn patch_applies_a_merge_patch_to_every_match() {
+    let mut app = app();
+    let target = "/Products/?category==clothing&inStock==false";
+    let patch = r#"{"inStock": true, "tags": null, "manufacturer": {"country": "PT"}}"#;
+
+    let (status, body) = send(&mut app, Method::Patch, &format!("{target}&dryRun=true"), "merchandiser", patch);
+    assert_eq!(status, 200);
+    assert_eq!(
+        body,
+        json!({ "table": "Products", "action": "update", "count": 1, "ids": ["prod-038"], "dryRun": true })
+    );
+    assert_eq!(app.store().get("Products", "prod-038").unwrap()["inStock"], false);
+
+    let (status, body) = send(&mut app, Method::Patch, target, "merchandiser", patch);
+    assert_eq!((status, body["count"].clone()), (200, json!(1)));
+    let record = app.store().get("Products", "prod-038").unwrap();
+    assert_eq!(record["inStock"], true);
+    assert!(record.get("tags").is_none());
+    assert_eq!(record["manufacturer"]["country"], "PT");
+    assert!(record["manufacturer"]["name"].is_string(), "merge keeps untouched members");
+
+    assert_eq!(send(&mut app, Method::Patch, target, "admin", r#"{"id": "x"}"#).0, 400);
+    assert_eq!(send(&mut app, Method::Patch, target, "admin", "[1]").0, 400);
+    assert_eq!(send(&mut app, Method::Patch, "/Products/", "admin", "{}").0, 400);
+    assert_eq!(send(&mut app, Method::Patch, "/Products/?price=gt=1&limit=2", "admin", "{}").0, 400);
+}
+
+#[test]
+fn patches_that_break_the_schema_are_rejected_before_anything_is_written() {
+    let mut app = app();
+    for (patch, error) in [
+        (r#"{"price": null}"#, "`price` is Float! and cannot be null"),
+        (r#"{"price": "abc"}"#, "`price` is Float!, not `\"abc\"`"),
+        (r#"{"pirce": 10}"#, "`Products` has no field `pirce`"),
+        (r#"{"tags": "sale"}"#, "`tags` is [String], not `\"sale\"`"),
+        (r#"{"variants": [{"size": "M", "color": "red"}]}"#, "`variants`: `stock` is Int! and cannot be null"),
+        (r#"{"brand": {"name": "Acme"}}"#, "`brand` is not stored on `Products` records"),
+    ] {
+        let (status, body) = send(&mut app, Method::Patch, "/Products/?price=gt=400&dryRun=true", "admin", patch);
+        assert_eq!(status, 400, "{patch}");
+        let message = body["error"].as_str().unwrap();
+        assert!(message.starts_with("record `prod-") && message.contains(error), "{patch}: {message}");
+
+        let (status, _) = send(&mut app, Method::Patch, "/Products/prod-001", "admin", patch);
+        assert_eq!(status, 400, "{patch}");
+    }
+    let record = app.store().get("Products", "prod-001").unwrap();
+    assert_eq!(record["price"], 499.99);
+    assert!(record.get("pirce").is_none());
+
+    // Every seeded record conforms, so an empty patch touches them all.
+    let (status, body) = send(&mut app, Method::Patch, "/Products/?price=gt=0&confirm=50", "admin", "{}");
+    assert_eq!((status, body["count"].clone()), (200, json!(50)), "{body}");
+    let (status, _) =
+        send(&mut app, Method::Patch, "/Brand/?country==JP", "admin", r#"{"location": {"lat": 35.7, "lon": 139.7}}"#);
+    assert_eq!(status, 200);
+    let (status, _) = send(&mut app, Method::Patch, "/Brand/?country==JP", "admin", r#"{"foundedYear": 1.5}"#);
+    assert_eq!(status, 400);
+}
+
+#[test]
+fn large_deletes_need_the_matching_confirm_count() {
+    let mut app = app();
+    let (status, body) = send(&mut app, Method::Delete, "/Products/?price=lt=50", "admin", "");
+    assert_eq!((status, body["count"].clone()), (428, json!(17)));
+    assert!(body["error"].as_str().unwrap().contains("confirm=17"));
+
+    assert_eq!(send(&mut app, Method::Delete, "/Products/?price=lt=50&confirm=16", "admin", "").0, 409);
+    assert_eq!(app.store().table("Products").unwrap().records.len(), 50);
+
+    let (status, body) = send(&mut app, Method::Delete, "/Products/?price=lt=50&confirm=17", "admin", "");
+    assert_eq!((status, body["count"].clone()), (200, json!(17)));
+    assert_eq!(app.store().table("Products").unwrap().records.len(), 33);
+
+    // Small deletes go through without confirmation.
+    let (status, body) = send(&mut app, Method::Delete, "/Products/?price=lt=60", "admin", "");
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(app.handle(&Request::get("/Products/?price=lt=60")).body_str().trim(), "[]");
+}
@@ -0,0 +1,77 @@
+//! `=eqi=`, `=cti=`, `=swi=`, `=ewi=`, the `i:` prefix and collated indexes.
+
+mod common;
+
+use common::{app_with, get, ids};
+use demo_fiql::app::App;
+use demo_fiql::fiql::{self, ErrorKind, Operator};
+use serde_json::json;
+
+const SDL: &str = r#"
+type Person @table {
+    id: ID! @primaryKey
+    name: String! @indexed(collation: "ci_ai")
+    city: String @indexed(collation: "ci")
+}
+"#;
+
+fn people() -> App {
+    let people = [("p1", "Zoë Müller", "Straße"), ("p2", "Zoe Muller", "MÜNCHEN"), ("p3", "Chloé", "Köln")];
+    app_with(SDL, "Person", people.map(|(id, name, city)| json!({ "id": id, "name": name, "city": city })))
+}
+
+#[test]
+fn folds_case_everywhere_and_accents_under_ci_ai() {
+    let app = people();
+    // `ß` folds to `ss`, and `Ü` to `ü` but not to `u` under plain `ci`.
+    assert_eq!(ids(&app, "/Person/?city=eqi=STRASSE"), ["p1"]);
+    assert_eq!(ids(&app, "/Person/?city=swi=mün"), ["p2"]);
+    assert!(ids(&app, "/Person/?city=swi=mun").is_empty());
+    // `ci_ai` also drops accents, on both sides.
+    assert_eq!(ids(&app, "/Person/?name=eqi=zoe%20muller"), ["p1", "p2"]);
+    assert_eq!(ids(&app, "/Person/?name=ewi=MÜLLER"), ["p1", "p2"]);
+    assert_eq!(ids(&app, "/Person/?name=cti=LOE"), ["p3"]);
+
+    let app = App::seeded();
+    assert_eq!(ids(&app, "/Brand/?name=eqi=viewtech"), ["brand-viewtech"]);
+    assert_eq!(ids(&app, "/Products/?name=cti=ULTRA"), ids(&app, "/Products/?name=~=(?i)ultra"));
+    // Without a collation the operators still ignore case.
+    assert_eq!(ids(&app, "/Products/?category=eqi=BOOKS"), ids(&app, "/Products/?category==books"));
+}
+
+#[test]
+fn i_prefix_selects_the_case_insensitive_operator() {
+    let query = fiql::parse("name==i:ViewTech&name=ct=i:view&name=sw=i:v&name=ew=i:TECH").unwrap();
+    let ops: Vec<Operator> = query.filter.unwrap().conditions().iter().map(|c| c.op).collect();
+    assert_eq!(ops, [Operator::EqI, Operator::ContainsI, Operator::StartsWithI, Operator::EndsWithI]);
+
+    let query = fiql::parse("name==i:ViewTech").unwrap();
+    let condition = query.filter.unwrap().conditions()[0].clone();
+    assert_eq!(condition.value.scalars()[0].raw, "ViewTech");
+    assert_eq!(condition.value.scalars()[0].span, fiql::Span::new(8, 16));
+
+    // `string:` escapes the prefix, and other operators keep it as text.
+    let app = App::seeded();
+    assert!(ids(&app, "/Brand/?name==string:i:viewtech").is_empty());
+    let query = fiql::parse("name=ne=i:x").unwrap();
+    assert_eq!(query.filter.unwrap().conditions()[0].value.scalars()[0].raw, "i:x");
+
+    assert_eq!(fiql::parse("name==i:").unwrap_err().kind, ErrorKind::MissingValue);
+    let (_, body) = get(&app, "/validate/Products/?price=cti=1");
+    assert_eq!(body["diagnostics"][0]["message"], "`=cti=` is a string operator but `price` is Float!");
+}
+
+#[test]
+fn collated_index_answers_case_insensitive_lookups() {
+    let app = App::seeded();
+    let (_, plan) = get(&app, "/Brand/?name==i:VIEWTECH&explain=true");
+    assert_eq!((plan["strategy"].as_str(), plan["index"].as_str()), (Some("index"), Some("name")));
+    assert_eq!(plan["estimate"]["rowsScanned"], 1);
+
+    let (_, plan) = get(&app, "/Brand/?name=swi=view&explain=true");
+    assert_eq!((plan["strategy"].as_str(), plan["estimatedCost"].as_str()), (Some("index"), Some("medium")));
+
+    // `category` has a plain index, whose keys are not folded.
+    let (_, plan) = get(&app, "/Products/?category=eqi=books&explain=true");
+    assert_eq!(plan["strategy"], "full_scan");
+}
@@ -0,0 +1,144 @@
+//! Arrow IPC and Parquet exports, read back with the Arrow readers.
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::Float64Type;
+use arrow_array::{Array, RecordBatch};
+use arrow_ipc::reader::StreamReader;
+use arrow_schema::DataType;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use demo_fiql::app::App;
+use demo_fiql::fiql;
+use demo_fiql::format::{BATCH_ROWS, arrow_schema, write_arrow, write_parquet};
+use demo_fiql::http::Request;
+use demo_fiql::store::Store;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::{Value as Json, json};
+
+fn fetch(app: &App, path: &str) -> Vec<u8> {
+    let response = app.handle(&Request::get(path));
+    assert_eq!(response.status, 200, "{}", response.body_str());
+    response.body
+}
+
+fn read_arrow(bytes: &[u8]) -> Vec<RecordBatch> {
+    StreamReader::try_new(bytes, None).unwrap().collect::<Result<_, _>>().unwrap()
+}
+
+#[test]
+fn arrow_schema_follows_graphql_types() {
+    let app = App::seeded();
+    let batches = read_arrow(&fetch(&app, "/Products/?category==electronics&format=arrow"));
+    let schema = batches[0].schema();
+    let field = |name: &str| schema.field_with_name(name).unwrap().clone();
+    assert_eq!((field("id").data_type().clone(), field("id").is_nullable()), (DataType::Utf8, false));
+    assert_eq!((field("price").data_type().clone(), field("price").is_nullable()), (DataType::Float64, false));
+    assert_eq!((field("height").data_type().clone(), field("height").is_nullable()), (DataType::Float64, true));
+    assert_eq!(field("inStock").data_type(), &DataType::Boolean);
+    assert!(schema.field_with_name("brand").is_err(), "relationships only appear when selected");
+
+    let brands = read_arrow(&fetch(&app, "/Brand/?select=name,foundedYear&format=arrow"));
+    let year = brands[0].schema().field_with_name("foundedYear").unwrap().clone();
+    assert_eq!((year.data_type().clone(), year.is_nullable()), (DataType::Int32, true));
+
+    let total: usize = batches.iter().map(RecordBatch::num_rows).sum();
+    let json: Json =
+        serde_json::from_slice(&app.handle(&Request::get("/Products/?category==electronics")).body).unwrap();
+    assert_eq!(total, json.as_array().unwrap().len());
+}
+
+#[test]
+fn selected_relationships_become_struct_columns() {
+    let app = App::seeded();
+    let bytes = fetch(&app, "/Products/?brand.country==JP&select=name,price,brand{name,country}&format=parquet");
+    let path = std::env::temp_dir().join(format!("demo-fiql-{}.parquet", std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    let reader =
+        ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap()).unwrap().build().unwrap();
+    let batches: Vec<RecordBatch> = reader.collect::<Result<_, _>>().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let batch = &batches[0];
+    assert_eq!(batch.num_rows(), 5);
+    let brand = batch.column_by_name("brand").unwrap().as_struct();
+    assert_eq!(brand.column_names(), ["name", "country"]);
+    let countries = brand.column_by_name("country").unwrap().as_string::<i32>();
+    assert!(countries.iter().all(|c| c == Some("JP")));
+    assert_eq!(batch.column_by_name("price").unwrap().as_primitive::<Float64Type>().value(0), 499.99);
+
+    let brands = read_arrow(&fetch(&app, "/Brand/brand-viewtech?select=name,products{name}&format=arrow"));
+    let products = brands[0].column_by_name("products").unwrap().as_list::<i32>();
+    assert!(matches!(products.value_type(), DataType::Struct(_)));
+    assert!(!products.value(0).is_empty());
+}
+
+#[test]
+fn stream_true_writes_bounded_record_batches() {
+    let mut store = Store::seeded();
+    let template = store.get("Products", "prod-001").unwrap().clone();
+    for n in 0..BATCH_ROWS + 10 {
+        let mut row = template.clone();
+        row["id"] = json!(format!("bulk-{n:05}"));
+        store.put("Products", row).unwrap();
+    }
+    let app = App::new(store);
+    let rows = 50 + BATCH_ROWS + 10;
+
+    let streamed = read_arrow(&fetch(&app, "/Products/?select=id,price&format=arrow&stream=true"));
+    assert_eq!(streamed.iter().map(RecordBatch::num_rows).collect::<Vec<_>>(), [BATCH_ROWS, rows - BATCH_ROWS]);
+    let buffered = read_arrow(&fetch(&app, "/Products/?select=id,price&format=arrow"));
+    assert_eq!(buffered.len(), 1);
+    assert_eq!(buffered[0].num_rows(), rows);
+
+    // `App::export` hands the body to a writer; it matches what `handle` buffers.
+    let path = "/Products/?select=id,price&format=parquet&stream=true&pagination=true&limit=2000";
+    let export = app.export(&Request::get(path)).unwrap();
+    assert_eq!(export.head.header("X-Total-Count"), Some(rows.to_string().as_str()));
+    assert!(export.head.body.is_empty());
+    let mut written = Vec::new();
+    export.write_to(&mut written).unwrap();
+    assert_eq!(written, fetch(&app, path));
+
+    // With `stream=true` each batch is written before the next rows are
+    // read; without it nothing but the header goes out until all are read.
+    let table = app.store().schema().table("Products").unwrap();
+    let select = fiql::parse("select=id,price").unwrap().controls.select.unwrap();
+    for parquet in [false, true] {
+        for stream in [true, false] {
+            let read = AtomicUsize::new(0);
+            let rows = (0..rows).map(|n| {
+                read.store(n + 1, Ordering::SeqCst);
+                json!({ "id": format!("r{n}"), "price": 1.5 })
+            });
+            let mut probe = Probe { read: &read, seen: Vec::new() };
+            let schema = arrow_schema(app.store().schema(), table, Some(&select));
+            match parquet {
+                true => write_parquet(&mut probe, schema, rows, stream).unwrap(),
+                false => write_arrow(&mut probe, schema, rows, stream).unwrap(),
+            }
+            let early = probe.seen.iter().any(|&n| n > 0 && n < read.load(Ordering::SeqCst));
+            assert_eq!(early, stream, "parquet={parquet} stream={stream}: {:?}", probe.seen);
+            if stream {
+                assert!(probe.seen.contains(&BATCH_ROWS), "{:?}", probe.seen);
+            }
+        }
+    }
+}
+
+/// Records how many rows had been read at each write.
+struct Probe<'a> {
+    read: &'a AtomicUsize,
+    seen: Vec<usize>,
+}
+
+impl Write for Probe<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.seen.push(self.read.load(Ordering::SeqCst));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+//! Helpers shared by the integration tests. Each test binary compiles its
+//! own copy and uses only some of them.
+#![allow(dead_code)]
+
+use demo_fiql::app::App;
+use demo_fiql::http::Request;
+use demo_fiql::schema::Schema;
+use demo_fiql::store::Store;
+use serde_json::Value as Json;
+
+/// The status and JSON body of a `GET`.
+pub fn get(app: &App, path: &str) -> (u16, Json) {
+    let response = app.handle(&Request::get(path));
+    (response.status, serde_json::from_slice(&response.body).unwrap())
+}
+
+/// The `id`s of the rows a successful `GET` returns, in order.
+pub fn ids(app: &App, path: &str) -> Vec<String> {
+    let (status, body) = get(app, path);
+    assert_eq!(status, 200, "{path}: {body}");
+    body.as_array().unwrap().iter().map(|r| r["id"].as_str().unwrap().to_string()).collect()
+}
+
+/// An app over the schema `sdl` holding `records` in `table`.
+pub fn app_with(sdl: &str, table: &str, records: impl IntoIterator<Item = Json>) -> App {
+    let mut store = Store::new(Schema::parse(sdl).unwrap());
+    for record in records {
+        store.put(table, record).unwrap();
+    }
+    App::new(store)
+}
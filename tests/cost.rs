@@ -0,0 +1,107 @@
+//! Query cost estimates and the per-request budget.
+
+mod common;
+
+use common::get;
+use demo_fiql::app::App;
+use demo_fiql::http::Request;
+use demo_fiql::live::ws::Session;
+use serde_json::{Value as Json, json};
+
+fn estimate(app: &App, path: &str) -> Json {
+    let (status, plan) = get(app, &format!("{path}&explain=true"));
+    assert_eq!(status, 200, "{plan}");
+    plan["estimate"].clone()
+}
+
+#[test]
+fn estimates_come_from_index_statistics() {
+    let app = App::seeded();
+    // Five categories over 50 products: a point lookup reads about ten.
+    assert_eq!(estimate(&app, "/Products/?category==electronics")["rowsScanned"], 10);
+    assert_eq!(estimate(&app, "/Products/?id==prod-001")["rowsScanned"], 1);
+    // Only one product name holds the term `rust`.
+    assert_eq!(estimate(&app, "/Products/?name=ft=rust")["rowsScanned"], 1);
+    assert_eq!(estimate(&app, "/Products/?inStock==false&price=lt=1000")["rowsScanned"], 25);
+
+    // Reading `category+price` in order costs no sort; sorting a scan does.
+    let keyset = estimate(&app, "/Products/?category==books&sort=price");
+    assert!(keyset["parts"].as_array().unwrap().iter().all(|p| p["part"] != "sort=price"), "{keyset}");
+    let sorted = estimate(&app, "/Products/?price=gt=0&sort=description");
+    assert_eq!(sorted["parts"][2]["part"], "sort=description");
+    assert!(sorted["parts"][2]["cost"].as_u64().unwrap() > 50);
+
+    // A reverse-join condition reads every product for each brand.
+    let brands = estimate(&app, "/Brand/?products.price=gt=800");
+    assert_eq!(brands["parts"][1]["cost"], brands["rowsScanned"].as_u64().unwrap() * 50);
+
+    // The statistics follow writes rather than being rebuilt per request.
+    let mut app = app;
+    for i in 0..50 {
+        let record =
+            json!({ "id": format!("new-{i}"), "name": "Rust Mug", "price": 5, "category": "mugs", "inStock": true });
+        app.store_mut().put("Products", record).unwrap();
+    }
+    assert_eq!(estimate(&app, "/Products/?category==electronics")["rowsScanned"], 17);
+    assert_eq!(estimate(&app, "/Products/?name=ft=rust")["rowsScanned"], 51);
+    for i in 0..50 {
+        app.store_mut().delete("Products", &format!("new-{i}")).unwrap();
+    }
+    assert_eq!(estimate(&app, "/Products/?category==electronics")["rowsScanned"], 10);
+    assert_eq!(estimate(&app, "/Products/?name=ft=rust")["rowsScanned"], 1);
+}
+
+#[test]
+fn expensive_queries_are_rejected_naming_the_condition() {
+    let app = App::seeded();
+    let (status, body) = get(&app, "/Products/?name=~=.*.*.*x&sort=description");
+    assert_eq!(status, 422);
+    let error = body["error"].as_str().unwrap();
+    assert!(error.contains("`name=~=.*.*.*x` costs 3200"), "{error}");
+    assert!(error.contains("3 unbounded repeats"), "{error}");
+    assert_eq!(body["span"], json!({ "start": 11, "end": 25 }));
+    assert!(body["estimate"]["cost"].as_u64().unwrap() > body["budget"].as_u64().unwrap());
+
+    // The same regex is affordable once an index narrows the scan, and
+    // explain always answers.
+    assert_eq!(get(&app, "/Products/?category==books&name=~=.*.*.*x").0, 200);
+    assert_eq!(get(&app, "/Products/?name=~=.*.*x").0, 200);
+    assert_eq!(get(&app, "/Products/?name=~=.*.*.*x&explain=true").0, 200);
+
+    // Relationship chains are capped when parsed; the longest allowed one is
+    // priced over the budget rather than wrapping around to nothing.
+    let chain = format!("/Brand/?{}.name==x", ["products.brand"; 12].join("."));
+    let (status, body) = get(&app, &chain);
+    assert_eq!(status, 400);
+    assert!(body["error"].as_str().unwrap().contains("has more than 4 segments"), "{body}");
+    let (status, body) = get(&app, "/Brand/?products.brand.products.name==x");
+    assert_eq!(status, 422);
+    assert_eq!(body["estimate"]["parts"][1]["cost"], 22 * 5000);
+}
+
+#[test]
+fn every_read_path_enforces_the_budget() {
+    let app = App::seeded();
+    for path in [
+        "/Products/aggregate?name=~=.*.*.*x&groupBy=category",
+        "/Products/facets?name=~=.*.*.*x",
+        "/Products/?name=~=.*.*.*x&subscribe=sse",
+    ] {
+        assert_eq!(get(&app, path).0, 422, "{path}");
+    }
+    assert!(app.subscribe(&Request::get("/Products/?name=~=.*.*.*x")).is_err());
+
+    // The `category` facet drops `category==books` and scans every name, so
+    // a filter that is cheap on its own can still be too much for facets.
+    assert_eq!(get(&app, "/Products/?category==books&name=~=.*.*x").0, 200);
+    let (status, body) = get(&app, "/Products/facets?category==books&name=~=.*.*x");
+    assert_eq!(status, 422);
+    assert!(body["error"].as_str().unwrap().contains("for the `category` facet"), "{body}");
+    assert_eq!(get(&app, "/Products/facets?category==books&name=~=.*.*x&facets=inStock").0, 200);
+
+    let mut session = Session::new();
+    let query = json!({ "type": "subscribe", "id": "x", "query": "/Products/?name=~=.*.*.*x" });
+    let messages = session.receive(app.store(), &query.to_string());
+    assert_eq!(messages[1]["status"], 422);
+    assert_eq!(session.ids().count(), 0);
+}
@@ -0,0 +1,91 @@
+//! `Date`/`DateTime` fields, `date:` values, `now-7d` and the
+//! `@createdTime`/`@updatedTime` stamps.
+
+mod common;
+
+use chrono::{TimeDelta, Utc};
+use common::{app_with, get, ids};
+use demo_fiql::app::App;
+use demo_fiql::fiql::{self, ErrorKind, Literal, Time};
+use serde_json::json;
+
+fn literal(query: &str) -> Literal {
+    fiql::parse(query).unwrap().filter.unwrap().conditions()[0].value.scalars()[0].literal.clone()
+}
+
+#[test]
+fn date_values_parse_and_print() {
+    let Literal::Time(Time::Day(day)) = literal("createdAt==date:2024-02-29") else { panic!("not a day") };
+    assert_eq!(day.to_string(), "2024-02-29");
+    assert_eq!(literal("updatedAt=gt=now-7d"), Literal::Time(Time::Now(-7 * 86_400)));
+    assert_eq!(literal("updatedAt=lt=now"), Literal::Time(Time::Now(0)));
+    // Under a string operator `now` is text, and an ISO date stays text
+    // until the schema says the field is a date.
+    assert_eq!(literal("name=ct=now-7d"), Literal::String("now-7d".into()));
+    assert_eq!(literal("createdAt=ge=2024-01-01"), Literal::String("2024-01-01".into()));
+
+    let at = Time::parse("2024-01-31T11:30:00+02:00").unwrap();
+    assert_eq!(at.to_string(), "2024-01-31T09:30:00Z");
+    assert_eq!(Time::parse("now+36h").unwrap().to_string(), "now+36h");
+    assert_eq!(Time::parse("now-14d").unwrap().to_string(), "now-2w");
+    for bad in ["now-7", "now7d", "now-d", "2024-02-30", "31/01/2024"] {
+        assert_eq!(Time::parse(bad), None, "{bad}");
+    }
+
+    let err = fiql::parse("createdAt==date:2024-02-30").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidDate("2024-02-30".into()));
+    let query = fiql::parse("updatedAt=gt=now-7d&createdAt=le=date:2024-06-30").unwrap();
+    assert_eq!(fiql::print(&query), "updatedAt=gt=now-1w&createdAt=le=date:2024-06-30");
+}
+
+#[test]
+fn filters_compare_dates_by_day_or_instant() {
+    let app = App::seeded();
+    // A day bound covers the whole day: prod-001 was created at 14:13 on
+    // the 8th, so `le` keeps it and `lt` drops it.
+    assert_eq!(ids(&app, "/Products/?createdAt=le=2024-01-08"), ["prod-001"]);
+    assert!(ids(&app, "/Products/?createdAt=lt=2024-01-08").is_empty());
+    assert_eq!(ids(&app, "/Products/?createdAt==2024-01-08"), ["prod-001"]);
+    assert_eq!(ids(&app, "/Products/?createdAt=gele=2024-03-01,2024-03-31"), ["prod-006", "prod-007", "prod-008"]);
+    // Instants with an offset compare in UTC.
+    assert_eq!(ids(&app, "/Brand/?createdAt==date:2023-10-02T11:00:00%2B02:00"), ["brand-viewtech"]);
+    assert!(ids(&app, "/Products/?updatedAt=gt=now-7d").is_empty());
+
+    let sdl = "type Event @table { id: ID! @primaryKey on: Date @indexed }";
+    let events = [("e1", "2024-05-01"), ("e2", "2024-05-02"), ("e3", "2024-06-01")];
+    let events = app_with(sdl, "Event", events.map(|(id, on)| json!({ "id": id, "on": on })));
+    assert_eq!(ids(&events, "/Event/?on=ge=date:2024-05-02T23:00:00Z"), ["e2", "e3"]);
+    assert_eq!(ids(&events, "/Event/?on=gelt=2024-05-01,2024-06-01"), ["e1", "e2"]);
+
+    let (_, plan) = get(&app, "/Products/?createdAt=gele=2024-03-01,2024-03-31&explain=true");
+    assert_eq!((plan["strategy"].as_str(), plan["index"].as_str()), (Some("index"), Some("createdAt")));
+    assert!(plan["estimate"]["rowsScanned"].as_u64().unwrap() < 10, "{plan}");
+
+    let (_, body) = get(&app, "/validate/Products/?createdAt=gt=yesterday");
+    assert_eq!(body["diagnostics"][0]["message"], "`yesterday` is not a date; `createdAt` is DateTime!");
+}
+
+#[test]
+fn writes_stamp_created_and_updated_times() {
+    let mut app = App::seeded();
+    let before = Utc::now() - TimeDelta::seconds(1);
+    let record = json!({ "id": "x-1", "name": "New", "price": 1, "category": "x", "inStock": true });
+    app.store_mut().put("Products", record).unwrap();
+    let stamped = app.store().get("Products", "x-1").unwrap().clone();
+    let created = Time::parse(stamped["createdAt"].as_str().unwrap()).unwrap();
+    assert!(matches!(created, Time::At(at) if at >= before), "{stamped}");
+    assert_eq!(stamped["createdAt"], stamped["updatedAt"]);
+    assert_eq!(ids(&app, "/Products/?updatedAt=gt=now-7d"), ["x-1"]);
+
+    // An update keeps createdAt, even if the new body tries to change it,
+    // and moves updatedAt.
+    let seeded = app.store().get("Products", "prod-001").unwrap().clone();
+    let mut changed = seeded.clone();
+    changed["price"] = json!(1.0);
+    changed["createdAt"] = json!("2030-01-01T00:00:00Z");
+    app.store_mut().put("Products", changed).unwrap();
+    let updated = app.store().get("Products", "prod-001").unwrap();
+    assert_eq!(updated["createdAt"], seeded["createdAt"]);
+    assert_ne!(updated["updatedAt"], seeded["updatedAt"]);
+    assert_eq!(ids(&app, "/Products/?updatedAt=gt=now-1h"), ["prod-001", "x-1"]);
+}
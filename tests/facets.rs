@@ -0,0 +1,62 @@
+//! `/<Table>/facets` over the seed data.
+
+mod common;
+
+use common::get;
+use demo_fiql::app::App;
+use serde_json::Value as Json;
+
+fn count(app: &App, path: &str) -> usize {
+    get(app, path).1.as_array().unwrap().len()
+}
+
+fn term(facet: &Json, value: &str) -> u64 {
+    facet["values"].as_array().unwrap().iter().find(|v| v["value"] == value).map_or(0, |v| v["count"].as_u64().unwrap())
+}
+
+#[test]
+fn a_facet_ignores_its_own_condition() {
+    let app = App::seeded();
+    let (status, body) = get(&app, "/Products/facets?category==books&inStock==true");
+    assert_eq!(status, 200);
+    assert_eq!(body["total"], count(&app, "/Products/?category==books&inStock==true"));
+    let categories = &body["facets"]["category"];
+    assert_eq!(term(categories, "electronics") as usize, count(&app, "/Products/?category==electronics&inStock==true"));
+    let out_of_stock = body["facets"]["inStock"]["values"].as_array().unwrap().iter().find(|v| v["value"] == false);
+    assert_eq!(out_of_stock.unwrap()["count"], count(&app, "/Products/?category==books&inStock==false"));
+
+    // A multi-select `|` on the facet's field is its own condition too, in
+    // either spelling.
+    let (_, or) = get(&app, "/Products/facets?(category==electronics|category==books)&inStock==true");
+    let (_, in_list) = get(&app, "/Products/facets?category=in=electronics,books&inStock==true");
+    assert_eq!(or["facets"]["category"], in_list["facets"]["category"]);
+    assert_eq!(or["facets"]["category"]["values"].as_array().unwrap().len(), 5);
+    assert_eq!(or["total"], in_list["total"]);
+    // A `|` that also constrains another field still applies.
+    let (_, mixed) = get(&app, "/Products/facets?category==books|inStock==false");
+    assert_eq!(mixed["total"], count(&app, "/Products/?category==books|inStock==false"));
+    let electronics = count(&app, "/Products/?(category==books|inStock==false)&category==electronics");
+    assert!(electronics > 0);
+    assert_eq!(term(&mixed["facets"]["category"], "electronics") as usize, electronics);
+}
+
+#[test]
+fn buckets_partition_the_result() {
+    let app = App::seeded();
+    let (_, body) = get(&app, "/Products/facets?category==electronics&buckets.price=0,100,500&facets=price");
+    let buckets = body["facets"]["price"]["buckets"].as_array().unwrap();
+    assert_eq!(buckets.len(), 4);
+    assert_eq!(buckets[1]["from"], 0);
+    assert_eq!(buckets[1]["to"], 100);
+    let total: u64 = buckets.iter().map(|b| b["count"].as_u64().unwrap()).sum();
+    assert_eq!(total, body["total"].as_u64().unwrap());
+    assert_eq!(buckets[2]["count"], count(&app, "/Products/?category==electronics&price=gelt=100,500"));
+    assert_eq!(body["facets"].as_object().unwrap().len(), 1);
+}
+
+#[test]
+fn rejects_unknown_facets_and_bad_edges() {
+    let app = App::seeded();
+    assert_eq!(get(&app, "/Products/facets?facets=colour").0, 400);
+    assert_eq!(get(&app, "/Products/facets?buckets.price=100,50").0, 400);
+}
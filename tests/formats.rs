@@ -0,0 +1,76 @@
+//! CSV and NDJSON encodings of table queries.
+
+use demo_fiql::app::App;
+use demo_fiql::http::{Request, Response};
+use serde_json::{Value as Json, json};
+
+fn fetch(app: &App, path: &str) -> Response {
+    let response = app.handle(&Request::get(path));
+    assert_eq!(response.status, 200, "{}", response.body_str());
+    response
+}
+
+#[test]
+fn csv_columns_follow_select_and_flatten_relationships() {
+    let app = App::seeded();
+    let response = fetch(&app, "/Products/?brand.country==JP&select=name,brand{name,country},tags&format=csv&limit=1");
+    assert_eq!(response.header("Content-Type"), Some("text/csv; charset=utf-8"));
+    let lines: Vec<&str> = response.body_str().split("\r\n").collect();
+    assert_eq!(lines[0], "name,brand.name,brand.country,tags");
+    assert_eq!(lines[1], "Ultra HD Monitor,ViewTech,JP,display|4k|popular");
+
+    let response = fetch(&app, "/Products/prod-001?select=id,manufacturer,tags&format=csv&arrays=json");
+    let lines: Vec<&str> = response.body_str().split("\r\n").collect();
+    assert_eq!(lines[0], "id,manufacturer.name,manufacturer.country,manufacturer.year,tags");
+    assert_eq!(lines[1], r#"prod-001,ViewTech,JP,2024,"[""display"",""4k"",""popular""]""#);
+}
+
+#[test]
+fn csv_columns_without_select_follow_the_schema() {
+    let mut app = App::seeded();
+    let header = |app: &App, path: &str| fetch(app, path).body_str().split("\r\n").next().unwrap().to_string();
+    let columns = "id,name,price,height,width,description,category,inStock,manufacturer,tags,\
+                   variants.size,variants.color,variants.stock,brandId,createdAt,updatedAt";
+    // The same header whatever the page holds, even when it holds nothing.
+    assert_eq!(header(&app, "/Products/?price=lt=0&format=csv"), columns);
+    assert_eq!(
+        header(&app, "/Products/?category==clothing&format=csv"),
+        header(&app, "/Products/?id==prod-001&format=csv")
+    );
+
+    // A scalar and an array of objects flattening onto one key share a cell.
+    let record = json!({ "id": "odd", "name": "Odd", "price": 1, "category": "x", "inStock": true,
+                         "manufacturer": { "a.b": 1, "a": [{ "b": 2 }] } });
+    app.store_mut().put("Products", record).unwrap();
+    let body = fetch(&app, "/Products/odd?select=id,manufacturer&format=csv").body_str().to_string();
+    assert_eq!(body, "id,manufacturer.a.b\r\nodd,1|2\r\n");
+}
+
+#[test]
+fn ndjson_writes_one_row_per_line() {
+    let app = App::seeded();
+    let response = fetch(&app, "/Products/?category==electronics&select=id,tags&format=ndjson");
+    assert_eq!(response.header("Content-Type"), Some("application/x-ndjson"));
+    let json: Json =
+        serde_json::from_slice(&fetch(&app, "/Products/?category==electronics&select=id,tags").body).unwrap();
+    let rows: Vec<Json> = response.body_str().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(Json::Array(rows), json);
+}
+
+#[test]
+fn accept_header_negotiates_and_paging_moves_to_headers() {
+    let app = App::seeded();
+    let request =
+        Request::get("/Products/?select=id&sort=price&limit=2&pagination=true").with_header("Accept", "text/csv");
+    let response = app.handle(&request);
+    assert_eq!(response.status, 200);
+    assert!(response.body_str().starts_with("id\r\n"));
+    assert_eq!(response.body_str().lines().count(), 3);
+    assert!(response.header("X-Total-Count").is_some());
+    assert!(response.header("X-Next-Cursor").is_some());
+
+    let response = app.handle(&Request::get("/Products/?format=xml"));
+    assert_eq!(response.status, 406);
+    let response = app.handle(&Request::get("/Products/?format=csv&arrays=yaml"));
+    assert_eq!(response.status, 400);
+}
@@ -0,0 +1,91 @@
+//! `GeoPoint` fields, `=near=`, `=within=` and sorting by distance.
+
+mod common;
+
+use arrow_array::RecordBatch;
+use arrow_array::cast::AsArray;
+use arrow_array::types::Float64Type;
+use arrow_ipc::reader::StreamReader;
+use common::{get, ids};
+use demo_fiql::app::App;
+use demo_fiql::fiql::{self, Area, ErrorKind, Operator, Point, Span, parse_distance};
+use demo_fiql::http::Request;
+
+#[test]
+fn near_and_within_values_parse_into_areas() {
+    let query = fiql::parse("location=near=35.68,139.69,50km").unwrap();
+    let condition = query.filter.as_ref().unwrap().conditions()[0].clone();
+    let area = Area::parse(condition.op, condition.value.scalars(), condition.span).unwrap();
+    assert_eq!(area, Area::Near { center: Point { lat: 35.68, lon: 139.69 }, radius: 50_000.0 });
+    // Order matters, so normalizing leaves the values where they are.
+    assert_eq!(fiql::canonical(&query), "location=near=35.68,139.69,50km");
+    assert_eq!(fiql::canonical(&fiql::parse("l=within=36,-10,60,20").unwrap()), "l=within=36,-10,60,20");
+
+    assert_eq!(parse_distance("800m"), Some(800.0));
+    assert_eq!(parse_distance("10mi"), Some(16_093.44));
+    assert_eq!(parse_distance("50"), None);
+    let (tokyo, yokohama) = (Point { lat: 35.6895, lon: 139.6917 }, Point { lat: 35.4437, lon: 139.638 });
+    assert!((tokyo.distance(yokohama) - 27_700.0).abs() < 500.0, "{}", tokyo.distance(yokohama));
+
+    let err = fiql::parse("location=near=35,190,5km").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidCoordinate { value: "190".into(), axis: "longitude" });
+    assert_eq!(err.span, Span::new(17, 20));
+    assert_eq!(fiql::parse("location=near=35,139,5").unwrap_err().kind, ErrorKind::InvalidDistance("5".into()));
+    assert_eq!(fiql::parse("location=within=60,-10,36,20").unwrap_err().kind, ErrorKind::InvalidBox);
+    assert_eq!(
+        fiql::parse("location=within=36,-10,60").unwrap_err().kind,
+        ErrorKind::WrongValueCount { op: Operator::Within.name(), expected: 4, found: 3 }
+    );
+}
+
+#[test]
+fn filters_and_sorts_brands_by_location() {
+    let app = App::seeded();
+    assert_eq!(ids(&app, "/Brand/?location=near=35.68,139.69,50km"), ["brand-soundwave", "brand-viewtech"]);
+    assert_eq!(
+        ids(&app, "/Brand/?location=near=35.68,139.69,50km&sort=location"),
+        ["brand-viewtech", "brand-soundwave"]
+    );
+    let lyon = "location=near=45.76,4.84,500km";
+    let nearest = ids(&app, &format!("/Brand/?{lyon}&sort=location"));
+    assert_eq!(nearest, ["brand-fabrichouse", "brand-verticaledge", "brand-trailblazer", "brand-alpinegear"]);
+    let farthest = ids(&app, &format!("/Brand/?{lyon}&sort=-location"));
+    assert_eq!(farthest, nearest.iter().rev().cloned().collect::<Vec<_>>());
+
+    // Cursors carry the distance, so pages follow the same order.
+    let (_, first) = get(&app, &format!("/Brand/?{lyon}&sort=location&limit=2&pagination=true"));
+    let cursor = first["pagination"]["nextCursor"].as_str().unwrap();
+    assert_eq!(ids(&app, &format!("/Brand/?{lyon}&sort=location&after={cursor}")), nearest[2..]);
+
+    // A box whose west edge is east of its east edge crosses the antimeridian.
+    assert_eq!(ids(&app, "/Brand/?location=within=-50,170,0,-170"), ["brand-woolcraft"]);
+    assert_eq!(ids(&app, "/Brand/?location=within=36,-10,60,20&country==IT"), ["brand-hidecraft", "brand-trailblazer"]);
+    assert_eq!(ids(&app, "/Brand/?location=within=-90,-180,90,180").len(), 22);
+}
+
+#[test]
+fn geo_index_plans_validates_and_exports() {
+    let app = App::seeded();
+    let (_, plan) = get(&app, "/Brand/?location=near=35.68,139.69,50km&explain=true");
+    assert_eq!((plan["strategy"].as_str(), plan["index"].as_str()), (Some("geo"), Some("location")));
+    assert_eq!(plan["estimate"]["rowsScanned"], 2);
+
+    let (_, body) = get(&app, "/validate/Brand/?location==Tokyo&foundedYear=near=1,2,3km&sort=location");
+    let messages: Vec<&str> =
+        body["diagnostics"].as_array().unwrap().iter().map(|d| d["message"].as_str().unwrap()).collect();
+    assert_eq!(
+        messages,
+        [
+            "`==` is not supported on GeoPoint field `location`; use `=near=` or `=within=`",
+            "`=near=` needs a GeoPoint field but `foundedYear` is Int",
+            "sorting by `location` orders by distance from a `location=near=` point, and the filter has none",
+        ]
+    );
+
+    let response = app.handle(&Request::get("/Brand/?name==ViewTech&select=name,location&format=arrow"));
+    let batches: Vec<RecordBatch> =
+        StreamReader::try_new(&response.body[..], None).unwrap().collect::<Result<_, _>>().unwrap();
+    let location = batches[0].column_by_name("location").unwrap().as_struct();
+    let lat = location.column_by_name("lat").unwrap().as_primitive::<Float64Type>();
+    assert_eq!(lat.value(0), 35.6895);
+}
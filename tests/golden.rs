@@ -0,0 +1,120 @@
+//! Golden snapshots: every example in the UI's `QUERIES` list is run against
+//! the seeded store and compared with `tests/golden/<label>.json`.
+//!
+//! After an intentional behavior change, regenerate the snapshots with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use demo_fiql::app::App;
+use demo_fiql::http::Request;
+use serde_json::{Value as Json, json};
+
+/// `(label, path)` for every entry in `QUERIES`.
+fn examples() -> Vec<(&'static str, &'static str)> {
+    let source = include_str!("../source/src/pages/FiqlPage.tsx");
+    let field = |line: &'static str, key: &str| line.trim().strip_prefix(key)?.strip_suffix("',");
+    let mut out = Vec::new();
+    let mut label = None;
+    for line in source.lines() {
+        if let Some(l) = field(line, "label: '") {
+            label = Some(l);
+        } else if let Some(path) = field(line, "path: '") {
+            out.push((label.take().expect("every query has a label"), path));
+        }
+    }
+    assert!(out.len() >= 50, "expected the QUERIES list, found {} examples", out.len());
+    out
+}
+
+fn slug(label: &str) -> String {
+    let mut out = String::new();
+    for c in label.chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c.to_ascii_lowercase());
+        } else if !out.is_empty() && !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    out.trim_end_matches('-').to_string()
+}
+
+fn run(app: &App, path: &str) -> Json {
+    let response = app.handle(&Request::get(path));
+    let body: Json = serde_json::from_slice(&response.body).unwrap_or_else(|e| panic!("{path}: {e}"));
+    json!({ "path": path, "status": response.status, "body": body })
+}
+
+fn data(app: &App, path: &str) -> Vec<Json> {
+    let result = run(app, path);
+    assert_eq!(result["status"], 200, "{path}: {}", result["body"]);
+    result["body"].as_array().unwrap_or_else(|| panic!("{path}: expected an array")).clone()
+}
+
+#[test]
+fn queries_match_golden_snapshots() {
+    let app = App::seeded();
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut seen = BTreeSet::new();
+    let mut failures = Vec::new();
+
+    for (label, path) in examples() {
+        let name = slug(label);
+        assert!(seen.insert(name.clone()), "two QUERIES labels share the slug `{name}`");
+        let file = dir.join(format!("{name}.json"));
+        let actual = run(&app, path);
+        if update {
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(&file, serde_json::to_string_pretty(&actual).unwrap() + "\n").unwrap();
+            continue;
+        }
+        let Ok(expected) = std::fs::read_to_string(&file) else {
+            failures.push(format!("{label}: missing {}", file.display()));
+            continue;
+        };
+        if serde_json::from_str::<Json>(&expected).ok().as_ref() != Some(&actual) {
+            failures.push(format!("{label} ({path}) differs from {}", file.display()));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} snapshot(s) out of date; rerun with UPDATE_GOLDEN=1:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn readme_jp_brand_join_returns_five_products() {
+    let app = App::seeded();
+    let rows = data(&app, "/Products/?brand.country==JP&select=name,price,brand{name,country}&stream=false");
+    let names: BTreeSet<&str> = rows.iter().map(|r| r["name"].as_str().unwrap()).collect();
+    let expected = BTreeSet::from([
+        "Ultra HD Monitor",
+        "Webcam 4K Pro",
+        "Curved Gaming Monitor",
+        "Noise Cancelling Headphones",
+        "Bluetooth Speaker Mini",
+    ]);
+    assert_eq!(names, expected);
+    assert!(rows.iter().all(|r| r["brand"]["country"] == "JP"));
+}
+
+#[test]
+fn readme_fulltext_example_matches_two_products() {
+    let app = App::seeded();
+    let rows = data(&app, "/Products/?description=ft=programming&select=id");
+    let ids: Vec<&str> = rows.iter().map(|r| r["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, ["prod-004", "prod-044"]);
+}
+
+#[test]
+fn readme_explain_uses_the_composite_index() {
+    let app = App::seeded();
+    let plan = run(&app, "/Products/?category==electronics&price=gt=100&explain=true&stream=false");
+    assert_eq!(plan["body"]["strategy"], "composite_index");
+    assert_eq!(plan["body"]["index"], "category+price");
+    assert_eq!(plan["body"]["estimatedCost"], "low");
+}
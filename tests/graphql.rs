@@ -0,0 +1,114 @@
+//! The `/graphql` endpoint over the seed data.
+
+use demo_fiql::app::App;
+use demo_fiql::http::{Method, Request};
+use serde_json::{Value as Json, json};
+
+fn post(app: &App, query: &str, variables: Json) -> (u16, Json) {
+    let body = json!({ "query": query, "variables": variables }).to_string();
+    let response = app.handle(&Request::new(Method::Post, "/graphql").with_body(body));
+    (response.status, serde_json::from_slice(&response.body).unwrap())
+}
+
+fn names(rows: &Json) -> Vec<&str> {
+    rows.as_array().unwrap().iter().map(|r| r["name"].as_str().unwrap()).collect()
+}
+
+#[test]
+fn root_fields_filter_sort_and_page_with_fiql() {
+    let app = App::seeded();
+    let query = r#"query ($after: String) {
+        products(filter: "category==books", sort: "-price", first: 2, after: $after) { id name price _cursor }
+    }"#;
+    let (status, body) = post(&app, query, json!({}));
+    assert_eq!(status, 200, "{body}");
+    let page = &body["data"]["products"];
+    assert_eq!(names(page), ["Linux Kernel Development", "Distributed Systems Guide"]);
+    assert_eq!(body["extensions"]["queries"], 1);
+
+    let (_, next) = post(&app, query, json!({ "after": page[1]["_cursor"] }));
+    let next = &next["data"]["products"];
+    assert_eq!(next.as_array().unwrap().len(), 2);
+    assert!(next[0]["price"].as_f64().unwrap() <= 59.99);
+    assert_ne!(next[0]["id"], page[1]["id"]);
+
+    // Aliases, `__typename`, `_score` and the GET form.
+    let query = r#"{ hits: products(filter: "name=ft=headset") { __typename name _score } }"#;
+    let path = format!("/graphql?query={}", query.replace(' ', "%20").replace('"', "%22").replace('=', "%3D"));
+    let response = app.handle(&Request::get(&path));
+    let body: Json = serde_json::from_slice(&response.body).unwrap();
+    assert_eq!(body["data"]["hits"][0]["__typename"], "Products");
+    assert_eq!(body["data"]["hits"][0]["name"], "Noise Cancelling Headphones");
+    assert!(body["data"]["hits"][0]["_score"].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn relationships_load_in_one_query_per_level() {
+    let app = App::seeded();
+    let (status, body) = post(&app, "{ brands { name products { name } } }", json!({}));
+    assert_eq!(status, 200, "{body}");
+    let brands = body["data"]["brands"].as_array().unwrap();
+    assert_eq!(brands.len(), 22);
+    let products: usize = brands.iter().map(|b| b["products"].as_array().unwrap().len()).sum();
+    assert_eq!(products, 50);
+    // One read for the brands and one for all of their products.
+    assert_eq!(body["extensions"]["queries"], 2);
+
+    // Nested arguments apply per brand; fragments and `@include` fold in.
+    let query = r#"query ($country: String, $brand: Boolean!) {
+        brands(filter: $country) {
+            name
+            products(sort: "-price", first: 1) { ...Card }
+        }
+    }
+    fragment Card on Products { name brand @include(if: $brand) { name } }"#;
+    let (_, body) = post(&app, query, json!({ "country": "country==JP", "brand": true }));
+    assert_eq!(
+        body["data"]["brands"],
+        json!([
+            { "name": "SoundWave", "products": [{ "name": "Noise Cancelling Headphones", "brand": { "name": "SoundWave" } }] },
+            { "name": "ViewTech", "products": [{ "name": "Curved Gaming Monitor", "brand": { "name": "ViewTech" } }] },
+        ])
+    );
+    assert_eq!(body["extensions"]["queries"], 3);
+
+    let (_, body) = post(&app, "{ products { brand { name } } }", json!({}));
+    assert_eq!(body["data"]["products"].as_array().unwrap().len(), 50);
+    assert_eq!(body["extensions"]["queries"], 2);
+}
+
+#[test]
+fn reports_syntax_validation_and_field_errors() {
+    let app = App::seeded();
+    for (query, error) in [
+        ("{ products { name }", "syntax error: expected `}`"),
+        ("{ products { nme } }", "`Products` has no field `nme`; did you mean `name`?"),
+        ("{ products { brand } }", "`brand` is a `Brand`; select some of its fields"),
+        ("{ products(limit: 3) { name } }", "`products` has no argument `limit`"),
+        ("{ products(first: \"3\") { name } }", "argument `first` on `products` expects a value of type `Int`"),
+        ("{ products { ...Missing } }", "unknown fragment `Missing`"),
+        ("mutation { products { name } }", "only queries are supported"),
+    ] {
+        let (status, body) = post(&app, query, json!({}));
+        assert_eq!(status, 400, "{query}");
+        let message = body["errors"][0]["message"].as_str().unwrap();
+        assert!(message.contains(error), "{query}: {message}");
+        assert!(body.get("data").is_none());
+    }
+    let (_, body) = post(&app, "{ products { nme } }", json!({}));
+    assert_eq!(body["errors"][0]["locations"], json!([{ "line": 1, "column": 14 }]));
+
+    // A bad filter nulls its own field and leaves the others.
+    let (status, body) =
+        post(&app, r#"{ products(filter: "price=gt=") { name } brands(first: 1) { name } }"#, json!({}));
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["products"], Json::Null);
+    assert_eq!(body["data"]["brands"], json!([{ "name": "AlpineGear" }]));
+    assert_eq!(body["errors"][0]["path"], json!(["products"]));
+    assert!(body["errors"][0]["message"].as_str().unwrap().starts_with("filter: missing value"));
+
+    let response = app.handle(&Request::get("/graphql/schema"));
+    let sdl = response.body_str();
+    assert!(sdl.contains("brands(filter: String, sort: String, first: Int, after: String): [Brand!]"));
+    assert!(sdl.contains("  brand: Brand\n"));
+}
@@ -0,0 +1,102 @@
+//! Live query subscriptions over Server-Sent Events.
+
+use demo_fiql::app::App;
+use demo_fiql::http::Request;
+use demo_fiql::live::{Event, Subscription, sse};
+use demo_fiql::store::CHANGE_LOG_CAPACITY;
+use serde_json::{Value as Json, json};
+
+fn subscribe(app: &App, path: &str) -> Subscription {
+    let (response, subscription) = app.subscribe(&Request::get(path)).unwrap();
+    assert_eq!(response.header("Content-Type"), Some("text/event-stream"));
+    assert!(response.body_str().starts_with("event: snapshot\ndata: {\"data\":["));
+    subscription
+}
+
+fn update(app: &mut App, table: &str, id: &str, patch: Json) {
+    let mut record = app.store().get(table, id).unwrap().clone();
+    for (k, v) in patch.as_object().unwrap() {
+        record[k] = v.clone();
+    }
+    app.store_mut().put(table, record).unwrap();
+}
+
+fn summary(events: &[Event]) -> Vec<String> {
+    events
+        .iter()
+        .map(|e| match e {
+            Event::Snapshot(_) => "snapshot".to_string(),
+            Event::Insert { id, .. } | Event::Update { id, .. } | Event::Delete { id } => format!("{} {id}", e.name()),
+        })
+        .collect()
+}
+
+#[test]
+fn records_entering_changing_and_leaving_the_filter() {
+    let mut app = App::seeded();
+    let mut live = subscribe(&app, "/Products/?category==electronics&inStock==false&subscribe=sse");
+
+    update(&mut app, "Products", "prod-001", json!({ "inStock": false }));
+    update(&mut app, "Products", "prod-002", json!({ "price": 1.0 }));
+    assert_eq!(summary(&live.poll(app.store())), ["insert prod-001"]);
+    update(&mut app, "Products", "prod-001", json!({ "price": 449.99 }));
+    update(&mut app, "Products", "prod-001", json!({ "price": 439.99 }));
+    let events = live.poll(app.store());
+    assert!(matches!(&events[..], [Event::Update { row, .. }] if row["price"] == 439.99), "{events:?}");
+
+    update(&mut app, "Products", "prod-001", json!({ "inStock": true }));
+    app.store_mut().delete("Products", "prod-031").unwrap();
+    let events = live.poll(app.store());
+    assert_eq!(summary(&events), ["delete prod-001", "delete prod-031"]);
+    assert_eq!(sse::frame(&events[0]), "event: delete\ndata: {\"id\":\"prod-001\"}\n\n");
+    assert!(live.poll(app.store()).is_empty());
+}
+
+#[test]
+fn relationship_conditions_follow_brand_writes() {
+    let mut app = App::seeded();
+    let mut products = subscribe(&app, "/Products/?brand.country==JP&select=name,brand{name,country}&subscribe=sse");
+    let mut brands = subscribe(&app, "/Brand/?products.price=gt=800&select=name&subscribe=sse");
+
+    update(&mut app, "Brand", "brand-viewtech", json!({ "country": "KR" }));
+    let viewtech = ["delete prod-001", "delete prod-026", "delete prod-040"];
+    assert_eq!(summary(&products.poll(app.store())), viewtech);
+
+    update(&mut app, "Brand", "brand-soundwave", json!({ "name": "SoundWave Audio" }));
+    let events = products.poll(app.store());
+    assert!(!events.is_empty());
+    assert!(events.iter().all(|e| matches!(e, Event::Update { row, .. } if row["brand"]["name"] == "SoundWave Audio")));
+
+    // Moving a product moves the reverse-join match from one brand to another.
+    let before = brands.poll(app.store());
+    assert!(before.is_empty(), "{before:?}");
+    update(&mut app, "Products", "prod-040", json!({ "brandId": "brand-soundwave" }));
+    assert_eq!(summary(&brands.poll(app.store())), ["delete brand-viewtech", "insert brand-soundwave"]);
+}
+
+#[test]
+fn falling_behind_the_change_log_resynchronizes() {
+    let mut app = App::seeded();
+    let mut live = subscribe(&app, "/Products/?price=gt=1000&subscribe=sse");
+    for n in 0..=CHANGE_LOG_CAPACITY {
+        update(&mut app, "Products", "prod-002", json!({ "price": n }));
+    }
+    assert_eq!(summary(&live.poll(app.store())), ["insert prod-002"]);
+    assert_eq!(live.version(), app.store().version());
+
+    let response = app.handle(&Request::get("/Products/?price=gt=1000&limit=5&subscribe=sse"));
+    assert_eq!(response.status, 400);
+}
+
+#[test]
+fn relative_dates_move_with_the_clock() {
+    let mut app = App::seeded();
+    let mut live = subscribe(&app, "/Products/?updatedAt=gt=now-2s&select=name&subscribe=sse");
+    update(&mut app, "Products", "prod-001", json!({ "price": 1.0 }));
+    assert_eq!(summary(&live.poll(app.store())), ["insert prod-001"]);
+    assert!(live.poll(app.store()).is_empty());
+
+    // Nothing is written, but the record ages out of the window.
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    assert_eq!(summary(&live.poll(app.store())), ["delete prod-001"]);
+}
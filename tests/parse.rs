@@ -0,0 +1,88 @@
+//! The `/parse` resource: the `ResourceQuery` JSON for a REST path.
+
+mod common;
+
+use common::get;
+use demo_fiql::app::App;
+use serde_json::json;
+
+#[test]
+fn top_level_terms_become_conditions_or_branches() {
+    let app = App::seeded();
+    let (status, body) = get(&app, "/parse/Products/?!(category==books|price=lt=5)&name=~=i:pro");
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(
+        body,
+        json!({
+            "table": "Products",
+            "conditions": [
+                {
+                    "operator": "or",
+                    "conditions": [
+                        { "field": "category", "op": "==", "value": "books" },
+                        { "field": "price", "op": "lt", "value": 5 },
+                    ],
+                    "negate": true,
+                },
+                { "field": "name", "op": "~", "value": "i:pro" },
+            ],
+        })
+    );
+
+    let (_, body) = get(&app, "/parse/Products/?category==books|price=lt=5");
+    assert_eq!(
+        body["or"],
+        json!([
+            [{ "field": "category", "op": "==", "value": "books" }],
+            [{ "field": "price", "op": "lt", "value": 5 }],
+        ])
+    );
+}
+
+#[test]
+fn controls_and_ids_come_from_either_form_of_the_path() {
+    let app = App::seeded();
+    let (_, functions) = get(&app, "/parse/Products/?price=gt=100&select(name,price)&sort(-price)&limit(2,10)");
+    assert_eq!(
+        functions,
+        json!({
+            "table": "Products",
+            "conditions": [{ "field": "price", "op": "gt", "value": 100 }],
+            "select": ["name", "price"],
+            "sort": [{ "field": "price", "descending": true }],
+            "limit": 10,
+            "offset": 2,
+        })
+    );
+    let (_, encoded) =
+        get(&app, "/parse?q=/Products/%3Fprice=gt=100%26select=name,price%26sort=-price%26offset=2%26limit=10");
+    assert_eq!(encoded, functions);
+
+    let (_, body) = get(&app, "/parse/Products/abc?select=name");
+    assert_eq!(body, json!({ "table": "Products", "id": "abc", "select": ["name"] }));
+}
+
+#[test]
+fn errors_carry_the_input_and_span() {
+    let app = App::seeded();
+    let (status, body) = get(&app, "/parse/Products/?price=foo=1");
+    assert_eq!(status, 400);
+    assert_eq!(
+        body,
+        json!({
+            "error": "unknown operator `=foo=`",
+            "input": "/Products/?price=foo=1",
+            "span": { "start": 16, "end": 21 },
+        })
+    );
+
+    // An unclosed `(` in a value is reported here and on the table itself.
+    let (status, body) = get(&app, "/parse/Products/?name==a(b&price=gt=1000");
+    assert_eq!((status, &body["error"]), (400, &json!("unclosed `(`")));
+    assert_eq!(body["span"], json!({ "start": 18, "end": 19 }));
+    assert_eq!(get(&app, "/Products/?name==a(b&price=gt=1000").0, 400);
+
+    let (status, body) = get(&app, "/parse");
+    assert_eq!(status, 400);
+    assert!(body["error"].as_str().unwrap().starts_with("missing `q` parameter"));
+}
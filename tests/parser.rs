@@ -0,0 +1,115 @@
+//! The FIQL parser: grouping, every operator the UI knows, type prefixes,
+//! function-style controls and spanned errors.
+
+use demo_fiql::fiql::{self, ErrorKind, Expr, Literal, Operator, Span, TypePrefix};
+
+/// Every operator token in the UI's `FIQL_OPS` list.
+fn ui_operators() -> Vec<&'static str> {
+    let source = include_str!("../source/src/pages/FiqlPage.tsx");
+    let start = source.find("const FIQL_OPS = [").expect("FIQL_OPS in FiqlPage.tsx");
+    let list = &source[start..start + source[start..].find(']').unwrap()];
+    let ops: Vec<&str> = list.split('\'').skip(1).step_by(2).collect();
+    assert!(ops.len() >= 35, "expected the FIQL_OPS list, found {} operators", ops.len());
+    ops
+}
+
+/// A value the operator accepts.
+fn sample_value(op: Operator) -> &'static str {
+    match op {
+        Operator::Size(_) => "2",
+        Operator::Null | Operator::Exists | Operator::Empty => "true",
+        Operator::GeLe | Operator::GeLt | Operator::GtLe | Operator::GtLt => "10,20",
+        Operator::In | Operator::Out | Operator::All | Operator::Any | Operator::None => "a,b",
+        Operator::Near => "35.68,139.69,50km",
+        Operator::Within => "35,139,36,140",
+        Operator::Regex => "^Ultra",
+        _ => "word",
+    }
+}
+
+fn conditions(expr: &Expr) -> Vec<(String, Operator)> {
+    expr.conditions().into_iter().map(|c| (c.field.to_string(), c.op)).collect()
+}
+
+#[test]
+fn parses_every_ui_operator() {
+    for token in ui_operators() {
+        let name = token.strip_prefix('=').and_then(|t| t.strip_suffix('=')).filter(|n| !n.is_empty());
+        let op = match (token, name) {
+            ("==", _) => Operator::Eq,
+            ("===", _) => Operator::StrictEq,
+            (_, Some(name)) => Operator::from_name(name).unwrap_or_else(|| panic!("{token} is not an operator")),
+            _ => unreachable!("{token}"),
+        };
+        assert_eq!(op.token(), token);
+        let query =
+            fiql::parse(&format!("field{token}{}", sample_value(op))).unwrap_or_else(|e| panic!("{token}: {e}"));
+        assert_eq!(conditions(query.filter.as_ref().unwrap()), [("field".to_string(), op)], "{token}");
+    }
+}
+
+#[test]
+fn nests_groups_and_applies_type_prefixes() {
+    let query = fiql::parse("(category==books|(price=lt=10&inStock==true))&name=ne=string:42").unwrap();
+    let Some(Expr::And(terms)) = &query.filter else { panic!("{:?}", query.filter) };
+    let Expr::Or(branches) = &terms[0] else { panic!("{:?}", terms[0]) };
+    assert!(matches!(&branches[0], Expr::Condition(c) if c.field.to_string() == "category"));
+    let Expr::And(inner) = &branches[1] else { panic!("{:?}", branches[1]) };
+    assert_eq!(
+        conditions(&Expr::And(inner.clone())),
+        [("price".into(), Operator::Lt), ("inStock".into(), Operator::Eq)]
+    );
+
+    let Expr::Condition(name) = &terms[1] else { panic!("{:?}", terms[1]) };
+    let value = &name.value.scalars()[0];
+    assert_eq!((value.prefix, &value.literal), (Some(TypePrefix::String), &Literal::String("42".into())));
+
+    let query = fiql::parse("price==number:10&name==string:true&createdAt=gt=date:2024-01-01").unwrap();
+    let literals: Vec<(Option<TypePrefix>, &Literal)> = query
+        .filter
+        .as_ref()
+        .unwrap()
+        .conditions()
+        .into_iter()
+        .map(|c| (c.value.scalars()[0].prefix, &c.value.scalars()[0].literal))
+        .collect();
+    assert_eq!(literals[0], (Some(TypePrefix::Number), &Literal::Number(10.0)));
+    assert_eq!(literals[1], (Some(TypePrefix::String), &Literal::String("true".into())));
+    assert!(matches!(literals[2], (Some(TypePrefix::Date), Literal::Time(_))));
+
+    // Balanced, escaped and bracketed parentheses stay inside a value.
+    for value in ["(?i)ultra", "\\(4K", "[(]"] {
+        let query = fiql::parse(&format!("name=~={value}&price=gt=1")).unwrap();
+        assert_eq!(query.filter.as_ref().unwrap().conditions()[0].value.scalars()[0].raw, value);
+    }
+}
+
+#[test]
+fn function_syntax_sets_the_same_controls_as_params() {
+    let functions = fiql::parse("category==books&select(name,price)&sort(-price,name)&limit(5,10)").unwrap();
+    let params = fiql::parse("category==books&select=name,price&sort=-price,name&offset=5&limit=10").unwrap();
+    assert_eq!(fiql::canonical(&functions), fiql::canonical(&params));
+    assert_eq!((functions.controls.offset, functions.controls.limit), (Some(5), Some(10)));
+    let sort: Vec<(String, bool)> =
+        functions.controls.sort.unwrap().iter().map(|k| (k.field.to_string(), k.descending)).collect();
+    assert_eq!(sort, [("price".to_string(), true), ("name".to_string(), false)]);
+}
+
+#[test]
+fn errors_point_at_the_offending_bytes() {
+    for (query, kind, span) in [
+        ("price=gt=", ErrorKind::MissingValue, (9, 9)),
+        ("price=foo=1", ErrorKind::UnknownOperator("foo".into()), (5, 10)),
+        ("(price==1", ErrorKind::UnclosedParen, (0, 1)),
+        ("price==1)", ErrorKind::UnmatchedParen, (8, 9)),
+        ("limit=5&limit=6", ErrorKind::DuplicateParam("limit"), (8, 15)),
+        ("nope(1)", ErrorKind::UnknownFunction("nope".into()), (0, 4)),
+        // An unclosed `(` in a value no longer swallows the terms after it.
+        ("name==a(b&price=gt=1000", ErrorKind::UnclosedParen, (7, 8)),
+        // A multibyte character is reported whole.
+        ("(name==a)é", ErrorKind::UnexpectedChar('é'), (9, 11)),
+    ] {
+        let err = fiql::parse(query).expect_err(query);
+        assert_eq!((err.kind, err.span), (kind, Span::new(span.0, span.1)), "{query}");
+    }
+}
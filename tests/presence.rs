@@ -0,0 +1,74 @@
+//! `=null=`, `=exists=` and `=empty=`.
+
+mod common;
+
+use common::{get, ids};
+use demo_fiql::app::App;
+use serde_json::json;
+
+/// The seed data plus products with a null brand, no brand at all, no
+/// manufacturer year, and empty tags and description, and brands with a
+/// null founding year and none at all.
+fn app() -> App {
+    let mut app = App::seeded();
+    for record in [
+        json!({ "id": "x-null", "name": "Null Brand", "price": 1, "category": "x", "inStock": true, "brandId": null,
+                "tags": [], "description": "" }),
+        json!({ "id": "x-missing", "name": "No Brand", "price": 1, "category": "x", "inStock": true,
+                "manufacturer": { "name": "Anon" } }),
+    ] {
+        app.store_mut().put("Products", record).unwrap();
+    }
+    app.store_mut().put("Brand", json!({ "id": "brand-new", "name": "New", "country": "NO" })).unwrap();
+    let unknown = json!({ "id": "brand-null-year", "name": "Unknown", "country": "NO", "foundedYear": null });
+    app.store_mut().put("Brand", unknown).unwrap();
+    app
+}
+
+#[test]
+fn null_is_missing_or_null_and_exists_is_any_key() {
+    let app = app();
+    assert_eq!(ids(&app, "/Brand/?foundedYear=null=true"), ["brand-new", "brand-null-year"]);
+    assert_eq!(ids(&app, "/Brand/?foundedYear=exists=false"), ["brand-new"]);
+    assert_eq!(ids(&app, "/Brand/?foundedYear=exists=true&foundedYear=null=true"), ["brand-null-year"]);
+    assert_eq!(ids(&app, "/Brand/?foundedYear=null=false").len(), 22);
+
+    // Products with no brand, by key or through the relationship.
+    assert_eq!(ids(&app, "/Products/?brandId=null=true"), ["x-missing", "x-null"]);
+    assert_eq!(ids(&app, "/Products/?brand=null=true"), ["x-missing", "x-null"]);
+    assert_eq!(ids(&app, "/Products/?brandId=exists=false"), ["x-missing"]);
+    assert_eq!(ids(&app, "/Products/?brand.country=exists=true&category==x"), Vec::<String>::new());
+
+    // Nested paths.
+    assert_eq!(ids(&app, "/Products/?manufacturer.year=exists=false"), ["x-missing", "x-null"]);
+    assert_eq!(ids(&app, "/Products/?manufacturer.name=exists=true&category==x"), ["x-missing"]);
+}
+
+#[test]
+fn empty_matches_empty_strings_arrays_and_joins() {
+    let app = app();
+    assert_eq!(ids(&app, "/Products/?tags=empty=true"), ["x-null"]);
+    assert_eq!(ids(&app, "/Products/?description=empty=true"), ["x-null"]);
+    // A missing field is neither empty nor non-empty.
+    assert!(!ids(&app, "/Products/?tags=empty=false").iter().any(|id| id.starts_with("x-")));
+    assert_eq!(ids(&app, "/Products/?!(tags=empty=false)&category==x"), ["x-missing", "x-null"]);
+
+    assert_eq!(ids(&app, "/Brand/?products=empty=true"), ["brand-new", "brand-null-year"]);
+    assert_eq!(ids(&app, "/Brand/?products=empty=false").len(), 22);
+}
+
+#[test]
+fn flags_must_be_booleans_and_null_markers_are_indexed() {
+    let app = app();
+    for path in ["/Products/?brandId=null=yes", "/Products/?brandId=exists=1", "/Products/?tags=empty=string:true"] {
+        assert_eq!(get(&app, path).0, 400, "{path}");
+    }
+
+    let (_, plan) = get(&app, "/Products/?brandId=null=true&explain=true");
+    assert_eq!((plan["strategy"].clone(), plan["index"].clone()), (json!("index"), json!("brandId")));
+    assert_eq!(plan["estimate"]["rowsScanned"], 2);
+    assert_eq!(plan["conditions"][0], json!({ "field": "brandId", "op": "null", "value": true }));
+
+    let (_, validate) = get(&app, "/validate/Products/?inStock=empty=true");
+    assert_eq!(validate["diagnostics"][0]["code"], "operator-not-supported");
+}
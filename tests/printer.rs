@@ -0,0 +1,108 @@
+//! Round-trip properties of the FIQL printer over every example in the UI's
+//! `QUERIES` list.
+
+use demo_fiql::fiql::{self, PathQuery};
+
+/// Every `path: '...'` entry in `QUERIES`.
+fn example_paths() -> Vec<&'static str> {
+    let source = include_str!("../source/src/pages/FiqlPage.tsx");
+    let paths: Vec<&str> = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("path: '"))
+        .filter_map(|rest| rest.strip_suffix("',"))
+        .collect();
+    assert!(paths.len() >= 50, "expected the QUERIES list, found {} paths", paths.len());
+    paths
+}
+
+fn parse(path: &str) -> PathQuery {
+    fiql::parse_path(path).unwrap_or_else(|e| panic!("{path}: {e}"))
+}
+
+fn canonical_path(path: &str) -> String {
+    let mut parsed = parse(path);
+    parsed.query = fiql::normalize(parsed.query);
+    fiql::print_path(&parsed)
+}
+
+#[test]
+fn printed_queries_reparse_to_the_same_tree() {
+    for path in example_paths() {
+        let printed = fiql::print_path(&parse(path));
+        let reprinted = fiql::print_path(&parse(&printed));
+        assert_eq!(printed, reprinted, "{path}");
+    }
+}
+
+#[test]
+fn canonical_form_is_a_fixed_point() {
+    for path in example_paths() {
+        let once = canonical_path(path);
+        assert_eq!(once, canonical_path(&once), "{path}");
+    }
+}
+
+#[test]
+fn printing_preserves_the_resource_query() {
+    use demo_fiql::query::ResourceQuery;
+    for path in example_paths() {
+        let original = parse(path);
+        let printed = parse(&fiql::print_path(&original));
+        assert_eq!(ResourceQuery::from_path(&original), ResourceQuery::from_path(&printed), "{path}");
+    }
+}
+
+#[test]
+fn reordering_top_level_terms_does_not_change_the_canonical_form() {
+    for path in example_paths() {
+        let Some((route, query)) = path.split_once('?') else { continue };
+        // Inherited fields depend on order; grouped queries contain `&` inside parentheses.
+        if query.contains('(') || parse(path).query.filter.iter().any(|f| f.conditions().iter().any(|c| c.inherited)) {
+            continue;
+        }
+        let terms: Vec<&str> = query.split('&').collect();
+        let expected = canonical_path(path);
+        for rotation in 1..terms.len() {
+            let mut rotated = terms.clone();
+            rotated.rotate_left(rotation);
+            let variant = format!("{route}?{}", rotated.join("&"));
+            assert_eq!(canonical_path(&variant), expected, "{variant}");
+        }
+    }
+}
+
+#[test]
+fn canonical_forms() {
+    let cases = [
+        ("/Products/?price=gt=50&lt=200", "/Products/?price=gtlt=50,200"),
+        ("/Products/?price=le=200&price=ge=50", "/Products/?price=gele=50,200"),
+        ("/Products/?limit(5,10)", "/Products/?limit=10&offset=5"),
+        ("/Products/?sort(-price)&select(name,price)", "/Products/?select=name,price&sort=-price"),
+        ("/Products/?limit=5&offset=0", "/Products/?limit=5"),
+        ("/Products/?price==number:499.99", "/Products/?price==499.99"),
+        ("/Products/?inStock==string:true", "/Products/?inStock==string:true"),
+        ("/Products/?name=ft=ultra%20monitor", "/Products/?name=ft=ultra%20monitor"),
+        ("/Products/?name=~=(?i)pro", "/Products/?name=~=%28%3Fi%29pro"),
+        ("/Products/?category=in=electronics,books,books", "/Products/?category=in=books,electronics"),
+        (
+            "/Products/?(category==electronics&price=gt=100)|inStock==false",
+            "/Products/?category==electronics&price=gt=100|inStock==false",
+        ),
+        (
+            "/Products/?inStock==true&(category==books|category==sports)",
+            "/Products/?(category==books|category==sports)&inStock==true",
+        ),
+        ("/Products/?!(!(price=gt=100))", "/Products/?price=gt=100"),
+        ("/Products/?((a==1&b==2)&c==3)", "/Products/?a==1&b==2&c==3"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(canonical_path(input), expected, "{input}");
+    }
+}
+
+#[test]
+fn nested_negation_keeps_its_parentheses() {
+    let printed = fiql::print_path(&parse("/Products/?!(a==1|(b==2&c==3))"));
+    assert_eq!(printed, "/Products/?!(a==1|b==2&c==3)");
+    assert_eq!(fiql::print_path(&parse(&printed)), printed);
+}
@@ -0,0 +1,69 @@
+//! Limits and errors of the `=~=` operator.
+
+use demo_fiql::app::App;
+use demo_fiql::http::Request;
+use demo_fiql::store::{self, MAX_PATTERN_LEN};
+use serde_json::Value as Json;
+
+fn get(app: &App, path: &str) -> (u16, String) {
+    let response = app.handle(&Request::get(path));
+    let body: Json = serde_json::from_slice(&response.body).unwrap();
+    let error = body["error"].as_str().unwrap_or_default().to_string();
+    (response.status, error)
+}
+
+#[test]
+fn backtracking_constructs_are_rejected_by_name() {
+    let app = App::seeded();
+    for (pattern, construct, at) in [
+        ("(pro)\\1", "backreferences (`\\1`)", "23..25"),
+        ("(?<p>pro)\\k<p>", "named backreferences (`\\k`)", "27..29"),
+        ("pro(?=\\s)", "lookaheads (`(?=`)", "21..24"),
+        ("(?!x)pro", "negative lookaheads (`(?!`)", "18..21"),
+        ("(?<=U)ltra", "lookbehinds (`(?<=`)", "18..22"),
+        ("(?>pro)", "atomic groups (`(?>`)", "18..21"),
+    ] {
+        let (status, error) = get(&app, &format!("/Products/?name=~={pattern}"));
+        assert_eq!(status, 400, "{pattern}");
+        assert!(error.contains(&format!("{construct} are not supported")), "{error}");
+        assert!(error.contains(&format!("at {at}:")), "{error}");
+    }
+
+    // Escaped or inside a class, the same characters are plain text.
+    assert_eq!(get(&app, "/Products/?name=~=[(?=]").0, 200);
+    assert_eq!(get(&app, "/Products/?name=~=\\(\\?=").0, 200);
+    assert_eq!(get(&app, "/Products/?name=~=(?<word>Pro)").0, 200);
+}
+
+#[test]
+fn patterns_are_capped_in_length_nesting_and_size() {
+    let app = App::seeded();
+    let long = "a".repeat(MAX_PATTERN_LEN + 1);
+    let (status, error) = get(&app, &format!("/Products/?name=~={long}"));
+    assert_eq!(status, 400);
+    assert!(error.contains(&format!("limited to {MAX_PATTERN_LEN}")), "{error}");
+    assert_eq!(get(&app, &format!("/Products/?name=~={}", &long[1..])).0, 200);
+
+    let nested = format!("{}a{}", "(".repeat(40), ")".repeat(40));
+    assert_eq!(get(&app, &format!("/Products/?name=~={nested}")).0, 400);
+
+    let (status, error) = get(&app, "/Products/?name=~=\\w{500}");
+    assert_eq!(status, 400);
+    assert!(error.contains("compiles to more than"), "{error}");
+}
+
+#[test]
+fn compiled_patterns_are_cached() {
+    let app = App::seeded();
+    let pattern = "^Wireless (Pro )?M";
+    assert!(!store::is_cached(pattern));
+    let first = app.handle(&Request::get(&format!("/Products/?name=~={pattern}")));
+    assert!(store::is_cached(pattern));
+    let again = app.handle(&Request::get(&format!("/Products/?name=~={pattern}")));
+    assert_eq!(first.body, again.body);
+    assert!(first.body_str().contains("prod-002"));
+
+    // Rejected patterns are not kept.
+    get(&app, "/Products/?name=~=(x)\\1");
+    assert!(!store::is_cached("(x)\\1"));
+}
@@ -0,0 +1,115 @@
+//! Pathological and generated `=~=` patterns against adversarial records.
+//!
+//! Every pattern must either compile and answer in linear time or be
+//! rejected with a 400; none may hang, panic or blow past the time limit.
+//! The store is queried directly, so the cost budget does not turn the
+//! expensive ones away first.
+
+use std::time::{Duration, Instant};
+
+use demo_fiql::fiql;
+use demo_fiql::store::{self, ExecError, Store};
+use serde_json::json;
+
+/// Products whose names are the classic inputs for catastrophic
+/// backtracking: long runs of one character that almost match.
+fn adversarial() -> Store {
+    let mut store = Store::seeded();
+    let names = ["a".repeat(5_000) + "!", "ab".repeat(2_500) + "c", "x".repeat(5_000), "a,".repeat(2_500)];
+    for (i, name) in names.into_iter().enumerate() {
+        let record = json!({ "id": format!("evil-{i}"), "name": name, "price": 1, "category": "x", "inStock": true });
+        store.put("Products", record).unwrap();
+    }
+    store
+}
+
+/// Runs `pattern` against every product, returning how long it took and
+/// whether it was rejected.
+fn run(store: &Store, pattern: &str) -> (Duration, Result<usize, ExecError>) {
+    let path = format!("/Products/?name=~={}", fiql::encode(pattern, b""));
+    let parsed = fiql::parse_path(&path).unwrap_or_else(|e| panic!("{pattern}: {e:?}"));
+    let started = Instant::now();
+    let result = store::execute(store, &parsed).map(|out| out.to_json().as_array().map_or(0, Vec::len));
+    (started.elapsed(), result)
+}
+
+fn assert_safe(store: &Store, pattern: &str) {
+    let (elapsed, result) = run(store, pattern);
+    match result {
+        Ok(_) | Err(ExecError::InvalidRegex { .. }) => {}
+        Err(other) => panic!("{pattern}: unexpected {other}"),
+    }
+    assert!(elapsed < Duration::from_secs(2), "{pattern} took {elapsed:?}");
+}
+
+#[test]
+fn classic_redos_patterns_run_in_linear_time() {
+    let store = adversarial();
+    for pattern in [
+        "^(a+)+$",
+        "^(a|a)*$",
+        "^(a|aa)+$",
+        "^(a*)*b$",
+        "(x+x+)+y",
+        "^(a?){25}a{25}$",
+        "^(([a-z])+.)+[A-Z]([a-z])+$",
+        "^(\\w+\\s?)*$",
+        "(.*a){12}",
+        "^(ab|a)*c$",
+        "(a,?)*;",
+    ] {
+        assert_safe(&store, pattern);
+    }
+    // They still match what they should.
+    assert_eq!(run(&store, "^(a+)+!$").1, Ok(1));
+    assert_eq!(run(&store, "^(ab)+c$").1, Ok(1));
+}
+
+#[test]
+fn generated_patterns_never_hang_or_panic() {
+    let store = adversarial();
+    let atoms = ["a", "b", "x", ".", "\\w", "\\s", "[a-c]", "[^x]", "(a|b)", "(?i)A", "\\d", "!", ","];
+    let quantifiers = ["", "", "*", "+", "?", "{2,}", "{1,3}", "*?"];
+    // xorshift: deterministic, so a failure reproduces.
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n as u64) as usize
+    };
+    for _ in 0..300 {
+        let mut pattern = String::new();
+        for _ in 0..1 + next(6) {
+            let mut piece = atoms[next(atoms.len())].to_string() + quantifiers[next(quantifiers.len())];
+            if next(3) == 0 {
+                piece = format!("({piece}){}", quantifiers[next(quantifiers.len())]);
+            }
+            pattern.push_str(&piece);
+        }
+        if next(4) == 0 {
+            pattern = format!("^{pattern}$");
+        }
+        assert_safe(&store, &pattern);
+    }
+}
+
+#[test]
+fn malformed_and_oversized_patterns_are_rejected_cleanly() {
+    let store = adversarial();
+    for pattern in ["(", "[a-", "a{3,1}", "*a", "\\", "(?P<>x)", "[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[a]", "x{99999}"] {
+        let (_, result) = run(&store, pattern);
+        assert!(matches!(result, Err(ExecError::InvalidRegex { .. })), "{pattern}: {result:?}");
+    }
+    // Escaped and bare multibyte characters are scanned whole.
+    for pattern in ["\\é", "\\é(?=x)", "[\\é]", "é\\1", "\\日本", "(?i)ü\\😀+"] {
+        assert_safe(&store, pattern);
+    }
+    assert!(matches!(run(&store, "\\é(?=x)").1, Err(ExecError::InvalidRegex { .. })));
+    assert!(matches!(run(&store, "é\\1").1, Err(ExecError::InvalidRegex { .. })));
+
+    let deep = format!("{}a{}", "(?:".repeat(33), ")".repeat(33));
+    assert!(matches!(run(&store, &deep).1, Err(ExecError::InvalidRegex { .. })));
+    let wide = format!("({})", ["\\w{20}"; 40].join("|"));
+    assert!(matches!(run(&store, &wide).1, Err(ExecError::InvalidRegex { .. })));
+}
@@ -0,0 +1,124 @@
+//! `=ft=` search syntax, BM25 `_score`, `_text` boosts and `_highlight`.
+
+mod common;
+
+use common::{app_with, get, ids};
+use demo_fiql::app::App;
+use demo_fiql::fiql::{ErrorKind, Search, Span, Term};
+use demo_fiql::http::Request;
+use serde_json::json;
+
+const SDL: &str = r#"
+type Note @table {
+    id: ID! @primaryKey
+    title: String @indexed(type: "fulltext", boost: 3)
+    body: String @indexed(type: "fulltext")
+}
+"#;
+
+fn notes() -> App {
+    let long =
+        format!("{} the garden gate was left open {}", "filler words ".repeat(20), "and more filler ".repeat(20));
+    let notes = [
+        ("n1", "Garden diary", "Tomatoes and beans"),
+        ("n2", "Shopping", "Seeds for the garden, garden gloves and a garden hose"),
+        ("n3", "Recipes", "Tomato soup with basil"),
+        ("n4", "Long read", long.as_str()),
+    ];
+    app_with(SDL, "Note", notes.map(|(id, title, body)| json!({ "id": id, "title": title, "body": body })))
+}
+
+#[test]
+fn searches_parse_into_groups_exclusions_phrases_and_prefixes() {
+    let search = Search::parse(r#"wireless OR "noise cancelling" monit* -cable NOT case"#, Span::new(0, 1)).unwrap();
+    assert_eq!(
+        search.required,
+        [
+            vec![Term::Word("wireless".into()), Term::Phrase("noise cancelling".into())],
+            vec![Term::Prefix("monit".into())],
+        ]
+    );
+    assert_eq!(search.excluded, [Term::Word("cable".into()), Term::Word("case".into())]);
+
+    for (text, why) in [
+        (r#""open"#, "a phrase is missing its closing `\"`"),
+        ("a OR", "`OR` needs a term on each side"),
+        ("OR a", "`OR` needs a term on each side"),
+        ("a NOT", "`NOT` and `-` need a term after them"),
+        ("mo*nit", "`*` can only end a word"),
+        ("*", "`*` can only end a word"),
+    ] {
+        assert_eq!(Search::parse(text, Span::new(0, 1)).unwrap_err().kind, ErrorKind::InvalidSearch(why), "{text}");
+    }
+
+    let app = App::seeded();
+    let (status, body) = get(&app, "/Products/?name=ft=%22ultra&select=name");
+    assert_eq!(status, 400);
+    assert_eq!(body["span"], json!({ "start": 19, "end": 27 }));
+    // Terms without prefixes or phrases still mean every word.
+    assert_eq!(ids(&app, "/Products/?name=ft=ultra%20monitor"), ["prod-001"]);
+    assert_eq!(ids(&app, "/Products/?description=ft=%22web%20apis%22"), ["prod-017"]);
+    assert!(ids(&app, "/Products/?description=ft=%22apis%20web%22").is_empty());
+}
+
+#[test]
+fn scores_rank_by_bm25_with_field_boosts() {
+    let mut app = notes();
+    // `garden` three times in a short body beats once in a long one, and a
+    // title match counts triple.
+    let (_, ranked) = get(&app, "/Note/?body=ft=garden&sort=-_score&select=id,_score");
+    let ranked = ranked.as_array().unwrap();
+    assert_eq!(ranked.iter().map(|r| r["id"].as_str().unwrap()).collect::<Vec<_>>(), ["n2", "n4"]);
+    assert!(ranked[0]["_score"].as_f64() > ranked[1]["_score"].as_f64());
+    assert_eq!(ids(&app, "/Note/?_text=ft=garden&sort=-_score"), ["n1", "n2", "n4"]);
+    assert_eq!(ids(&app, "/Note/?_text=ft=tomato*%20-soup"), ["n1"]);
+    assert_eq!(ids(&app, "/Note/?_text=ft=garden%20-gate&sort=_score"), ["n2", "n1"]);
+
+    // Cursors carry the score.
+    let path = "/Note/?_text=ft=garden&sort=-_score&limit=1&pagination=true";
+    let (_, first) = get(&app, path);
+    let cursor = first["pagination"]["nextCursor"].as_str().unwrap();
+    assert_eq!(ids(&app, &format!("/Note/?_text=ft=garden&sort=-_score&after={cursor}")), ["n2", "n4"]);
+
+    // A term more records hold is worth less.
+    let score = |app: &App| get(app, "/Note/?body=ft=tomato&select=_score").1[0]["_score"].as_f64().unwrap();
+    let rare = score(&app);
+    app.store_mut().put("Note", json!({ "id": "n5", "title": "More", "body": "tomato tomato" })).unwrap();
+    app.store_mut().put("Note", json!({ "id": "n6", "title": "Most", "body": "tomato" })).unwrap();
+    assert!(score(&app) < rare);
+    app.store_mut().delete("Note", "n5").unwrap();
+    app.store_mut().delete("Note", "n6").unwrap();
+    assert_eq!(score(&app), rare);
+}
+
+#[test]
+fn highlights_validates_and_refuses_subscriptions() {
+    let app = notes();
+    let (_, rows) = get(&app, "/Note/?_text=ft=garden%20OR%20%22gate%20was%22&sort=id&select=id,_highlight");
+    assert_eq!(rows[0]["_highlight"], json!({ "title": "<em>Garden</em> diary" }));
+    assert_eq!(
+        rows[1]["_highlight"]["body"],
+        "Seeds for the <em>garden</em>, <em>garden</em> gloves and a <em>garden</em> hose"
+    );
+    let snippet = rows[2]["_highlight"]["body"].as_str().unwrap();
+    assert!(snippet.starts_with('…') && snippet.ends_with('…'), "{snippet}");
+    assert!(snippet.contains("the <em>garden</em> <em>gate was</em> left open"), "{snippet}");
+
+    let (_, body) = get(&app, "/validate/Note/?_text==x&sort=-_score&select=id,_score,_highlight");
+    let messages: Vec<&str> =
+        body["diagnostics"].as_array().unwrap().iter().map(|d| d["message"].as_str().unwrap()).collect();
+    assert_eq!(
+        messages,
+        [
+            "`_text` stands for every fulltext field and only takes `=ft=`",
+            "sorting by `_score` ranks by relevance to `=ft=` conditions, and the filter has none",
+        ]
+    );
+    let (_, body) = get(&app, "/validate/Note/?_text=ft=garden&sort=-_score&select=id,_score");
+    assert_eq!(body["valid"], true);
+    assert!(body["diagnostics"].as_array().unwrap().is_empty());
+
+    let refused = app.subscribe(&Request::get("/Note/?_text=ft=garden&select=id,_score&subscribe=sse")).unwrap_err();
+    assert_eq!(refused.status, 400);
+    assert!(refused.body_str().contains("`_score` cannot be combined with a subscription"));
+}
@@ -0,0 +1,90 @@
+//! `/<Table>/suggest` over the seed data.
+
+mod common;
+
+use common::get;
+use demo_fiql::app::App;
+use demo_fiql::store::{self, Store, StoreError};
+use serde_json::json;
+
+fn texts(app: &App, path: &str) -> Vec<String> {
+    let (status, body) = get(app, path);
+    assert_eq!(status, 200, "{path}: {body}");
+    body["suggestions"].as_array().unwrap().iter().map(|s| s["text"].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn completes_words_from_the_fulltext_index() {
+    let app = App::seeded();
+    let (_, body) = get(&app, "/Products/suggest?field=name&prefix=ult&limit=8");
+    assert_eq!(body["suggestions"], json!([{ "text": "Ultra", "count": 1, "span": { "start": 0, "end": 3 } }]));
+
+    // Most records first, then alphabetical; `limit` cuts the list.
+    assert_eq!(texts(&app, "/Products/suggest?field=name&prefix=w&limit=3"), ["Wall", "Webcam", "Wireless"]);
+    // Whole words before the last must be next to it.
+    let (_, body) = get(&app, "/Products/suggest?field=name&prefix=Ultra%20h");
+    assert_eq!(body["suggestions"], json!([{ "text": "Ultra HD", "count": 1, "span": { "start": 0, "end": 7 } }]));
+    assert!(texts(&app, "/Products/suggest?field=name&prefix=hd%20ultra").is_empty());
+    // Stemmed words still mark what was typed.
+    let (_, body) = get(&app, "/Products/suggest?field=name&prefix=headphone%20");
+    assert_eq!(body["suggestions"][0], json!({ "text": "Headphones", "count": 1, "span": { "start": 0, "end": 9 } }));
+    assert!(texts(&app, "/Products/suggest?field=name&prefix=zzz").is_empty());
+}
+
+#[test]
+fn suggests_values_of_enumerable_fields_within_a_filter() {
+    let app = App::seeded();
+    let (_, body) = get(&app, "/Products/suggest?field=category&prefix=E");
+    assert_eq!(body["suggestions"], json!([{ "text": "electronics", "count": 14, "span": { "start": 0, "end": 1 } }]));
+    assert_eq!(texts(&app, "/Products/suggest?field=brand.name&prefix=c"), ["CodePress", "ClickCo"]);
+    assert_eq!(texts(&app, "/Brand/suggest?field=name&prefix=VIEW"), ["ViewTech"]);
+    assert!(texts(&app, "/Products/suggest?field=brand.name&prefix=zzz").is_empty());
+    assert!(texts(&app, "/Products/suggest?field=category&prefix=zzz").is_empty());
+
+    // The filter scopes what is counted.
+    let (_, all) = get(&app, "/Products/suggest?field=description&prefix=prog");
+    let (_, books) = get(&app, "/Products/suggest?field=description&prefix=prog&category==books");
+    assert_eq!(all["suggestions"][0]["text"], "programming");
+    assert_eq!(all["suggestions"].as_array().unwrap().len(), 2);
+    assert_eq!(books["suggestions"], json!([{ "text": "programming", "count": 2, "span": { "start": 0, "end": 4 } }]));
+}
+
+#[test]
+fn rejects_bad_fields_limits_and_filters() {
+    let app = App::seeded();
+    for (path, error) in [
+        ("/Products/suggest?prefix=a", "missing `field` parameter; suggestable fields: name, description, category"),
+        (
+            "/Products/suggest?field=price&prefix=1",
+            "field: `price` has no suggestions on `Products`; suggestable fields: name, description, category, brand.name",
+        ),
+        ("/Products/suggest?field=name", "missing `prefix` parameter"),
+        ("/Products/suggest?field=name&prefix=a&limit=0", "limit: expected a whole number from 1 to 100"),
+        ("/Products/suggest?field=name&prefix=a&limit=many", "limit: expected a whole number from 1 to 100"),
+    ] {
+        let (status, body) = get(&app, path);
+        assert_eq!(status, 400, "{path}");
+        assert!(body["error"].as_str().unwrap().contains(error), "{path}: {body}");
+    }
+    let (status, body) = get(&app, "/Products/suggest?field=name&prefix=a&price=gt=");
+    assert_eq!(status, 400);
+    assert!(body["span"].is_object());
+}
+
+#[test]
+fn records_cannot_take_a_resource_name_as_key() {
+    let mut store = Store::seeded();
+    let products = store.schema().table("Products").unwrap().clone();
+    for key in ["suggest", "aggregate", "facets"] {
+        let mut record = store.get("Products", "prod-001").unwrap().clone();
+        record["id"] = json!(key);
+        let message = store::conform(store.schema(), &products, &record).unwrap_err();
+        assert!(message.contains(&format!("`/Products/{key}` is a resource")), "{message}");
+        let err = store.put("Products", record).unwrap_err();
+        assert_eq!(err, StoreError::ReservedKey { table: "Products".into(), key: key.into() });
+    }
+    let app = App::new(store);
+    let (status, body) = get(&app, "/Products/suggest?field=category&prefix=b");
+    assert_eq!(status, 200, "{body}");
+    assert!(body["suggestions"].is_array());
+}
@@ -0,0 +1,83 @@
+//! Schema diagnostics from `/validate`, with "did you mean" suggestions.
+
+use demo_fiql::app::App;
+use demo_fiql::http::Request;
+use serde_json::{Value as Json, json};
+
+fn validate(app: &App, path: &str) -> Json {
+    let response = app.handle(&Request::get(&format!("/validate{path}")));
+    assert_eq!(response.status, 200);
+    serde_json::from_slice(&response.body).unwrap()
+}
+
+/// `(code, message, start..end, suggestion)` of each diagnostic.
+fn diagnostics(body: &Json) -> Vec<(String, String, String, Option<String>)> {
+    let text = |v: &Json| v.as_str().unwrap().to_string();
+    body["diagnostics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| {
+            let span = format!("{}..{}", d["span"]["start"], d["span"]["end"]);
+            (text(&d["code"]), text(&d["message"]), span, d["suggestion"].as_str().map(str::to_string))
+        })
+        .collect()
+}
+
+#[test]
+fn misspelled_names_get_the_closest_suggestion() {
+    let app = App::seeded();
+    let body = validate(&app, "/Prodcuts/?price=gt=1");
+    assert_eq!(body["valid"], false);
+    assert_eq!(
+        diagnostics(&body),
+        [("unknown-table".into(), "unknown table `Prodcuts`".into(), "1..9".into(), Some("Products".into()))]
+    );
+
+    // Fields are looked up on the type the path has reached.
+    let body = validate(&app, "/Products/?prcie=gt=100&brand.nmae==Acme&select=name,pirce");
+    assert_eq!(
+        diagnostics(&body),
+        [
+            ("unknown-field".into(), "`Products` has no field `prcie`".into(), "11..16".into(), Some("price".into())),
+            ("unknown-field".into(), "`Brand` has no field `nmae`".into(), "30..34".into(), Some("name".into())),
+            ("unknown-field".into(), "`Products` has no field `pirce`".into(), "53..58".into(), Some("price".into())),
+        ]
+    );
+
+    // Nothing close enough: no suggestion rather than a wild guess.
+    let body = validate(&app, "/Products/?xyzzy==1");
+    assert_eq!(diagnostics(&body)[0].3, None);
+}
+
+#[test]
+fn values_operators_and_relationships_are_checked_against_field_types() {
+    let app = App::seeded();
+    let body = validate(&app, "/Products/?price=gt=abc&name=size=2&createdAt=gt=notadate");
+    let codes: Vec<(String, String)> =
+        diagnostics(&body).into_iter().map(|(code, message, ..)| (code, message)).collect();
+    assert_eq!(
+        codes,
+        [
+            ("type-mismatch".into(), "`abc` is not a number; `price` is Float!".into()),
+            ("operator-not-supported".into(), "`=size=` applies to lists but `name` is String!".into()),
+            ("type-mismatch".into(), "`notadate` is not a date; `createdAt` is DateTime!".into()),
+        ]
+    );
+
+    let body = validate(&app, "/Products/?brand==Acme");
+    assert_eq!(diagnostics(&body)[0].0, "relationship-without-field");
+    assert_eq!(diagnostics(&body)[0].3.as_deref(), Some("brand.name"));
+}
+
+#[test]
+fn valid_queries_and_syntax_errors() {
+    let app = App::seeded();
+    assert_eq!(
+        validate(&app, "/Products/?price=gt=10&name=ct=pro&sort=-price"),
+        json!({ "input": "/Products/?price=gt=10&name=ct=pro&sort=-price", "valid": true, "diagnostics": [] })
+    );
+
+    let body = validate(&app, "/Products/?price=foo=1");
+    assert_eq!(diagnostics(&body), [("syntax".into(), "unknown operator `=foo=`".into(), "16..21".into(), None)]);
+}
@@ -0,0 +1,119 @@
+//! `@embedding` fields, the HNSW vector index and `=sim=`.
+
+mod common;
+
+use common::{get, ids};
+use demo_fiql::app::App;
+use demo_fiql::fiql::{self, ErrorKind, Similar, Span};
+use demo_fiql::http::Request;
+use demo_fiql::store::{Embedder, HashingEmbedder, Store};
+use serde_json::json;
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Puts every text mentioning a desk on one axis and everything else on
+/// another.
+#[derive(Debug)]
+struct DeskEmbedder;
+
+impl Embedder for DeskEmbedder {
+    fn embed(&self, text: &str, dimensions: usize) -> Vec<f32> {
+        let mut vector = vec![0.0; dimensions];
+        vector[usize::from(!text.to_lowercase().contains("desk"))] = 1.0;
+        vector
+    }
+}
+
+#[test]
+fn sim_values_parse_and_the_hashing_embedder_is_stable() {
+    let query = fiql::parse("description=sim=rust%20programming,k=3").unwrap();
+    let condition = query.filter.as_ref().unwrap().conditions()[0].clone();
+    let similar = Similar::parse(condition.value.scalars(), condition.span).unwrap();
+    assert_eq!(similar, Similar { text: "rust programming".into(), k: 3 });
+    // Order matters, so normalizing leaves the values where they are.
+    assert_eq!(fiql::canonical(&query), "description=sim=rust%20programming,k%3D3");
+    assert_eq!(fiql::parse("description=sim=desk").map(|q| q.filter.is_some()), Ok(true));
+
+    let err = fiql::parse("description=sim=desk,k=0").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidSimilarOption("k=0".into()));
+    assert_eq!(err.span, Span::new(21, 24));
+    let err = fiql::parse("description=sim=desk,k=2,k=3").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidSimilarOption("k=3".into()));
+
+    let embed = |text: &str| HashingEmbedder.embed(text, 256);
+    let programming = embed("Comprehensive guide to systems programming with Rust");
+    assert_eq!(programming, embed("Comprehensive guide to systems programming with Rust"));
+    assert!((dot(&programming, &programming) - 1.0).abs() < 1e-5);
+    assert!(dot(&embed("programs"), &programming) > dot(&embed("velvet"), &programming));
+    assert!(embed("the and of").iter().all(|x| *x == 0.0));
+}
+
+#[test]
+fn keeps_the_nearest_records_that_pass_the_rest_of_the_filter() {
+    let mut app = App::seeded();
+    let nearest = ids(&app, "/Products/?description=sim=rust%20programming,k=3&sort=description&select=id");
+    assert_eq!(nearest[0], "prod-004");
+    assert_eq!(nearest.len(), 3);
+
+    // The other conditions filter before the top `k` is taken, not after.
+    let books = ids(&app, "/Products/?category==books&description=sim=wireless%20mouse,k=4&select=id");
+    assert_eq!(books.len(), 4);
+    assert!(books.iter().all(|id| app.store().get("Products", id).unwrap()["category"] == "books"));
+
+    // Sorting by the field orders by distance, and cursors carry it.
+    let desk = "description=sim=standing%20desk,k=6&sort=description&select=id";
+    let all = ids(&app, &format!("/Products/?{desk}"));
+    assert_eq!(all[0], "prod-003");
+    let reversed = ids(&app, &format!("/Products/?{}", desk.replace("sort=", "sort=-")));
+    assert_eq!(reversed, all.iter().rev().cloned().collect::<Vec<_>>());
+    let (_, first) = get(&app, &format!("/Products/?{desk}&limit=4&pagination=true"));
+    let cursor = first["pagination"]["nextCursor"].as_str().unwrap();
+    assert_eq!(ids(&app, &format!("/Products/?{desk}&after={cursor}")), all[4..]);
+
+    // Writes keep the index current; the vector never lands in the record.
+    let mut record = app.store().get("Products", "prod-047").unwrap().clone();
+    record["description"] = json!("Height-adjustable standing desk frame");
+    record["embedding"] = json!([1.0, 2.0]);
+    app.store_mut().put("Products", record).unwrap();
+    assert_eq!(ids(&app, &format!("/Products/?{desk}"))[..2], ["prod-047", "prod-003"]);
+    assert!(app.store().get("Products", "prod-047").unwrap().get("embedding").is_none());
+    app.store_mut().delete("Products", "prod-047").unwrap();
+    assert_eq!(ids(&app, &format!("/Products/?{desk}")), all);
+
+    let app = App::new(Store::seeded().with_embedder(DeskEmbedder));
+    let desks = ids(&app, "/Products/?description=sim=desk,k=3&sort=description&select=id");
+    assert_eq!(desks, ["prod-003", "prod-016", "prod-001"]);
+}
+
+#[test]
+fn vector_index_plans_validates_projects_and_refuses_subscriptions() {
+    let app = App::seeded();
+    let (_, plan) = get(&app, "/Products/?inStock==true&description=sim=desk,k=5&explain=true");
+    assert_eq!((plan["strategy"].as_str(), plan["index"].as_str()), (Some("vector"), Some("embedding")));
+    assert_eq!(plan["estimate"]["rowsScanned"], 5);
+
+    let (_, body) = get(&app, "/validate/Products/?name=sim=desk&embedding=gt=1&sort=embedding");
+    let messages: Vec<&str> =
+        body["diagnostics"].as_array().unwrap().iter().map(|d| d["message"].as_str().unwrap()).collect();
+    assert_eq!(
+        messages,
+        [
+            "`name` has no embedding; `=sim=` works on description, embedding",
+            "`=gt=` is not supported on embedding field `embedding`; use `=sim=`",
+            "sorting by `embedding` orders by similarity to a `embedding=sim=` text, and the filter has none",
+        ]
+    );
+
+    let (_, record) = get(&app, "/Products/prod-004?select=name,embedding");
+    let vector: Vec<f64> = record["embedding"].as_array().unwrap().iter().map(|x| x.as_f64().unwrap()).collect();
+    assert_eq!(vector.len(), 256);
+    assert!((vector.iter().map(|x| x * x).sum::<f64>() - 1.0).abs() < 1e-3);
+    let (_, record) = get(&app, "/Products/prod-004");
+    assert!(record.get("embedding").is_none());
+
+    let refused = app.subscribe(&Request::get("/Products/?description=sim=desk&subscribe=sse")).unwrap_err();
+    assert_eq!(refused.status, 400);
+    assert!(refused.body_str().contains("`=sim=` cannot be combined with a subscription"));
+}
@@ -0,0 +1,78 @@
+//! The `/live` WebSocket endpoint and its multiplexed subscription protocol.
+
+use demo_fiql::app::App;
+use demo_fiql::http::Request;
+use demo_fiql::live::ws::Session;
+use serde_json::{Value as Json, json};
+
+fn send(session: &mut Session, app: &App, message: Json) -> Vec<Json> {
+    session.receive(app.store(), &message.to_string())
+}
+
+fn kinds(messages: &[Json]) -> Vec<String> {
+    messages.iter().map(|m| format!("{} {}", m["type"].as_str().unwrap(), m["id"].as_str().unwrap_or("-"))).collect()
+}
+
+#[test]
+fn handshake_upgrades_only_websocket_requests() {
+    let app = App::seeded();
+    let request = Request::get("/live")
+        .with_header("Upgrade", "websocket")
+        .with_header("Connection", "Upgrade")
+        .with_header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==");
+    let response = app.handle(&request);
+    assert_eq!(response.status, 101);
+    // The worked example from RFC 6455, section 1.3.
+    assert_eq!(response.header("Sec-WebSocket-Accept"), Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+
+    let response = app.handle(&Request::get("/live"));
+    assert_eq!(response.status, 426);
+    assert_eq!(response.header("Upgrade"), Some("websocket"));
+}
+
+#[test]
+fn subscriptions_are_multiplexed_and_cancelled_by_id() {
+    let mut app = App::seeded();
+    let mut session = Session::new();
+    let opened =
+        send(&mut session, &app, json!({ "type": "subscribe", "id": "oos", "query": "/Products/?inStock==false" }));
+    assert_eq!(kinds(&opened), ["query oos", "snapshot oos"]);
+    assert_eq!(opened[0]["query"]["conditions"][0]["field"], "inStock");
+    let query = json!({ "type": "subscribe", "id": "jp", "query": "/Brand/?country==JP&select=id,name" });
+    let opened = send(&mut session, &app, query);
+    assert_eq!(opened[1]["data"].as_array().unwrap().len(), 2);
+
+    let mut record = app.store().get("Products", "prod-001").unwrap().clone();
+    record["inStock"] = json!(false);
+    app.store_mut().put("Products", record).unwrap();
+    let mut brand = app.store().get("Brand", "brand-viewtech").unwrap().clone();
+    brand["country"] = json!("KR");
+    app.store_mut().put("Brand", brand).unwrap();
+    let deltas = session.poll(app.store());
+    assert_eq!(kinds(&deltas), ["delete jp", "insert oos"]);
+    assert_eq!(deltas[0]["key"], "brand-viewtech");
+    assert_eq!(deltas[1]["data"]["id"], "prod-001");
+
+    assert_eq!(kinds(&send(&mut session, &app, json!({ "type": "cancel", "id": "oos" }))), ["cancelled oos"]);
+    app.store_mut().delete("Products", "prod-001").unwrap();
+    assert!(session.poll(app.store()).is_empty());
+    assert_eq!(session.ids().collect::<Vec<_>>(), ["jp"]);
+}
+
+#[test]
+fn errors_name_the_subscription_they_belong_to() {
+    let app = App::seeded();
+    let mut session = Session::new();
+    let bad = send(&mut session, &app, json!({ "type": "subscribe", "id": "a", "query": "/Products/?price=gt=" }));
+    assert_eq!(kinds(&bad), ["error a"]);
+    assert_eq!((bad[0]["status"].clone(), bad[0]["span"]["start"].clone()), (json!(400), json!(20)));
+
+    let paged = send(&mut session, &app, json!({ "type": "subscribe", "id": "b", "query": "/Products/?limit=5" }));
+    assert_eq!(kinds(&paged), ["query b", "error b"]);
+
+    send(&mut session, &app, json!({ "type": "subscribe", "id": "c", "query": "/Products/" }));
+    let again = send(&mut session, &app, json!({ "type": "subscribe", "id": "c", "query": "/Brand/" }));
+    assert_eq!(again[0]["status"], 409);
+    assert_eq!(send(&mut session, &app, json!({ "type": "cancel", "id": "zz" }))[0]["status"], 404);
+    assert_eq!(kinds(&session.receive(app.store(), "{\"type\":\"shout\"}")), ["error -"]);
+}